@@ -1,5 +1,5 @@
 use crate::db::{
-    Hermano, Familia, Cuota, EstadisticasCuotas, DbConnection,
+    Hermano, Familia, Cuota, EstadisticasCuotas, Periodicidad, DbConnection,
     get_all_hermanos, get_hermanos_activos, get_hermano_by_id, search_hermanos,
     create_hermano, update_hermano, delete_hermano, set_hermano_inactive, get_hermanos_by_familia,
     update_hermano_familia,
@@ -7,10 +7,74 @@ use crate::db::{
     update_familia, delete_familia, get_familia_stats, get_familia_with_hermanos, get_familia_with_address,
     get_all_cuotas, get_cuotas_by_hermano, get_cuotas_by_year, get_cuotas_pendientes,
     create_cuota, update_cuota, delete_cuota, marcar_cuota_pagada,
-    generar_cuotas_trimestre, get_estadisticas_cuotas
+    generar_cuotas_trimestre, generar_cuotas_periodo, set_cuotas_deducibles, get_estadisticas_cuotas,
+    get_periodicidad_cuotas, set_periodicidad_cuotas, get_periodicidades_hermanos, set_periodicidad_hermano
 };
+use crate::db::integridad::{reparar_integridad, InformeIntegridad};
+use crate::db::backup::{backup_database, restore_database, list_backups, CopiaSeguridad};
+use crate::db::perfiles::{
+    list_profiles, create_profile, switch_profile, delete_profile, AvisoArranque, Perfil, RegistroPerfiles,
+};
+use crate::db::papelera::{
+    list_papelera, restaurar_papelera, purgar_papelera, get_dias_papelera, set_dias_papelera, ElementoPapelera,
+};
+use crate::db::numeracion::{
+    preview_renumeracion, aplicar_renumeracion, get_historial_numeros, get_numeracion_anio,
+    get_politica_numeracion, set_politica_numeracion, get_siguiente_numero,
+    list_reservas, reservar_numero, liberar_reserva,
+    CambioNumero, NumeroHistorico, OpcionesRenumeracion, PoliticaNumeracion, ReservaNumero,
+};
+use crate::db::membresia::{
+    get_periodos_hermano, dar_de_baja, readmitir_hermano, get_hermanos_activos_en_fecha, get_resumen_membresia,
+    PeriodoMembresia, ResumenMembresia, SolicitudBaja,
+};
+use crate::db::documentos::{
+    comprobar_documento, revisar_documentos, ComprobacionDocumento, IncidenciaDocumento, TipoDocumento,
+};
+use crate::db::sepa::{
+    list_mandatos_sepa, get_mandato_vigente, create_mandato_sepa, update_mandato_sepa, revocar_mandato_sepa,
+    get_acreedor_sepa, set_acreedor_sepa, normalizar_iban, AcreedorSepa, MandatoSepa,
+};
+use crate::db::remesas::{generar_remesa, list_remesas, get_cuotas_remesa, CuotaRemesa, Remesa, SolicitudRemesa};
+use crate::db::devoluciones::{
+    importar_devoluciones, get_devoluciones, Devolucion, ResumenDevoluciones, SolicitudDevoluciones,
+};
+use crate::db::conciliacion::{
+    importar_extracto, list_movimientos, get_cuotas_movimiento, proponer_conciliaciones, confirmar_conciliaciones,
+    descartar_movimiento, ConfirmacionConciliacion, EstadoMovimiento, ImportacionExtracto, MovimientoBancario,
+    PropuestaConciliacion,
+};
+use crate::db::importacion::{
+    previsualizar_importacion, simular_importacion, importar_hermanos, InformeImportacion, SolicitudImportacion,
+    VistaPreviaImportacion,
+};
+use crate::db::hermandad::{get_datos_hermandad, set_datos_hermandad, DatosHermandad};
+use crate::db::carnets::{generar_carnets, verificar_carnet, SolicitudCarnets, VerificacionCarnet};
+use crate::db::recibos::{generar_pdf_recibo, get_recibos_cuota, imprimir_recibos_periodo, list_recibos, Recibo};
+use crate::db::pagos::{anular_pago, create_pago, get_pagos_cuota, get_pagos_hermano, Pago, SolicitudPago};
+use crate::db::certificados::{
+    emitir_certificado, generar_pdf_certificado, get_plantillas_certificado, get_variables_certificado,
+    list_certificados, restablecer_plantilla_certificado, set_plantilla_certificado, Certificado,
+    PlantillaCertificado, SolicitudCertificado, TipoCertificado, VariableCertificado,
+};
+use crate::db::envios::{generar_envio, get_lista_envio, ListaEnvio, SolicitudEnvio};
+use crate::db::donativos::{
+    create_donante, create_donativo, delete_donante, delete_donativo, generar_certificados_donativos, generar_modelo182,
+    get_donantes, get_donativos, get_resumen_modelo182, update_donante, update_donativo, Donante, Donativo,
+    ResumenModelo182, SolicitudCertificadoDonativos, SolicitudModelo182,
+};
+use crate::db::tarifas::{
+    create_categoria_cuota, create_tarifa, delete_categoria_cuota, delete_tarifa, get_categorias_cuota,
+    get_categorias_hermanos, get_tarifas, set_categoria_hermano, update_categoria_cuota, update_tarifa,
+    CategoriaCuota, CategoriaHermano, Tarifa,
+};
+use crate::db::exportacion::{exportar, get_columnas_exportacion, ColumnaExportacion, EntidadExportacion, SolicitudExportacion};
+use crate::db::auditoria::{get_historial_hermano, get_historial_cuota, get_historial_familia, RegistroAuditoria};
+use crate::error::HermanarError;
 use serde_json::Value;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
 use tauri::State;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,192 +85,204 @@ pub struct HermanoConFamiliaData {
 
 // Comandos para Hermanos
 #[tauri::command]
-pub fn get_all_hermanos_cmd(db: State<DbConnection>) -> Result<Vec<Hermano>, String> {
+pub fn get_all_hermanos_cmd(db: State<DbConnection>) -> Result<Vec<Hermano>, HermanarError> {
     get_all_hermanos(&db)
-        .map_err(|e| format!("Error al obtener hermanos: {}", e))
 }
 
 #[tauri::command]
-pub fn get_hermanos_activos_cmd(db: State<DbConnection>) -> Result<Vec<Hermano>, String> {
+pub fn get_hermanos_activos_cmd(db: State<DbConnection>) -> Result<Vec<Hermano>, HermanarError> {
     get_hermanos_activos(&db)
-        .map_err(|e| format!("Error al obtener hermanos activos: {}", e))
 }
 
 #[tauri::command]
-pub fn get_hermano_by_id_cmd(db: State<DbConnection>, id: i32) -> Result<Option<Hermano>, String> {
+pub fn get_hermano_by_id_cmd(db: State<DbConnection>, id: i32) -> Result<Option<Hermano>, HermanarError> {
     get_hermano_by_id(&db, id)
-        .map_err(|e| format!("Error al obtener hermano: {}", e))
 }
 
 #[tauri::command]
-pub fn get_hermano_cmd(db: State<DbConnection>, id: i32) -> Result<Hermano, String> {
-    match get_hermano_by_id(&db, id) {
-        Ok(Some(hermano)) => Ok(hermano),
-        Ok(None) => Err("Hermano no encontrado".to_string()),
-        Err(e) => Err(format!("Error al obtener hermano: {}", e)),
-    }
+pub fn get_hermano_cmd(db: State<DbConnection>, id: i32) -> Result<Hermano, HermanarError> {
+    get_hermano_by_id(&db, id)?.ok_or_else(|| HermanarError::not_found("Hermano"))
 }
 
 #[tauri::command]
-pub fn search_hermanos_cmd(db: State<DbConnection>, query: String) -> Result<Vec<Hermano>, String> {
+pub fn search_hermanos_cmd(db: State<DbConnection>, query: String) -> Result<Vec<Hermano>, HermanarError> {
     search_hermanos(&db, &query)
-        .map_err(|e| format!("Error al buscar hermanos: {}", e))
 }
 
 #[tauri::command]
-pub fn create_hermano_cmd(db: State<DbConnection>, hermano: Hermano) -> Result<i32, String> {
+pub fn create_hermano_cmd(db: State<DbConnection>, hermano: Hermano) -> Result<i32, HermanarError> {
     create_hermano(&db, &hermano)
-        .map_err(|e| format!("Error al crear hermano: {}", e))
 }
 
 #[tauri::command]
-pub fn update_hermano_cmd(db: State<DbConnection>, id: i32, hermano: Hermano) -> Result<(), String> {
+pub fn update_hermano_cmd(db: State<DbConnection>, id: i32, hermano: Hermano) -> Result<(), HermanarError> {
     update_hermano(&db, id, &hermano)
-        .map_err(|e| format!("Error al actualizar hermano: {}", e))
 }
 
 #[tauri::command]
-pub fn update_hermano_familia_cmd(db: State<DbConnection>, hermano_id: i32, familia_id: Option<i32>) -> Result<(), String> {
+pub fn update_hermano_familia_cmd(db: State<DbConnection>, hermano_id: i32, familia_id: Option<i32>) -> Result<(), HermanarError> {
     update_hermano_familia(&db, hermano_id, familia_id)
-        .map_err(|e| format!("Error al actualizar familia del hermano: {}", e))
 }
 
 #[tauri::command]
-pub fn delete_hermano_cmd(db: State<DbConnection>, id: i32) -> Result<(), String> {
+pub fn delete_hermano_cmd(db: State<DbConnection>, id: i32) -> Result<(), HermanarError> {
     delete_hermano(&db, id)
-        .map_err(|e| format!("Error al eliminar hermano: {}", e))
 }
 
 #[tauri::command]
-pub fn set_hermano_inactive_cmd(db: State<DbConnection>, id: i32) -> Result<(), String> {
+pub fn set_hermano_inactive_cmd(db: State<DbConnection>, id: i32) -> Result<(), HermanarError> {
     set_hermano_inactive(&db, id)
-        .map_err(|e| format!("Error al dar de baja hermano: {}", e))
 }
 
 #[tauri::command]
-pub fn get_hermanos_by_familia_cmd(db: State<DbConnection>, familia_id: i32) -> Result<Vec<Hermano>, String> {
+pub fn get_hermanos_by_familia_cmd(db: State<DbConnection>, familia_id: i32) -> Result<Vec<Hermano>, HermanarError> {
     get_hermanos_by_familia(&db, familia_id)
-        .map_err(|e| format!("Error al obtener hermanos de la familia: {}", e))
 }
 
 // Comandos para Familias
 #[tauri::command]
-pub fn get_all_familias_cmd(db: State<DbConnection>) -> Result<Vec<Familia>, String> {
+pub fn get_all_familias_cmd(db: State<DbConnection>) -> Result<Vec<Familia>, HermanarError> {
     get_all_familias(&db)
-        .map_err(|e| format!("Error al obtener familias: {}", e))
 }
 
 #[tauri::command]
-pub fn get_familia_by_id_cmd(db: State<DbConnection>, id: i32) -> Result<Option<Familia>, String> {
+pub fn get_familia_by_id_cmd(db: State<DbConnection>, id: i32) -> Result<Option<Familia>, HermanarError> {
     get_familia_by_id(&db, id)
-        .map_err(|e| format!("Error al obtener familia: {}", e))
 }
 
 #[tauri::command]
-pub fn search_familias_cmd(db: State<DbConnection>, query: String) -> Result<Vec<Familia>, String> {
+pub fn search_familias_cmd(db: State<DbConnection>, query: String) -> Result<Vec<Familia>, HermanarError> {
     search_familias(&db, &query)
-        .map_err(|e| format!("Error al buscar familias: {}", e))
 }
 
 #[tauri::command]
-pub fn create_familia_cmd(db: State<DbConnection>, familia: Familia) -> Result<i32, String> {
+pub fn create_familia_cmd(db: State<DbConnection>, familia: Familia) -> Result<i32, HermanarError> {
     create_familia(&db, &familia)
-        .map_err(|e| format!("Error al crear familia: {}", e))
 }
 
 #[tauri::command]
-pub fn update_familia_cmd(db: State<DbConnection>, id: i32, familia: Familia) -> Result<(), String> {
+pub fn update_familia_cmd(db: State<DbConnection>, id: i32, familia: Familia) -> Result<(), HermanarError> {
     update_familia(&db, id, &familia)
-        .map_err(|e| format!("Error al actualizar familia: {}", e))
 }
 
 #[tauri::command]
-pub fn delete_familia_cmd(db: State<DbConnection>, id: i32) -> Result<(), String> {
+pub fn delete_familia_cmd(db: State<DbConnection>, id: i32) -> Result<(), HermanarError> {
     delete_familia(&db, id)
-        .map_err(|e| format!("Error al eliminar familia: {}", e))
 }
 
 #[tauri::command]
-pub fn get_familia_stats_cmd(db: State<DbConnection>, familia_id: i32) -> Result<(i32, i32), String> {
+pub fn get_familia_stats_cmd(db: State<DbConnection>, familia_id: i32) -> Result<(i32, i32), HermanarError> {
     get_familia_stats(&db, familia_id)
-        .map_err(|e| format!("Error al obtener estadísticas de familia: {}", e))
 }
 
 // Comandos para Cuotas
 #[tauri::command]
-pub fn get_all_cuotas_cmd(db: State<DbConnection>) -> Result<Vec<Cuota>, String> {
+pub fn get_all_cuotas_cmd(db: State<DbConnection>) -> Result<Vec<Cuota>, HermanarError> {
     get_all_cuotas(&db)
-        .map_err(|e| format!("Error al obtener cuotas: {}", e))
 }
 
 #[tauri::command]
-pub fn get_cuotas_by_hermano_cmd(db: State<DbConnection>, hermano_id: i32) -> Result<Vec<Cuota>, String> {
+pub fn get_cuotas_by_hermano_cmd(db: State<DbConnection>, hermano_id: i32) -> Result<Vec<Cuota>, HermanarError> {
     get_cuotas_by_hermano(&db, hermano_id)
-        .map_err(|e| format!("Error al obtener cuotas del hermano: {}", e))
 }
 
 #[tauri::command]
-pub fn get_cuotas_by_year_cmd(db: State<DbConnection>, anio: i32) -> Result<Vec<Cuota>, String> {
+pub fn get_cuotas_by_year_cmd(db: State<DbConnection>, anio: i32) -> Result<Vec<Cuota>, HermanarError> {
     get_cuotas_by_year(&db, anio)
-        .map_err(|e| format!("Error al obtener cuotas del año: {}", e))
 }
 
 #[tauri::command]
-pub fn get_cuotas_pendientes_cmd(db: State<DbConnection>) -> Result<Vec<Cuota>, String> {
+pub fn get_cuotas_pendientes_cmd(db: State<DbConnection>) -> Result<Vec<Cuota>, HermanarError> {
     get_cuotas_pendientes(&db)
-        .map_err(|e| format!("Error al obtener cuotas pendientes: {}", e))
 }
 
 #[tauri::command]
-pub fn create_cuota_cmd(db: State<DbConnection>, cuota: Cuota) -> Result<i32, String> {
+pub fn create_cuota_cmd(db: State<DbConnection>, cuota: Cuota) -> Result<i32, HermanarError> {
     create_cuota(&db, &cuota)
-        .map_err(|e| format!("Error al crear cuota: {}", e))
 }
 
 #[tauri::command]
-pub fn update_cuota_cmd(db: State<DbConnection>, id: i32, cuota: Cuota) -> Result<(), String> {
+pub fn update_cuota_cmd(db: State<DbConnection>, id: i32, cuota: Cuota) -> Result<(), HermanarError> {
     update_cuota(&db, id, &cuota)
-        .map_err(|e| format!("Error al actualizar cuota: {}", e))
 }
 
 #[tauri::command]
-pub fn marcar_cuota_pagada_cmd(db: State<DbConnection>, id: i32, fecha_pago: String, metodo_pago: String) -> Result<(), String> {
+pub fn marcar_cuota_pagada_cmd(db: State<DbConnection>, id: i32, fecha_pago: String, metodo_pago: String) -> Result<(), HermanarError> {
     marcar_cuota_pagada(&db, id, &fecha_pago, &metodo_pago)
-        .map_err(|e| format!("Error al marcar cuota como pagada: {}", e))
 }
 
 #[tauri::command]
-pub fn delete_cuota_cmd(db: State<DbConnection>, id: i32) -> Result<(), String> {
+pub fn delete_cuota_cmd(db: State<DbConnection>, id: i32) -> Result<(), HermanarError> {
     delete_cuota(&db, id)
-        .map_err(|e| format!("Error al eliminar cuota: {}", e))
 }
 
 #[tauri::command]
-pub fn generar_cuotas_trimestre_cmd(db: State<DbConnection>, anio: i32, trimestre: i32, importe: f64) -> Result<i32, String> {
+pub fn generar_cuotas_trimestre_cmd(
+    db: State<DbConnection>,
+    anio: i32,
+    trimestre: i32,
+    importe: Option<f64>,
+) -> Result<i32, HermanarError> {
     generar_cuotas_trimestre(&db, anio, trimestre, importe)
-        .map_err(|e| format!("Error al generar cuotas: {}", e))
 }
 
 #[tauri::command]
-pub fn get_estadisticas_cuotas_cmd(db: State<DbConnection>, anio: Option<i32>) -> Result<EstadisticasCuotas, String> {
+pub fn generar_cuotas_periodo_cmd(
+    db: State<DbConnection>,
+    anio: i32,
+    periodicidad: Periodicidad,
+    periodo: i32,
+    importe: Option<f64>,
+) -> Result<i32, HermanarError> {
+    generar_cuotas_periodo(&db, anio, periodicidad, periodo, importe)
+}
+
+#[tauri::command]
+pub fn get_periodicidad_cuotas_cmd(db: State<DbConnection>) -> Result<Periodicidad, HermanarError> {
+    get_periodicidad_cuotas(&db)
+}
+
+#[tauri::command]
+pub fn set_periodicidad_cuotas_cmd(db: State<DbConnection>, periodicidad: Periodicidad) -> Result<(), HermanarError> {
+    set_periodicidad_cuotas(&db, periodicidad)
+}
+
+#[tauri::command]
+pub fn get_periodicidades_hermanos_cmd(db: State<DbConnection>) -> Result<HashMap<i32, Periodicidad>, HermanarError> {
+    get_periodicidades_hermanos(&db)
+}
+
+#[tauri::command]
+pub fn set_periodicidad_hermano_cmd(
+    db: State<DbConnection>,
+    hermano_id: i32,
+    periodicidad: Option<Periodicidad>,
+) -> Result<(), HermanarError> {
+    set_periodicidad_hermano(&db, hermano_id, periodicidad)
+}
+
+#[tauri::command]
+pub fn get_estadisticas_cuotas_cmd(db: State<DbConnection>, anio: Option<i32>) -> Result<EstadisticasCuotas, HermanarError> {
     get_estadisticas_cuotas(&db, anio)
-        .map_err(|e| format!("Error al obtener estadísticas: {}", e))
 }
 
 #[tauri::command]
-pub fn get_familia_with_hermanos_cmd(db: State<DbConnection>, id: i32) -> Result<Option<Familia>, String> {
+pub fn set_cuotas_deducibles_cmd(db: State<DbConnection>, anio: i32, deducible: bool) -> Result<usize, HermanarError> {
+    set_cuotas_deducibles(&db, anio, deducible)
+}
+
+#[tauri::command]
+pub fn get_familia_with_hermanos_cmd(db: State<DbConnection>, id: i32) -> Result<Option<Familia>, HermanarError> {
     get_familia_with_hermanos(&db, id)
-        .map_err(|e| format!("Error al obtener familia con hermanos: {}", e))
 }
 
 #[tauri::command]
-pub fn get_familia_with_address_cmd(db: State<DbConnection>, id: i32) -> Result<Option<Value>, String> {
+pub fn get_familia_with_address_cmd(db: State<DbConnection>, id: i32) -> Result<Option<Value>, HermanarError> {
     get_familia_with_address(&db, id)
-        .map_err(|e| format!("Error al obtener familia con dirección: {}", e))
 }
 
 #[tauri::command]
-pub fn create_hermano_con_familia_cmd(db: State<DbConnection>, data: HermanoConFamiliaData) -> Result<i32, String> {
+pub fn create_hermano_con_familia_cmd(db: State<DbConnection>, data: HermanoConFamiliaData) -> Result<i32, HermanarError> {
     let nueva_familia_nombre = data.nueva_familia_nombre.clone();
     let hermano = data.hermano;
 
@@ -219,10 +295,7 @@ pub fn create_hermano_con_familia_cmd(db: State<DbConnection>, data: HermanoConF
             updated_at: None,
         };
 
-        match create_familia(&db, &nueva_familia) {
-            Ok(familia_id) => Some(familia_id),
-            Err(e) => return Err(format!("Error al crear familia '{}': {}", nombre_familia, e)),
-        }
+        Some(create_familia(&db, &nueva_familia)?)
     } else {
         hermano.familia_id
     };
@@ -230,10 +303,7 @@ pub fn create_hermano_con_familia_cmd(db: State<DbConnection>, data: HermanoConF
     let mut hermano_para_crear = hermano;
     hermano_para_crear.familia_id = familia_id;
 
-    let hermano_id = match create_hermano(&db, &hermano_para_crear) {
-        Ok(id) => id,
-        Err(e) => return Err(format!("Error al crear hermano: {}", e)),
-    };
+    let hermano_id = create_hermano(&db, &hermano_para_crear)?;
 
     if let (Some(familia_id_nueva), Some(nombre_familia)) = (familia_id, nueva_familia_nombre) {
         let familia_actualizada = Familia {
@@ -251,3 +321,591 @@ pub fn create_hermano_con_familia_cmd(db: State<DbConnection>, data: HermanoConF
 
     Ok(hermano_id)
 }
+
+// Comandos de pagos
+#[tauri::command]
+pub fn get_pagos_cuota_cmd(db: State<DbConnection>, cuota_id: i32) -> Result<Vec<Pago>, HermanarError> {
+    get_pagos_cuota(&db, cuota_id)
+}
+
+#[tauri::command]
+pub fn get_pagos_hermano_cmd(db: State<DbConnection>, hermano_id: i32) -> Result<Vec<Pago>, HermanarError> {
+    get_pagos_hermano(&db, hermano_id)
+}
+
+#[tauri::command]
+pub fn create_pago_cmd(db: State<DbConnection>, solicitud: SolicitudPago) -> Result<Pago, HermanarError> {
+    create_pago(&db, &solicitud)
+}
+
+#[tauri::command]
+pub fn anular_pago_cmd(db: State<DbConnection>, id: i64, motivo: Option<String>) -> Result<(), HermanarError> {
+    anular_pago(&db, id, motivo)
+}
+
+// Comandos de mantenimiento
+#[tauri::command]
+pub fn reparar_integridad_cmd(db: State<DbConnection>) -> Result<InformeIntegridad, HermanarError> {
+    reparar_integridad(&db)
+}
+
+#[tauri::command]
+pub fn backup_database_cmd(db: State<DbConnection>, dest: String) -> Result<(), HermanarError> {
+    backup_database(&db, Path::new(&dest))
+}
+
+#[tauri::command]
+pub fn restore_database_cmd(db: State<DbConnection>, src: String) -> Result<(), HermanarError> {
+    restore_database(&db, Path::new(&src))
+}
+
+#[tauri::command]
+pub fn list_backups_cmd(db: State<DbConnection>) -> Result<Vec<CopiaSeguridad>, HermanarError> {
+    list_backups(&db)
+}
+
+// Comandos de perfiles
+#[tauri::command]
+pub fn list_profiles_cmd() -> Result<RegistroPerfiles, HermanarError> {
+    list_profiles()
+}
+
+#[tauri::command]
+pub fn create_profile_cmd(nombre: String, ruta: Option<String>) -> Result<Perfil, HermanarError> {
+    create_profile(&nombre, ruta.as_deref())
+}
+
+#[tauri::command]
+pub fn switch_profile_cmd(db: State<DbConnection>, nombre: String) -> Result<Perfil, HermanarError> {
+    switch_profile(&db, &nombre)
+}
+
+#[tauri::command]
+pub fn delete_profile_cmd(nombre: String) -> Result<(), HermanarError> {
+    delete_profile(&nombre)
+}
+
+#[tauri::command]
+pub fn take_aviso_arranque_cmd(aviso: State<AvisoArranque>) -> Option<String> {
+    aviso.tomar()
+}
+
+// Comandos de auditoría
+#[tauri::command]
+pub fn get_historial_hermano_cmd(db: State<DbConnection>, id: i32) -> Result<Vec<RegistroAuditoria>, HermanarError> {
+    get_historial_hermano(&db, id)
+}
+
+#[tauri::command]
+pub fn get_historial_cuota_cmd(db: State<DbConnection>, id: i32) -> Result<Vec<RegistroAuditoria>, HermanarError> {
+    get_historial_cuota(&db, id)
+}
+
+#[tauri::command]
+pub fn get_historial_familia_cmd(db: State<DbConnection>, id: i32) -> Result<Vec<RegistroAuditoria>, HermanarError> {
+    get_historial_familia(&db, id)
+}
+
+#[tauri::command]
+pub fn get_operador_cmd(db: State<DbConnection>) -> Result<String, HermanarError> {
+    Ok(db.operador())
+}
+
+#[tauri::command]
+pub fn set_operador_cmd(db: State<DbConnection>, nombre: String) -> Result<(), HermanarError> {
+    if nombre.trim().is_empty() {
+        return Err(HermanarError::validation("nombre", "El nombre del operador es obligatorio"));
+    }
+    db.set_operador(&nombre);
+    Ok(())
+}
+
+// Comandos de papelera
+#[tauri::command]
+pub fn list_papelera_cmd(db: State<DbConnection>) -> Result<Vec<ElementoPapelera>, HermanarError> {
+    list_papelera(&db)
+}
+
+#[tauri::command]
+pub fn restaurar_papelera_cmd(db: State<DbConnection>, id: i64) -> Result<ElementoPapelera, HermanarError> {
+    restaurar_papelera(&db, id)
+}
+
+#[tauri::command]
+pub fn purgar_papelera_cmd(db: State<DbConnection>, dias: Option<i64>) -> Result<usize, HermanarError> {
+    purgar_papelera(&db, dias)
+}
+
+#[tauri::command]
+pub fn get_dias_papelera_cmd(db: State<DbConnection>) -> Result<i64, HermanarError> {
+    get_dias_papelera(&db)
+}
+
+#[tauri::command]
+pub fn set_dias_papelera_cmd(db: State<DbConnection>, dias: i64) -> Result<(), HermanarError> {
+    set_dias_papelera(&db, dias)
+}
+
+// Comandos de numeración
+#[tauri::command]
+pub fn preview_renumeracion_cmd(db: State<DbConnection>, opciones: OpcionesRenumeracion) -> Result<Vec<CambioNumero>, HermanarError> {
+    preview_renumeracion(&db, &opciones)
+}
+
+#[tauri::command]
+pub fn aplicar_renumeracion_cmd(db: State<DbConnection>, opciones: OpcionesRenumeracion) -> Result<Vec<CambioNumero>, HermanarError> {
+    aplicar_renumeracion(&db, &opciones)
+}
+
+#[tauri::command]
+pub fn get_historial_numeros_cmd(db: State<DbConnection>, hermano_id: i32) -> Result<Vec<NumeroHistorico>, HermanarError> {
+    get_historial_numeros(&db, hermano_id)
+}
+
+#[tauri::command]
+pub fn get_numeracion_anio_cmd(db: State<DbConnection>, anio: i32) -> Result<Vec<CambioNumero>, HermanarError> {
+    get_numeracion_anio(&db, anio)
+}
+
+#[tauri::command]
+pub fn get_politica_numeracion_cmd(db: State<DbConnection>) -> Result<PoliticaNumeracion, HermanarError> {
+    get_politica_numeracion(&db)
+}
+
+#[tauri::command]
+pub fn set_politica_numeracion_cmd(db: State<DbConnection>, politica: PoliticaNumeracion) -> Result<(), HermanarError> {
+    set_politica_numeracion(&db, &politica)
+}
+
+#[tauri::command]
+pub fn get_siguiente_numero_cmd(db: State<DbConnection>) -> Result<String, HermanarError> {
+    get_siguiente_numero(&db)
+}
+
+#[tauri::command]
+pub fn list_reservas_cmd(db: State<DbConnection>) -> Result<Vec<ReservaNumero>, HermanarError> {
+    list_reservas(&db)
+}
+
+#[tauri::command]
+pub fn reservar_numero_cmd(
+    db: State<DbConnection>,
+    solicitante: String,
+    numero: Option<String>,
+    observaciones: Option<String>,
+) -> Result<ReservaNumero, HermanarError> {
+    reservar_numero(&db, &solicitante, numero.as_deref(), observaciones.as_deref())
+}
+
+#[tauri::command]
+pub fn liberar_reserva_cmd(db: State<DbConnection>, numero: String) -> Result<(), HermanarError> {
+    liberar_reserva(&db, &numero)
+}
+
+// Comandos de altas y bajas
+#[tauri::command]
+pub fn get_periodos_hermano_cmd(db: State<DbConnection>, hermano_id: i32) -> Result<Vec<PeriodoMembresia>, HermanarError> {
+    get_periodos_hermano(&db, hermano_id)
+}
+
+#[tauri::command]
+pub fn dar_de_baja_cmd(db: State<DbConnection>, hermano_id: i32, baja: SolicitudBaja) -> Result<(), HermanarError> {
+    dar_de_baja(&db, hermano_id, &baja)
+}
+
+#[tauri::command]
+pub fn readmitir_hermano_cmd(
+    db: State<DbConnection>,
+    hermano_id: i32,
+    fecha: String,
+    observaciones: Option<String>,
+) -> Result<(), HermanarError> {
+    readmitir_hermano(&db, hermano_id, &fecha, observaciones.as_deref())
+}
+
+#[tauri::command]
+pub fn get_hermanos_activos_en_fecha_cmd(db: State<DbConnection>, fecha: String) -> Result<Vec<Hermano>, HermanarError> {
+    get_hermanos_activos_en_fecha(&db, &fecha)
+}
+
+#[tauri::command]
+pub fn get_resumen_membresia_cmd(db: State<DbConnection>, anio: i32) -> Result<ResumenMembresia, HermanarError> {
+    get_resumen_membresia(&db, anio)
+}
+
+// Comandos de documentos de identidad
+#[tauri::command]
+pub fn comprobar_documento_cmd(
+    db: State<DbConnection>,
+    valor: String,
+    tipo: Option<TipoDocumento>,
+    hermano_id: Option<i32>,
+) -> Result<ComprobacionDocumento, HermanarError> {
+    comprobar_documento(&db, &valor, tipo, hermano_id)
+}
+
+#[tauri::command]
+pub fn revisar_documentos_cmd(db: State<DbConnection>) -> Result<Vec<IncidenciaDocumento>, HermanarError> {
+    revisar_documentos(&db)
+}
+
+// Comandos de domiciliaciones SEPA
+#[tauri::command]
+pub fn list_mandatos_sepa_cmd(db: State<DbConnection>) -> Result<Vec<MandatoSepa>, HermanarError> {
+    list_mandatos_sepa(&db)
+}
+
+#[tauri::command]
+pub fn get_mandato_vigente_cmd(db: State<DbConnection>, hermano_id: i32) -> Result<Option<MandatoSepa>, HermanarError> {
+    get_mandato_vigente(&db, hermano_id)
+}
+
+#[tauri::command]
+pub fn create_mandato_sepa_cmd(db: State<DbConnection>, mandato: MandatoSepa) -> Result<i64, HermanarError> {
+    create_mandato_sepa(&db, &mandato)
+}
+
+#[tauri::command]
+pub fn update_mandato_sepa_cmd(db: State<DbConnection>, id: i64, mandato: MandatoSepa) -> Result<(), HermanarError> {
+    update_mandato_sepa(&db, id, &mandato)
+}
+
+#[tauri::command]
+pub fn revocar_mandato_sepa_cmd(db: State<DbConnection>, id: i64) -> Result<(), HermanarError> {
+    revocar_mandato_sepa(&db, id)
+}
+
+#[tauri::command]
+pub fn validar_iban_cmd(iban: String) -> Result<String, HermanarError> {
+    normalizar_iban(&iban).map_err(|e| HermanarError::validation("iban", e))
+}
+
+#[tauri::command]
+pub fn get_acreedor_sepa_cmd(db: State<DbConnection>) -> Result<Option<AcreedorSepa>, HermanarError> {
+    get_acreedor_sepa(&db)
+}
+
+#[tauri::command]
+pub fn set_acreedor_sepa_cmd(db: State<DbConnection>, acreedor: AcreedorSepa) -> Result<(), HermanarError> {
+    set_acreedor_sepa(&db, &acreedor)
+}
+
+#[tauri::command]
+pub fn generar_remesa_cmd(db: State<DbConnection>, solicitud: SolicitudRemesa) -> Result<Remesa, HermanarError> {
+    generar_remesa(&db, &solicitud)
+}
+
+#[tauri::command]
+pub fn list_remesas_cmd(db: State<DbConnection>) -> Result<Vec<Remesa>, HermanarError> {
+    list_remesas(&db)
+}
+
+#[tauri::command]
+pub fn get_cuotas_remesa_cmd(db: State<DbConnection>, remesa_id: i64) -> Result<Vec<CuotaRemesa>, HermanarError> {
+    get_cuotas_remesa(&db, remesa_id)
+}
+
+#[tauri::command]
+pub fn importar_devoluciones_cmd(
+    db: State<DbConnection>,
+    solicitud: SolicitudDevoluciones,
+) -> Result<ResumenDevoluciones, HermanarError> {
+    importar_devoluciones(&db, &solicitud)
+}
+
+#[tauri::command]
+pub fn get_devoluciones_cmd(
+    db: State<DbConnection>,
+    remesa_id: Option<i64>,
+    solo_pendientes: Option<bool>,
+) -> Result<Vec<Devolucion>, HermanarError> {
+    get_devoluciones(&db, remesa_id, solo_pendientes.unwrap_or(false))
+}
+
+// Comandos de conciliación bancaria
+#[tauri::command]
+pub fn importar_extracto_cmd(db: State<DbConnection>, fichero: String) -> Result<ImportacionExtracto, HermanarError> {
+    importar_extracto(&db, &fichero)
+}
+
+#[tauri::command]
+pub fn list_movimientos_cmd(
+    db: State<DbConnection>,
+    estado: Option<EstadoMovimiento>,
+) -> Result<Vec<MovimientoBancario>, HermanarError> {
+    list_movimientos(&db, estado)
+}
+
+#[tauri::command]
+pub fn get_cuotas_movimiento_cmd(db: State<DbConnection>, movimiento_id: i64) -> Result<Vec<Cuota>, HermanarError> {
+    get_cuotas_movimiento(&db, movimiento_id)
+}
+
+#[tauri::command]
+pub fn proponer_conciliaciones_cmd(db: State<DbConnection>) -> Result<Vec<PropuestaConciliacion>, HermanarError> {
+    proponer_conciliaciones(&db)
+}
+
+#[tauri::command]
+pub fn confirmar_conciliaciones_cmd(
+    db: State<DbConnection>,
+    confirmaciones: Vec<ConfirmacionConciliacion>,
+) -> Result<i32, HermanarError> {
+    confirmar_conciliaciones(&db, &confirmaciones)
+}
+
+#[tauri::command]
+pub fn descartar_movimiento_cmd(db: State<DbConnection>, id: i64) -> Result<(), HermanarError> {
+    descartar_movimiento(&db, id)
+}
+
+// Comandos de importación
+#[tauri::command]
+pub fn previsualizar_importacion_cmd(
+    fichero: String,
+    hoja: Option<String>,
+) -> Result<VistaPreviaImportacion, HermanarError> {
+    previsualizar_importacion(&fichero, hoja.as_deref())
+}
+
+#[tauri::command]
+pub fn simular_importacion_cmd(
+    db: State<DbConnection>,
+    solicitud: SolicitudImportacion,
+) -> Result<InformeImportacion, HermanarError> {
+    simular_importacion(&db, &solicitud)
+}
+
+#[tauri::command]
+pub fn importar_hermanos_cmd(
+    db: State<DbConnection>,
+    solicitud: SolicitudImportacion,
+) -> Result<InformeImportacion, HermanarError> {
+    importar_hermanos(&db, &solicitud)
+}
+
+// Comandos de exportación
+#[tauri::command]
+pub fn get_columnas_exportacion_cmd(entidad: EntidadExportacion) -> Vec<ColumnaExportacion> {
+    get_columnas_exportacion(entidad)
+}
+
+#[tauri::command]
+pub fn exportar_cmd(db: State<DbConnection>, solicitud: SolicitudExportacion) -> Result<usize, HermanarError> {
+    exportar(&db, &solicitud)
+}
+
+// Comandos de documentos impresos
+#[tauri::command]
+pub fn get_datos_hermandad_cmd(db: State<DbConnection>) -> Result<DatosHermandad, HermanarError> {
+    get_datos_hermandad(&db)
+}
+
+#[tauri::command]
+pub fn set_datos_hermandad_cmd(db: State<DbConnection>, datos: DatosHermandad) -> Result<(), HermanarError> {
+    set_datos_hermandad(&db, &datos)
+}
+
+#[tauri::command]
+pub fn generar_carnets_cmd(db: State<DbConnection>, solicitud: SolicitudCarnets) -> Result<usize, HermanarError> {
+    generar_carnets(&db, &solicitud)
+}
+
+#[tauri::command]
+pub fn verificar_carnet_cmd(db: State<DbConnection>, codigo: String) -> Result<VerificacionCarnet, HermanarError> {
+    verificar_carnet(&db, &codigo)
+}
+
+#[tauri::command]
+pub fn get_recibos_cuota_cmd(db: State<DbConnection>, cuota_id: i32) -> Result<Vec<Recibo>, HermanarError> {
+    get_recibos_cuota(&db, cuota_id)
+}
+
+#[tauri::command]
+pub fn list_recibos_cmd(db: State<DbConnection>, anio: i32) -> Result<Vec<Recibo>, HermanarError> {
+    list_recibos(&db, anio)
+}
+
+#[tauri::command]
+pub fn generar_pdf_recibo_cmd(db: State<DbConnection>, id: i64, fichero: String) -> Result<(), HermanarError> {
+    generar_pdf_recibo(&db, id, &fichero)
+}
+
+#[tauri::command]
+pub fn imprimir_recibos_periodo_cmd(
+    db: State<DbConnection>,
+    anio: i32,
+    periodicidad: Periodicidad,
+    periodo: i32,
+    fichero: String,
+) -> Result<usize, HermanarError> {
+    imprimir_recibos_periodo(&db, anio, periodicidad, periodo, &fichero)
+}
+
+// Comandos de certificados
+#[tauri::command]
+pub fn get_variables_certificado_cmd() -> Vec<VariableCertificado> {
+    get_variables_certificado()
+}
+
+#[tauri::command]
+pub fn get_plantillas_certificado_cmd(db: State<DbConnection>) -> Result<Vec<PlantillaCertificado>, HermanarError> {
+    get_plantillas_certificado(&db)
+}
+
+#[tauri::command]
+pub fn set_plantilla_certificado_cmd(db: State<DbConnection>, plantilla: PlantillaCertificado) -> Result<(), HermanarError> {
+    set_plantilla_certificado(&db, &plantilla)
+}
+
+#[tauri::command]
+pub fn restablecer_plantilla_certificado_cmd(db: State<DbConnection>, tipo: TipoCertificado) -> Result<(), HermanarError> {
+    restablecer_plantilla_certificado(&db, tipo)
+}
+
+#[tauri::command]
+pub fn emitir_certificado_cmd(db: State<DbConnection>, solicitud: SolicitudCertificado) -> Result<Certificado, HermanarError> {
+    emitir_certificado(&db, &solicitud)
+}
+
+#[tauri::command]
+pub fn list_certificados_cmd(db: State<DbConnection>, hermano_id: Option<i32>) -> Result<Vec<Certificado>, HermanarError> {
+    list_certificados(&db, hermano_id)
+}
+
+#[tauri::command]
+pub fn generar_pdf_certificado_cmd(db: State<DbConnection>, id: i32, fichero: String) -> Result<(), HermanarError> {
+    generar_pdf_certificado(&db, id, &fichero)
+}
+
+// Comandos de envíos
+#[tauri::command]
+pub fn get_lista_envio_cmd(db: State<DbConnection>) -> Result<ListaEnvio, HermanarError> {
+    get_lista_envio(&db)
+}
+
+#[tauri::command]
+pub fn generar_envio_cmd(db: State<DbConnection>, solicitud: SolicitudEnvio) -> Result<usize, HermanarError> {
+    generar_envio(&db, &solicitud)
+}
+
+// Comandos de donativos y modelo 182
+#[tauri::command]
+pub fn get_donantes_cmd(db: State<DbConnection>) -> Result<Vec<Donante>, HermanarError> {
+    get_donantes(&db)
+}
+
+#[tauri::command]
+pub fn create_donante_cmd(db: State<DbConnection>, donante: Donante) -> Result<i32, HermanarError> {
+    create_donante(&db, &donante)
+}
+
+#[tauri::command]
+pub fn update_donante_cmd(db: State<DbConnection>, id: i32, donante: Donante) -> Result<(), HermanarError> {
+    update_donante(&db, id, &donante)
+}
+
+#[tauri::command]
+pub fn delete_donante_cmd(db: State<DbConnection>, id: i32) -> Result<(), HermanarError> {
+    delete_donante(&db, id)
+}
+
+#[tauri::command]
+pub fn get_donativos_cmd(
+    db: State<DbConnection>,
+    anio: Option<i32>,
+    donante_id: Option<i32>,
+) -> Result<Vec<Donativo>, HermanarError> {
+    get_donativos(&db, anio, donante_id)
+}
+
+#[tauri::command]
+pub fn create_donativo_cmd(db: State<DbConnection>, donativo: Donativo) -> Result<i32, HermanarError> {
+    create_donativo(&db, &donativo)
+}
+
+#[tauri::command]
+pub fn update_donativo_cmd(db: State<DbConnection>, id: i32, donativo: Donativo) -> Result<(), HermanarError> {
+    update_donativo(&db, id, &donativo)
+}
+
+#[tauri::command]
+pub fn delete_donativo_cmd(db: State<DbConnection>, id: i32) -> Result<(), HermanarError> {
+    delete_donativo(&db, id)
+}
+
+#[tauri::command]
+pub fn get_resumen_modelo182_cmd(db: State<DbConnection>, anio: i32) -> Result<ResumenModelo182, HermanarError> {
+    get_resumen_modelo182(&db, anio)
+}
+
+#[tauri::command]
+pub fn generar_modelo182_cmd(db: State<DbConnection>, solicitud: SolicitudModelo182) -> Result<ResumenModelo182, HermanarError> {
+    generar_modelo182(&db, &solicitud)
+}
+
+#[tauri::command]
+pub fn generar_certificados_donativos_cmd(
+    db: State<DbConnection>,
+    solicitud: SolicitudCertificadoDonativos,
+) -> Result<usize, HermanarError> {
+    generar_certificados_donativos(&db, &solicitud)
+}
+
+// Comandos de categorías y tarifas
+#[tauri::command]
+pub fn get_categorias_cuota_cmd(db: State<DbConnection>) -> Result<Vec<CategoriaCuota>, HermanarError> {
+    get_categorias_cuota(&db)
+}
+
+#[tauri::command]
+pub fn create_categoria_cuota_cmd(db: State<DbConnection>, categoria: CategoriaCuota) -> Result<i32, HermanarError> {
+    create_categoria_cuota(&db, &categoria)
+}
+
+#[tauri::command]
+pub fn update_categoria_cuota_cmd(db: State<DbConnection>, id: i32, categoria: CategoriaCuota) -> Result<(), HermanarError> {
+    update_categoria_cuota(&db, id, &categoria)
+}
+
+#[tauri::command]
+pub fn delete_categoria_cuota_cmd(db: State<DbConnection>, id: i32) -> Result<(), HermanarError> {
+    delete_categoria_cuota(&db, id)
+}
+
+#[tauri::command]
+pub fn get_tarifas_cmd(db: State<DbConnection>, categoria_id: Option<i32>) -> Result<Vec<Tarifa>, HermanarError> {
+    get_tarifas(&db, categoria_id)
+}
+
+#[tauri::command]
+pub fn create_tarifa_cmd(db: State<DbConnection>, tarifa: Tarifa) -> Result<i32, HermanarError> {
+    create_tarifa(&db, &tarifa)
+}
+
+#[tauri::command]
+pub fn update_tarifa_cmd(db: State<DbConnection>, id: i32, tarifa: Tarifa) -> Result<(), HermanarError> {
+    update_tarifa(&db, id, &tarifa)
+}
+
+#[tauri::command]
+pub fn delete_tarifa_cmd(db: State<DbConnection>, id: i32) -> Result<(), HermanarError> {
+    delete_tarifa(&db, id)
+}
+
+#[tauri::command]
+pub fn set_categoria_hermano_cmd(
+    db: State<DbConnection>,
+    hermano_id: i32,
+    categoria_id: Option<i32>,
+) -> Result<(), HermanarError> {
+    set_categoria_hermano(&db, hermano_id, categoria_id)
+}
+
+#[tauri::command]
+pub fn get_categorias_hermanos_cmd(
+    db: State<DbConnection>,
+    fecha: Option<String>,
+) -> Result<Vec<CategoriaHermano>, HermanarError> {
+    get_categorias_hermanos(&db, fecha)
+}
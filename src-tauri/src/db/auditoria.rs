@@ -0,0 +1,117 @@
+use rusqlite::{params, Connection, Result, Row};
+use serde::{Deserialize, Serialize};
+use crate::db::DbConnection;
+use crate::error::{HermanarError, HermanarResult};
+
+pub const ENTIDAD_HERMANO: &str = "hermano";
+pub const ENTIDAD_FAMILIA: &str = "familia";
+pub const ENTIDAD_CUOTA: &str = "cuota";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistroAuditoria {
+    pub id: i64,
+    pub entidad: String,
+    pub entidad_id: i32,
+    pub operacion: String,
+    pub valores_anteriores: Option<serde_json::Value>,
+    pub valores_nuevos: Option<serde_json::Value>,
+    pub operador: String,
+    pub fecha: String,
+}
+
+impl RegistroAuditoria {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        Ok(RegistroAuditoria {
+            id: row.get(0)?,
+            entidad: row.get(1)?,
+            entidad_id: row.get(2)?,
+            operacion: row.get(3)?,
+            valores_anteriores: json(row.get(4)?),
+            valores_nuevos: json(row.get(5)?),
+            operador: row.get(6)?,
+            fecha: row.get(7)?,
+        })
+    }
+}
+
+fn json(valor: Option<String>) -> Option<serde_json::Value> {
+    valor.and_then(|v| serde_json::from_str(&v).ok())
+}
+
+/// Anota un cambio en la tabla de auditoría. La operación se deduce de los
+/// valores: sin anteriores es una creación, sin nuevos una eliminación y con
+/// ambos una actualización. Debe llamarse dentro de la misma transacción que
+/// el cambio para que ambos se confirmen o se deshagan juntos.
+pub fn registrar<T: Serialize>(
+    conn: &Connection,
+    operador: &str,
+    entidad: &str,
+    entidad_id: i32,
+    antes: Option<&T>,
+    despues: Option<&T>,
+) -> HermanarResult<()> {
+    let operacion = match (antes, despues) {
+        (None, _) => "crear",
+        (Some(_), None) => "eliminar",
+        (Some(_), Some(_)) => "actualizar",
+    };
+
+    let antes = antes.map(serde_json::to_string).transpose()
+        .map_err(|e| HermanarError::database(e.to_string()))?;
+    let despues = despues.map(serde_json::to_string).transpose()
+        .map_err(|e| HermanarError::database(e.to_string()))?;
+
+    conn.prepare_cached(
+        "INSERT INTO auditoria
+         (entidad, entidad_id, operacion, valores_anteriores, valores_nuevos, operador)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?
+    .execute(params![entidad, entidad_id, operacion, antes, despues, operador])?;
+
+    Ok(())
+}
+
+/// Historial de un hermano, incluidos los cambios en sus cuotas, del más
+/// reciente al más antiguo.
+pub fn get_historial_hermano(db: &DbConnection, hermano_id: i32) -> HermanarResult<Vec<RegistroAuditoria>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, entidad, entidad_id, operacion, valores_anteriores, valores_nuevos, operador, fecha
+         FROM auditoria
+         WHERE (entidad = ?1 AND entidad_id = ?2)
+            OR (entidad = ?3 AND json_extract(COALESCE(valores_nuevos, valores_anteriores), '$.hermano_id') = ?2)
+         ORDER BY id DESC"
+    )?;
+
+    let registros = stmt.query_map(params![ENTIDAD_HERMANO, hermano_id, ENTIDAD_CUOTA], |row| {
+        RegistroAuditoria::from_row(row)
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(registros)
+}
+
+pub fn get_historial_cuota(db: &DbConnection, cuota_id: i32) -> HermanarResult<Vec<RegistroAuditoria>> {
+    get_historial(db, ENTIDAD_CUOTA, cuota_id)
+}
+
+pub fn get_historial_familia(db: &DbConnection, familia_id: i32) -> HermanarResult<Vec<RegistroAuditoria>> {
+    get_historial(db, ENTIDAD_FAMILIA, familia_id)
+}
+
+fn get_historial(db: &DbConnection, entidad: &str, entidad_id: i32) -> HermanarResult<Vec<RegistroAuditoria>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, entidad, entidad_id, operacion, valores_anteriores, valores_nuevos, operador, fecha
+         FROM auditoria
+         WHERE entidad = ?1 AND entidad_id = ?2
+         ORDER BY id DESC"
+    )?;
+
+    let registros = stmt.query_map(params![entidad, entidad_id], |row| {
+        RegistroAuditoria::from_row(row)
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(registros)
+}
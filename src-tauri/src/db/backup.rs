@@ -0,0 +1,192 @@
+use rusqlite::{Connection, OpenFlags, MAIN_DB};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use crate::db::{configurar_conexion, migrations, DbConnection};
+use crate::error::{HermanarError, HermanarResult};
+
+// Copias automáticas que se conservan de cada tipo
+const RETENCION_DIARIAS: usize = 7;
+const RETENCION_SEMANALES: usize = 4;
+const RETENCION_MENSUALES: usize = 12;
+
+const DIRECTORIO_COPIAS: &str = "copias";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CopiaSeguridad {
+    pub ruta: String,
+    pub tipo: String,
+    pub periodo: String,
+    pub tamano: u64,
+}
+
+/// Copia la base de datos en uso a `destino` con la API de copia en caliente de
+/// SQLite, sin cerrar la conexión de la aplicación.
+pub fn backup_database(db: &DbConnection, destino: &Path) -> HermanarResult<()> {
+    if db.path() == destino {
+        return Err(HermanarError::validation("destino", "El destino no puede ser la propia base de datos"));
+    }
+
+    db.reader().backup(MAIN_DB, destino, None)?;
+
+    Ok(())
+}
+
+/// Sustituye el contenido de la base de datos en uso por el de `origen`.
+///
+/// Antes de restaurar se comprueba que el fichero sea una base de datos de
+/// Hermanar íntegra y de una versión de esquema que esta aplicación entienda,
+/// y se guarda una copia del estado actual. Si la copia es de una versión
+/// anterior, se migra tras restaurarla.
+pub fn restore_database(db: &DbConnection, origen: &Path) -> HermanarResult<()> {
+    validar_copia(origen)?;
+
+    let ruta = db.path();
+    let previa = directorio_copias(&ruta).join(format!(
+        "previa-restauracion-{}.db",
+        chrono::Local::now().format("%Y%m%d%H%M%S")
+    ));
+    std::fs::create_dir_all(directorio_copias(&ruta))
+        .map_err(|e| HermanarError::database(format!("No se pudo crear el directorio de copias: {}", e)))?;
+    db.reader().backup(MAIN_DB, &previa, None)?;
+    println!("Copia del estado anterior a la restauración: {}", previa.display());
+
+    // Ninguna lectura puede ver la base de datos a medio sustituir
+    db.exclusive(|conn| {
+        conn.restore(MAIN_DB, origen, None::<fn(rusqlite::backup::Progress)>)?;
+
+        migrations::run_migrations(conn, &ruta)
+            .map_err(|e| HermanarError::database(e.to_string()))?;
+        configurar_conexion(conn)
+            .map_err(|e| HermanarError::database(e.to_string()))?;
+
+        Ok(())
+    })
+}
+
+/// Lista las copias automáticas disponibles, de la más reciente a la más antigua.
+pub fn list_backups(db: &DbConnection) -> HermanarResult<Vec<CopiaSeguridad>> {
+    let ruta = db.path();
+
+    let mut copias = Vec::new();
+    for tipo in ["diaria", "semanal", "mensual"] {
+        for fichero in copias_de_tipo(&directorio_copias(&ruta), tipo) {
+            let tamano = std::fs::metadata(&fichero).map(|m| m.len()).unwrap_or(0);
+            let periodo = fichero
+                .file_stem()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_prefix(&format!("{}-", tipo)))
+                .unwrap_or_default()
+                .to_string();
+
+            copias.push(CopiaSeguridad {
+                ruta: fichero.to_string_lossy().to_string(),
+                tipo: tipo.to_string(),
+                periodo,
+                tamano,
+            });
+        }
+    }
+    copias.sort_by(|a, b| b.periodo.cmp(&a.periodo));
+
+    Ok(copias)
+}
+
+/// Crea, si aún no existen, la copia diaria, semanal y mensual del periodo
+/// actual y elimina las que exceden la retención de cada tipo.
+pub fn rotate_backups(conn: &Connection, db_path: &Path) -> Result<(), anyhow::Error> {
+    let directorio = directorio_copias(db_path);
+    std::fs::create_dir_all(&directorio)?;
+
+    let hoy = chrono::Local::now();
+    let periodos = [
+        ("diaria", hoy.format("%Y-%m-%d").to_string(), RETENCION_DIARIAS),
+        ("semanal", hoy.format("%G-W%V").to_string(), RETENCION_SEMANALES),
+        ("mensual", hoy.format("%Y-%m").to_string(), RETENCION_MENSUALES),
+    ];
+
+    for (tipo, periodo, retencion) in periodos {
+        let fichero = directorio.join(format!("{}-{}.db", tipo, periodo));
+        if !fichero.exists() {
+            conn.backup(MAIN_DB, &fichero, None)?;
+            println!("Copia automática creada: {}", fichero.display());
+        }
+
+        for antigua in copias_de_tipo(&directorio, tipo).into_iter().skip(retencion) {
+            std::fs::remove_file(&antigua)?;
+            println!("Copia automática eliminada: {}", antigua.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn validar_copia(origen: &Path) -> HermanarResult<()> {
+    if !origen.is_file() {
+        return Err(HermanarError::validation("origen", "El fichero de copia no existe"));
+    }
+
+    let copia = Connection::open_with_flags(origen, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|_| HermanarError::validation("origen", "El fichero no es una base de datos válida"))?;
+
+    let integridad: String = copia
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|_| HermanarError::validation("origen", "El fichero no es una base de datos válida"))?;
+    if integridad != "ok" {
+        return Err(HermanarError::validation("origen", "La copia de seguridad está dañada"));
+    }
+
+    let tablas: i32 = copia.query_row(
+        "SELECT COUNT(*) FROM sqlite_master
+         WHERE type = 'table' AND name IN ('hermanos', 'familias', 'cuotas')",
+        [],
+        |row| row.get(0),
+    )?;
+    if tablas != 3 {
+        return Err(HermanarError::validation("origen", "El fichero no es una copia de seguridad de Hermanar"));
+    }
+
+    let version = migrations::schema_version(&copia)
+        .map_err(|e| HermanarError::database(e.to_string()))?;
+    if version > migrations::SCHEMA_VERSION {
+        return Err(HermanarError::validation(
+            "origen",
+            format!(
+                "La copia es de una versión más reciente de Hermanar (esquema {}, se admite hasta el {})",
+                version,
+                migrations::SCHEMA_VERSION
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+fn directorio_copias(db_path: &Path) -> PathBuf {
+    db_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(DIRECTORIO_COPIAS)
+}
+
+// Ficheros `<tipo>-<periodo>.db` ordenados del más reciente al más antiguo;
+// el periodo tiene formato ISO, así que el orden alfabético es el cronológico.
+fn copias_de_tipo(directorio: &Path, tipo: &str) -> Vec<PathBuf> {
+    let prefijo = format!("{}-", tipo);
+    let mut ficheros: Vec<PathBuf> = std::fs::read_dir(directorio)
+        .map(|entradas| {
+            entradas
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| {
+                    p.extension().is_some_and(|ext| ext == "db")
+                        && p.file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(|n| n.starts_with(&prefijo))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    ficheros.sort();
+    ficheros.reverse();
+    ficheros
+}
@@ -0,0 +1,197 @@
+use chrono::Datelike;
+use hmac::{Hmac, Mac};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::path::Path;
+use crate::db::{configuracion, DbConnection, Hermano};
+use crate::db::hermanos::{buscar_hermano, listar_hermanos};
+use crate::db::hermandad::cargar_datos_documento;
+use crate::db::numeracion::nombre_completo;
+use crate::db::pdf::{fecha_espanola, fichero_destino, Documento, Pagina};
+use crate::error::{HermanarError, HermanarResult};
+
+const CLAVE_FIRMA: &str = "carnets_clave_firma";
+const PREFIJO_CODIGO: &str = "HERMANAR-C";
+
+// Hoja A4 de tarjetas precortadas de 85 x 54 mm, dos columnas y cinco filas
+// (formato de las hojas de tarjetas de visita más comunes).
+const ANCHO_HOJA: f32 = 210.0;
+const ALTO_HOJA: f32 = 297.0;
+const ANCHO_CARNET: f32 = 85.0;
+const ALTO_CARNET: f32 = 54.0;
+const MARGEN_IZQUIERDO: f32 = 15.0;
+const MARGEN_SUPERIOR: f32 = 13.5;
+const SEPARACION_COLUMNAS: f32 = 10.0;
+const COLUMNAS: usize = 2;
+const FILAS: usize = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolicitudCarnets {
+    /// Hermanos para los que se imprime carnet. Vacío imprime el de todos los
+    /// hermanos activos.
+    #[serde(default)]
+    pub hermano_ids: Vec<i32>,
+    /// Año de validez del carnet.
+    pub anio: i32,
+    pub fichero: String,
+    /// Posiciones ya usadas de la primera hoja, para aprovechar hojas
+    /// empezadas.
+    #[serde(default)]
+    pub posicion_inicial: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificacionCarnet {
+    pub hermano_id: i32,
+    pub numero_hermano: String,
+    pub nombre: String,
+    pub anio: i32,
+    pub activo: bool,
+    /// El carnet es del año en curso y el hermano sigue activo.
+    pub vigente: bool,
+}
+
+/// Genera un PDF con los carnets listos para imprimir sobre hojas de
+/// tarjetas. Devuelve el número de carnets generados.
+pub fn generar_carnets(db: &DbConnection, solicitud: &SolicitudCarnets) -> HermanarResult<usize> {
+    if !(2000..=2100).contains(&solicitud.anio) {
+        return Err(HermanarError::validation("anio", "El año de validez no es válido"));
+    }
+    if solicitud.posicion_inicial >= COLUMNAS * FILAS {
+        return Err(HermanarError::validation(
+            "posicion_inicial",
+            format!("La hoja tiene {} carnets", COLUMNAS * FILAS),
+        ));
+    }
+    let fichero = fichero_destino(&solicitud.fichero)?;
+
+    let (hermandad, hermanos, clave) = {
+        let conn = db.writer();
+        let hermandad = cargar_datos_documento(&conn)?;
+        let hermanos = if solicitud.hermano_ids.is_empty() {
+            listar_hermanos(&conn)?.into_iter().filter(|h| h.activo).collect()
+        } else {
+            solicitud
+                .hermano_ids
+                .iter()
+                .map(|&id| buscar_hermano(&conn, id)?.ok_or_else(|| HermanarError::not_found("Hermano")))
+                .collect::<HermanarResult<Vec<Hermano>>>()?
+        };
+        (hermandad, hermanos, clave_firma(&conn)?)
+    };
+    if hermanos.is_empty() {
+        return Err(HermanarError::validation("hermano_ids", "No hay hermanos para los que generar carnet"));
+    }
+
+    let documento = Documento::new(&format!("Carnets de hermano {}", solicitud.anio))?;
+    let logo = hermandad.logo.as_deref().map(Path::new);
+    let mut pagina = None;
+
+    for (indice, hermano) in hermanos.iter().enumerate() {
+        let posicion = (indice + solicitud.posicion_inicial) % (COLUMNAS * FILAS);
+        if posicion == 0 {
+            pagina = None;
+        }
+        let pagina = pagina.get_or_insert_with(|| documento.pagina(ANCHO_HOJA, ALTO_HOJA));
+
+        let x = MARGEN_IZQUIERDO + (posicion % COLUMNAS) as f32 * (ANCHO_CARNET + SEPARACION_COLUMNAS);
+        let y = MARGEN_SUPERIOR + (posicion / COLUMNAS) as f32 * ALTO_CARNET;
+        let Some(id) = hermano.id else { continue };
+        let codigo = codigo_carnet(&clave, id, solicitud.anio)?;
+
+        dibujar_carnet(pagina, x, y, &hermandad.nombre, logo, hermano, solicitud.anio, &codigo)?;
+    }
+
+    documento.guardar(fichero)?;
+
+    Ok(hermanos.len())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn dibujar_carnet(
+    pagina: &Pagina,
+    x: f32,
+    y: f32,
+    hermandad: &str,
+    logo: Option<&Path>,
+    hermano: &Hermano,
+    anio: i32,
+    codigo: &str,
+) -> HermanarResult<()> {
+    pagina.rectangulo(x, y, ANCHO_CARNET, ALTO_CARNET, None);
+
+    let texto_x = match logo {
+        Some(logo) => {
+            pagina.imagen(x + 4.0, y + 3.0, 13.0, 13.0, logo)?;
+            x + 20.0
+        }
+        None => x + 4.0,
+    };
+    pagina.texto_ajustado(texto_x, y + 8.5, x + ANCHO_CARNET - 4.0 - texto_x, 8.0, true, hermandad);
+    pagina.texto(texto_x, y + 13.0, 6.5, false, "CARNET DE HERMANO");
+    pagina.rectangulo(x + 4.0, y + 17.5, ANCHO_CARNET - 8.0, 0.3, Some((0.45, 0.1, 0.15)));
+
+    pagina.texto_ajustado(x + 4.0, y + 25.0, 52.0, 9.5, true, &nombre_completo(hermano));
+    pagina.texto(x + 4.0, y + 32.0, 7.5, false, &format!("Nº de hermano: {}", hermano.numero_hermano));
+    pagina.texto(x + 4.0, y + 37.5, 7.5, false, &format!("Fecha de alta: {}", fecha_espanola(&hermano.fecha_alta)));
+    pagina.texto(x + 4.0, y + 47.0, 8.0, true, &format!("Válido durante el año {}", anio));
+
+    pagina.qr(x + ANCHO_CARNET - 27.0, y + 22.0, 23.0, codigo)
+}
+
+/// Comprueba el código leído del QR de un carnet y devuelve a quién
+/// pertenece. Los códigos llevan una firma con una clave propia de cada base
+/// de datos, de modo que no se pueden fabricar sin ella.
+pub fn verificar_carnet(db: &DbConnection, codigo: &str) -> HermanarResult<VerificacionCarnet> {
+    let no_valido = || HermanarError::validation("codigo", "El código no corresponde a un carnet de esta hermandad");
+
+    let partes: Vec<&str> = codigo.trim().split(':').collect();
+    let [prefijo, id, anio, _] = partes.as_slice() else {
+        return Err(no_valido());
+    };
+    if *prefijo != PREFIJO_CODIGO {
+        return Err(no_valido());
+    }
+    let id: i32 = id.parse().map_err(|_| no_valido())?;
+    let anio: i32 = anio.parse().map_err(|_| no_valido())?;
+
+    let conn = db.reader();
+    let clave = configuracion::leer(&conn, CLAVE_FIRMA)?.ok_or_else(no_valido)?;
+    if codigo_carnet(&clave, id, anio)? != codigo.trim() {
+        return Err(no_valido());
+    }
+    let hermano = buscar_hermano(&conn, id)?.ok_or_else(|| HermanarError::not_found("Hermano"))?;
+
+    Ok(VerificacionCarnet {
+        hermano_id: id,
+        nombre: nombre_completo(&hermano),
+        numero_hermano: hermano.numero_hermano,
+        anio,
+        activo: hermano.activo,
+        vigente: hermano.activo && anio == chrono::Local::now().year(),
+    })
+}
+
+// El código identifica al hermano por su id, que no cambia al renumerar.
+fn codigo_carnet(clave: &str, hermano_id: i32, anio: i32) -> HermanarResult<String> {
+    let datos = format!("{}:{}", hermano_id, anio);
+    let mut mac = Hmac::<Sha256>::new_from_slice(clave.as_bytes())
+        .map_err(|e| HermanarError::database(format!("Clave de firma de carnets no válida: {}", e)))?;
+    mac.update(datos.as_bytes());
+    let firma: String = mac.finalize().into_bytes()[..8].iter().map(|b| format!("{:02X}", b)).collect();
+
+    Ok(format!("{}:{}:{}", PREFIJO_CODIGO, datos, firma))
+}
+
+// La clave de firma se crea al generar los primeros carnets.
+fn clave_firma(conn: &Connection) -> HermanarResult<String> {
+    if let Some(clave) = configuracion::leer(conn, CLAVE_FIRMA)? {
+        return Ok(clave);
+    }
+
+    let clave: String = conn.query_row("SELECT hex(randomblob(32))", [], |row| row.get(0))?;
+    configuracion::guardar(conn, CLAVE_FIRMA, &clave)?;
+
+    Ok(clave)
+}
@@ -0,0 +1,624 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use crate::db::{DbConnection, Hermano, TipoDocumento};
+use crate::db::cuotas::{buscar_cuotas_hermano, MES_INICIO};
+use crate::db::hermandad::{cargar_datos_documento, congelar_logo, contenido_logo, DatosHermandad};
+use crate::db::hermanos::buscar_hermano;
+use crate::db::membresia::hoy;
+use crate::db::numeracion::nombre_completo;
+use crate::db::pdf::{ancho_texto, fecha_espanola, fecha_en_letras, fichero_destino, Documento, Pagina};
+use crate::error::{HermanarError, HermanarResult};
+
+const ANCHO_PAGINA: f32 = 210.0;
+const ALTO_PAGINA: f32 = 297.0;
+const MARGEN: f32 = 25.0;
+// Por debajo de esta altura el cuerpo del texto ya no deja sitio a las firmas.
+const LIMITE_CUERPO: f32 = 225.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TipoCertificado {
+    Pertenencia,
+    Antiguedad,
+    AlCorriente,
+}
+
+impl TipoCertificado {
+    const TODOS: [TipoCertificado; 3] =
+        [TipoCertificado::Pertenencia, TipoCertificado::Antiguedad, TipoCertificado::AlCorriente];
+
+    pub(crate) fn clave(&self) -> &'static str {
+        match self {
+            TipoCertificado::Pertenencia => "pertenencia",
+            TipoCertificado::Antiguedad => "antiguedad",
+            TipoCertificado::AlCorriente => "al_corriente",
+        }
+    }
+
+    pub(crate) fn desde_clave(clave: &str) -> Option<Self> {
+        Self::TODOS.into_iter().find(|tipo| tipo.clave() == clave)
+    }
+}
+
+/// Texto de un tipo de certificado. Título y cuerpo admiten las variables de
+/// `get_variables_certificado` entre llaves; cada línea del cuerpo es un
+/// párrafo.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlantillaCertificado {
+    pub tipo: TipoCertificado,
+    pub titulo: String,
+    pub cuerpo: String,
+    /// Cargo que firma el certificado, p. ej. «El Secretario».
+    pub firmante: String,
+    /// Cargo que da el visto bueno, si el certificado lo lleva.
+    pub visto_bueno: Option<String>,
+    /// La hermandad ha cambiado el texto predeterminado.
+    #[serde(default)]
+    pub personalizada: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariableCertificado {
+    pub clave: String,
+    pub descripcion: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolicitudCertificado {
+    pub hermano_id: i32,
+    pub tipo: TipoCertificado,
+    /// Para qué se pide el certificado (una boda, otra hermandad...).
+    pub finalidad: Option<String>,
+    pub fichero: String,
+}
+
+/// Certificado anotado en el registro, con el texto tal como se imprimió.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Certificado {
+    pub id: i32,
+    /// Número de registro: año y número correlativo, p. ej. 2026/0007.
+    pub numero_registro: String,
+    pub anio: i32,
+    pub numero: i32,
+    pub tipo: TipoCertificado,
+    pub hermano_id: i32,
+    pub numero_hermano: String,
+    pub nombre_hermano: String,
+    pub finalidad: Option<String>,
+    pub fecha: String,
+    pub titulo: String,
+    pub cuerpo: String,
+    pub firmante: String,
+    pub visto_bueno: Option<String>,
+    pub operador: String,
+    pub created_at: String,
+}
+
+const COLUMNAS_CERTIFICADO: &str = "id, anio, numero, tipo, hermano_id, numero_hermano, nombre_hermano, finalidad,
+     fecha, titulo, cuerpo, firmante, visto_bueno, operador, created_at";
+
+impl Certificado {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        let anio: i32 = row.get(1)?;
+        let numero: i32 = row.get(2)?;
+
+        Ok(Certificado {
+            id: row.get(0)?,
+            numero_registro: format!("{}/{:04}", anio, numero),
+            anio,
+            numero,
+            tipo: TipoCertificado::desde_clave(&row.get::<_, String>(3)?).unwrap_or(TipoCertificado::Pertenencia),
+            hermano_id: row.get(4)?,
+            numero_hermano: row.get(5)?,
+            nombre_hermano: row.get(6)?,
+            finalidad: row.get(7)?,
+            fecha: row.get(8)?,
+            titulo: row.get(9)?,
+            cuerpo: row.get(10)?,
+            firmante: row.get(11)?,
+            visto_bueno: row.get(12)?,
+            operador: row.get(13)?,
+            created_at: row.get(14)?,
+        })
+    }
+}
+
+// Las variables que empiezan por coma se quedan vacías cuando falta el dato,
+// de modo que la frase sigue leyéndose bien; las demás son obligatorias.
+const VARIABLES: &[(&str, &str)] = &[
+    ("hermandad", "Nombre de la hermandad"),
+    ("localidad", "Localidad de la hermandad"),
+    ("fecha", "Fecha de expedición, en letras"),
+    ("numero_registro", "Número de registro del certificado"),
+    ("firmante", "Cargo que firma el certificado"),
+    ("nombre", "Nombre y apellidos del hermano"),
+    ("numero_hermano", "Número de hermano"),
+    ("dni", "Documento de identidad del hermano"),
+    ("documento", "«, con DNI ...», o nada si no consta"),
+    ("fecha_nacimiento", "Fecha de nacimiento, en letras"),
+    ("fecha_alta", "Fecha de alta en la hermandad, en letras"),
+    ("antiguedad", "Años completos desde el alta, p. ej. «25 años»"),
+    ("bautismo", "«, bautizado en la parroquia de ...», o nada si no consta"),
+    ("ultima_cuota", "«, siendo la última abonada la del ...» con su periodo, o nada si no ha pagado ninguna"),
+    ("finalidad", "«, para ...» con la finalidad indicada, o nada"),
+];
+
+const INTRODUCCION: &str = "{firmante} de la {hermandad},\nCERTIFICA:";
+const CIERRE: &str =
+    "Y para que conste y surta los efectos oportunos{finalidad}, expido el presente certificado en {localidad}, a {fecha}.";
+
+fn plantilla_predeterminada(tipo: TipoCertificado) -> PlantillaCertificado {
+    let (titulo, texto) = match tipo {
+        TipoCertificado::Pertenencia => (
+            "CERTIFICADO DE PERTENENCIA",
+            "Que {nombre}{documento}, hermano número {numero_hermano}, figura inscrito en el libro de registro \
+             de hermanos de esta Hermandad con fecha de alta el {fecha_alta}{bautismo}.",
+        ),
+        TipoCertificado::Antiguedad => (
+            "CERTIFICADO DE ANTIGÜEDAD",
+            "Que {nombre}{documento}, hermano número {numero_hermano}, pertenece a esta Hermandad desde el \
+             {fecha_alta}, por lo que cuenta con una antigüedad de {antiguedad}.",
+        ),
+        TipoCertificado::AlCorriente => (
+            "CERTIFICADO DE ESTAR AL CORRIENTE DE PAGO",
+            "Que {nombre}{documento}, hermano número {numero_hermano}, se encuentra al corriente en el pago \
+             de sus cuotas a la fecha de este certificado{ultima_cuota}.",
+        ),
+    };
+
+    PlantillaCertificado {
+        tipo,
+        titulo: titulo.to_string(),
+        cuerpo: format!("{}\n{}\n{}", INTRODUCCION, texto, CIERRE),
+        firmante: "El Secretario".to_string(),
+        visto_bueno: Some("Vº Bº El Hermano Mayor".to_string()),
+        personalizada: false,
+    }
+}
+
+pub fn get_variables_certificado() -> Vec<VariableCertificado> {
+    VARIABLES
+        .iter()
+        .map(|(clave, descripcion)| VariableCertificado { clave: clave.to_string(), descripcion: descripcion.to_string() })
+        .collect()
+}
+
+pub fn get_plantillas_certificado(db: &DbConnection) -> HermanarResult<Vec<PlantillaCertificado>> {
+    let conn = db.reader();
+
+    TipoCertificado::TODOS.into_iter().map(|tipo| buscar_plantilla(&conn, tipo)).collect()
+}
+
+/// Guarda el texto de un tipo de certificado, comprobando que solo usa
+/// variables conocidas.
+pub fn set_plantilla_certificado(db: &DbConnection, plantilla: &PlantillaCertificado) -> HermanarResult<()> {
+    let titulo = plantilla.titulo.trim();
+    let cuerpo = plantilla.cuerpo.trim();
+    let firmante = plantilla.firmante.trim();
+    if titulo.is_empty() {
+        return Err(HermanarError::validation("titulo", "Indique el título del certificado"));
+    }
+    if cuerpo.is_empty() {
+        return Err(HermanarError::validation("cuerpo", "Indique el texto del certificado"));
+    }
+    if firmante.is_empty() {
+        return Err(HermanarError::validation("firmante", "Indique quién firma el certificado"));
+    }
+    let ejemplo: Vec<(&str, Option<String>)> = VARIABLES.iter().map(|(clave, _)| (*clave, Some(String::new()))).collect();
+    sustituir("titulo", titulo, &ejemplo)?;
+    sustituir("cuerpo", cuerpo, &ejemplo)?;
+    let visto_bueno = plantilla.visto_bueno.as_deref().map(str::trim).filter(|v| !v.is_empty());
+
+    let conn = db.writer();
+    conn.prepare_cached(
+        "INSERT INTO plantillas_certificado (tipo, titulo, cuerpo, firmante, visto_bueno)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(tipo) DO UPDATE SET
+             titulo = excluded.titulo, cuerpo = excluded.cuerpo, firmante = excluded.firmante,
+             visto_bueno = excluded.visto_bueno, updated_at = CURRENT_TIMESTAMP"
+    )?
+    .execute(params![plantilla.tipo.clave(), titulo, cuerpo, firmante, visto_bueno])?;
+
+    Ok(())
+}
+
+/// Vuelve al texto predeterminado de un tipo de certificado.
+pub fn restablecer_plantilla_certificado(db: &DbConnection, tipo: TipoCertificado) -> HermanarResult<()> {
+    db.writer()
+        .prepare_cached("DELETE FROM plantillas_certificado WHERE tipo = ?1")?
+        .execute([tipo.clave()])?;
+
+    Ok(())
+}
+
+fn buscar_plantilla(conn: &Connection, tipo: TipoCertificado) -> HermanarResult<PlantillaCertificado> {
+    let guardada = conn
+        .prepare_cached("SELECT titulo, cuerpo, firmante, visto_bueno FROM plantillas_certificado WHERE tipo = ?1")?
+        .query_row([tipo.clave()], |row| {
+            Ok(PlantillaCertificado {
+                tipo,
+                titulo: row.get(0)?,
+                cuerpo: row.get(1)?,
+                firmante: row.get(2)?,
+                visto_bueno: row.get(3)?,
+                personalizada: true,
+            })
+        })
+        .optional()?;
+
+    Ok(guardada.unwrap_or_else(|| plantilla_predeterminada(tipo)))
+}
+
+/// Expide un certificado para un hermano de alta: lo anota en el registro con
+/// el siguiente número del año y genera el PDF. El de estar al corriente de
+/// pago solo se expide si el hermano no tiene cuotas pendientes.
+pub fn emitir_certificado(db: &DbConnection, solicitud: &SolicitudCertificado) -> HermanarResult<Certificado> {
+    let fichero = fichero_destino(&solicitud.fichero)?;
+    let finalidad = solicitud.finalidad.as_deref().map(str::trim).filter(|f| !f.is_empty());
+
+    let mut conn = db.writer();
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let hermano = buscar_hermano(&tx, solicitud.hermano_id)?.ok_or_else(|| HermanarError::not_found("Hermano"))?;
+    if !hermano.activo {
+        return Err(HermanarError::conflict("El hermano está dado de baja"));
+    }
+    let mut hermandad = cargar_datos_documento(&tx)?;
+    congelar_logo(&tx, &mut hermandad)?;
+    let logo = contenido_logo(&tx, &hermandad)?;
+    let plantilla = buscar_plantilla(&tx, solicitud.tipo)?;
+
+    let fecha = hoy();
+    let dia = NaiveDate::parse_from_str(&fecha, "%Y-%m-%d").unwrap_or_else(|_| chrono::Local::now().date_naive());
+    let pendientes = cuotas_pendientes(&tx, solicitud.hermano_id, dia)?;
+    if solicitud.tipo == TipoCertificado::AlCorriente && pendientes > 0 {
+        return Err(HermanarError::conflict(format!(
+            "El hermano tiene {} cuota{} pendiente{} de pago",
+            pendientes,
+            if pendientes == 1 { "" } else { "s" },
+            if pendientes == 1 { "" } else { "s" },
+        )));
+    }
+    // La última cuota pagada es la que cubre el mes más tardío
+    let ultima_cuota = buscar_cuotas_hermano(&tx, solicitud.hermano_id)?
+        .into_iter()
+        .filter(|cuota| cuota.pagado)
+        .max_by_key(|cuota| (cuota.anio, cuota.periodo * 12 / cuota.periodicidad.periodos()))
+        .map(|cuota| cuota.descripcion_periodo());
+
+    let anio = dia.year();
+    let numero: i32 = tx
+        .prepare_cached("SELECT COALESCE(MAX(numero), 0) + 1 FROM certificados WHERE anio = ?1")?
+        .query_row([anio], |row| row.get(0))?;
+
+    let valores = valores_certificado(
+        &hermano,
+        &hermandad,
+        &plantilla,
+        &fecha,
+        &format!("{}/{:04}", anio, numero),
+        ultima_cuota,
+        finalidad,
+    );
+    let titulo = sustituir("titulo", &plantilla.titulo, &valores)?;
+    let cuerpo = sustituir("cuerpo", &plantilla.cuerpo, &valores)?;
+    let datos_hermandad = serde_json::to_string(&hermandad)
+        .map_err(|e| HermanarError::database(format!("No se pudieron guardar los datos del certificado: {}", e)))?;
+
+    tx.prepare_cached(
+        "INSERT INTO certificados
+         (anio, numero, tipo, hermano_id, numero_hermano, nombre_hermano, finalidad, fecha, titulo, cuerpo,
+          firmante, visto_bueno, hermandad, operador)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)"
+    )?
+    .execute(params![
+        anio,
+        numero,
+        solicitud.tipo.clave(),
+        solicitud.hermano_id,
+        hermano.numero_hermano,
+        nombre_completo(&hermano),
+        finalidad,
+        fecha,
+        titulo,
+        cuerpo,
+        plantilla.firmante,
+        plantilla.visto_bueno,
+        datos_hermandad,
+        db.operador(),
+    ])?;
+    let id = tx.last_insert_rowid() as i32;
+    let certificado = buscar_certificado(&tx, id)?.ok_or_else(|| HermanarError::not_found("Certificado"))?;
+
+    // Si el PDF no se puede generar el certificado no llega a registrarse.
+    documento_certificado(&certificado, &hermandad, logo.as_deref())?.guardar(fichero)?;
+    tx.commit()?;
+
+    Ok(certificado)
+}
+
+/// Certificados registrados, los más recientes primero. Con `hermano_id`
+/// solo los de ese hermano.
+pub fn list_certificados(db: &DbConnection, hermano_id: Option<i32>) -> HermanarResult<Vec<Certificado>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM certificados
+         WHERE ?1 IS NULL OR hermano_id = ?1
+         ORDER BY anio DESC, numero DESC",
+        COLUMNAS_CERTIFICADO
+    ))?;
+
+    let certificados = stmt.query_map([hermano_id], Certificado::from_row)?.collect::<Result<Vec<_>, _>>()?;
+
+    Ok(certificados)
+}
+
+/// Vuelve a generar el PDF de un certificado ya registrado, idéntico al
+/// original.
+pub fn generar_pdf_certificado(db: &DbConnection, id: i32, fichero: &str) -> HermanarResult<()> {
+    let fichero = fichero_destino(fichero)?;
+
+    let (certificado, hermandad, logo) = {
+        let conn = db.writer();
+        let certificado = buscar_certificado(&conn, id)?.ok_or_else(|| HermanarError::not_found("Certificado"))?;
+        let hermandad: String = conn
+            .prepare_cached("SELECT hermandad FROM certificados WHERE id = ?1")?
+            .query_row([id], |row| row.get(0))?;
+        let mut hermandad: DatosHermandad = serde_json::from_str(&hermandad)
+            .map_err(|e| HermanarError::database(format!("Datos del certificado dañados: {}", e)))?;
+        // Los certificados expedidos antes de guardarse copia del escudo
+        // reciben la del escudo actual, que ya no cambia
+        if hermandad.logo.is_some() && hermandad.logo_huella.is_none() {
+            congelar_logo(&conn, &mut hermandad)?;
+            let json = serde_json::to_string(&hermandad)
+                .map_err(|e| HermanarError::database(format!("No se pudieron guardar los datos del certificado: {}", e)))?;
+            conn.prepare_cached("UPDATE certificados SET hermandad = ?1 WHERE id = ?2")?
+                .execute(params![json, id])?;
+        }
+        let logo = contenido_logo(&conn, &hermandad)?;
+        (certificado, hermandad, logo)
+    };
+
+    documento_certificado(&certificado, &hermandad, logo.as_deref())?.guardar(fichero)
+}
+
+// Cuotas sin pagar del hermano cuyo periodo ya ha empezado en `fecha`. Las de
+// periodos posteriores, generadas por adelantado, todavía no se deben.
+fn cuotas_pendientes(conn: &Connection, hermano_id: i32, fecha: NaiveDate) -> HermanarResult<i32> {
+    let pendientes = conn
+        .prepare_cached(&format!(
+            "SELECT COUNT(*) FROM cuotas
+             WHERE hermano_id = ?1 AND pagado = 0 AND (anio < ?2 OR (anio = ?2 AND {} <= ?3))",
+            MES_INICIO
+        ))?
+        .query_row(params![hermano_id, fecha.year(), fecha.month()], |row| row.get(0))?;
+
+    Ok(pendientes)
+}
+
+fn buscar_certificado(conn: &Connection, id: i32) -> HermanarResult<Option<Certificado>> {
+    let certificado = conn
+        .prepare_cached(&format!("SELECT {} FROM certificados WHERE id = ?1", COLUMNAS_CERTIFICADO))?
+        .query_row([id], Certificado::from_row)
+        .optional()?;
+
+    Ok(certificado)
+}
+
+fn valores_certificado(
+    hermano: &Hermano,
+    hermandad: &DatosHermandad,
+    plantilla: &PlantillaCertificado,
+    fecha: &str,
+    numero_registro: &str,
+    ultima_cuota: Option<String>,
+    finalidad: Option<&str>,
+) -> Vec<(&'static str, Option<String>)> {
+    let no_vacio = |valor: Option<&str>| valor.map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+
+    let documento = no_vacio(hermano.dni.as_deref()).map(|dni| {
+        let tipo = match hermano.tipo_documento {
+            Some(TipoDocumento::Nie) => "NIE",
+            Some(TipoDocumento::Pasaporte) => "pasaporte",
+            Some(TipoDocumento::Otro) => "documento",
+            _ => "DNI",
+        };
+        format!(", con {} {}", tipo, dni)
+    });
+    let bautismo = no_vacio(hermano.parroquia_bautismo.as_deref()).map(|parroquia| {
+        let parroquia = ["parroquia de ", "parroquia "]
+            .iter()
+            .find_map(|prefijo| {
+                parroquia
+                    .get(..prefijo.len())
+                    .filter(|inicio| inicio.eq_ignore_ascii_case(prefijo))
+                    .map(|_| parroquia[prefijo.len()..].to_string())
+            })
+            .unwrap_or(parroquia);
+        let lugar = match (no_vacio(hermano.localidad_bautismo.as_deref()), no_vacio(hermano.provincia_bautismo.as_deref())) {
+            (Some(localidad), Some(provincia)) if localidad != provincia => Some(format!("{} ({})", localidad, provincia)),
+            (Some(localidad), _) => Some(localidad),
+            (None, provincia) => provincia,
+        };
+        match lugar {
+            Some(lugar) => format!(", bautizado en la parroquia de {}, de {}", parroquia, lugar),
+            None => format!(", bautizado en la parroquia de {}", parroquia),
+        }
+    });
+
+    vec![
+        ("hermandad", Some(hermandad.nombre.clone())),
+        ("localidad", no_vacio(hermandad.localidad.as_deref()).or_else(|| no_vacio(hermandad.provincia.as_deref()))),
+        ("fecha", Some(fecha_en_letras(fecha))),
+        ("numero_registro", Some(numero_registro.to_string())),
+        ("firmante", Some(plantilla.firmante.clone())),
+        ("nombre", Some(nombre_completo(hermano))),
+        ("numero_hermano", Some(hermano.numero_hermano.clone())),
+        ("dni", no_vacio(hermano.dni.as_deref())),
+        ("documento", Some(documento.unwrap_or_default())),
+        ("fecha_nacimiento", no_vacio(hermano.fecha_nacimiento.as_deref()).map(|f| fecha_en_letras(&f))),
+        ("fecha_alta", Some(fecha_en_letras(&hermano.fecha_alta))),
+        ("antiguedad", antiguedad(&hermano.fecha_alta, fecha)),
+        ("bautismo", Some(bautismo.unwrap_or_default())),
+        (
+            "ultima_cuota",
+            Some(
+                ultima_cuota
+                    .map(|periodo| format!(", siendo la última abonada la {}", periodo))
+                    .unwrap_or_default(),
+            ),
+        ),
+        ("finalidad", Some(finalidad.map(|f| format!(", para {}", f)).unwrap_or_default())),
+    ]
+}
+
+// Años completos entre el alta y la fecha del certificado.
+fn antiguedad(fecha_alta: &str, fecha: &str) -> Option<String> {
+    let alta = NaiveDate::parse_from_str(fecha_alta.get(..10)?, "%Y-%m-%d").ok()?;
+    let fecha = NaiveDate::parse_from_str(fecha, "%Y-%m-%d").ok()?;
+    let anios = fecha.years_since(alta)?;
+
+    Some(match anios {
+        0 => "menos de un año".to_string(),
+        1 => "1 año".to_string(),
+        anios => format!("{} años", anios),
+    })
+}
+
+// Sustituye las variables {clave} del texto. Una variable desconocida, una
+// llave sin cerrar o un dato obligatorio que falta son errores de `campo`.
+fn sustituir(campo: &str, texto: &str, valores: &[(&str, Option<String>)]) -> HermanarResult<String> {
+    let mut resultado = String::with_capacity(texto.len());
+    let mut resto = texto;
+
+    while let Some(inicio) = resto.find('{') {
+        resultado.push_str(&resto[..inicio]);
+        let Some(fin) = resto[inicio..].find('}') else {
+            return Err(HermanarError::validation(campo, "Hay una llave { sin cerrar"));
+        };
+        let clave = &resto[inicio + 1..inicio + fin];
+        match valores.iter().find(|(nombre, _)| *nombre == clave) {
+            Some((_, Some(valor))) => resultado.push_str(valor),
+            Some((_, None)) => {
+                return Err(HermanarError::validation(
+                    campo,
+                    format!("El certificado usa {{{}}}, pero ese dato no está registrado", clave),
+                ))
+            }
+            None => return Err(HermanarError::validation(campo, format!("La variable {{{}}} no existe", clave))),
+        }
+        resto = &resto[inicio + fin + 1..];
+    }
+    resultado.push_str(resto);
+
+    Ok(resultado)
+}
+
+fn documento_certificado(
+    certificado: &Certificado,
+    hermandad: &DatosHermandad,
+    logo: Option<&[u8]>,
+) -> HermanarResult<Documento> {
+    let emitido = NaiveDateTime::parse_from_str(&certificado.created_at, "%Y-%m-%d %H:%M:%S").unwrap_or_default();
+    let documento = Documento::reproducible(
+        &format!("{} {}", certificado.titulo, certificado.numero_registro),
+        &format!("certificado {}", certificado.numero_registro),
+        emitido,
+    )?;
+    dibujar_certificado(&documento.pagina(ANCHO_PAGINA, ALTO_PAGINA), certificado, hermandad, logo)?;
+
+    Ok(documento)
+}
+
+fn dibujar_certificado(
+    pagina: &Pagina,
+    certificado: &Certificado,
+    hermandad: &DatosHermandad,
+    logo: Option<&[u8]>,
+) -> HermanarResult<()> {
+    let derecha = ANCHO_PAGINA - MARGEN;
+    let centrado = |x: f32, ancho: f32, y: f32, tamano: f32, negrita: bool, texto: &str| {
+        let ocupado = ancho_texto(texto, tamano, negrita).min(ancho);
+        pagina.texto_ajustado(x + (ancho - ocupado) / 2.0, y, ancho, tamano, negrita, texto);
+    };
+
+    // Cabecera: hermandad a la izquierda, número de registro a la derecha
+    let texto_x = match logo {
+        Some(logo) => {
+            pagina.imagen_datos(MARGEN, 15.0, 25.0, 25.0, logo)?;
+            MARGEN + 31.0
+        }
+        None => MARGEN,
+    };
+    pagina.texto_ajustado(texto_x, 24.0, 140.0 - texto_x, 12.0, true, &hermandad.nombre);
+    let lineas_hermandad = [
+        hermandad.cif.as_ref().map(|cif| format!("CIF: {}", cif)),
+        hermandad.direccion.clone(),
+        hermandad.poblacion(),
+    ];
+    for (indice, linea) in lineas_hermandad.iter().flatten().enumerate() {
+        pagina.texto_ajustado(texto_x, 30.0 + indice as f32 * 4.5, 140.0 - texto_x, 8.5, false, linea);
+    }
+    let registro = format!("Registro nº {}", certificado.numero_registro);
+    pagina.texto(derecha - ancho_texto(&registro, 9.0, true), 24.0, 9.0, true, &registro);
+
+    pagina.rectangulo(MARGEN, 48.0, ANCHO_PAGINA - 2.0 * MARGEN, 0.4, Some((0.45, 0.1, 0.15)));
+
+    centrado(MARGEN, ANCHO_PAGINA - 2.0 * MARGEN, 68.0, 15.0, true, &certificado.titulo);
+
+    let mut y = 88.0;
+    for parrafo in certificado.cuerpo.lines().map(str::trim).filter(|p| !p.is_empty()) {
+        y = pagina.parrafo(MARGEN, y, ANCHO_PAGINA - 2.0 * MARGEN, 11.0, 6.0, parrafo) + 3.0;
+    }
+    if y > LIMITE_CUERPO {
+        return Err(HermanarError::validation("cuerpo", "El texto del certificado no cabe en una página"));
+    }
+
+    // Firmas: quien certifica a la derecha y el visto bueno a la izquierda
+    let firmas = (y + 15.0).max(215.0);
+    let ancho_firma = 65.0;
+    centrado(derecha - ancho_firma, ancho_firma, firmas, 10.0, false, &certificado.firmante);
+    pagina.rectangulo(derecha - ancho_firma, firmas + 25.0, ancho_firma, 0.3, Some((0.0, 0.0, 0.0)));
+    if let Some(visto_bueno) = &certificado.visto_bueno {
+        centrado(MARGEN, ancho_firma, firmas, 10.0, false, visto_bueno);
+        pagina.rectangulo(MARGEN, firmas + 25.0, ancho_firma, 0.3, Some((0.0, 0.0, 0.0)));
+    }
+
+    let pie = format!(
+        "Certificado nº {} expedido el {} y anotado en el registro de certificados de la hermandad.",
+        certificado.numero_registro,
+        fecha_espanola(&certificado.fecha),
+    );
+    centrado(MARGEN, ANCHO_PAGINA - 2.0 * MARGEN, ALTO_PAGINA - 12.0, 7.0, false, &pie);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::base_de_datos_en_memoria;
+
+    #[test]
+    fn pendientes_solo_de_periodos_empezados() {
+        let conn = base_de_datos_en_memoria();
+        conn.execute_batch(
+            "INSERT INTO hermanos (id, numero_hermano, nombre, primer_apellido, fecha_alta)
+             VALUES (1, '1', 'José', 'Núñez', '2020-01-01');
+             INSERT INTO cuotas (hermano_id, anio, periodicidad, periodo, importe, pagado) VALUES
+                 (1, 2025, 'mensual', 12, 5, 0),
+                 (1, 2026, 'trimestral', 1, 15, 1),
+                 (1, 2026, 'trimestral', 2, 15, 0),
+                 (1, 2026, 'trimestral', 3, 15, 0),
+                 (1, 2027, 'anual', 1, 60, 0);",
+        )
+        .unwrap();
+
+        let fecha = |texto: &str| NaiveDate::parse_from_str(texto, "%Y-%m-%d").unwrap();
+        assert_eq!(cuotas_pendientes(&conn, 1, fecha("2026-03-31")).unwrap(), 1);
+        assert_eq!(cuotas_pendientes(&conn, 1, fecha("2026-04-01")).unwrap(), 2);
+        assert_eq!(cuotas_pendientes(&conn, 1, fecha("2026-12-31")).unwrap(), 3);
+        assert_eq!(cuotas_pendientes(&conn, 1, fecha("2027-01-01")).unwrap(), 4);
+    }
+}
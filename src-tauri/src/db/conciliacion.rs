@@ -0,0 +1,471 @@
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use crate::db::{norma43, Cuota, DbConnection, Hermano};
+use crate::db::cuotas::{buscar_cuota, buscar_cuotas_pendientes};
+use crate::db::pagos::{registrar_pago, SolicitudPago};
+use crate::db::hermanos::listar_hermanos;
+use crate::db::sepa::texto_sepa;
+use crate::error::{HermanarError, HermanarResult};
+
+const METODO_TRANSFERENCIA: &str = "transferencia";
+
+// Puntuación mínima para proponer un hermano. Solo el importe no basta: muchas
+// cuotas valen lo mismo, así que hace falta algún dato que lo identifique.
+const PUNTUACION_MINIMA: i32 = 40;
+
+const SELECT_MOVIMIENTOS: &str =
+    "SELECT id, cuenta, fecha_operacion, fecha_valor, importe, concepto_comun, concepto_propio, documento,
+            referencia_1, referencia_2, concepto, estado, fichero, created_at
+     FROM movimientos_bancarios";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EstadoMovimiento {
+    Pendiente,
+    Conciliado,
+    /// Movimiento que no corresponde a ninguna cuota (comisiones, otros cobros).
+    Descartado,
+}
+
+impl EstadoMovimiento {
+    fn clave(&self) -> &'static str {
+        match self {
+            EstadoMovimiento::Pendiente => "pendiente",
+            EstadoMovimiento::Conciliado => "conciliado",
+            EstadoMovimiento::Descartado => "descartado",
+        }
+    }
+
+    fn desde_clave(clave: &str) -> Option<Self> {
+        match clave {
+            "pendiente" => Some(EstadoMovimiento::Pendiente),
+            "conciliado" => Some(EstadoMovimiento::Conciliado),
+            "descartado" => Some(EstadoMovimiento::Descartado),
+            _ => None,
+        }
+    }
+}
+
+/// Apunte importado de un extracto bancario.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MovimientoBancario {
+    pub id: i64,
+    pub cuenta: String,
+    pub fecha_operacion: String,
+    pub fecha_valor: String,
+    /// Positivo para los abonos y negativo para los cargos.
+    pub importe: f64,
+    pub concepto_comun: String,
+    pub concepto_propio: String,
+    pub documento: String,
+    pub referencia_1: String,
+    pub referencia_2: String,
+    pub concepto: String,
+    pub estado: EstadoMovimiento,
+    pub fichero: String,
+    pub created_at: String,
+}
+
+impl MovimientoBancario {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        Ok(MovimientoBancario {
+            id: row.get(0)?,
+            cuenta: row.get(1)?,
+            fecha_operacion: row.get(2)?,
+            fecha_valor: row.get(3)?,
+            importe: row.get(4)?,
+            concepto_comun: row.get(5)?,
+            concepto_propio: row.get(6)?,
+            documento: row.get(7)?,
+            referencia_1: row.get(8)?,
+            referencia_2: row.get(9)?,
+            concepto: row.get(10)?,
+            estado: EstadoMovimiento::desde_clave(&row.get::<_, String>(11)?).unwrap_or(EstadoMovimiento::Pendiente),
+            fichero: row.get(12)?,
+            created_at: row.get(13)?,
+        })
+    }
+
+    // Texto libre del movimiento en mayúsculas y sin acentos, donde buscar
+    // nombres, números de hermano y DNI.
+    fn texto_busqueda(&self) -> String {
+        texto_sepa(
+            &format!("{} {} {}", self.referencia_1, self.referencia_2, self.concepto),
+            usize::MAX,
+        )
+        .to_uppercase()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportacionExtracto {
+    pub fichero: String,
+    pub apuntes: i32,
+    pub nuevos: i32,
+    /// Apuntes que ya estaban importados de otro extracto.
+    pub repetidos: i32,
+}
+
+/// Hermano y cuotas que probablemente paga un movimiento, con la puntuación
+/// obtenida y los indicios en los que se basa.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PropuestaConciliacion {
+    pub movimiento: MovimientoBancario,
+    pub hermano_id: i32,
+    pub numero_hermano: String,
+    pub nombre: String,
+    pub cuotas: Vec<Cuota>,
+    pub importe_cuotas: f64,
+    pub puntuacion: i32,
+    pub motivos: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfirmacionConciliacion {
+    pub movimiento_id: i64,
+    pub cuota_ids: Vec<i32>,
+}
+
+/// Importa los apuntes de un extracto Norma 43. Cada apunte se identifica por
+/// su contenido (y su orden entre apuntes idénticos), de modo que importar
+/// extractos que se solapan no duplica movimientos ya importados o conciliados.
+pub fn importar_extracto(db: &DbConnection, fichero: &str) -> HermanarResult<ImportacionExtracto> {
+    let ruta = Path::new(fichero.trim());
+    let contenido = std::fs::read(ruta)
+        .map_err(|e| HermanarError::validation("fichero", format!("No se pudo leer el fichero: {}", e)))?;
+    let apuntes = norma43::leer(&contenido)?;
+    let nombre_fichero = ruta
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| fichero.to_string());
+
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+
+    let mut resumen = ImportacionExtracto {
+        fichero: nombre_fichero.clone(),
+        apuntes: apuntes.len() as i32,
+        nuevos: 0,
+        repetidos: 0,
+    };
+
+    let mut ocurrencias: HashMap<String, usize> = HashMap::new();
+    let mut insertar = tx.prepare_cached(
+        "INSERT OR IGNORE INTO movimientos_bancarios
+         (clave, cuenta, fecha_operacion, fecha_valor, importe, concepto_comun, concepto_propio, documento,
+          referencia_1, referencia_2, concepto, fichero)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)"
+    )?;
+    for apunte in &apuntes {
+        let contenido = format!(
+            "{}|{}|{}|{}|{}|{}|{}|{}",
+            apunte.cuenta,
+            apunte.fecha_operacion,
+            apunte.fecha_valor,
+            apunte.centimos,
+            apunte.documento,
+            apunte.referencia_1,
+            apunte.referencia_2,
+            apunte.concepto
+        );
+        let ocurrencia = ocurrencias.entry(contenido.clone()).or_insert(0);
+        *ocurrencia += 1;
+        let clave = format!("{}#{}", contenido, ocurrencia);
+
+        let insertados = insertar.execute(params![
+            clave,
+            apunte.cuenta,
+            apunte.fecha_operacion,
+            apunte.fecha_valor,
+            apunte.centimos as f64 / 100.0,
+            apunte.concepto_comun,
+            apunte.concepto_propio,
+            apunte.documento,
+            apunte.referencia_1,
+            apunte.referencia_2,
+            apunte.concepto,
+            nombre_fichero,
+        ])?;
+        if insertados == 0 {
+            resumen.repetidos += 1;
+        } else {
+            resumen.nuevos += 1;
+        }
+    }
+    drop(insertar);
+
+    tx.commit()?;
+
+    Ok(resumen)
+}
+
+pub fn list_movimientos(db: &DbConnection, estado: Option<EstadoMovimiento>) -> HermanarResult<Vec<MovimientoBancario>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(&format!(
+        "{} WHERE (?1 IS NULL OR estado = ?1) ORDER BY fecha_operacion DESC, id DESC",
+        SELECT_MOVIMIENTOS
+    ))?;
+
+    let movimientos = stmt.query_map([estado.map(|e| e.clave())], |row| {
+        MovimientoBancario::from_row(row)
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(movimientos)
+}
+
+fn buscar_movimiento(conn: &Connection, id: i64) -> HermanarResult<Option<MovimientoBancario>> {
+    let mut stmt = conn.prepare_cached(&format!("{} WHERE id = ?1", SELECT_MOVIMIENTOS))?;
+
+    Ok(stmt.query_row([id], MovimientoBancario::from_row).optional()?)
+}
+
+/// Cuotas pagadas con un movimiento conciliado.
+pub fn get_cuotas_movimiento(db: &DbConnection, movimiento_id: i64) -> HermanarResult<Vec<Cuota>> {
+    let conn = db.reader();
+    let ids = conn
+        .prepare_cached("SELECT cuota_id FROM conciliaciones WHERE movimiento_id = ?1 ORDER BY cuota_id")?
+        .query_map([movimiento_id], |row| row.get::<_, i32>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut cuotas = Vec::with_capacity(ids.len());
+    for id in ids {
+        if let Some(cuota) = buscar_cuota(&conn, id)? {
+            cuotas.push(cuota);
+        }
+    }
+
+    Ok(cuotas)
+}
+
+/// Propone, para cada abono pendiente, el hermano y las cuotas que
+/// probablemente paga. Se puntúa que el concepto contenga el DNI, el número de
+/// hermano o su nombre, y que el importe coincida con sus cuotas pendientes
+/// más antiguas. Si dos hermanos empatan no se propone ninguno.
+pub fn proponer_conciliaciones(db: &DbConnection) -> HermanarResult<Vec<PropuestaConciliacion>> {
+    let conn = db.reader();
+
+    let movimientos: Vec<MovimientoBancario> = conn
+        .prepare_cached(&format!(
+            "{} WHERE estado = 'pendiente' AND importe > 0 ORDER BY fecha_operacion, id",
+            SELECT_MOVIMIENTOS
+        ))?
+        .query_map([], MovimientoBancario::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut pendientes: BTreeMap<i32, Vec<Cuota>> = BTreeMap::new();
+    for cuota in buscar_cuotas_pendientes(&conn)? {
+        pendientes.entry(cuota.hermano_id).or_default().push(cuota);
+    }
+    let hermanos: Vec<Hermano> = listar_hermanos(&conn)?
+        .into_iter()
+        .filter(|h| h.id.is_some_and(|id| pendientes.contains_key(&id)))
+        .collect();
+
+    let mut propuestas = Vec::new();
+    for movimiento in movimientos {
+        let texto = movimiento.texto_busqueda();
+        let centimos = (movimiento.importe * 100.0).round() as i64;
+
+        let mut mejores: Vec<PropuestaConciliacion> = Vec::new();
+        for hermano in &hermanos {
+            let id = hermano.id.unwrap_or_default();
+            let Some((puntuacion, motivos, cuotas)) = puntuar(&texto, centimos, hermano, &pendientes[&id]) else {
+                continue;
+            };
+            if puntuacion < PUNTUACION_MINIMA || mejores.first().is_some_and(|m| m.puntuacion > puntuacion) {
+                continue;
+            }
+            if mejores.first().is_some_and(|m| m.puntuacion < puntuacion) {
+                mejores.clear();
+            }
+
+            let importe_cuotas = cuotas.iter().map(|c| (c.importe_pendiente * 100.0).round() as i64).sum::<i64>() as f64 / 100.0;
+            mejores.push(PropuestaConciliacion {
+                movimiento: movimiento.clone(),
+                hermano_id: id,
+                numero_hermano: hermano.numero_hermano.clone(),
+                nombre: format!(
+                    "{} {} {}",
+                    hermano.nombre,
+                    hermano.primer_apellido,
+                    hermano.segundo_apellido.as_deref().unwrap_or_default()
+                )
+                .trim()
+                .to_string(),
+                cuotas,
+                importe_cuotas,
+                puntuacion,
+                motivos,
+            });
+        }
+
+        if mejores.len() == 1 {
+            propuestas.extend(mejores);
+        }
+    }
+
+    propuestas.sort_by_key(|p| std::cmp::Reverse(p.puntuacion));
+
+    Ok(propuestas)
+}
+
+// Puntúa un hermano frente al texto de un movimiento y elige las cuotas
+// pendientes más antiguas que cubre el importe. Devuelve `None` si el importe
+// no llega ni para la primera.
+fn puntuar(texto: &str, centimos: i64, hermano: &Hermano, cuotas: &[Cuota]) -> Option<(i32, Vec<String>, Vec<Cuota>)> {
+    let palabras: HashSet<&str> = texto.split(|c: char| !c.is_ascii_alphanumeric()).filter(|p| !p.is_empty()).collect();
+    let compacto: String = texto.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+
+    let mut puntuacion = 0;
+    let mut motivos = Vec::new();
+
+    if let Some(dni) = hermano.dni.as_deref().filter(|d| d.len() >= 8) {
+        if compacto.contains(&dni.to_uppercase()) {
+            puntuacion += 50;
+            motivos.push(format!("DNI {}", dni));
+        }
+    }
+
+    let numero: String = hermano.numero_hermano.chars().filter(|c| c.is_ascii_digit()).collect();
+    if let Ok(numero) = numero.parse::<u64>() {
+        let aparece = palabras
+            .iter()
+            .any(|p| p.chars().all(|c| c.is_ascii_digit()) && p.parse::<u64>().ok() == Some(numero));
+        if aparece {
+            puntuacion += 30;
+            motivos.push(format!("número de hermano {}", hermano.numero_hermano));
+        }
+    }
+
+    let nombre = texto_sepa(
+        &format!(
+            "{} {} {}",
+            hermano.nombre,
+            hermano.primer_apellido,
+            hermano.segundo_apellido.as_deref().unwrap_or_default()
+        ),
+        usize::MAX,
+    )
+    .to_uppercase();
+    let partes: Vec<&str> = nombre.split_whitespace().filter(|p| p.len() >= 3).collect();
+    if !partes.is_empty() {
+        let coinciden = partes.iter().filter(|p| palabras.contains(*p)).count();
+        let proporcion = coinciden as f64 / partes.len() as f64;
+        if proporcion >= 0.5 {
+            puntuacion += (proporcion * 40.0).round() as i32;
+            motivos.push(format!("nombre ({} de {} palabras)", coinciden, partes.len()));
+        }
+    }
+
+    let mut seleccion = Vec::new();
+    let mut acumulado = 0;
+    for cuota in cuotas {
+        let importe = (cuota.importe_pendiente * 100.0).round() as i64;
+        if acumulado + importe > centimos {
+            break;
+        }
+        acumulado += importe;
+        seleccion.push(cuota.clone());
+    }
+    if seleccion.is_empty() {
+        return None;
+    }
+    if acumulado == centimos {
+        puntuacion += 30;
+        motivos.push(if seleccion.len() == 1 {
+            "importe igual a la cuota pendiente más antigua".to_string()
+        } else {
+            format!("importe igual a {} cuotas pendientes", seleccion.len())
+        });
+    }
+
+    Some((puntuacion, motivos, seleccion))
+}
+
+/// Concilia en bloque los movimientos confirmados: a sus cuotas se les anota
+/// un pago por transferencia de lo que les quedaba pendiente, en la fecha del
+/// movimiento, y este pasa a conciliado. Si alguna confirmación no es válida
+/// no se aplica ninguna. Devuelve el número de cuotas pagadas.
+pub fn confirmar_conciliaciones(db: &DbConnection, confirmaciones: &[ConfirmacionConciliacion]) -> HermanarResult<i32> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+    let operador = db.operador();
+
+    let mut pagadas = 0;
+    for confirmacion in confirmaciones {
+        let movimiento = buscar_movimiento(&tx, confirmacion.movimiento_id)?
+            .ok_or_else(|| HermanarError::not_found("Movimiento"))?;
+        if movimiento.estado != EstadoMovimiento::Pendiente {
+            return Err(HermanarError::conflict(format!(
+                "El movimiento del {} por {:.2} € ya está conciliado o descartado",
+                movimiento.fecha_operacion, movimiento.importe
+            )));
+        }
+        if confirmacion.cuota_ids.is_empty() {
+            return Err(HermanarError::validation("cuota_ids", "Indique las cuotas que paga el movimiento"));
+        }
+
+        let mut total = 0;
+        for &cuota_id in &confirmacion.cuota_ids {
+            let cuota = buscar_cuota(&tx, cuota_id)?.ok_or_else(|| HermanarError::not_found("Cuota"))?;
+            if cuota.pagado {
+                return Err(HermanarError::conflict(format!(
+                    "La cuota {} ya está pagada",
+                    cuota.etiqueta_periodo()
+                )));
+            }
+            let importe = (cuota.importe_pendiente * 100.0).round() as i64;
+            total += importe;
+
+            registrar_pago(&tx, &operador, &SolicitudPago {
+                cuota_id,
+                importe: Some(importe as f64 / 100.0),
+                fecha: movimiento.fecha_operacion.clone(),
+                metodo: METODO_TRANSFERENCIA.to_string(),
+                referencia: Some(movimiento.referencia_1.clone()).filter(|r| !r.trim().is_empty()),
+                observaciones: None,
+            })?;
+            tx.prepare_cached(
+                "INSERT INTO conciliaciones (movimiento_id, cuota_id, importe, operador) VALUES (?1, ?2, ?3, ?4)"
+            )?
+            .execute(params![movimiento.id, cuota_id, importe as f64 / 100.0, operador])?;
+            pagadas += 1;
+        }
+
+        if total > (movimiento.importe * 100.0).round() as i64 {
+            return Err(HermanarError::validation(
+                "cuota_ids",
+                format!(
+                    "Las cuotas suman más que el movimiento del {} por {:.2} €",
+                    movimiento.fecha_operacion, movimiento.importe
+                ),
+            ));
+        }
+
+        tx.prepare_cached("UPDATE movimientos_bancarios SET estado = 'conciliado' WHERE id = ?1")?
+            .execute([movimiento.id])?;
+    }
+
+    tx.commit()?;
+
+    Ok(pagadas)
+}
+
+/// Marca un movimiento pendiente como ajeno a las cuotas para que deje de
+/// proponerse.
+pub fn descartar_movimiento(db: &DbConnection, id: i64) -> HermanarResult<()> {
+    let conn = db.writer();
+
+    let movimiento = buscar_movimiento(&conn, id)?.ok_or_else(|| HermanarError::not_found("Movimiento"))?;
+    if movimiento.estado == EstadoMovimiento::Conciliado {
+        return Err(HermanarError::conflict("El movimiento ya está conciliado"));
+    }
+
+    conn.prepare_cached("UPDATE movimientos_bancarios SET estado = 'descartado' WHERE id = ?1")?
+        .execute([id])?;
+
+    Ok(())
+}
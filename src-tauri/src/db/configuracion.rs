@@ -0,0 +1,32 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use crate::error::{HermanarError, HermanarResult};
+
+/// Valor guardado para `clave` en la tabla de configuración general.
+pub(crate) fn leer(conn: &Connection, clave: &str) -> HermanarResult<Option<String>> {
+    let valor = conn
+        .prepare_cached("SELECT valor FROM configuracion WHERE clave = ?1")?
+        .query_row([clave], |row| row.get(0))
+        .optional()?;
+
+    Ok(valor)
+}
+
+/// Valor numérico de `clave`, o `por_defecto` si todavía no se ha configurado.
+pub(crate) fn leer_entero(conn: &Connection, clave: &str, por_defecto: i64) -> HermanarResult<i64> {
+    match leer(conn, clave)? {
+        Some(valor) => valor.parse().map_err(|_| {
+            HermanarError::database(format!("Valor no válido en la configuración para '{}': {}", clave, valor))
+        }),
+        None => Ok(por_defecto),
+    }
+}
+
+pub(crate) fn guardar(conn: &Connection, clave: &str, valor: &str) -> HermanarResult<()> {
+    conn.prepare_cached(
+        "INSERT INTO configuracion (clave, valor) VALUES (?1, ?2)
+         ON CONFLICT(clave) DO UPDATE SET valor = excluded.valor"
+    )?
+    .execute(params![clave, valor])?;
+
+    Ok(())
+}
@@ -1,32 +1,177 @@
-use rusqlite::{params, Result, Row};
-use crate::db::{Cuota, DbConnection, EstadisticasCuotas};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::db::{
+    configuracion, pagos, papelera, Cuota, DbConnection, EstadisticasCuotas, EstadisticasPeriodicidad, EstadoPago, Hermano,
+};
+use crate::db::auditoria::{self, ENTIDAD_CUOTA};
+use crate::db::hermanos::{buscar_hermano, listar_hermanos};
+use crate::db::membresia::hoy;
+use crate::db::pagos::SolicitudPago;
+use crate::db::tarifas::{CuadroTarifas, ImporteTarifa};
+use crate::error::{HermanarError, HermanarResult};
+
+const CLAVE_PERIODICIDAD: &str = "cuotas_periodicidad";
+
+const MESES: [&str; 12] = [
+    "enero", "febrero", "marzo", "abril", "mayo", "junio",
+    "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre",
+];
+
+/// Cada cuántos meses se cobra una cuota. El año se divide en tantos periodos
+/// como cuotas, numerados desde 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Periodicidad {
+    Anual,
+    Semestral,
+    #[default]
+    Trimestral,
+    Mensual,
+}
+
+impl Periodicidad {
+    pub const TODAS: [Periodicidad; 4] =
+        [Periodicidad::Anual, Periodicidad::Semestral, Periodicidad::Trimestral, Periodicidad::Mensual];
+
+    pub(crate) fn clave(&self) -> &'static str {
+        match self {
+            Periodicidad::Anual => "anual",
+            Periodicidad::Semestral => "semestral",
+            Periodicidad::Trimestral => "trimestral",
+            Periodicidad::Mensual => "mensual",
+        }
+    }
+
+    pub(crate) fn desde_clave(clave: &str) -> Option<Self> {
+        Self::TODAS.into_iter().find(|periodicidad| periodicidad.clave() == clave)
+    }
+
+    /// Número de periodos en un año.
+    pub fn periodos(&self) -> i32 {
+        12 / self.meses()
+    }
+
+    fn meses(&self) -> i32 {
+        match self {
+            Periodicidad::Anual => 12,
+            Periodicidad::Semestral => 6,
+            Periodicidad::Trimestral => 3,
+            Periodicidad::Mensual => 1,
+        }
+    }
+
+    /// Primer y último mes del año que cubre el periodo.
+    fn meses_periodo(&self, periodo: i32) -> (i32, i32) {
+        ((periodo - 1) * self.meses() + 1, periodo * self.meses())
+    }
+
+    pub(crate) fn validar_periodo(&self, periodo: i32) -> HermanarResult<()> {
+        if !(1..=self.periodos()).contains(&periodo) {
+            return Err(HermanarError::validation(
+                "periodo",
+                match self {
+                    Periodicidad::Anual => "Las cuotas anuales solo tienen el periodo 1".to_string(),
+                    _ => format!("El periodo debe estar entre 1 y {}", self.periodos()),
+                },
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Periodo con su preposición, para textos como «Cuota del 1º trimestre
+    /// de 2026» o «Cuota de marzo de 2026».
+    pub(crate) fn descripcion(&self, anio: i32, periodo: i32) -> String {
+        match self {
+            Periodicidad::Anual => format!("del año {}", anio),
+            Periodicidad::Semestral => format!("del {}º semestre de {}", periodo, anio),
+            Periodicidad::Trimestral => format!("del {}º trimestre de {}", periodo, anio),
+            Periodicidad::Mensual => {
+                let mes = usize::try_from(periodo - 1).ok().and_then(|indice| MESES.get(indice));
+                format!("de {} de {}", mes.copied().unwrap_or("?"), anio)
+            }
+        }
+    }
+
+    /// Forma corta del periodo para listados y mensajes: «2026 T1».
+    pub(crate) fn etiqueta(&self, anio: i32, periodo: i32) -> String {
+        match self {
+            Periodicidad::Anual => anio.to_string(),
+            Periodicidad::Semestral => format!("{} S{}", anio, periodo),
+            Periodicidad::Trimestral => format!("{} T{}", anio, periodo),
+            Periodicidad::Mensual => format!("{} M{:02}", anio, periodo),
+        }
+    }
+}
+
+// Columnas de `Cuota::from_row`, en su orden.
+const COLUMNAS_CUOTA: &str = "id, hermano_id, anio, periodo, importe, pagado,
+     fecha_pago, metodo_pago, observaciones, created_at, updated_at, deducible, periodicidad,
+     (SELECT COALESCE(SUM(p.importe), 0) FROM pagos p WHERE p.cuota_id = cuotas.id AND p.anulado = 0)";
+
+// Lo pagado de cada cuota según sus pagos vigentes, para las consultas que
+// agregan sobre `cuotas`.
+const CUOTAS_CON_PAGADO: &str = "(SELECT cuotas.*,
+         (SELECT COALESCE(SUM(p.importe), 0) FROM pagos p WHERE p.cuota_id = cuotas.id AND p.anulado = 0) AS cobrado
+     FROM cuotas)";
+
+// Primer mes del año que cubre la cuota, para ordenar cuotas de distinta
+// periodicidad.
+pub(crate) const MES_INICIO: &str = "((periodo - 1) * CASE periodicidad
+         WHEN 'anual' THEN 12 WHEN 'semestral' THEN 6 WHEN 'trimestral' THEN 3 ELSE 1 END + 1)";
 
 impl Cuota {
+    /// Periodo de la cuota con su preposición: «del 1º trimestre de 2026».
+    pub(crate) fn descripcion_periodo(&self) -> String {
+        self.periodicidad.descripcion(self.anio, self.periodo)
+    }
+
+    /// Forma corta del periodo de la cuota: «2026 T1».
+    pub(crate) fn etiqueta_periodo(&self) -> String {
+        self.periodicidad.etiqueta(self.anio, self.periodo)
+    }
+
     fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        let importe: f64 = row.get(4)?;
+        let pagado: bool = row.get(5)?;
+        let importe_pagado: f64 = row.get(13)?;
+
         Ok(Cuota {
             id: Some(row.get(0)?),
             hermano_id: row.get(1)?,
             anio: row.get(2)?,
-            trimestre: row.get(3)?,
-            importe: row.get(4)?,
-            pagado: row.get(5)?,
+            periodo: row.get(3)?,
+            importe,
+            pagado,
             fecha_pago: row.get(6)?,
             metodo_pago: row.get(7)?,
             observaciones: row.get(8)?,
             created_at: row.get(9)?,
             updated_at: row.get(10)?,
+            deducible: row.get(11)?,
+            periodicidad: Periodicidad::desde_clave(&row.get::<_, String>(12)?).unwrap_or_default(),
+            importe_pagado,
+            importe_pendiente: ((importe - importe_pagado).max(0.0) * 100.0).round() / 100.0,
+            estado: if pagado {
+                EstadoPago::Pagada
+            } else if importe_pagado > 0.0 {
+                EstadoPago::Parcial
+            } else {
+                EstadoPago::Pendiente
+            },
         })
     }
 }
 
-pub fn get_all_cuotas(db: &DbConnection) -> Result<Vec<Cuota>, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    let mut stmt = conn.prepare(
-        "SELECT id, hermano_id, anio, trimestre, importe, pagado,
-                fecha_pago, metodo_pago, observaciones, created_at, updated_at
-         FROM cuotas
-         ORDER BY anio DESC, trimestre DESC, hermano_id"
-    )?;
+pub fn get_all_cuotas(db: &DbConnection) -> HermanarResult<Vec<Cuota>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(
+        &format!(
+            "SELECT {} FROM cuotas
+             ORDER BY anio DESC, {} DESC, hermano_id",
+            COLUMNAS_CUOTA, MES_INICIO
+        ))?;
 
     let cuotas = stmt.query_map([], |row| {
         Cuota::from_row(row)
@@ -36,15 +181,18 @@ pub fn get_all_cuotas(db: &DbConnection) -> Result<Vec<Cuota>, anyhow::Error> {
     Ok(cuotas)
 }
 
-pub fn get_cuotas_by_hermano(db: &DbConnection, hermano_id: i32) -> Result<Vec<Cuota>, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    let mut stmt = conn.prepare(
-        "SELECT id, hermano_id, anio, trimestre, importe, pagado,
-                fecha_pago, metodo_pago, observaciones, created_at, updated_at
-         FROM cuotas
-         WHERE hermano_id = ?1
-         ORDER BY anio DESC, trimestre DESC"
-    )?;
+pub fn get_cuotas_by_hermano(db: &DbConnection, hermano_id: i32) -> HermanarResult<Vec<Cuota>> {
+    buscar_cuotas_hermano(&db.reader(), hermano_id)
+}
+
+pub(crate) fn buscar_cuotas_hermano(conn: &Connection, hermano_id: i32) -> HermanarResult<Vec<Cuota>> {
+    let mut stmt = conn.prepare_cached(
+        &format!(
+            "SELECT {} FROM cuotas
+             WHERE hermano_id = ?1
+             ORDER BY anio DESC, {} DESC",
+            COLUMNAS_CUOTA, MES_INICIO
+        ))?;
 
     let cuotas = stmt.query_map([hermano_id], |row| {
         Cuota::from_row(row)
@@ -54,16 +202,24 @@ pub fn get_cuotas_by_hermano(db: &DbConnection, hermano_id: i32) -> Result<Vec<C
     Ok(cuotas)
 }
 
-pub fn get_cuotas_by_year(db: &DbConnection, anio: i32) -> Result<Vec<Cuota>, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    let mut stmt = conn.prepare(
-        "SELECT id, hermano_id, anio, trimestre, importe, pagado,
-                fecha_pago, metodo_pago, observaciones, created_at, updated_at
-         FROM cuotas
-         WHERE anio = ?1
-         ORDER BY trimestre, hermano_id"
+pub(crate) fn buscar_cuota(conn: &Connection, id: i32) -> HermanarResult<Option<Cuota>> {
+    let mut stmt = conn.prepare_cached(
+        &format!("SELECT {} FROM cuotas WHERE id = ?1", COLUMNAS_CUOTA)
     )?;
 
+    Ok(stmt.query_row([id], Cuota::from_row).optional()?)
+}
+
+pub fn get_cuotas_by_year(db: &DbConnection, anio: i32) -> HermanarResult<Vec<Cuota>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(
+        &format!(
+            "SELECT {} FROM cuotas
+             WHERE anio = ?1
+             ORDER BY {}, hermano_id",
+            COLUMNAS_CUOTA, MES_INICIO
+        ))?;
+
     let cuotas = stmt.query_map([anio], |row| {
         Cuota::from_row(row)
     })?
@@ -72,15 +228,18 @@ pub fn get_cuotas_by_year(db: &DbConnection, anio: i32) -> Result<Vec<Cuota>, an
     Ok(cuotas)
 }
 
-pub fn get_cuotas_pendientes(db: &DbConnection) -> Result<Vec<Cuota>, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    let mut stmt = conn.prepare(
-        "SELECT id, hermano_id, anio, trimestre, importe, pagado,
-                fecha_pago, metodo_pago, observaciones, created_at, updated_at
-         FROM cuotas
-         WHERE pagado = 0
-         ORDER BY anio ASC, trimestre ASC, hermano_id"
-    )?;
+pub fn get_cuotas_pendientes(db: &DbConnection) -> HermanarResult<Vec<Cuota>> {
+    buscar_cuotas_pendientes(&db.reader())
+}
+
+pub(crate) fn buscar_cuotas_pendientes(conn: &Connection) -> HermanarResult<Vec<Cuota>> {
+    let mut stmt = conn.prepare_cached(
+        &format!(
+            "SELECT {} FROM cuotas
+             WHERE pagado = 0
+             ORDER BY anio ASC, {} ASC, hermano_id",
+            COLUMNAS_CUOTA, MES_INICIO
+        ))?;
 
     let cuotas = stmt.query_map([], |row| {
         Cuota::from_row(row)
@@ -90,155 +249,490 @@ pub fn get_cuotas_pendientes(db: &DbConnection) -> Result<Vec<Cuota>, anyhow::Er
     Ok(cuotas)
 }
 
-pub fn create_cuota(db: &DbConnection, cuota: &Cuota) -> Result<i32, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
+/// Crea una cuota. Si viene marcada como pagada se le anota un pago por todo
+/// su importe con la fecha y la forma de pago indicadas.
+pub fn create_cuota(db: &DbConnection, cuota: &Cuota) -> HermanarResult<i32> {
+    cuota.periodicidad.validar_periodo(cuota.periodo)?;
+
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+    let operador = db.operador();
+
+    comprobar_periodo_libre(&tx, cuota, None)?;
+    tx.prepare_cached(
+        "INSERT INTO cuotas (hermano_id, anio, periodicidad, periodo, importe, pagado, observaciones, deducible)
+         VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6, ?7)"
+    )?
+    .execute(params![
+        cuota.hermano_id,
+        cuota.anio,
+        cuota.periodicidad.clave(),
+        cuota.periodo,
+        cuota.importe,
+        cuota.observaciones,
+        cuota.deducible,
+    ])?;
+
+    let id = tx.last_insert_rowid() as i32;
+    if cuota.pagado && !sin_importe(cuota) {
+        pagos::anotar_pago(&tx, &operador, &pago_del_formulario(id, cuota))?;
+    } else {
+        pagos::actualizar_estado(&tx, id)?;
+    }
+    let nueva = buscar_cuota(&tx, id)?;
+    auditoria::registrar(&tx, &operador, ENTIDAD_CUOTA, id, None, nueva.as_ref())?;
+
+    tx.commit()?;
+
+    Ok(id)
+}
 
-    conn.execute(
+/// Vuelve a insertar una cuota tal y como estaba, conservando su `id` y sus
+/// fechas. Se usa al restaurarla desde la papelera.
+pub(crate) fn reinsertar_cuota(conn: &Connection, cuota: &Cuota) -> HermanarResult<()> {
+    conn.prepare_cached(
         "INSERT INTO cuotas
-         (hermano_id, anio, trimestre, importe, pagado, fecha_pago, metodo_pago, observaciones)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-        params![
-            cuota.hermano_id,
-            cuota.anio,
-            cuota.trimestre,
-            cuota.importe,
-            cuota.pagado,
-            cuota.fecha_pago,
-            cuota.metodo_pago,
-            cuota.observaciones,
-        ],
-    )?;
+         (id, hermano_id, anio, periodo, importe, pagado, fecha_pago, metodo_pago, observaciones,
+          created_at, updated_at, deducible, periodicidad)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9,
+                 COALESCE(?10, CURRENT_TIMESTAMP), COALESCE(?11, CURRENT_TIMESTAMP), ?12, ?13)"
+    )?
+    .execute(params![
+        cuota.id,
+        cuota.hermano_id,
+        cuota.anio,
+        cuota.periodo,
+        cuota.importe,
+        cuota.pagado,
+        cuota.fecha_pago,
+        cuota.metodo_pago,
+        cuota.observaciones,
+        cuota.created_at,
+        cuota.updated_at,
+        cuota.deducible,
+        cuota.periodicidad.clave(),
+    ])?;
 
-    Ok(conn.last_insert_rowid() as i32)
+    Ok(())
 }
 
-pub fn update_cuota(db: &DbConnection, id: i32, cuota: &Cuota) -> Result<(), anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
+/// Modifica una cuota. Marcarla como pagada le anota un pago por lo que le
+/// quede pendiente; desmarcarla anula todos sus pagos y sus recibos.
+pub fn update_cuota(db: &DbConnection, id: i32, cuota: &Cuota) -> HermanarResult<()> {
+    cuota.periodicidad.validar_periodo(cuota.periodo)?;
+
+    let operador = db.operador();
+    modificar_cuota(db, id, |conn| {
+        let antes = buscar_cuota(conn, id)?.ok_or_else(|| HermanarError::not_found("Cuota"))?;
+        if (cuota.importe * 100.0).round() < (antes.importe_pagado * 100.0).round() {
+            return Err(HermanarError::validation(
+                "importe",
+                format!("La cuota ya tiene pagados {:.2} €; anule antes algún pago", antes.importe_pagado),
+            ));
+        }
 
-    conn.execute(
-        "UPDATE cuotas
-         SET hermano_id = ?1, anio = ?2, trimestre = ?3, importe = ?4,
-             pagado = ?5, fecha_pago = ?6, metodo_pago = ?7,
-             observaciones = ?8, updated_at = CURRENT_TIMESTAMP
-         WHERE id = ?9",
-        params![
+        comprobar_periodo_libre(conn, cuota, Some(id))?;
+        conn.prepare_cached(
+            "UPDATE cuotas
+             SET hermano_id = ?1, anio = ?2, periodicidad = ?3, periodo = ?4, importe = ?5,
+                 observaciones = ?6, deducible = ?7, updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?8"
+        )?
+        .execute(params![
             cuota.hermano_id,
             cuota.anio,
-            cuota.trimestre,
+            cuota.periodicidad.clave(),
+            cuota.periodo,
             cuota.importe,
-            cuota.pagado,
-            cuota.fecha_pago,
-            cuota.metodo_pago,
             cuota.observaciones,
+            cuota.deducible,
             id,
-        ],
-    )?;
+        ])?;
+
+        match (antes.pagado, cuota.pagado) {
+            (false, true) if !sin_importe(cuota) => {
+                pagos::anotar_pago(conn, &operador, &pago_del_formulario(id, cuota))?;
+            }
+            (true, false) => pagos::anular_pagos_cuota(conn, id)?,
+            _ => pagos::actualizar_estado(conn, id)?,
+        }
+        Ok(())
+    })
+}
+
+/// Paga todo lo pendiente de una cuota. Ver `pagos::create_pago` para
+/// anotar pagos parciales.
+pub fn marcar_cuota_pagada(db: &DbConnection, id: i32, fecha_pago: &str, metodo_pago: &str) -> HermanarResult<()> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+
+    pagos::registrar_pago(&tx, &db.operador(), &SolicitudPago {
+        cuota_id: id,
+        importe: None,
+        fecha: fecha_pago.to_string(),
+        metodo: metodo_pago.to_string(),
+        referencia: None,
+        observaciones: None,
+    })?;
+
+    tx.commit()?;
 
     Ok(())
 }
 
-pub fn marcar_cuota_pagada(db: &DbConnection, id: i32, fecha_pago: &str, metodo_pago: &str) -> Result<(), anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
+// Las cuotas de 0 € no reciben pagos: están pagadas desde que se crean.
+fn sin_importe(cuota: &Cuota) -> bool {
+    (cuota.importe * 100.0).round() <= 0.0
+}
 
-    conn.execute(
-        "UPDATE cuotas
-         SET pagado = 1, fecha_pago = ?1, metodo_pago = ?2, updated_at = CURRENT_TIMESTAMP
-         WHERE id = ?3",
-        params![fecha_pago, metodo_pago, id],
-    )?;
+// Pago por lo pendiente con la fecha y la forma de pago del formulario de la
+// cuota, o de hoy y en efectivo si no se indican.
+fn pago_del_formulario(id: i32, cuota: &Cuota) -> SolicitudPago {
+    SolicitudPago {
+        cuota_id: id,
+        importe: None,
+        fecha: cuota.fecha_pago.clone().filter(|f| !f.trim().is_empty()).unwrap_or_else(hoy),
+        metodo: cuota.metodo_pago.clone().filter(|m| !m.trim().is_empty()).unwrap_or_else(|| "efectivo".to_string()),
+        referencia: None,
+        observaciones: None,
+    }
+}
+
+/// Elimina una cuota dejando una copia en la papelera.
+pub fn delete_cuota(db: &DbConnection, id: i32) -> HermanarResult<()> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+
+    let cuota = buscar_cuota(&tx, id)?.ok_or_else(|| HermanarError::not_found("Cuota"))?;
+
+    papelera::guardar_cuota(&tx, &db.operador(), &cuota)?;
+    tx.prepare_cached("DELETE FROM cuotas WHERE id = ?1")?.execute([id])?;
+    auditoria::registrar(&tx, &db.operador(), ENTIDAD_CUOTA, id, Some(&cuota), None)?;
+
+    tx.commit()?;
 
     Ok(())
 }
 
-pub fn delete_cuota(db: &DbConnection, id: i32) -> Result<(), anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
+/// Aplica `cambio` a una cuota existente dentro de una transacción y anota en
+/// la auditoría su estado antes y después.
+fn modificar_cuota(
+    db: &DbConnection,
+    id: i32,
+    cambio: impl FnOnce(&Connection) -> HermanarResult<()>,
+) -> HermanarResult<()> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+
+    let antes = buscar_cuota(&tx, id)?.ok_or_else(|| HermanarError::not_found("Cuota"))?;
+    cambio(&tx)?;
+    let despues = buscar_cuota(&tx, id)?;
+    auditoria::registrar(&tx, &db.operador(), ENTIDAD_CUOTA, id, Some(&antes), despues.as_ref())?;
 
-    conn.execute("DELETE FROM cuotas WHERE id = ?1", [id])?;
+    tx.commit()?;
 
     Ok(())
 }
 
-pub fn generar_cuotas_trimestre(db: &DbConnection, anio: i32, trimestre: i32, importe: f64) -> Result<i32, anyhow::Error> {
-    if trimestre < 1 || trimestre > 4 {
-        return Err(anyhow::anyhow!("El trimestre debe estar entre 1 y 4"));
+/// Comprueba que el hermano no tiene ya otra cuota que cubra alguno de los
+/// meses de `cuota`, de la misma o de otra periodicidad.
+pub(crate) fn comprobar_periodo_libre(conn: &Connection, cuota: &Cuota, excepto: Option<i32>) -> HermanarResult<()> {
+    if let Some(otra) = cuota_solapada(conn, cuota.hermano_id, cuota.anio, cuota.periodicidad, cuota.periodo, excepto)? {
+        return Err(HermanarError::conflict(format!(
+            "El hermano ya tiene la cuota {} que cubre ese periodo",
+            otra.etiqueta_periodo()
+        )));
     }
 
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
+    Ok(())
+}
 
-    let mut stmt = conn.prepare("SELECT id FROM hermanos WHERE activo = 1")?;
-    let hermano_ids: Result<Vec<i32>, _> = stmt.query_map([], |row| {
-        Ok(row.get::<_, i32>(0)?)
-    })?.collect();
+fn cuota_solapada(
+    conn: &Connection,
+    hermano_id: i32,
+    anio: i32,
+    periodicidad: Periodicidad,
+    periodo: i32,
+    excepto: Option<i32>,
+) -> HermanarResult<Option<Cuota>> {
+    let (desde, hasta) = periodicidad.meses_periodo(periodo);
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {columnas} FROM cuotas
+         WHERE hermano_id = ?1 AND anio = ?2 AND id IS NOT ?3
+           AND {inicio} <= ?5
+           AND {inicio} + CASE periodicidad
+               WHEN 'anual' THEN 12 WHEN 'semestral' THEN 6 WHEN 'trimestral' THEN 3 ELSE 1 END - 1 >= ?4
+         LIMIT 1",
+        columnas = COLUMNAS_CUOTA,
+        inicio = MES_INICIO
+    ))?;
+
+    Ok(stmt.query_row(params![hermano_id, anio, excepto, desde, hasta], Cuota::from_row).optional()?)
+}
 
-    let hermano_ids = hermano_ids?;
-    let mut creadas = 0;
+/// Genera las cuotas trimestrales del trimestre. Ver `generar_cuotas_periodo`.
+pub fn generar_cuotas_trimestre(
+    db: &DbConnection,
+    anio: i32,
+    trimestre: i32,
+    importe: Option<f64>,
+) -> HermanarResult<i32> {
+    generar_cuotas_periodo(db, anio, Periodicidad::Trimestral, trimestre, importe)
+}
 
-    for hermano_id in hermano_ids {
-        let mut check_stmt = conn.prepare(
-            "SELECT COUNT(*) FROM cuotas WHERE hermano_id = ?1 AND anio = ?2 AND trimestre = ?3"
-        )?;
+/// Genera la cuota del periodo de cada hermano activo que paga con esa
+/// periodicidad y aún no tiene otra cuota que lo cubra. Con `importe` todos
+/// pagan lo mismo; sin él, cada uno paga la parte del periodo de la tarifa
+/// anual de su categoría vigente el primer día del periodo, y los de
+/// categorías exentas no reciben cuota.
+pub fn generar_cuotas_periodo(
+    db: &DbConnection,
+    anio: i32,
+    periodicidad: Periodicidad,
+    periodo: i32,
+    importe: Option<f64>,
+) -> HermanarResult<i32> {
+    periodicidad.validar_periodo(periodo)?;
+    if importe.is_some_and(|importe| !importe.is_finite() || importe <= 0.0) {
+        return Err(HermanarError::validation("importe", "El importe debe ser mayor que cero"));
+    }
 
-        let existe: i32 = check_stmt.query_row(params![hermano_id, anio, trimestre], |row| {
-            row.get(0)
-        })?;
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+    let operador = db.operador();
+
+    let importes = {
+        let por_defecto = leer_periodicidad(&tx)?;
+        let propias = periodicidades_hermanos(&tx)?;
+        let hermanos = listar_hermanos(&tx)?.into_iter().filter(|hermano| {
+            let propia = hermano.id.and_then(|id| propias.get(&id).copied());
+            hermano.activo && propia.unwrap_or(por_defecto) == periodicidad
+        });
+        let inicio = format!("{}-{:02}-01", anio, periodicidad.meses_periodo(periodo).0);
+        match importe {
+            Some(importe) => hermanos.map(|hermano| (hermano.id.unwrap_or_default(), importe)).collect(),
+            None => importes_tarifa(&tx, hermanos, &inicio, periodo, periodicidad.periodos())?,
+        }
+    };
 
-        if existe == 0 {
-            conn.execute(
-                "INSERT INTO cuotas (hermano_id, anio, trimestre, importe, pagado)
-                 VALUES (?1, ?2, ?3, ?4, 0)",
-                params![hermano_id, anio, trimestre, importe],
-            )?;
-            creadas += 1;
+    let mut creadas = 0;
+
+    for (hermano_id, importe) in importes {
+        if cuota_solapada(&tx, hermano_id, anio, periodicidad, periodo, None)?.is_some() {
+            continue;
         }
+        tx.prepare_cached(
+            "INSERT INTO cuotas (hermano_id, anio, periodicidad, periodo, importe, pagado)
+             VALUES (?1, ?2, ?3, ?4, ?5, 0)"
+        )?.execute(params![hermano_id, anio, periodicidad.clave(), periodo, importe])?;
+
+        let id = tx.last_insert_rowid() as i32;
+        pagos::actualizar_estado(&tx, id)?;
+        let nueva = buscar_cuota(&tx, id)?;
+        auditoria::registrar(&tx, &operador, ENTIDAD_CUOTA, id, None, nueva.as_ref())?;
+        creadas += 1;
     }
 
+    tx.commit()?;
+
     Ok(creadas)
 }
 
-pub fn get_estadisticas_cuotas(db: &DbConnection, anio: Option<i32>) -> Result<EstadisticasCuotas, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
+// Importe de cada hermano para el periodo `periodo` de los `periodos` del año
+// según las tarifas vigentes en `fecha`, sin los exentos. Si a algún hermano
+// no se le puede calcular, no se genera ninguna cuota.
+fn importes_tarifa(
+    conn: &Connection,
+    hermanos: impl Iterator<Item = Hermano>,
+    fecha: &str,
+    periodo: i32,
+    periodos: i32,
+) -> HermanarResult<Vec<(i32, f64)>> {
+    let cuadro = CuadroTarifas::cargar(conn)?;
+    let mut importes = Vec::new();
+    let mut sin_importe = Vec::new();
+
+    for hermano in hermanos {
+        match cuadro.importe(&hermano, fecha) {
+            ImporteTarifa::Importe(importe) => {
+                importes.push((hermano.id.unwrap_or_default(), parte_periodo(importe, periodo, periodos)))
+            }
+            ImporteTarifa::Exento => {}
+            ImporteTarifa::SinCategoria => {
+                sin_importe.push(format!("{} (sin categoría)", hermano.numero_hermano))
+            }
+            ImporteTarifa::SinTarifa(categoria) => {
+                sin_importe.push(format!("{} ({} sin tarifa vigente)", hermano.numero_hermano, categoria))
+            }
+        }
+    }
 
-    let where_clause = if let Some(year) = anio {
-        format!("WHERE anio = {}", year)
+    if !sin_importe.is_empty() {
+        return Err(HermanarError::validation(
+            "importe",
+            format!(
+                "No se puede calcular la cuota de {} hermanos: {}",
+                sin_importe.len(),
+                sin_importe.iter().take(10).cloned().collect::<Vec<_>>().join(", ")
+            ),
+        ));
+    }
+
+    Ok(importes)
+}
+
+// Parte de la tarifa anual que toca pagar en el periodo. Los céntimos que no
+// se reparten a partes iguales se cargan en el último, para que las cuotas del
+// año sumen exactamente la tarifa.
+fn parte_periodo(tarifa: f64, periodo: i32, periodos: i32) -> f64 {
+    let total = (tarifa * 100.0).round() as i64;
+    let parte = total / periodos as i64;
+    let centimos = if periodo == periodos {
+        total - parte * (periodos as i64 - 1)
     } else {
-        String::new()
+        parte
+    };
+
+    centimos as f64 / 100.0
+}
+
+/// Periodicidad con la que pagan los hermanos que no tienen una propia.
+pub fn get_periodicidad_cuotas(db: &DbConnection) -> HermanarResult<Periodicidad> {
+    leer_periodicidad(&db.reader())
+}
+
+fn leer_periodicidad(conn: &Connection) -> HermanarResult<Periodicidad> {
+    Ok(configuracion::leer(conn, CLAVE_PERIODICIDAD)?
+        .as_deref()
+        .and_then(Periodicidad::desde_clave)
+        .unwrap_or_default())
+}
+
+pub fn set_periodicidad_cuotas(db: &DbConnection, periodicidad: Periodicidad) -> HermanarResult<()> {
+    configuracion::guardar(&db.writer(), CLAVE_PERIODICIDAD, periodicidad.clave())
+}
+
+/// Hermanos que pagan con una periodicidad distinta de la general.
+pub fn get_periodicidades_hermanos(db: &DbConnection) -> HermanarResult<HashMap<i32, Periodicidad>> {
+    periodicidades_hermanos(&db.reader())
+}
+
+fn periodicidades_hermanos(conn: &Connection) -> HermanarResult<HashMap<i32, Periodicidad>> {
+    let mut stmt = conn.prepare_cached("SELECT hermano_id, periodicidad FROM periodicidades_hermano")?;
+    let periodicidades = stmt
+        .query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(periodicidades
+        .into_iter()
+        .filter_map(|(hermano_id, clave)| Periodicidad::desde_clave(&clave).map(|p| (hermano_id, p)))
+        .collect())
+}
+
+/// Fija la periodicidad con la que paga un hermano. Sin periodicidad, el
+/// hermano vuelve a pagar con la general.
+pub fn set_periodicidad_hermano(
+    db: &DbConnection,
+    hermano_id: i32,
+    periodicidad: Option<Periodicidad>,
+) -> HermanarResult<()> {
+    let conn = db.writer();
+    if buscar_hermano(&conn, hermano_id)?.is_none() {
+        return Err(HermanarError::not_found("Hermano"));
+    }
+
+    match periodicidad {
+        Some(periodicidad) => {
+            conn.prepare_cached(
+                "INSERT INTO periodicidades_hermano (hermano_id, periodicidad) VALUES (?1, ?2)
+                 ON CONFLICT(hermano_id) DO UPDATE SET periodicidad = excluded.periodicidad"
+            )?
+            .execute(params![hermano_id, periodicidad.clave()])?;
+        }
+        None => {
+            conn.prepare_cached("DELETE FROM periodicidades_hermano WHERE hermano_id = ?1")?.execute([hermano_id])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Periodicidad propia de un hermano, si la tiene.
+pub(crate) fn buscar_periodicidad_hermano(conn: &Connection, hermano_id: i32) -> HermanarResult<Option<Periodicidad>> {
+    let clave: Option<String> = conn
+        .prepare_cached("SELECT periodicidad FROM periodicidades_hermano WHERE hermano_id = ?1")?
+        .query_row([hermano_id], |row| row.get(0))
+        .optional()?;
+
+    Ok(clave.as_deref().and_then(Periodicidad::desde_clave))
+}
+
+/// Vuelve a fijar la periodicidad propia de un hermano restaurado de la
+/// papelera.
+pub(crate) fn reinsertar_periodicidad_hermano(
+    conn: &Connection,
+    hermano_id: i32,
+    periodicidad: Periodicidad,
+) -> HermanarResult<()> {
+    conn.prepare_cached("INSERT INTO periodicidades_hermano (hermano_id, periodicidad) VALUES (?1, ?2)")?
+        .execute(params![hermano_id, periodicidad.clave()])?;
+
+    Ok(())
+}
+
+/// Marca o desmarca como deducibles todas las cuotas de un año. Devuelve
+/// cuántas cambian.
+pub fn set_cuotas_deducibles(db: &DbConnection, anio: i32, deducible: bool) -> HermanarResult<usize> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+    let operador = db.operador();
+
+    let ids = {
+        let mut stmt = tx.prepare_cached("SELECT id FROM cuotas WHERE anio = ?1 AND deducible != ?2")?;
+        let ids = stmt.query_map(params![anio, deducible], |row| row.get::<_, i32>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        ids
     };
 
-    let query = format!(
+    for &id in &ids {
+        let antes = buscar_cuota(&tx, id)?;
+        tx.prepare_cached("UPDATE cuotas SET deducible = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2")?
+            .execute(params![deducible, id])?;
+        let despues = buscar_cuota(&tx, id)?;
+        auditoria::registrar(&tx, &operador, ENTIDAD_CUOTA, id, antes.as_ref(), despues.as_ref())?;
+    }
+
+    tx.commit()?;
+
+    Ok(ids.len())
+}
+
+pub fn get_estadisticas_cuotas(db: &DbConnection, anio: Option<i32>) -> HermanarResult<EstadisticasCuotas> {
+    let conn = db.reader();
+
+    let mut stmt = conn.prepare_cached(&format!(
         "SELECT
-            SUM(CASE WHEN pagado = 1 THEN importe ELSE 0 END) as total_recaudado,
+            COALESCE(SUM(cobrado), 0) as total_recaudado,
             COUNT(CASE WHEN pagado = 0 THEN 1 END) as cuotas_pendientes,
-            COUNT(CASE WHEN pagado = 1 THEN 1 END) as cuotas_pagadas
-         FROM cuotas
-         {}",
-        where_clause
-    );
-
-    let mut stmt = conn.prepare(&query)?;
-    let (total_recaudado, cuotas_pendientes, cuotas_pagadas) = stmt.query_row([], |row| {
-        Ok((
-            row.get::<_, f64>(0)?,
-            row.get::<_, i32>(1)?,
-            row.get::<_, i32>(2)?
-        ))
-    })?;
+            COUNT(CASE WHEN pagado = 1 THEN 1 END) as cuotas_pagadas,
+            COUNT(CASE WHEN pagado = 0 AND cobrado > 0 THEN 1 END) as cuotas_parciales,
+            COALESCE(SUM(CASE WHEN pagado = 0 THEN MAX(importe - cobrado, 0) ELSE 0 END), 0) as total_pendiente
+         FROM {}
+         WHERE ?1 IS NULL OR anio = ?1",
+        CUOTAS_CON_PAGADO
+    ))?;
+    let (total_recaudado, cuotas_pendientes, cuotas_pagadas, cuotas_parciales, total_pendiente) =
+        stmt.query_row([anio], |row| {
+            Ok((
+                row.get::<_, f64>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, i32>(2)?,
+                row.get::<_, i32>(3)?,
+                row.get::<_, f64>(4)?
+            ))
+        })?;
 
-    let hermanos_query = if let Some(year) = anio {
-        format!(
-            "SELECT
-                COUNT(DISTINCT CASE WHEN moroso = 0 THEN hermano_id END) as al_dia,
-                COUNT(DISTINCT CASE WHEN moroso = 1 THEN hermano_id END) as morosos
-             FROM (
-                 SELECT
-                     hermano_id,
-                     CASE WHEN COUNT(CASE WHEN pagado = 0 THEN 1 END) > 0 THEN 1 ELSE 0 END as moroso
-                 FROM cuotas
-                 WHERE anio = {}
-                 GROUP BY hermano_id
-             )",
-            year
-        )
-    } else {
+    let mut hermanos_stmt = conn.prepare_cached(
         "SELECT
             COUNT(DISTINCT CASE WHEN moroso = 0 THEN hermano_id END) as al_dia,
             COUNT(DISTINCT CASE WHEN moroso = 1 THEN hermano_id END) as morosos
@@ -247,23 +741,114 @@ pub fn get_estadisticas_cuotas(db: &DbConnection, anio: Option<i32>) -> Result<E
                  hermano_id,
                  CASE WHEN COUNT(CASE WHEN pagado = 0 THEN 1 END) > 0 THEN 1 ELSE 0 END as moroso
              FROM cuotas
+             WHERE ?1 IS NULL OR anio = ?1
              GROUP BY hermano_id
-         )".to_string()
-    };
-
-    let mut hermanos_stmt = conn.prepare(&hermanos_query)?;
-    let (hermanos_al_dia, hermanos_morosos) = hermanos_stmt.query_row([], |row| {
+         )"
+    )?;
+    let (hermanos_al_dia, hermanos_morosos) = hermanos_stmt.query_row([anio], |row| {
         Ok((
             row.get::<_, i32>(0)?,
             row.get::<_, i32>(1)?
         ))
     })?;
 
+    let mut periodicidad_stmt = conn.prepare_cached(&format!(
+        "SELECT
+            periodicidad,
+            COALESCE(SUM(cobrado), 0) as total_recaudado,
+            COUNT(CASE WHEN pagado = 0 THEN 1 END) as cuotas_pendientes,
+            COUNT(CASE WHEN pagado = 1 THEN 1 END) as cuotas_pagadas,
+            COUNT(DISTINCT hermano_id) as hermanos
+         FROM {}
+         WHERE ?1 IS NULL OR anio = ?1
+         GROUP BY periodicidad",
+        CUOTAS_CON_PAGADO
+    ))?;
+    let mut por_periodicidad = periodicidad_stmt
+        .query_map([anio], |row| {
+            Ok(EstadisticasPeriodicidad {
+                periodicidad: Periodicidad::desde_clave(&row.get::<_, String>(0)?).unwrap_or_default(),
+                total_recaudado: row.get(1)?,
+                cuotas_pendientes: row.get(2)?,
+                cuotas_pagadas: row.get(3)?,
+                hermanos: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    por_periodicidad.sort_by_key(|e| Periodicidad::TODAS.iter().position(|p| *p == e.periodicidad));
+
     Ok(EstadisticasCuotas {
         total_recaudado,
         cuotas_pendientes,
         cuotas_pagadas,
+        cuotas_parciales,
+        total_pendiente: (total_pendiente * 100.0).round() / 100.0,
         hermanos_al_dia,
         hermanos_morosos,
+        por_periodicidad,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::base_de_datos_en_memoria;
+
+    fn centimos_periodos(tarifa: f64, periodicidad: Periodicidad) -> Vec<i64> {
+        let periodos = periodicidad.periodos();
+        (1..=periodos)
+            .map(|periodo| (parte_periodo(tarifa, periodo, periodos) * 100.0).round() as i64)
+            .collect()
+    }
+
+    #[test]
+    fn partes_de_la_tarifa() {
+        assert_eq!(centimos_periodos(100.0, Periodicidad::Trimestral), [2500, 2500, 2500, 2500]);
+        assert_eq!(centimos_periodos(100.0, Periodicidad::Anual), [10000]);
+
+        let tercios: Vec<i64> = (1..=3)
+            .map(|periodo| (parte_periodo(100.0, periodo, 3) * 100.0).round() as i64)
+            .collect();
+        assert_eq!(tercios, [3333, 3333, 3334]);
+        let mensual = centimos_periodos(10.0, Periodicidad::Mensual);
+        assert_eq!(mensual[..11], [83; 11]);
+        assert_eq!(mensual[11], 87);
+
+        for tarifa in [10.0, 25.5, 99.99, 100.0, 120.01] {
+            for periodicidad in Periodicidad::TODAS {
+                let suma: i64 = centimos_periodos(tarifa, periodicidad).iter().sum();
+                assert_eq!(suma, (tarifa * 100.0).round() as i64, "{} € {:?}", tarifa, periodicidad);
+            }
+        }
+    }
+
+    #[test]
+    fn cuotas_solapadas() {
+        let conn = base_de_datos_en_memoria();
+        conn.execute_batch(
+            "INSERT INTO hermanos (id, numero_hermano, nombre, primer_apellido, fecha_alta)
+             VALUES (1, '1', 'José', 'Núñez', '2020-01-01');
+             INSERT INTO cuotas (id, hermano_id, anio, periodicidad, periodo, importe) VALUES
+                 (1, 1, 2026, 'anual', 1, 60),
+                 (2, 1, 2027, 'mensual', 5, 5);",
+        )
+        .unwrap();
+        let solapada = |anio: i32, periodicidad: Periodicidad, periodo: i32, excepto: Option<i32>| {
+            cuota_solapada(&conn, 1, anio, periodicidad, periodo, excepto).unwrap().and_then(|cuota| cuota.id)
+        };
+
+        // La cuota anual cubre todos los trimestres de su año
+        for trimestre in 1..=4 {
+            assert_eq!(solapada(2026, Periodicidad::Trimestral, trimestre, None), Some(1));
+        }
+        assert_eq!(solapada(2026, Periodicidad::Anual, 1, Some(1)), None);
+
+        // La cuota de mayo bloquea el segundo trimestre y el primer semestre
+        assert_eq!(solapada(2027, Periodicidad::Trimestral, 2, None), Some(2));
+        assert_eq!(solapada(2027, Periodicidad::Semestral, 1, None), Some(2));
+        assert_eq!(solapada(2027, Periodicidad::Anual, 1, None), Some(2));
+        assert_eq!(solapada(2027, Periodicidad::Trimestral, 1, None), None);
+        assert_eq!(solapada(2027, Periodicidad::Trimestral, 3, None), None);
+        assert_eq!(solapada(2027, Periodicidad::Mensual, 6, None), None);
+    }
+}
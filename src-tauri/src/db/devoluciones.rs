@@ -0,0 +1,519 @@
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::db::DbConnection;
+use crate::db::cuotas::buscar_cuota;
+use crate::db::pagos::revertir_pago;
+use crate::db::membresia::hoy;
+use crate::error::{HermanarError, HermanarResult};
+
+const SELECT_DEVOLUCIONES: &str =
+    "SELECT d.id, d.remesa_id, d.cuota_id, d.hermano_id, d.end_to_end_id, d.codigo_motivo, d.fecha_devolucion,
+            d.importe, d.comision, d.comision_repercutida, d.fichero, d.operador, d.created_at,
+            h.numero_hermano, TRIM(h.nombre || ' ' || h.primer_apellido || ' ' || COALESCE(h.segundo_apellido, '')),
+            h.telefono, h.email, rc.mandato_referencia, c.pagado
+     FROM devoluciones d
+     LEFT JOIN hermanos h ON h.id = d.hermano_id
+     LEFT JOIN remesa_cuotas rc ON rc.end_to_end_id = d.end_to_end_id
+     LEFT JOIN cuotas c ON c.id = d.cuota_id";
+
+/// Adeudo de una remesa que el banco ha devuelto, con los datos del hermano
+/// necesarios para reclamar el pago.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Devolucion {
+    pub id: i64,
+    pub remesa_id: i64,
+    pub cuota_id: i32,
+    pub hermano_id: i32,
+    pub end_to_end_id: String,
+    pub codigo_motivo: String,
+    pub motivo: String,
+    pub fecha_devolucion: String,
+    pub importe: f64,
+    pub comision: f64,
+    /// Si la comisión se ha sumado al importe de la cuota.
+    pub comision_repercutida: bool,
+    pub fichero: String,
+    pub operador: String,
+    pub created_at: String,
+    pub numero_hermano: Option<String>,
+    pub nombre_hermano: Option<String>,
+    pub telefono: Option<String>,
+    pub email: Option<String>,
+    pub mandato_referencia: Option<String>,
+    /// `None` si la cuota ya no existe.
+    pub cuota_pagada: Option<bool>,
+}
+
+impl Devolucion {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        let codigo_motivo: String = row.get(5)?;
+        Ok(Devolucion {
+            id: row.get(0)?,
+            remesa_id: row.get(1)?,
+            cuota_id: row.get(2)?,
+            hermano_id: row.get(3)?,
+            end_to_end_id: row.get(4)?,
+            motivo: descripcion_motivo(&codigo_motivo).to_string(),
+            codigo_motivo,
+            fecha_devolucion: row.get(6)?,
+            importe: row.get(7)?,
+            comision: row.get(8)?,
+            comision_repercutida: row.get(9)?,
+            fichero: row.get(10)?,
+            operador: row.get(11)?,
+            created_at: row.get(12)?,
+            numero_hermano: row.get(13)?,
+            nombre_hermano: row.get(14)?,
+            telefono: row.get(15)?,
+            email: row.get(16)?,
+            mandato_referencia: row.get(17)?,
+            cuota_pagada: row.get(18)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolicitudDevoluciones {
+    /// Ruta del fichero de estado (pain.002) recibido del banco.
+    pub fichero: String,
+    /// Comisión que cobra el banco por cada recibo devuelto.
+    pub comision: Option<f64>,
+    /// Suma la comisión al importe de la cuota para reclamársela al hermano.
+    #[serde(default)]
+    pub repercutir_comision: bool,
+}
+
+/// Resultado de importar un fichero de devoluciones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumenDevoluciones {
+    pub fichero: String,
+    /// Operaciones rechazadas o devueltas que trae el fichero.
+    pub rechazadas_en_fichero: i32,
+    pub registradas: Vec<Devolucion>,
+    /// Operaciones que ya se habían importado con otro fichero.
+    pub ya_registradas: Vec<String>,
+    /// Operaciones que no corresponden a ninguna remesa de la aplicación.
+    pub no_encontradas: Vec<String>,
+    pub importe_devuelto: f64,
+    pub comisiones: f64,
+}
+
+/// Rechazo leído del fichero del banco.
+#[derive(Debug, Clone, PartialEq)]
+struct OperacionRechazada {
+    alcance: Alcance,
+    codigo_motivo: String,
+    fecha: Option<String>,
+}
+
+/// Adeudos a los que afecta un rechazo: una operación concreta, un bloque de
+/// pago entero (PmtInfId) o la remesa completa (MsgId).
+#[derive(Debug, Clone, PartialEq)]
+enum Alcance {
+    Operacion(String),
+    Bloque(String),
+    Remesa(String),
+}
+
+/// Texto de los códigos de motivo de devolución de SEPA más habituales.
+pub fn descripcion_motivo(codigo: &str) -> &'static str {
+    match codigo {
+        "AC01" => "Número de cuenta incorrecto",
+        "AC04" => "Cuenta cancelada",
+        "AC06" => "Cuenta bloqueada",
+        "AC13" => "Cuenta de consumidor no válida para este adeudo",
+        "AG01" => "Operación no permitida en esta cuenta",
+        "AG02" => "Código de operación no válido",
+        "AM04" => "Fondos insuficientes",
+        "AM05" => "Operación duplicada",
+        "BE05" => "Acreedor no reconocido",
+        "CNOR" => "La entidad del acreedor no está registrada",
+        "DNOR" => "La entidad del deudor no está registrada",
+        "FF01" => "Formato del fichero no válido",
+        "MD01" => "Sin mandato o mandato no válido",
+        "MD02" => "Datos del mandato incompletos o incorrectos",
+        "MD06" => "Devolución solicitada por el deudor",
+        "MD07" => "Deudor fallecido",
+        "MS02" => "Motivo no indicado por el deudor",
+        "MS03" => "Motivo no indicado por la entidad",
+        "RC01" => "BIC incorrecto",
+        "RR01" | "RR02" | "RR03" | "RR04" => "Motivo regulatorio",
+        "SL01" => "Servicio específico de la entidad del deudor",
+        _ => "Motivo no reconocido",
+    }
+}
+
+// Primer descendiente con el nombre indicado, sin tener en cuenta el espacio de
+// nombres (cada versión de pain.002 usa el suyo).
+fn hijo<'a, 'i>(nodo: roxmltree::Node<'a, 'i>, nombre: &str) -> Option<roxmltree::Node<'a, 'i>> {
+    nodo.descendants().find(|n| n.is_element() && n.tag_name().name() == nombre)
+}
+
+fn texto<'a>(nodo: roxmltree::Node<'a, '_>, nombre: &str) -> Option<&'a str> {
+    hijo(nodo, nombre).and_then(|n| n.text()).map(str::trim).filter(|t| !t.is_empty())
+}
+
+// Código del motivo de rechazo que el banco indica directamente en el nodo.
+fn motivo_rechazo(nodo: roxmltree::Node) -> String {
+    nodo.children()
+        .find(|n| n.tag_name().name() == "StsRsnInf")
+        .and_then(|n| hijo(n, "Rsn"))
+        .and_then(|n| texto(n, "Cd").or_else(|| texto(n, "Prtry")))
+        .unwrap_or("MS03")
+        .to_string()
+}
+
+// Texto de un hijo directo del nodo.
+fn texto_hijo<'a>(nodo: roxmltree::Node<'a, '_>, nombre: &str) -> Option<&'a str> {
+    nodo.children()
+        .find(|n| n.tag_name().name() == nombre)
+        .and_then(|n| n.text())
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}
+
+/// Lee los rechazos de un informe de estado pain.002. El estado puede venir en
+/// cada operación, en el bloque de pago si el banco rechaza el bloque entero, o
+/// en la cabecera si rechaza la remesa completa.
+fn leer_pain_002(contenido: &str) -> HermanarResult<Vec<OperacionRechazada>> {
+    let documento = roxmltree::Document::parse(contenido)
+        .map_err(|e| HermanarError::validation("fichero", format!("El fichero no es un XML válido: {}", e)))?;
+    let raiz = documento.root_element();
+    if hijo(raiz, "CstmrPmtStsRpt").is_none() {
+        return Err(HermanarError::validation(
+            "fichero",
+            "El fichero no es un informe de estado de adeudos (pain.002)",
+        ));
+    }
+
+    let fecha_informe = hijo(raiz, "GrpHdr")
+        .and_then(|n| texto(n, "CreDtTm"))
+        .map(|f| f.chars().take(10).collect::<String>());
+
+    // Remesa rechazada entera: no se detalla nada por debajo.
+    if let Some(grupo) = hijo(raiz, "OrgnlGrpInfAndSts") {
+        if texto_hijo(grupo, "GrpSts") == Some("RJCT") {
+            let Some(mensaje) = texto_hijo(grupo, "OrgnlMsgId") else {
+                return Err(HermanarError::validation(
+                    "fichero",
+                    "El banco rechaza la remesa completa pero el fichero no indica cuál",
+                ));
+            };
+            return Ok(vec![OperacionRechazada {
+                alcance: Alcance::Remesa(mensaje.to_string()),
+                codigo_motivo: motivo_rechazo(grupo),
+                fecha: fecha_informe,
+            }]);
+        }
+    }
+
+    let mut operaciones = Vec::new();
+    for bloque in raiz.descendants().filter(|n| n.tag_name().name() == "OrgnlPmtInfAndSts") {
+        let estado_bloque = texto_hijo(bloque, "PmtInfSts");
+        let mut detalladas = false;
+
+        for operacion in bloque.children().filter(|n| n.tag_name().name() == "TxInfAndSts") {
+            detalladas = true;
+            let estado = texto(operacion, "TxSts").or(estado_bloque);
+            if estado != Some("RJCT") {
+                continue;
+            }
+            let Some(end_to_end_id) = texto(operacion, "OrgnlEndToEndId") else {
+                continue;
+            };
+
+            let fecha = texto(operacion, "AccptncDtTm")
+                .map(|f| f.chars().take(10).collect::<String>())
+                .or_else(|| fecha_informe.clone());
+
+            operaciones.push(OperacionRechazada {
+                alcance: Alcance::Operacion(end_to_end_id.to_string()),
+                codigo_motivo: motivo_rechazo(operacion),
+                fecha,
+            });
+        }
+
+        // Bloque rechazado sin detalle de operaciones: afecta a todas las suyas.
+        if !detalladas && estado_bloque == Some("RJCT") {
+            if let Some(bloque_id) = texto_hijo(bloque, "OrgnlPmtInfId") {
+                operaciones.push(OperacionRechazada {
+                    alcance: Alcance::Bloque(bloque_id.to_string()),
+                    codigo_motivo: motivo_rechazo(bloque),
+                    fecha: fecha_informe.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(operaciones)
+}
+
+// Referencias de los adeudos a los que afecta un rechazo. El bloque de pago se
+// identifica como la referencia de la remesa seguida del tipo de adeudo, igual
+// que al generar el fichero.
+fn adeudos_rechazados(conn: &Connection, alcance: &Alcance) -> HermanarResult<Vec<String>> {
+    let (condicion, valor) = match alcance {
+        Alcance::Operacion(end_to_end_id) => return Ok(vec![end_to_end_id.clone()]),
+        Alcance::Bloque(bloque) => ("r.referencia || '-' || rc.secuencia = ?1", bloque),
+        Alcance::Remesa(mensaje) => ("r.referencia = ?1", mensaje),
+    };
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT rc.end_to_end_id FROM remesa_cuotas rc
+         JOIN remesas r ON r.id = rc.remesa_id
+         WHERE {}
+         ORDER BY rc.end_to_end_id",
+        condicion
+    ))?;
+    let referencias = stmt.query_map([valor], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+
+    Ok(referencias)
+}
+
+/// Importa un informe de devoluciones del banco. Cada operación rechazada se
+/// localiza por su referencia de extremo a extremo en las remesas generadas;
+/// si el banco rechaza un bloque o la remesa entera, se toman todos sus
+/// adeudos. Se anula el pago que se anotó con cada uno, la cuota vuelve a
+/// deberlo con el motivo y la fecha anotados, y se registra la devolución.
+/// Las operaciones que ya se habían importado se ignoran, así que el mismo
+/// fichero puede importarse dos veces sin efecto.
+pub fn importar_devoluciones(db: &DbConnection, solicitud: &SolicitudDevoluciones) -> HermanarResult<ResumenDevoluciones> {
+    let comision = solicitud.comision.unwrap_or(0.0);
+    if comision < 0.0 {
+        return Err(HermanarError::validation("comision", "La comisión no puede ser negativa"));
+    }
+    let comision = (comision * 100.0).round() / 100.0;
+
+    let ruta = Path::new(solicitud.fichero.trim());
+    let contenido = std::fs::read_to_string(ruta)
+        .map_err(|e| HermanarError::validation("fichero", format!("No se pudo leer el fichero: {}", e)))?;
+    let operaciones = leer_pain_002(&contenido)?;
+    let fichero = ruta
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| solicitud.fichero.clone());
+
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+    let operador = db.operador();
+
+    let mut resumen = ResumenDevoluciones {
+        fichero: fichero.clone(),
+        rechazadas_en_fichero: 0,
+        registradas: Vec::new(),
+        ya_registradas: Vec::new(),
+        no_encontradas: Vec::new(),
+        importe_devuelto: 0.0,
+        comisiones: 0.0,
+    };
+
+    let mut rechazadas = Vec::new();
+    for operacion in operaciones {
+        let referencias = adeudos_rechazados(&tx, &operacion.alcance)?;
+        if referencias.is_empty() {
+            // Bloque o remesa que no se ha generado con la aplicación.
+            if let Alcance::Bloque(id) | Alcance::Remesa(id) = operacion.alcance {
+                resumen.no_encontradas.push(id);
+                resumen.rechazadas_en_fichero += 1;
+            }
+            continue;
+        }
+        for end_to_end_id in referencias {
+            rechazadas.push((end_to_end_id, operacion.codigo_motivo.clone(), operacion.fecha.clone()));
+        }
+    }
+    resumen.rechazadas_en_fichero += rechazadas.len() as i32;
+
+    for (end_to_end_id, codigo_motivo, fecha) in rechazadas {
+        let registrada: bool = tx
+            .prepare_cached("SELECT EXISTS(SELECT 1 FROM devoluciones WHERE end_to_end_id = ?1)")?
+            .query_row([&end_to_end_id], |row| row.get(0))?;
+        if registrada {
+            resumen.ya_registradas.push(end_to_end_id);
+            continue;
+        }
+
+        let adeudo: Option<(i64, i32, i32, f64)> = tx
+            .prepare_cached(
+                "SELECT remesa_id, cuota_id, hermano_id, importe FROM remesa_cuotas WHERE end_to_end_id = ?1"
+            )?
+            .query_row([&end_to_end_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .optional()?;
+        let Some((remesa_id, cuota_id, hermano_id, importe)) = adeudo else {
+            resumen.no_encontradas.push(end_to_end_id);
+            continue;
+        };
+
+        let fecha = fecha.unwrap_or_else(hoy);
+        let cuota_existe = buscar_cuota(&tx, cuota_id)?.is_some();
+        let repercutida = cuota_existe && solicitud.repercutir_comision && comision > 0.0;
+        if cuota_existe {
+            let nota = format!(
+                "Recibo devuelto el {} ({}: {})",
+                fecha,
+                codigo_motivo,
+                descripcion_motivo(&codigo_motivo)
+            );
+            revertir_pago(
+                &tx,
+                &operador,
+                cuota_id,
+                &end_to_end_id,
+                if repercutida { comision } else { 0.0 },
+                &nota,
+            )?;
+        }
+
+        tx.prepare_cached(
+            "INSERT INTO devoluciones
+             (remesa_id, cuota_id, hermano_id, end_to_end_id, codigo_motivo, fecha_devolucion, importe,
+              comision, comision_repercutida, fichero, operador)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)"
+        )?
+        .execute(params![
+            remesa_id,
+            cuota_id,
+            hermano_id,
+            end_to_end_id,
+            codigo_motivo,
+            fecha,
+            importe,
+            comision,
+            repercutida,
+            fichero,
+            operador,
+        ])?;
+
+        let devolucion = buscar_devolucion(&tx, tx.last_insert_rowid())?
+            .ok_or_else(|| HermanarError::not_found("Devolución"))?;
+        resumen.importe_devuelto += devolucion.importe;
+        resumen.comisiones += devolucion.comision;
+        resumen.registradas.push(devolucion);
+    }
+
+    tx.commit()?;
+
+    Ok(resumen)
+}
+
+fn buscar_devolucion(conn: &Connection, id: i64) -> HermanarResult<Option<Devolucion>> {
+    let mut stmt = conn.prepare_cached(&format!("{} WHERE d.id = ?1", SELECT_DEVOLUCIONES))?;
+
+    Ok(stmt.query_row([id], Devolucion::from_row).optional()?)
+}
+
+/// Devoluciones registradas, de la más reciente a la más antigua. Con
+/// `solo_pendientes` se limitan a las que todavía tienen la cuota sin cobrar.
+pub fn get_devoluciones(
+    db: &DbConnection,
+    remesa_id: Option<i64>,
+    solo_pendientes: bool,
+) -> HermanarResult<Vec<Devolucion>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(&format!(
+        "{}
+         WHERE (?1 IS NULL OR d.remesa_id = ?1)
+           AND (?2 = 0 OR c.pagado = 0)
+         ORDER BY d.fecha_devolucion DESC, d.id DESC",
+        SELECT_DEVOLUCIONES
+    ))?;
+
+    let devoluciones = stmt.query_map(params![remesa_id, solo_pendientes], |row| {
+        Devolucion::from_row(row)
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(devoluciones)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rechazo(alcance: Alcance, codigo_motivo: &str, fecha: &str) -> OperacionRechazada {
+        OperacionRechazada {
+            alcance,
+            codigo_motivo: codigo_motivo.to_string(),
+            fecha: Some(fecha.to_string()),
+        }
+    }
+
+    #[test]
+    fn rechazos_de_operacion_y_de_bloque() {
+        let informe = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.002.001.03">
+  <CstmrPmtStsRpt>
+    <GrpHdr><MsgId>STS-1</MsgId><CreDtTm>2026-01-15T09:30:00</CreDtTm></GrpHdr>
+    <OrgnlGrpInfAndSts>
+      <OrgnlMsgId>REMESA-000001-20260102100000</OrgnlMsgId>
+      <OrgnlMsgNmId>pain.008.001.02</OrgnlMsgNmId>
+      <GrpSts>PART</GrpSts>
+    </OrgnlGrpInfAndSts>
+    <OrgnlPmtInfAndSts>
+      <OrgnlPmtInfId>REMESA-000001-20260102100000-RCUR</OrgnlPmtInfId>
+      <TxInfAndSts>
+        <OrgnlEndToEndId>E2E-1</OrgnlEndToEndId>
+        <TxSts>RJCT</TxSts>
+        <StsRsnInf><Rsn><Cd>AM04</Cd></Rsn></StsRsnInf>
+        <AccptncDtTm>2026-01-12T00:00:00</AccptncDtTm>
+      </TxInfAndSts>
+      <TxInfAndSts>
+        <OrgnlEndToEndId>E2E-2</OrgnlEndToEndId>
+        <TxSts>ACCP</TxSts>
+      </TxInfAndSts>
+      <TxInfAndSts>
+        <OrgnlEndToEndId>E2E-3</OrgnlEndToEndId>
+        <TxSts>RJCT</TxSts>
+        <StsRsnInf><Rsn><Prtry>X999</Prtry></Rsn></StsRsnInf>
+      </TxInfAndSts>
+    </OrgnlPmtInfAndSts>
+    <OrgnlPmtInfAndSts>
+      <OrgnlPmtInfId>REMESA-000001-20260102100000-FRST</OrgnlPmtInfId>
+      <PmtInfSts>RJCT</PmtInfSts>
+      <StsRsnInf><Rsn><Cd>FF01</Cd></Rsn></StsRsnInf>
+    </OrgnlPmtInfAndSts>
+    <OrgnlPmtInfAndSts>
+      <OrgnlPmtInfId>REMESA-000001-20260102100000-OOFF</OrgnlPmtInfId>
+      <PmtInfSts>RJCT</PmtInfSts>
+      <TxInfAndSts>
+        <OrgnlEndToEndId>E2E-4</OrgnlEndToEndId>
+      </TxInfAndSts>
+    </OrgnlPmtInfAndSts>
+  </CstmrPmtStsRpt>
+</Document>"#;
+
+        assert_eq!(
+            leer_pain_002(informe).unwrap(),
+            [
+                rechazo(Alcance::Operacion("E2E-1".to_string()), "AM04", "2026-01-12"),
+                rechazo(Alcance::Operacion("E2E-3".to_string()), "X999", "2026-01-15"),
+                rechazo(Alcance::Bloque("REMESA-000001-20260102100000-FRST".to_string()), "FF01", "2026-01-15"),
+                rechazo(Alcance::Operacion("E2E-4".to_string()), "MS03", "2026-01-15"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rechazo_de_la_remesa() {
+        let informe = r#"<Document><CstmrPmtStsRpt>
+            <GrpHdr><MsgId>STS-2</MsgId><CreDtTm>2026-01-16T09:30:00</CreDtTm></GrpHdr>
+            <OrgnlGrpInfAndSts>
+              <OrgnlMsgId>REMESA-000002</OrgnlMsgId>
+              <GrpSts>RJCT</GrpSts>
+              <StsRsnInf><Rsn><Cd>DNOR</Cd></Rsn></StsRsnInf>
+            </OrgnlGrpInfAndSts>
+        </CstmrPmtStsRpt></Document>"#;
+
+        assert_eq!(
+            leer_pain_002(informe).unwrap(),
+            [rechazo(Alcance::Remesa("REMESA-000002".to_string()), "DNOR", "2026-01-16")]
+        );
+    }
+
+    #[test]
+    fn fichero_no_valido() {
+        assert!(leer_pain_002("no es XML").is_err());
+        assert!(leer_pain_002("<Document><CstmrDrctDbtInitn/></Document>").is_err());
+    }
+}
@@ -0,0 +1,377 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::db::DbConnection;
+use crate::error::{HermanarError, HermanarResult};
+
+const LETRAS_CONTROL: &[u8] = b"TRWAGMYFPDXBNJZSQVHLCKE";
+
+/// Tipo de documento de identidad. DNI y NIE se validan con su letra de
+/// control; pasaportes y otros documentos extranjeros solo se normalizan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TipoDocumento {
+    Dni,
+    Nie,
+    Pasaporte,
+    Otro,
+}
+
+impl TipoDocumento {
+    pub(crate) fn clave(&self) -> &'static str {
+        match self {
+            TipoDocumento::Dni => "dni",
+            TipoDocumento::Nie => "nie",
+            TipoDocumento::Pasaporte => "pasaporte",
+            TipoDocumento::Otro => "otro",
+        }
+    }
+
+    pub(crate) fn desde_clave(clave: &str) -> Option<Self> {
+        match clave {
+            "dni" => Some(TipoDocumento::Dni),
+            "nie" => Some(TipoDocumento::Nie),
+            "pasaporte" => Some(TipoDocumento::Pasaporte),
+            "otro" => Some(TipoDocumento::Otro),
+            _ => None,
+        }
+    }
+}
+
+/// Normaliza y valida un documento. Se quitan espacios, puntos y guiones, se
+/// pasa a mayúsculas y a los DNI se les completan los ceros a la izquierda.
+/// Sin tipo indicado se deduce entre DNI y NIE; un documento extranjero debe
+/// marcarse como pasaporte u otro para aceptarse sin comprobar la letra.
+pub fn normalizar_documento(valor: &str, tipo: Option<TipoDocumento>) -> Result<(String, TipoDocumento), String> {
+    let limpio: String = valor
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '.' && *c != '-')
+        .collect::<String>()
+        .to_uppercase();
+
+    if limpio.is_empty() {
+        return Err("El documento está vacío".to_string());
+    }
+    if !limpio.is_ascii() {
+        return Err("El documento solo puede contener letras sin acentos y cifras".to_string());
+    }
+
+    let tipo = match tipo {
+        Some(tipo) => tipo,
+        None if limpio.starts_with(['X', 'Y', 'Z']) => TipoDocumento::Nie,
+        None if limpio.starts_with(|c: char| c.is_ascii_digit()) => TipoDocumento::Dni,
+        None => {
+            return Err(format!(
+                "'{}' no es un DNI ni un NIE. Si es un documento extranjero, indique su tipo",
+                valor.trim()
+            ));
+        }
+    };
+
+    let normalizado = match tipo {
+        TipoDocumento::Dni => validar_dni(&limpio)?,
+        TipoDocumento::Nie => validar_nie(&limpio)?,
+        TipoDocumento::Pasaporte | TipoDocumento::Otro => {
+            if !(3..=20).contains(&limpio.len()) || !limpio.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err("El documento debe tener entre 3 y 20 letras o cifras".to_string());
+            }
+            limpio
+        }
+    };
+
+    Ok((normalizado, tipo))
+}
+
+fn validar_dni(limpio: &str) -> Result<String, String> {
+    let (cifras, letra) = limpio.split_at(limpio.len().saturating_sub(1));
+
+    if cifras.is_empty() || cifras.len() > 8 || !cifras.chars().all(|c| c.is_ascii_digit()) {
+        return Err("El DNI debe tener hasta 8 cifras seguidas de la letra".to_string());
+    }
+    let numero: u32 = cifras.parse().map_err(|_| "DNI no válido".to_string())?;
+    comprobar_letra(numero, letra, "DNI")?;
+
+    Ok(format!("{:08}{}", numero, letra))
+}
+
+fn validar_nie(limpio: &str) -> Result<String, String> {
+    let mut chars = limpio.chars();
+    let prefijo = match chars.next() {
+        Some('X') => 0,
+        Some('Y') => 1,
+        Some('Z') => 2,
+        _ => return Err("El NIE debe empezar por X, Y o Z".to_string()),
+    };
+    let resto = chars.as_str();
+    let (cifras, letra) = resto.split_at(resto.len().saturating_sub(1));
+
+    if cifras.is_empty() || cifras.len() > 7 || !cifras.chars().all(|c| c.is_ascii_digit()) {
+        return Err("El NIE debe tener una letra inicial, hasta 7 cifras y la letra final".to_string());
+    }
+    let numero: u32 = cifras.parse().map_err(|_| "NIE no válido".to_string())?;
+    comprobar_letra(prefijo * 10_000_000 + numero, letra, "NIE")?;
+
+    Ok(format!("{}{:07}{}", &limpio[..1], numero, letra))
+}
+
+fn comprobar_letra(numero: u32, letra: &str, tipo: &str) -> Result<(), String> {
+    let esperada = LETRAS_CONTROL[(numero % 23) as usize] as char;
+
+    if letra.len() != 1 || !letra.starts_with(esperada) {
+        return Err(format!("La letra del {} no es correcta (debería ser {})", tipo, esperada));
+    }
+    Ok(())
+}
+
+/// Prepara un documento para guardarlo en `campo`. Un valor que no ha cambiado
+/// respecto a `actual` se guarda tal cual aunque no sea válido, para poder
+/// editar otros datos de fichas antiguas; los errores se ven con
+/// `revisar_documentos`.
+pub(crate) fn preparar_documento(
+    campo: &str,
+    valor: Option<&str>,
+    tipo: Option<TipoDocumento>,
+    actual: Option<&str>,
+) -> HermanarResult<(Option<String>, Option<TipoDocumento>)> {
+    let Some(valor) = valor.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok((None, None));
+    };
+
+    match normalizar_documento(valor, tipo) {
+        Ok((normalizado, tipo)) => Ok((Some(normalizado), Some(tipo))),
+        Err(_) if actual == Some(valor) => Ok((Some(valor.to_string()), tipo)),
+        Err(mensaje) => Err(HermanarError::validation(campo, mensaje)),
+    }
+}
+
+/// Rechaza el documento si ya figura como DNI de otro hermano.
+pub(crate) fn comprobar_documento_libre(conn: &Connection, dni: &str, excepto: Option<i32>) -> HermanarResult<()> {
+    if let Some(otro) = titular_documento(conn, dni, excepto)? {
+        return Err(HermanarError::validation(
+            "dni",
+            format!("El documento {} ya pertenece a {} (hermano nº {})", dni, otro.nombre, otro.numero_hermano),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TitularDocumento {
+    pub hermano_id: i32,
+    pub numero_hermano: String,
+    pub nombre: String,
+}
+
+fn titular_documento(conn: &Connection, dni: &str, excepto: Option<i32>) -> HermanarResult<Option<TitularDocumento>> {
+    let titular = conn
+        .prepare_cached(
+            "SELECT id, numero_hermano, TRIM(nombre || ' ' || primer_apellido || ' ' || COALESCE(segundo_apellido, ''))
+             FROM hermanos
+             WHERE dni = ?1 AND (?2 IS NULL OR id != ?2)
+             LIMIT 1"
+        )?
+        .query_row(params![dni, excepto], |row| {
+            Ok(TitularDocumento {
+                hermano_id: row.get(0)?,
+                numero_hermano: row.get(1)?,
+                nombre: row.get(2)?,
+            })
+        })
+        .optional()?;
+
+    Ok(titular)
+}
+
+/// Resultado de comprobar un documento antes de guardar la ficha, para avisar
+/// en el formulario.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComprobacionDocumento {
+    pub valido: bool,
+    pub normalizado: Option<String>,
+    pub tipo: Option<TipoDocumento>,
+    pub mensaje: Option<String>,
+    pub duplicado_de: Option<TitularDocumento>,
+}
+
+pub fn comprobar_documento(
+    db: &DbConnection,
+    valor: &str,
+    tipo: Option<TipoDocumento>,
+    hermano_id: Option<i32>,
+) -> HermanarResult<ComprobacionDocumento> {
+    match normalizar_documento(valor, tipo) {
+        Ok((normalizado, tipo)) => {
+            let duplicado_de = titular_documento(&db.reader(), &normalizado, hermano_id)?;
+            Ok(ComprobacionDocumento {
+                valido: true,
+                normalizado: Some(normalizado),
+                tipo: Some(tipo),
+                mensaje: None,
+                duplicado_de,
+            })
+        }
+        Err(mensaje) => Ok(ComprobacionDocumento {
+            valido: false,
+            normalizado: None,
+            tipo,
+            mensaje: Some(mensaje),
+            duplicado_de: None,
+        }),
+    }
+}
+
+/// Documento con algún problema encontrado al revisar la base de datos.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidenciaDocumento {
+    pub hermano_id: i32,
+    pub numero_hermano: String,
+    pub nombre: String,
+    pub campo: String,
+    pub valor: String,
+    /// "invalido", "sin_normalizar" o "duplicado".
+    pub problema: String,
+    pub detalle: String,
+}
+
+/// Revisa los documentos de todos los hermanos y devuelve los que no son
+/// válidos, los que se guardarían de otra forma al normalizarlos y los DNI
+/// repetidos entre hermanos.
+pub fn revisar_documentos(db: &DbConnection) -> HermanarResult<Vec<IncidenciaDocumento>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, numero_hermano, TRIM(nombre || ' ' || primer_apellido || ' ' || COALESCE(segundo_apellido, '')),
+                dni, tipo_documento, dni_representante_legal, tipo_documento_representante
+         FROM hermanos
+         ORDER BY numero_hermano"
+    )?;
+
+    let filas = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i32>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            [
+                ("dni", row.get::<_, Option<String>>(3)?, row.get::<_, Option<String>>(4)?),
+                ("dni_representante_legal", row.get::<_, Option<String>>(5)?, row.get::<_, Option<String>>(6)?),
+            ],
+        ))
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    let mut incidencias = Vec::new();
+    let mut por_documento: HashMap<String, Vec<usize>> = HashMap::new();
+
+    for (indice, (hermano_id, numero_hermano, nombre, documentos)) in filas.iter().enumerate() {
+        for (campo, valor, tipo) in documentos {
+            let Some(valor) = valor.as_deref().filter(|v| !v.trim().is_empty()) else {
+                continue;
+            };
+            let tipo = tipo.as_deref().and_then(TipoDocumento::desde_clave);
+
+            let incidencia = |problema: &str, detalle: String| IncidenciaDocumento {
+                hermano_id: *hermano_id,
+                numero_hermano: numero_hermano.clone(),
+                nombre: nombre.clone(),
+                campo: campo.to_string(),
+                valor: valor.to_string(),
+                problema: problema.to_string(),
+                detalle,
+            };
+
+            let normalizado = match normalizar_documento(valor, tipo) {
+                Ok((normalizado, _)) => normalizado,
+                Err(mensaje) => {
+                    incidencias.push(incidencia("invalido", mensaje));
+                    continue;
+                }
+            };
+            if normalizado != valor {
+                incidencias.push(incidencia("sin_normalizar", format!("Se guardaría como {}", normalizado)));
+            }
+            if *campo == "dni" {
+                por_documento.entry(normalizado).or_default().push(indice);
+            }
+        }
+    }
+
+    let mut repetidos: Vec<_> = por_documento.into_iter().filter(|(_, h)| h.len() > 1).collect();
+    repetidos.sort();
+
+    for (documento, indices) in repetidos {
+        for &indice in &indices {
+            let (hermano_id, numero_hermano, nombre, documentos) = &filas[indice];
+            let otros = indices
+                .iter()
+                .filter(|&&i| i != indice)
+                .map(|&i| filas[i].1.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            incidencias.push(IncidenciaDocumento {
+                hermano_id: *hermano_id,
+                numero_hermano: numero_hermano.clone(),
+                nombre: nombre.clone(),
+                campo: "dni".to_string(),
+                valor: documentos[0].1.clone().unwrap_or_default(),
+                problema: "duplicado".to_string(),
+                detalle: format!("El documento {} también figura en los hermanos nº {}", documento, otros),
+            });
+        }
+    }
+
+    Ok(incidencias)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalizado(valor: &str) -> Result<(String, TipoDocumento), String> {
+        normalizar_documento(valor, None)
+    }
+
+    #[test]
+    fn dni_valido() {
+        assert_eq!(normalizado("12345678Z"), Ok(("12345678Z".to_string(), TipoDocumento::Dni)));
+        assert_eq!(normalizado("12.345.678-z"), Ok(("12345678Z".to_string(), TipoDocumento::Dni)));
+        assert_eq!(normalizado("0T"), Ok(("00000000T".to_string(), TipoDocumento::Dni)));
+        assert_eq!(normalizado("1234567 l").unwrap().0, "01234567L");
+    }
+
+    #[test]
+    fn dni_no_valido() {
+        let error = normalizado("12345678A").unwrap_err();
+        assert!(error.contains("debería ser Z"), "{}", error);
+        assert!(normalizado("123456789Z").is_err());
+        assert!(normalizado("1234A678Z").is_err());
+        assert!(normalizado("12345678").is_err());
+        assert!(normalizar_documento("X1234567L", Some(TipoDocumento::Dni)).is_err());
+    }
+
+    #[test]
+    fn nie_valido() {
+        assert_eq!(normalizado("X1234567L"), Ok(("X1234567L".to_string(), TipoDocumento::Nie)));
+        assert_eq!(normalizado("y-0000000-z").unwrap().0, "Y0000000Z");
+        assert_eq!(normalizado("Z0M").unwrap().0, "Z0000000M");
+    }
+
+    #[test]
+    fn nie_no_valido() {
+        let error = normalizado("X1234567A").unwrap_err();
+        assert!(error.contains("debería ser L"), "{}", error);
+        assert!(normalizado("X12345678L").is_err());
+        assert!(normalizar_documento("W1234567L", Some(TipoDocumento::Nie)).is_err());
+    }
+
+    #[test]
+    fn otros_documentos() {
+        assert!(normalizado("").is_err());
+        assert!(normalizado("Ñ1234567").is_err());
+        assert!(normalizado("AB123456").is_err());
+        assert_eq!(
+            normalizar_documento("ab 123456", Some(TipoDocumento::Pasaporte)),
+            Ok(("AB123456".to_string(), TipoDocumento::Pasaporte))
+        );
+        assert!(normalizar_documento("A1", Some(TipoDocumento::Otro)).is_err());
+    }
+}
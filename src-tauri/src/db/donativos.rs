@@ -0,0 +1,1171 @@
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use crate::db::{DbConnection, Hermano, TipoDocumento};
+use crate::db::cuotas::buscar_cuota;
+use crate::db::documentos::normalizar_documento;
+use crate::db::hermandad::{cargar_datos_documento, DatosHermandad};
+use crate::db::hermanos::buscar_hermano;
+use crate::db::membresia::{hoy, validar_fecha};
+use crate::db::numeracion::nombre_completo;
+use crate::db::pdf::{
+    ancho_texto, fecha_en_letras, fecha_espanola, fichero_destino, formatear_importe, importe_en_letras, Documento,
+    Pagina,
+};
+use crate::error::{HermanarError, HermanarResult};
+
+// Las líneas del modelo 182 miden siempre 250 caracteres.
+const LONGITUD_REGISTRO: usize = 250;
+// Filas de donativos que caben en la página del certificado.
+const FILAS_CERTIFICADO: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NaturalezaDonante {
+    Fisica,
+    Juridica,
+}
+
+impl NaturalezaDonante {
+    fn clave(&self) -> &'static str {
+        match self {
+            NaturalezaDonante::Fisica => "F",
+            NaturalezaDonante::Juridica => "J",
+        }
+    }
+
+    fn desde_clave(clave: &str) -> Option<Self> {
+        match clave {
+            "F" => Some(NaturalezaDonante::Fisica),
+            "J" => Some(NaturalezaDonante::Juridica),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TipoDonativo {
+    Dinerario,
+    Especie,
+}
+
+impl TipoDonativo {
+    fn clave(&self) -> &'static str {
+        match self {
+            TipoDonativo::Dinerario => "dinerario",
+            TipoDonativo::Especie => "especie",
+        }
+    }
+
+    fn desde_clave(clave: &str) -> Option<Self> {
+        match clave {
+            "dinerario" => Some(TipoDonativo::Dinerario),
+            "especie" => Some(TipoDonativo::Especie),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeriodicidadDonativo {
+    Puntual,
+    Mensual,
+    Trimestral,
+    Semestral,
+    Anual,
+}
+
+impl PeriodicidadDonativo {
+    const TODAS: [PeriodicidadDonativo; 5] = [
+        PeriodicidadDonativo::Puntual,
+        PeriodicidadDonativo::Mensual,
+        PeriodicidadDonativo::Trimestral,
+        PeriodicidadDonativo::Semestral,
+        PeriodicidadDonativo::Anual,
+    ];
+
+    fn clave(&self) -> &'static str {
+        match self {
+            PeriodicidadDonativo::Puntual => "puntual",
+            PeriodicidadDonativo::Mensual => "mensual",
+            PeriodicidadDonativo::Trimestral => "trimestral",
+            PeriodicidadDonativo::Semestral => "semestral",
+            PeriodicidadDonativo::Anual => "anual",
+        }
+    }
+
+    fn desde_clave(clave: &str) -> Option<Self> {
+        Self::TODAS.into_iter().find(|periodicidad| periodicidad.clave() == clave)
+    }
+}
+
+/// Persona o entidad que hace donativos a la hermandad. Puede ser un
+/// hermano, enlazado por `hermano_id`, o alguien de fuera.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Donante {
+    pub id: Option<i32>,
+    pub nif: String,
+    pub naturaleza: NaturalezaDonante,
+    /// Nombre de pila, o razón social si es una persona jurídica.
+    pub nombre: String,
+    pub apellidos: Option<String>,
+    pub hermano_id: Option<i32>,
+    pub direccion: Option<String>,
+    /// De sus dos primeras cifras sale el código de provincia del modelo 182.
+    pub codigo_postal: Option<String>,
+    pub localidad: Option<String>,
+    pub provincia: Option<String>,
+    pub email: Option<String>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Donativo {
+    pub id: Option<i32>,
+    pub donante_id: i32,
+    pub fecha: String,
+    pub importe: f64,
+    pub tipo: TipoDonativo,
+    pub periodicidad: PeriodicidadDonativo,
+    pub concepto: Option<String>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+/// Lo que aporta un declarado en el año, sumando donativos y cuotas
+/// deducibles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Declarado182 {
+    pub nif: String,
+    /// Apellidos y nombre, o razón social, en el orden que pide el modelo.
+    pub nombre: String,
+    pub naturaleza: NaturalezaDonante,
+    /// Código de provincia de dos cifras.
+    pub provincia: String,
+    pub importe: f64,
+    pub importe_especie: f64,
+    /// Ha donado en los dos años anteriores, el último al menos lo mismo que
+    /// el penúltimo, y tiene derecho al porcentaje incrementado.
+    pub recurrente: bool,
+    pub donante_id: Option<i32>,
+    pub hermano_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumenModelo182 {
+    pub anio: i32,
+    pub declarados: Vec<Declarado182>,
+    /// Registros de declarado que lleva el fichero: los donantes personas
+    /// físicas que pasan del primer tramo ocupan dos.
+    pub registros: usize,
+    pub total: f64,
+    /// Aportaciones que no pueden declararse por falta de datos.
+    pub incidencias: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolicitudModelo182 {
+    pub anio: i32,
+    pub fichero: String,
+    /// Persona con quien relacionarse, en el orden apellidos y nombre.
+    pub contacto: String,
+    /// Teléfono de contacto; si no se indica, el de la hermandad.
+    pub telefono: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolicitudCertificadoDonativos {
+    pub anio: i32,
+    /// NIF del donante. Sin él se genera el certificado de todos.
+    pub nif: Option<String>,
+    pub fichero: String,
+}
+
+const COLUMNAS_DONANTE: &str = "id, nif, naturaleza, nombre, apellidos, hermano_id, direccion, codigo_postal,
+     localidad, provincia, email, created_at, updated_at";
+
+const COLUMNAS_DONATIVO: &str =
+    "id, donante_id, fecha, importe, tipo, periodicidad, concepto, created_at, updated_at";
+
+impl Donante {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        Ok(Donante {
+            id: Some(row.get(0)?),
+            nif: row.get(1)?,
+            naturaleza: NaturalezaDonante::desde_clave(&row.get::<_, String>(2)?).unwrap_or(NaturalezaDonante::Fisica),
+            nombre: row.get(3)?,
+            apellidos: row.get(4)?,
+            hermano_id: row.get(5)?,
+            direccion: row.get(6)?,
+            codigo_postal: row.get(7)?,
+            localidad: row.get(8)?,
+            provincia: row.get(9)?,
+            email: row.get(10)?,
+            created_at: row.get(11)?,
+            updated_at: row.get(12)?,
+        })
+    }
+
+    fn nombre_completo(&self) -> String {
+        match self.apellidos.as_deref().map(str::trim).filter(|a| !a.is_empty()) {
+            Some(apellidos) => format!("{} {}", self.nombre.trim(), apellidos),
+            None => self.nombre.trim().to_string(),
+        }
+    }
+}
+
+impl Donativo {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        Ok(Donativo {
+            id: Some(row.get(0)?),
+            donante_id: row.get(1)?,
+            fecha: row.get(2)?,
+            importe: row.get(3)?,
+            tipo: TipoDonativo::desde_clave(&row.get::<_, String>(4)?).unwrap_or(TipoDonativo::Dinerario),
+            periodicidad: PeriodicidadDonativo::desde_clave(&row.get::<_, String>(5)?)
+                .unwrap_or(PeriodicidadDonativo::Puntual),
+            concepto: row.get(6)?,
+            created_at: row.get(7)?,
+            updated_at: row.get(8)?,
+        })
+    }
+}
+
+pub fn get_donantes(db: &DbConnection) -> HermanarResult<Vec<Donante>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM donantes ORDER BY COALESCE(apellidos, nombre), nombre",
+        COLUMNAS_DONANTE
+    ))?;
+
+    let donantes = stmt.query_map([], Donante::from_row)?.collect::<Result<Vec<_>, _>>()?;
+
+    Ok(donantes)
+}
+
+fn buscar_donante(conn: &Connection, id: i32) -> HermanarResult<Option<Donante>> {
+    let donante = conn
+        .prepare_cached(&format!("SELECT {} FROM donantes WHERE id = ?1", COLUMNAS_DONANTE))?
+        .query_row([id], Donante::from_row)
+        .optional()?;
+
+    Ok(donante)
+}
+
+pub fn create_donante(db: &DbConnection, donante: &Donante) -> HermanarResult<i32> {
+    let donante = validar_donante(donante)?;
+
+    let conn = db.writer();
+    conn.prepare_cached(
+        "INSERT INTO donantes
+         (nif, naturaleza, nombre, apellidos, hermano_id, direccion, codigo_postal, localidad, provincia, email)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"
+    )?
+    .execute(params![
+        donante.nif,
+        donante.naturaleza.clave(),
+        donante.nombre,
+        donante.apellidos,
+        donante.hermano_id,
+        donante.direccion,
+        donante.codigo_postal,
+        donante.localidad,
+        donante.provincia,
+        donante.email,
+    ])?;
+
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+pub fn update_donante(db: &DbConnection, id: i32, donante: &Donante) -> HermanarResult<()> {
+    let donante = validar_donante(donante)?;
+
+    let conn = db.writer();
+    let actualizados = conn
+        .prepare_cached(
+            "UPDATE donantes
+             SET nif = ?1, naturaleza = ?2, nombre = ?3, apellidos = ?4, hermano_id = ?5, direccion = ?6,
+                 codigo_postal = ?7, localidad = ?8, provincia = ?9, email = ?10, updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?11"
+        )?
+        .execute(params![
+            donante.nif,
+            donante.naturaleza.clave(),
+            donante.nombre,
+            donante.apellidos,
+            donante.hermano_id,
+            donante.direccion,
+            donante.codigo_postal,
+            donante.localidad,
+            donante.provincia,
+            donante.email,
+            id,
+        ])?;
+    if actualizados == 0 {
+        return Err(HermanarError::not_found("Donante"));
+    }
+
+    Ok(())
+}
+
+/// Elimina un donante que no tiene donativos registrados.
+pub fn delete_donante(db: &DbConnection, id: i32) -> HermanarResult<()> {
+    let conn = db.writer();
+    let donativos: i32 = conn
+        .prepare_cached("SELECT COUNT(*) FROM donativos WHERE donante_id = ?1")?
+        .query_row([id], |row| row.get(0))?;
+    if donativos > 0 {
+        return Err(HermanarError::conflict("No se puede eliminar el donante porque tiene donativos registrados"));
+    }
+    if conn.prepare_cached("DELETE FROM donantes WHERE id = ?1")?.execute([id])? == 0 {
+        return Err(HermanarError::not_found("Donante"));
+    }
+
+    Ok(())
+}
+
+fn validar_donante(donante: &Donante) -> HermanarResult<Donante> {
+    let texto = |valor: &Option<String>| valor.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+
+    let nombre = donante.nombre.trim();
+    if nombre.is_empty() {
+        return Err(HermanarError::validation("nombre", "Indique el nombre del donante"));
+    }
+    let nif = match donante.naturaleza {
+        NaturalezaDonante::Fisica => match normalizar_documento(&donante.nif, None) {
+            Ok((nif, TipoDocumento::Dni | TipoDocumento::Nie)) => nif,
+            Ok(_) => return Err(HermanarError::validation("nif", "El NIF debe ser un DNI o un NIE")),
+            Err(mensaje) => return Err(HermanarError::validation("nif", mensaje)),
+        },
+        NaturalezaDonante::Juridica => validar_cif(&donante.nif).map_err(|mensaje| HermanarError::validation("nif", mensaje))?,
+    };
+    let codigo_postal = texto(&donante.codigo_postal);
+    if let Some(codigo_postal) = &codigo_postal {
+        if codigo_provincia(codigo_postal).is_none() {
+            return Err(HermanarError::validation("codigo_postal", "El código postal no es válido"));
+        }
+    }
+
+    Ok(Donante {
+        nif,
+        nombre: nombre.to_string(),
+        apellidos: texto(&donante.apellidos).filter(|_| donante.naturaleza == NaturalezaDonante::Fisica),
+        direccion: texto(&donante.direccion),
+        codigo_postal,
+        localidad: texto(&donante.localidad),
+        provincia: texto(&donante.provincia),
+        email: texto(&donante.email),
+        ..donante.clone()
+    })
+}
+
+// CIF de una persona jurídica: letra, siete cifras y un carácter de control
+// que, según la letra, es una cifra o una letra.
+fn validar_cif(valor: &str) -> Result<String, String> {
+    let cif: String = valor
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '.' && *c != '-')
+        .collect::<String>()
+        .to_uppercase();
+    let no_valido = || format!("'{}' no es un CIF válido", valor.trim());
+
+    let bytes = cif.as_bytes();
+    if bytes.len() != 9 || !b"ABCDEFGHJNPQRSUVW".contains(&bytes[0]) || !bytes[1..8].iter().all(u8::is_ascii_digit) {
+        return Err(no_valido());
+    }
+    let suma: u32 = bytes[1..8]
+        .iter()
+        .enumerate()
+        .map(|(indice, cifra)| {
+            let cifra = (cifra - b'0') as u32;
+            match indice % 2 {
+                0 => (cifra * 2) / 10 + (cifra * 2) % 10,
+                _ => cifra,
+            }
+        })
+        .sum();
+    let control = (10 - suma % 10) % 10;
+    let digito = b'0' + control as u8;
+    let letra = b"JABCDEFGHI"[control as usize];
+
+    let valido = match bytes[0] {
+        b'P' | b'Q' | b'R' | b'S' | b'N' | b'W' => bytes[8] == letra,
+        b'A' | b'B' | b'E' | b'H' => bytes[8] == digito,
+        _ => bytes[8] == letra || bytes[8] == digito,
+    };
+    if !valido {
+        return Err(format!("El carácter de control del CIF no es correcto en '{}'", valor.trim()));
+    }
+
+    Ok(cif)
+}
+
+// Las dos primeras cifras del código postal son el código de la provincia.
+fn codigo_provincia(codigo_postal: &str) -> Option<String> {
+    let codigo_postal = codigo_postal.trim();
+    if codigo_postal.len() != 5 || !codigo_postal.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let provincia = &codigo_postal[..2];
+    provincia.parse::<u32>().ok().filter(|p| (1..=52).contains(p)).map(|_| provincia.to_string())
+}
+
+/// Donativos registrados, filtrados por año de la fecha y por donante.
+pub fn get_donativos(db: &DbConnection, anio: Option<i32>, donante_id: Option<i32>) -> HermanarResult<Vec<Donativo>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM donativos
+         WHERE (?1 IS NULL OR CAST(substr(fecha, 1, 4) AS INTEGER) = ?1)
+           AND (?2 IS NULL OR donante_id = ?2)
+         ORDER BY fecha DESC, id DESC",
+        COLUMNAS_DONATIVO
+    ))?;
+
+    let donativos = stmt.query_map(params![anio, donante_id], Donativo::from_row)?.collect::<Result<Vec<_>, _>>()?;
+
+    Ok(donativos)
+}
+
+pub fn create_donativo(db: &DbConnection, donativo: &Donativo) -> HermanarResult<i32> {
+    let donativo = validar_donativo(donativo)?;
+
+    let conn = db.writer();
+    if buscar_donante(&conn, donativo.donante_id)?.is_none() {
+        return Err(HermanarError::not_found("Donante"));
+    }
+    conn.prepare_cached(
+        "INSERT INTO donativos (donante_id, fecha, importe, tipo, periodicidad, concepto)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
+    )?
+    .execute(params![
+        donativo.donante_id,
+        donativo.fecha,
+        donativo.importe,
+        donativo.tipo.clave(),
+        donativo.periodicidad.clave(),
+        donativo.concepto,
+    ])?;
+
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+pub fn update_donativo(db: &DbConnection, id: i32, donativo: &Donativo) -> HermanarResult<()> {
+    let donativo = validar_donativo(donativo)?;
+
+    let conn = db.writer();
+    if buscar_donante(&conn, donativo.donante_id)?.is_none() {
+        return Err(HermanarError::not_found("Donante"));
+    }
+    let actualizados = conn
+        .prepare_cached(
+            "UPDATE donativos
+             SET donante_id = ?1, fecha = ?2, importe = ?3, tipo = ?4, periodicidad = ?5, concepto = ?6,
+                 updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?7"
+        )?
+        .execute(params![
+            donativo.donante_id,
+            donativo.fecha,
+            donativo.importe,
+            donativo.tipo.clave(),
+            donativo.periodicidad.clave(),
+            donativo.concepto,
+            id,
+        ])?;
+    if actualizados == 0 {
+        return Err(HermanarError::not_found("Donativo"));
+    }
+
+    Ok(())
+}
+
+pub fn delete_donativo(db: &DbConnection, id: i32) -> HermanarResult<()> {
+    if db.writer().prepare_cached("DELETE FROM donativos WHERE id = ?1")?.execute([id])? == 0 {
+        return Err(HermanarError::not_found("Donativo"));
+    }
+
+    Ok(())
+}
+
+fn validar_donativo(donativo: &Donativo) -> HermanarResult<Donativo> {
+    let fecha = validar_fecha("fecha", &donativo.fecha)?;
+    let importe = (donativo.importe * 100.0).round() / 100.0;
+    if !importe.is_finite() || importe <= 0.0 {
+        return Err(HermanarError::validation("importe", "El importe debe ser mayor que cero"));
+    }
+
+    Ok(Donativo {
+        fecha,
+        importe,
+        concepto: donativo.concepto.as_deref().map(str::trim).filter(|c| !c.is_empty()).map(str::to_string),
+        ..donativo.clone()
+    })
+}
+
+// Todo lo que aporta un NIF en un año, con las líneas que salen en su
+// certificado.
+struct Aportacion {
+    nif: String,
+    naturaleza: NaturalezaDonante,
+    nombre: String,
+    // Apellidos y nombre, como los pide el modelo 182.
+    nombre_fiscal: String,
+    codigo_postal: Option<String>,
+    donante_id: Option<i32>,
+    hermano_id: Option<i32>,
+    lineas: Vec<LineaAportacion>,
+}
+
+struct LineaAportacion {
+    fecha: String,
+    concepto: String,
+    importe: f64,
+    especie: bool,
+}
+
+impl Aportacion {
+    fn desde_donante(donante: &Donante) -> Self {
+        Aportacion {
+            nif: donante.nif.clone(),
+            naturaleza: donante.naturaleza,
+            nombre: donante.nombre_completo(),
+            nombre_fiscal: match &donante.apellidos {
+                Some(apellidos) => format!("{} {}", apellidos, donante.nombre),
+                None => donante.nombre.clone(),
+            },
+            codigo_postal: donante.codigo_postal.clone(),
+            donante_id: donante.id,
+            hermano_id: donante.hermano_id,
+            lineas: Vec::new(),
+        }
+    }
+
+    fn desde_hermano(hermano: &Hermano, nif: String) -> Self {
+        let apellidos = [Some(hermano.primer_apellido.as_str()), hermano.segundo_apellido.as_deref()]
+            .into_iter()
+            .flatten()
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Aportacion {
+            nif,
+            naturaleza: NaturalezaDonante::Fisica,
+            nombre: format!("{} {}", hermano.nombre.trim(), apellidos),
+            nombre_fiscal: format!("{} {}", apellidos, hermano.nombre.trim()),
+            codigo_postal: hermano.codigo_postal.clone(),
+            donante_id: None,
+            hermano_id: hermano.id,
+            lineas: Vec::new(),
+        }
+    }
+
+    fn total(&self) -> f64 {
+        redondear(self.lineas.iter().map(|linea| linea.importe).sum())
+    }
+
+    fn total_especie(&self) -> f64 {
+        redondear(self.lineas.iter().filter(|linea| linea.especie).map(|linea| linea.importe).sum())
+    }
+}
+
+// Donativos del año y pagos de cuotas deducibles hechos en el año, agrupados
+// por NIF. Las cuotas de un hermano se suman a su ficha de donante si la
+// tiene; si no, se declaran con el DNI de su ficha de hermano.
+fn aportaciones_anio(conn: &Connection, anio: i32) -> HermanarResult<(BTreeMap<String, Aportacion>, Vec<String>)> {
+    let mut aportaciones: BTreeMap<String, Aportacion> = BTreeMap::new();
+    let mut incidencias = Vec::new();
+
+    let donantes = {
+        let mut stmt = conn.prepare_cached(&format!("SELECT {} FROM donantes", COLUMNAS_DONANTE))?;
+        let donantes = stmt.query_map([], Donante::from_row)?.collect::<Result<Vec<_>, _>>()?;
+        donantes
+    };
+
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM donativos WHERE CAST(substr(fecha, 1, 4) AS INTEGER) = ?1 ORDER BY fecha, id",
+        COLUMNAS_DONATIVO
+    ))?;
+    for donativo in stmt.query_map([anio], Donativo::from_row)? {
+        let donativo = donativo?;
+        let Some(donante) = donantes.iter().find(|d| d.id == Some(donativo.donante_id)) else {
+            continue;
+        };
+        aportaciones
+            .entry(donante.nif.clone())
+            .or_insert_with(|| Aportacion::desde_donante(donante))
+            .lineas
+            .push(LineaAportacion {
+                fecha: donativo.fecha,
+                concepto: donativo.concepto.unwrap_or_else(|| match donativo.tipo {
+                    TipoDonativo::Dinerario => "Donativo".to_string(),
+                    TipoDonativo::Especie => "Donativo en especie".to_string(),
+                }),
+                importe: donativo.importe,
+                especie: donativo.tipo == TipoDonativo::Especie,
+            });
+    }
+
+    // Cada pago de una cuota deducible cuenta en el año en que se hizo,
+    // aunque la cuota se termine de pagar en otro o no llegue a pagarse.
+    let mut stmt = conn.prepare_cached(
+        "SELECT p.cuota_id, p.fecha, p.importe FROM pagos p
+         JOIN cuotas c ON c.id = p.cuota_id
+         WHERE c.deducible = 1 AND p.anulado = 0 AND CAST(substr(p.fecha, 1, 4) AS INTEGER) = ?1
+         ORDER BY p.fecha, p.id"
+    )?;
+    let pagos = stmt
+        .query_map([anio], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?, row.get::<_, f64>(2)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (cuota_id, fecha, importe) in pagos {
+        let Some(cuota) = buscar_cuota(conn, cuota_id)? else {
+            continue;
+        };
+        let hermano_id = cuota.hermano_id;
+        let concepto = if (importe * 100.0).round() < (cuota.importe * 100.0).round() {
+            format!("Pago a cuenta de la cuota {}", cuota.descripcion_periodo())
+        } else {
+            format!("Cuota {}", cuota.descripcion_periodo())
+        };
+        let linea = LineaAportacion {
+            fecha,
+            concepto,
+            importe,
+            especie: false,
+        };
+
+        if let Some(donante) = donantes.iter().find(|d| d.hermano_id == Some(hermano_id)) {
+            aportaciones.entry(donante.nif.clone()).or_insert_with(|| Aportacion::desde_donante(donante)).lineas.push(linea);
+            continue;
+        }
+        let Some(hermano) = buscar_hermano(conn, hermano_id)? else {
+            continue;
+        };
+        let nif = hermano.dni.as_deref().and_then(|dni| match normalizar_documento(dni, hermano.tipo_documento) {
+            Ok((nif, TipoDocumento::Dni | TipoDocumento::Nie)) => Some(nif),
+            _ => None,
+        });
+        let Some(nif) = nif else {
+            let mensaje = format!(
+                "El hermano {} ({}) tiene cuotas deducibles pero no tiene un DNI o NIE válido",
+                hermano.numero_hermano,
+                nombre_completo(&hermano)
+            );
+            if !incidencias.contains(&mensaje) {
+                incidencias.push(mensaje);
+            }
+            continue;
+        };
+        match donantes.iter().find(|d| d.nif == nif) {
+            Some(donante) => aportaciones.entry(nif).or_insert_with(|| Aportacion::desde_donante(donante)),
+            None => aportaciones.entry(nif.clone()).or_insert_with(|| Aportacion::desde_hermano(&hermano, nif)),
+        }
+        .lineas
+        .push(linea);
+    }
+
+    Ok((aportaciones, incidencias))
+}
+
+/// Resumen de lo que se declarará en el modelo 182 del año, con las
+/// aportaciones que no pueden declararse por falta de datos.
+pub fn get_resumen_modelo182(db: &DbConnection, anio: i32) -> HermanarResult<ResumenModelo182> {
+    resumen_modelo182(&db.reader(), anio)
+}
+
+fn resumen_modelo182(conn: &Connection, anio: i32) -> HermanarResult<ResumenModelo182> {
+    let (aportaciones, mut incidencias) = aportaciones_anio(conn, anio)?;
+    let (anterior, _) = aportaciones_anio(conn, anio - 1)?;
+    let (penultimo, _) = aportaciones_anio(conn, anio - 2)?;
+    let total_de = |anio: &BTreeMap<String, Aportacion>, nif: &str| anio.get(nif).map(Aportacion::total).unwrap_or(0.0);
+
+    let mut declarados = Vec::new();
+    for aportacion in aportaciones.values() {
+        let Some(provincia) = aportacion.codigo_postal.as_deref().and_then(codigo_provincia) else {
+            incidencias.push(format!(
+                "{} ({}) no tiene un código postal válido para saber su provincia",
+                aportacion.nombre, aportacion.nif
+            ));
+            continue;
+        };
+        let (importe_anterior, importe_penultimo) = (total_de(&anterior, &aportacion.nif), total_de(&penultimo, &aportacion.nif));
+
+        declarados.push(Declarado182 {
+            nif: aportacion.nif.clone(),
+            nombre: aportacion.nombre_fiscal.clone(),
+            naturaleza: aportacion.naturaleza,
+            provincia,
+            importe: aportacion.total(),
+            importe_especie: aportacion.total_especie(),
+            recurrente: importe_penultimo > 0.0 && importe_anterior >= importe_penultimo,
+            donante_id: aportacion.donante_id,
+            hermano_id: aportacion.hermano_id,
+        });
+    }
+
+    let registros = declarados.iter().map(|d| registros_declarado(anio, d).len()).sum();
+    let total = redondear(declarados.iter().map(|d| d.importe).sum());
+
+    Ok(ResumenModelo182 { anio, declarados, registros, total, incidencias })
+}
+
+/// Porcentajes de deducción de la Ley 49/2002 para el año: importe del
+/// primer tramo de las personas físicas, su porcentaje, y los porcentajes
+/// general e incrementado por recurrencia del resto.
+fn porcentajes(anio: i32, naturaleza: NaturalezaDonante) -> (f64, f64, f64, f64) {
+    match (naturaleza, anio >= 2024) {
+        (NaturalezaDonante::Fisica, true) => (250.0, 80.0, 40.0, 45.0),
+        (NaturalezaDonante::Fisica, false) => (150.0, 80.0, 35.0, 40.0),
+        (NaturalezaDonante::Juridica, true) => (0.0, 0.0, 40.0, 50.0),
+        (NaturalezaDonante::Juridica, false) => (0.0, 0.0, 35.0, 40.0),
+    }
+}
+
+// Registros de un declarado como (importe, porcentaje, en especie). A las
+// personas físicas el primer tramo se les declara aparte con su porcentaje;
+// se aplica antes a lo dinerario que a lo donado en especie.
+fn registros_declarado(anio: i32, declarado: &Declarado182) -> Vec<(f64, f64, bool)> {
+    let (tramo, porcentaje_tramo, general, incrementado) = porcentajes(anio, declarado.naturaleza);
+    let porcentaje_resto = if declarado.recurrente { incrementado } else { general };
+    let mut tramo_libre = tramo;
+    let mut registros = Vec::new();
+
+    for (importe, especie) in [(redondear(declarado.importe - declarado.importe_especie), false), (declarado.importe_especie, true)] {
+        if importe <= 0.0 {
+            continue;
+        }
+        let en_tramo = importe.min(tramo_libre);
+        tramo_libre -= en_tramo;
+        if en_tramo > 0.0 {
+            registros.push((redondear(en_tramo), porcentaje_tramo, especie));
+        }
+        if importe - en_tramo > 0.0 {
+            registros.push((redondear(importe - en_tramo), porcentaje_resto, especie));
+        }
+    }
+
+    registros
+}
+
+/// Genera el fichero del modelo 182 del año para presentarlo en la sede
+/// electrónica de la AEAT. No se genera mientras haya aportaciones que no
+/// pueden declararse.
+pub fn generar_modelo182(db: &DbConnection, solicitud: &SolicitudModelo182) -> HermanarResult<ResumenModelo182> {
+    let fichero = fichero_destino(&solicitud.fichero)?;
+    let contacto = solicitud.contacto.trim();
+    if contacto.is_empty() {
+        return Err(HermanarError::validation("contacto", "Indique la persona de contacto"));
+    }
+
+    let (resumen, hermandad) = {
+        let conn = db.reader();
+        (resumen_modelo182(&conn, solicitud.anio)?, cargar_datos_documento(&conn)?)
+    };
+    let Some(cif) = hermandad.cif.as_deref().map(validar_cif).transpose().map_err(|m| HermanarError::validation("cif", m))? else {
+        return Err(HermanarError::validation("cif", "Configure el CIF de la hermandad en los datos de la hermandad"));
+    };
+    let telefono: String = solicitud
+        .telefono
+        .as_deref()
+        .or(hermandad.telefono.as_deref())
+        .unwrap_or_default()
+        .chars()
+        .filter(char::is_ascii_digit)
+        .collect();
+    if telefono.len() != 9 {
+        return Err(HermanarError::validation("telefono", "Indique un teléfono de contacto de 9 cifras"));
+    }
+    if let Some(incidencia) = resumen.incidencias.first() {
+        return Err(HermanarError::validation(
+            "anio",
+            format!("Hay {} aportaciones sin datos suficientes: {}", resumen.incidencias.len(), incidencia),
+        ));
+    }
+    if resumen.declarados.is_empty() {
+        return Err(HermanarError::validation("anio", "No hay donativos que declarar en ese año"));
+    }
+
+    let mut lineas = vec![registro_declarante(solicitud.anio, &cif, &hermandad.nombre, &telefono, contacto, &resumen)];
+    for declarado in &resumen.declarados {
+        for (importe, porcentaje, especie) in registros_declarado(solicitud.anio, declarado) {
+            lineas.push(registro_declarado(solicitud.anio, &cif, declarado, importe, porcentaje, especie));
+        }
+    }
+
+    let mut contenido = Vec::with_capacity(lineas.len() * (LONGITUD_REGISTRO + 2));
+    for linea in lineas {
+        // Todo el texto ha pasado por `texto_aeat`, así que cada carácter
+        // cabe en un byte de ISO-8859-1.
+        let linea: Vec<u8> = linea.chars().map(|c| c as u32 as u8).collect();
+        contenido.extend_from_slice(&linea);
+        contenido.extend_from_slice(b"\r\n");
+    }
+    std::fs::write(fichero, contenido)
+        .map_err(|e| HermanarError::database(format!("No se pudo crear el fichero {}: {}", fichero.display(), e)))?;
+
+    Ok(resumen)
+}
+
+// Registro de tipo 1 del modelo 182, con los datos del declarante y los
+// totales de la declaración.
+fn registro_declarante(
+    anio: i32,
+    cif: &str,
+    nombre: &str,
+    telefono: &str,
+    contacto: &str,
+    resumen: &ResumenModelo182,
+) -> String {
+    let anio = format!("{:04}", anio);
+    let registro = [
+        "1182",
+        &anio,
+        cif,
+        &texto_aeat(nombre, 40),
+        "T",
+        telefono,
+        &texto_aeat(contacto, 40),
+        &format!("182{}{:06}", anio, 1),
+        "  ",
+        &"0".repeat(13),
+        &format!("{:09}", resumen.registros),
+        &format!("{:015}", centimos(resumen.total)),
+        // Entidad beneficiaria de las del artículo 16 de la Ley 49/2002
+        "1",
+        &" ".repeat(9),
+        &" ".repeat(40),
+    ]
+    .concat();
+
+    format!("{:<1$}", registro, LONGITUD_REGISTRO)
+}
+
+// Registro de tipo 2 del modelo 182 para una parte de lo donado por un
+// declarado, como las devuelve `registros_declarado`.
+fn registro_declarado(
+    anio: i32,
+    cif: &str,
+    declarado: &Declarado182,
+    importe: f64,
+    porcentaje: f64,
+    especie: bool,
+) -> String {
+    let registro = [
+        "2182",
+        &format!("{:04}", anio),
+        cif,
+        &declarado.nif,
+        &" ".repeat(9),
+        &texto_aeat(&declarado.nombre, 40),
+        &declarado.provincia,
+        // Donativos no incluidos en actividades prioritarias de mecenazgo
+        "A",
+        &format!("{:05}", (porcentaje * 100.0).round() as u32),
+        &format!("{:013}", centimos(importe)),
+        if especie { "X" } else { " " },
+        "00",
+        "00000",
+        declarado.naturaleza.clave(),
+        " ",
+        "0000",
+        " ",
+        &" ".repeat(20),
+        if declarado.recurrente { "1" } else { "2" },
+        &" ".repeat(9),
+        &" ".repeat(40),
+    ]
+    .concat();
+
+    format!("{:<1$}", registro, LONGITUD_REGISTRO)
+}
+
+fn centimos(importe: f64) -> u64 {
+    (importe.abs() * 100.0).round() as u64
+}
+
+fn redondear(importe: f64) -> f64 {
+    (importe * 100.0).round() / 100.0
+}
+
+// Texto en el juego de caracteres de los modelos de la AEAT: mayúsculas sin
+// acentos, conservando la Ñ y la Ç, y relleno con espacios hasta `longitud`.
+fn texto_aeat(texto: &str, longitud: usize) -> String {
+    let convertido: String = texto
+        .to_uppercase()
+        .chars()
+        .map(|c| match c {
+            'Á' | 'À' | 'Â' | 'Ä' => 'A',
+            'É' | 'È' | 'Ê' | 'Ë' => 'E',
+            'Í' | 'Ì' | 'Î' | 'Ï' => 'I',
+            'Ó' | 'Ò' | 'Ô' | 'Ö' => 'O',
+            'Ú' | 'Ù' | 'Û' | 'Ü' => 'U',
+            c if c.is_ascii_uppercase() || c.is_ascii_digit() || c == 'Ñ' || c == 'Ç' || ".,-'&".contains(c) => c,
+            _ => ' ',
+        })
+        .collect();
+    let convertido: String = convertido.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(longitud).collect();
+
+    format!("{:<1$}", convertido, longitud)
+}
+
+/// Genera en un PDF el certificado de donativos del año de un donante o, sin
+/// NIF, de todos, uno por página. Devuelve el número de certificados.
+pub fn generar_certificados_donativos(
+    db: &DbConnection,
+    solicitud: &SolicitudCertificadoDonativos,
+) -> HermanarResult<usize> {
+    let fichero = fichero_destino(&solicitud.fichero)?;
+
+    let (aportaciones, hermandad) = {
+        let conn = db.reader();
+        (aportaciones_anio(&conn, solicitud.anio)?.0, cargar_datos_documento(&conn)?)
+    };
+    if hermandad.cif.is_none() {
+        return Err(HermanarError::validation("cif", "Configure el CIF de la hermandad en los datos de la hermandad"));
+    }
+    let nif = solicitud.nif.as_deref().map(|nif| nif.trim().to_uppercase()).filter(|nif| !nif.is_empty());
+    let seleccion: Vec<&Aportacion> =
+        aportaciones.values().filter(|a| nif.as_ref().is_none_or(|nif| *nif == a.nif)).collect();
+    if seleccion.is_empty() {
+        return Err(HermanarError::validation("nif", "No hay donativos de ese año"));
+    }
+
+    let documento = Documento::new(&format!("Certificados de donativos {}", solicitud.anio))?;
+    let fecha = hoy();
+    for aportacion in &seleccion {
+        dibujar_certificado(&documento.pagina(210.0, 297.0), &hermandad, aportacion, solicitud.anio, &fecha)?;
+    }
+    documento.guardar(fichero)?;
+
+    Ok(seleccion.len())
+}
+
+fn dibujar_certificado(
+    pagina: &Pagina,
+    hermandad: &DatosHermandad,
+    aportacion: &Aportacion,
+    anio: i32,
+    fecha: &str,
+) -> HermanarResult<()> {
+    const MARGEN: f32 = 25.0;
+    const ANCHO: f32 = 210.0 - 2.0 * MARGEN;
+
+    let texto_x = match hermandad.logo.as_deref() {
+        Some(logo) => {
+            pagina.imagen(MARGEN, 15.0, 25.0, 25.0, Path::new(logo))?;
+            MARGEN + 31.0
+        }
+        None => MARGEN,
+    };
+    pagina.texto_ajustado(texto_x, 24.0, 185.0 - texto_x, 12.0, true, &hermandad.nombre);
+    let lineas_hermandad = [
+        hermandad.cif.as_ref().map(|cif| format!("CIF: {}", cif)),
+        hermandad.direccion.clone(),
+        hermandad.poblacion(),
+    ];
+    for (indice, linea) in lineas_hermandad.iter().flatten().enumerate() {
+        pagina.texto_ajustado(texto_x, 30.0 + indice as f32 * 4.5, 185.0 - texto_x, 8.5, false, linea);
+    }
+    pagina.rectangulo(MARGEN, 48.0, ANCHO, 0.4, Some((0.45, 0.1, 0.15)));
+
+    let titulo = format!("CERTIFICADO DE DONATIVOS DEL EJERCICIO {}", anio);
+    pagina.texto(MARGEN + (ANCHO - ancho_texto(&titulo, 14.0, true)) / 2.0, 64.0, 14.0, true, &titulo);
+
+    let total = aportacion.total();
+    let documento_donante = match aportacion.naturaleza {
+        NaturalezaDonante::Fisica => "NIF",
+        NaturalezaDonante::Juridica => "CIF",
+    };
+    let mut y = pagina.parrafo(
+        MARGEN,
+        80.0,
+        ANCHO,
+        10.5,
+        5.5,
+        &format!(
+            "{}, con CIF {}, entidad acogida al régimen fiscal de las entidades sin fines lucrativos y de los \
+             incentivos fiscales al mecenazgo de la Ley 49/2002, de 23 de diciembre, CERTIFICA:",
+            hermandad.nombre,
+            hermandad.cif.as_deref().unwrap_or_default()
+        ),
+    ) + 3.0;
+    y = pagina.parrafo(
+        MARGEN,
+        y,
+        ANCHO,
+        10.5,
+        5.5,
+        &format!(
+            "Que {}, con {} {}, ha entregado a esta entidad durante el año {} los donativos irrevocables que se \
+             detallan, por un importe total de {} ({}), que se destinan al cumplimiento de sus fines.",
+            aportacion.nombre,
+            documento_donante,
+            aportacion.nif,
+            anio,
+            formatear_importe(total),
+            importe_en_letras(total)
+        ),
+    ) + 5.0;
+
+    // Relación de donativos: fecha, concepto e importe alineado a la derecha
+    pagina.texto(MARGEN, y, 9.5, true, "Fecha");
+    pagina.texto(MARGEN + 28.0, y, 9.5, true, "Concepto");
+    pagina.texto(MARGEN + ANCHO - ancho_texto("Importe", 9.5, true), y, 9.5, true, "Importe");
+    pagina.rectangulo(MARGEN, y + 1.5, ANCHO, 0.2, Some((0.0, 0.0, 0.0)));
+    y += 6.5;
+
+    let (mostradas, resto) = match aportacion.lineas.len() > FILAS_CERTIFICADO {
+        true => aportacion.lineas.split_at(FILAS_CERTIFICADO - 1),
+        false => (aportacion.lineas.as_slice(), &[][..]),
+    };
+    let mut fila = |fecha: &str, concepto: &str, importe: f64| {
+        let importe = formatear_importe(importe);
+        pagina.texto(MARGEN, y, 9.5, false, fecha);
+        pagina.texto_ajustado(MARGEN + 28.0, y, ANCHO - 60.0, 9.5, false, concepto);
+        pagina.texto(MARGEN + ANCHO - ancho_texto(&importe, 9.5, false), y, 9.5, false, &importe);
+        y += 5.0;
+    };
+    for linea in mostradas {
+        let concepto = if linea.especie { format!("{} (en especie)", linea.concepto) } else { linea.concepto.clone() };
+        fila(&fecha_espanola(&linea.fecha), &concepto, linea.importe);
+    }
+    if !resto.is_empty() {
+        fila("", &format!("Otros {} donativos", resto.len()), redondear(resto.iter().map(|l| l.importe).sum()));
+    }
+    pagina.rectangulo(MARGEN, y - 3.5, ANCHO, 0.2, Some((0.0, 0.0, 0.0)));
+    let total_texto = format!("Total: {}", formatear_importe(total));
+    pagina.texto(MARGEN + ANCHO - ancho_texto(&total_texto, 10.0, true), y + 1.5, 10.0, true, &total_texto);
+
+    let localidad = hermandad.localidad.clone().or(hermandad.provincia.clone()).unwrap_or_default();
+    let cierre = match localidad.is_empty() {
+        true => format!("Y para que conste a efectos de la deducción fiscal correspondiente, se expide el presente certificado a {}.", fecha_en_letras(fecha)),
+        false => format!(
+            "Y para que conste a efectos de la deducción fiscal correspondiente, se expide el presente certificado en {}, a {}.",
+            localidad,
+            fecha_en_letras(fecha)
+        ),
+    };
+    let y = pagina.parrafo(MARGEN, y + 14.0, ANCHO, 10.5, 5.5, &cierre);
+
+    let firma = y + 12.0;
+    pagina.texto(MARGEN + ANCHO - 65.0, firma, 10.0, false, "El Tesorero");
+    pagina.rectangulo(MARGEN + ANCHO - 65.0, firma + 22.0, 65.0, 0.3, Some((0.0, 0.0, 0.0)));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn declarado(naturaleza: NaturalezaDonante, importe: f64, importe_especie: f64, recurrente: bool) -> Declarado182 {
+        Declarado182 {
+            nif: "12345678Z".to_string(),
+            nombre: "Núñez García José".to_string(),
+            naturaleza,
+            provincia: "41".to_string(),
+            importe,
+            importe_especie,
+            recurrente,
+            donante_id: None,
+            hermano_id: Some(1),
+        }
+    }
+
+    // Campo del registro entre las posiciones `desde` y `hasta`, contando
+    // desde 1 y ambas incluidas, como en el diseño de registro de la AEAT.
+    fn campo(registro: &str, desde: usize, hasta: usize) -> String {
+        registro.chars().skip(desde - 1).take(hasta + 1 - desde).collect()
+    }
+
+    #[test]
+    fn tramos_persona_fisica() {
+        let fisica = |importe, especie, recurrente| declarado(NaturalezaDonante::Fisica, importe, especie, recurrente);
+
+        assert_eq!(registros_declarado(2025, &fisica(100.0, 0.0, false)), [(100.0, 80.0, false)]);
+        assert_eq!(registros_declarado(2025, &fisica(300.0, 0.0, false)), [(250.0, 80.0, false), (50.0, 40.0, false)]);
+        assert_eq!(registros_declarado(2025, &fisica(300.0, 0.0, true)), [(250.0, 80.0, false), (50.0, 45.0, false)]);
+        assert_eq!(registros_declarado(2023, &fisica(200.0, 0.0, false)), [(150.0, 80.0, false), (50.0, 35.0, false)]);
+        // El primer tramo se aplica antes a lo dinerario que a lo donado en especie.
+        assert_eq!(
+            registros_declarado(2025, &fisica(300.0, 100.0, true)),
+            [(200.0, 80.0, false), (50.0, 80.0, true), (50.0, 45.0, true)]
+        );
+        assert_eq!(registros_declarado(2025, &fisica(260.1, 0.0, false)), [(250.0, 80.0, false), (10.1, 40.0, false)]);
+    }
+
+    #[test]
+    fn porcentajes_persona_juridica() {
+        let juridica = |anio, recurrente| registros_declarado(anio, &declarado(NaturalezaDonante::Juridica, 1000.0, 0.0, recurrente));
+
+        assert_eq!(juridica(2025, false), [(1000.0, 40.0, false)]);
+        assert_eq!(juridica(2025, true), [(1000.0, 50.0, false)]);
+        assert_eq!(juridica(2023, true), [(1000.0, 40.0, false)]);
+    }
+
+    #[test]
+    fn registro_tipo_1() {
+        let resumen = ResumenModelo182 {
+            anio: 2025,
+            declarados: Vec::new(),
+            registros: 3,
+            total: 1234.56,
+            incidencias: Vec::new(),
+        };
+        let registro = registro_declarante(2025, "G12345678", "Hermandad de la Peña", "954000000", "Pérez López, Ana", &resumen);
+
+        assert_eq!(registro.chars().count(), LONGITUD_REGISTRO);
+        assert_eq!(campo(&registro, 1, 4), "1182");
+        assert_eq!(campo(&registro, 5, 8), "2025");
+        assert_eq!(campo(&registro, 9, 17), "G12345678");
+        assert_eq!(campo(&registro, 18, 57).trim_end(), "HERMANDAD DE LA PEÑA");
+        assert_eq!(campo(&registro, 58, 58), "T");
+        assert_eq!(campo(&registro, 59, 67), "954000000");
+        assert_eq!(campo(&registro, 68, 107).trim_end(), "PEREZ LOPEZ, ANA");
+        assert_eq!(campo(&registro, 108, 120), "1822025000001");
+        assert_eq!(campo(&registro, 121, 122), "  ");
+        assert_eq!(campo(&registro, 123, 135), "0".repeat(13));
+        assert_eq!(campo(&registro, 136, 144), "000000003");
+        assert_eq!(campo(&registro, 145, 159), "000000000123456");
+        assert_eq!(campo(&registro, 160, 160), "1");
+        assert_eq!(campo(&registro, 161, 250).trim(), "");
+    }
+
+    #[test]
+    fn registro_tipo_2() {
+        let fisica = declarado(NaturalezaDonante::Fisica, 300.0, 0.0, true);
+        let registro = registro_declarado(2025, "G12345678", &fisica, 50.0, 45.0, false);
+
+        assert_eq!(registro.chars().count(), LONGITUD_REGISTRO);
+        assert_eq!(campo(&registro, 1, 4), "2182");
+        assert_eq!(campo(&registro, 5, 8), "2025");
+        assert_eq!(campo(&registro, 9, 17), "G12345678");
+        assert_eq!(campo(&registro, 18, 26), "12345678Z");
+        assert_eq!(campo(&registro, 27, 35), " ".repeat(9));
+        assert_eq!(campo(&registro, 36, 75).trim_end(), "NUÑEZ GARCIA JOSE");
+        assert_eq!(campo(&registro, 76, 77), "41");
+        assert_eq!(campo(&registro, 78, 78), "A");
+        assert_eq!(campo(&registro, 79, 83), "04500");
+        assert_eq!(campo(&registro, 84, 96), "0000000005000");
+        assert_eq!(campo(&registro, 97, 97), " ");
+        assert_eq!(campo(&registro, 98, 104), "0000000");
+        assert_eq!(campo(&registro, 105, 105), "F");
+        assert_eq!(campo(&registro, 106, 106), " ");
+        assert_eq!(campo(&registro, 107, 110), "0000");
+        assert_eq!(campo(&registro, 132, 132), "1");
+        assert_eq!(campo(&registro, 133, 250).trim(), "");
+
+        let juridica = declarado(NaturalezaDonante::Juridica, 80.0, 80.0, false);
+        let registro = registro_declarado(2025, "G12345678", &juridica, 80.0, 40.0, true);
+        assert_eq!(campo(&registro, 79, 83), "04000");
+        assert_eq!(campo(&registro, 97, 97), "X");
+        assert_eq!(campo(&registro, 105, 105), "J");
+        assert_eq!(campo(&registro, 132, 132), "2");
+    }
+}
@@ -0,0 +1,419 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use crate::db::{DbConnection, Familia, Hermano};
+use crate::db::exportacion;
+use crate::db::familias::listar_familias;
+use crate::db::hermandad::{cargar_datos, DatosHermandad};
+use crate::db::hermanos::listar_hermanos;
+use crate::db::numeracion::nombre_completo;
+use crate::db::pdf::{fichero_destino, Documento, Pagina};
+use crate::db::sepa::texto_sepa;
+use crate::error::{HermanarError, HermanarResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormatoEnvio {
+    /// Hoja A4 de 24 etiquetas de 70 x 37 mm (Apli 1273 y equivalentes).
+    Etiquetas3x8,
+    /// Hoja A4 de 21 etiquetas de 70 x 42,4 mm (Apli 1275 y equivalentes).
+    Etiquetas3x7,
+    /// Hoja A4 de 16 etiquetas de 105 x 37 mm (Apli 1277 y equivalentes).
+    Etiquetas2x8,
+    /// Sobre americano DL de 220 x 110 mm, con o sin ventanilla.
+    SobreDl,
+    /// Sobre C5 de 229 x 162 mm, para la carta sin doblar por la mitad.
+    SobreC5,
+    /// Listado para Correos, en el mismo CSV que el resto de exportaciones.
+    Csv,
+}
+
+/// Un envío: una familia o un grupo de hermanos que viven en la misma
+/// dirección.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Destinatario {
+    pub nombre: String,
+    pub direccion: String,
+    pub codigo_postal: Option<String>,
+    pub localidad: Option<String>,
+    pub provincia: Option<String>,
+    pub familia_id: Option<i32>,
+    /// Hermanos activos que reciben la carta en este envío.
+    pub hermano_ids: Vec<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HermanoSinDireccion {
+    pub hermano_id: i32,
+    pub numero_hermano: String,
+    pub nombre: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListaEnvio {
+    pub destinatarios: Vec<Destinatario>,
+    /// Hermanos activos que se quedan sin carta por no tener dirección.
+    pub sin_direccion: Vec<HermanoSinDireccion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolicitudEnvio {
+    pub formato: FormatoEnvio,
+    pub fichero: String,
+    /// Etiquetas ya usadas de la primera hoja, para aprovechar hojas
+    /// empezadas.
+    #[serde(default)]
+    pub posicion_inicial: usize,
+}
+
+struct Hoja {
+    columnas: usize,
+    filas: usize,
+    ancho: f32,
+    alto: f32,
+    margen_izquierdo: f32,
+    margen_superior: f32,
+}
+
+struct Sobre {
+    ancho: f32,
+    alto: f32,
+    // Esquina de la dirección, dentro de la ventanilla de los sobres que la
+    // tienen.
+    x: f32,
+    y: f32,
+}
+
+const ANCHO_A4: f32 = 210.0;
+const ALTO_A4: f32 = 297.0;
+const MARGEN_ETIQUETA: f32 = 6.0;
+
+impl FormatoEnvio {
+    fn hoja(&self) -> Option<Hoja> {
+        let (columnas, filas, ancho, alto) = match self {
+            FormatoEnvio::Etiquetas3x8 => (3, 8, 70.0, 37.0),
+            FormatoEnvio::Etiquetas3x7 => (3, 7, 70.0, 42.4),
+            FormatoEnvio::Etiquetas2x8 => (2, 8, 105.0, 37.0),
+            _ => return None,
+        };
+
+        Some(Hoja {
+            columnas,
+            filas,
+            ancho,
+            alto,
+            margen_izquierdo: (ANCHO_A4 - columnas as f32 * ancho) / 2.0,
+            margen_superior: (ALTO_A4 - filas as f32 * alto) / 2.0,
+        })
+    }
+
+    fn sobre(&self) -> Option<Sobre> {
+        match self {
+            FormatoEnvio::SobreDl => Some(Sobre { ancho: 220.0, alto: 110.0, x: 115.0, y: 60.0 }),
+            FormatoEnvio::SobreC5 => Some(Sobre { ancho: 229.0, alto: 162.0, x: 120.0, y: 100.0 }),
+            _ => None,
+        }
+    }
+}
+
+/// Lista de envíos a los hermanos activos, uno por domicilio: las familias
+/// reciben una sola carta en la dirección de su titular, y los hermanos sin
+/// familia que comparten dirección se agrupan en un único envío. Se ordena
+/// por código postal, como lo pide Correos.
+pub fn get_lista_envio(db: &DbConnection) -> HermanarResult<ListaEnvio> {
+    let conn = db.reader();
+    let hermanos = listar_hermanos(&conn)?;
+    let familias = listar_familias(&conn)?;
+
+    Ok(construir_lista(&hermanos, &familias))
+}
+
+/// Genera las etiquetas, los sobres o el CSV de la lista de envíos. Devuelve
+/// el número de envíos.
+pub fn generar_envio(db: &DbConnection, solicitud: &SolicitudEnvio) -> HermanarResult<usize> {
+    let fichero = fichero_destino(&solicitud.fichero)?;
+
+    let (lista, remitente) = {
+        let conn = db.reader();
+        let lista = construir_lista(&listar_hermanos(&conn)?, &listar_familias(&conn)?);
+        (lista, cargar_datos(&conn)?)
+    };
+    let destinatarios = lista.destinatarios;
+    if destinatarios.is_empty() {
+        return Err(HermanarError::validation("formato", "No hay hermanos activos con dirección"));
+    }
+
+    if let Some(hoja) = solicitud.formato.hoja() {
+        if solicitud.posicion_inicial >= hoja.columnas * hoja.filas {
+            return Err(HermanarError::validation(
+                "posicion_inicial",
+                format!("La hoja tiene {} etiquetas", hoja.columnas * hoja.filas),
+            ));
+        }
+        generar_etiquetas(fichero, &hoja, solicitud.posicion_inicial, &destinatarios)?;
+    } else if let Some(sobre) = solicitud.formato.sobre() {
+        generar_sobres(fichero, &sobre, &remitente, &destinatarios)?;
+    } else {
+        escribir_csv(fichero, &destinatarios)?;
+    }
+
+    Ok(destinatarios.len())
+}
+
+// Grupo de hermanos que comparten envío, con la dirección de `titular`.
+struct Grupo<'a> {
+    familia: Option<&'a Familia>,
+    titular: &'a Hermano,
+    hermanos: Vec<&'a Hermano>,
+}
+
+fn construir_lista(hermanos: &[Hermano], familias: &[Familia]) -> ListaEnvio {
+    let por_id: HashMap<i32, &Hermano> = hermanos.iter().filter_map(|h| Some((h.id?, h))).collect();
+    let mut grupos: Vec<Grupo> = Vec::new();
+    let mut por_direccion: HashMap<String, usize> = HashMap::new();
+    let mut incluidos: HashSet<i32> = HashSet::new();
+
+    // Las familias van primero para que los hermanos sueltos que viven en su
+    // domicilio se sumen a su envío.
+    for familia in familias {
+        let miembros: Vec<&Hermano> =
+            hermanos.iter().filter(|h| h.activo && h.familia_id.is_some() && h.familia_id == familia.id).collect();
+        let titular = familia
+            .hermano_direccion_id
+            .and_then(|id| por_id.get(&id).copied())
+            .filter(|titular| tiene_direccion(titular));
+        let Some(titular) = titular.filter(|_| !miembros.is_empty()) else {
+            continue;
+        };
+
+        incluidos.extend(miembros.iter().filter_map(|h| h.id));
+        por_direccion.entry(clave_direccion(titular)).or_insert(grupos.len());
+        grupos.push(Grupo { familia: Some(familia), titular, hermanos: miembros });
+    }
+
+    let mut sin_direccion = Vec::new();
+    for hermano in hermanos.iter().filter(|h| h.activo) {
+        let Some(id) = hermano.id.filter(|id| !incluidos.contains(id)) else {
+            continue;
+        };
+        if !tiene_direccion(hermano) {
+            sin_direccion.push(HermanoSinDireccion {
+                hermano_id: id,
+                numero_hermano: hermano.numero_hermano.clone(),
+                nombre: nombre_completo(hermano),
+            });
+            continue;
+        }
+
+        match por_direccion.get(&clave_direccion(hermano)) {
+            Some(&indice) => grupos[indice].hermanos.push(hermano),
+            None => {
+                por_direccion.insert(clave_direccion(hermano), grupos.len());
+                grupos.push(Grupo { familia: None, titular: hermano, hermanos: vec![hermano] });
+            }
+        }
+    }
+
+    let mut destinatarios: Vec<Destinatario> = grupos
+        .iter()
+        .map(|grupo| Destinatario {
+            nombre: match grupo.familia {
+                Some(familia) => nombre_familia(&familia.nombre_familia),
+                None => nombre_grupo(&grupo.hermanos),
+            },
+            direccion: grupo.titular.direccion.clone().unwrap_or_default().trim().to_string(),
+            codigo_postal: no_vacio(&grupo.titular.codigo_postal),
+            localidad: no_vacio(&grupo.titular.localidad),
+            provincia: no_vacio(&grupo.titular.provincia),
+            familia_id: grupo.familia.and_then(|familia| familia.id),
+            hermano_ids: grupo.hermanos.iter().filter_map(|h| h.id).collect(),
+        })
+        .collect();
+    destinatarios.sort_by(|a, b| {
+        (&a.codigo_postal, &a.localidad, &a.nombre).cmp(&(&b.codigo_postal, &b.localidad, &b.nombre))
+    });
+
+    ListaEnvio { destinatarios, sin_direccion }
+}
+
+fn tiene_direccion(hermano: &Hermano) -> bool {
+    hermano.direccion.as_deref().is_some_and(|direccion| !direccion.trim().is_empty())
+}
+
+fn no_vacio(valor: &Option<String>) -> Option<String> {
+    valor.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+// Dos direcciones son la misma si coinciden sus letras y cifras, sin
+// distinguir mayúsculas, acentos, espacios ni signos: «C/ Feria, 12» y
+// «c/feria 12».
+fn clave_direccion(hermano: &Hermano) -> String {
+    let texto = [&hermano.direccion, &hermano.codigo_postal, &hermano.localidad]
+        .into_iter()
+        .map(|parte| parte.as_deref().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("|");
+
+    texto_sepa(&texto, usize::MAX)
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn nombre_familia(nombre: &str) -> String {
+    let nombre = nombre.trim();
+    match nombre.get(..8) {
+        Some(inicio) if inicio.eq_ignore_ascii_case("familia ") => nombre.to_string(),
+        _ => format!("Familia {}", nombre),
+    }
+}
+
+// Los hermanos que comparten dirección y primer apellido se tratan como una
+// familia; si no, se nombran todos.
+fn nombre_grupo(hermanos: &[&Hermano]) -> String {
+    let [primero, resto @ ..] = hermanos else {
+        return String::new();
+    };
+    if resto.is_empty() {
+        return nombre_completo(primero);
+    }
+
+    let apellido = primero.primer_apellido.trim();
+    if resto.iter().all(|h| h.primer_apellido.trim().eq_ignore_ascii_case(apellido)) {
+        let segundo = primero.segundo_apellido.as_deref().map(str::trim).unwrap_or_default();
+        let mismo_segundo = resto
+            .iter()
+            .all(|h| h.segundo_apellido.as_deref().map(str::trim).unwrap_or_default().eq_ignore_ascii_case(segundo));
+        return match mismo_segundo && !segundo.is_empty() {
+            true => format!("Familia {} {}", apellido, segundo),
+            false => format!("Familia {}", apellido),
+        };
+    }
+
+    let nombres: Vec<String> = hermanos.iter().map(|h| nombre_completo(h)).collect();
+    match nombres.split_last() {
+        Some((ultimo, anteriores)) => format!("{} y {}", anteriores.join(", "), ultimo),
+        None => String::new(),
+    }
+}
+
+// Líneas de la dirección postal: calle, código postal con localidad y, si es
+// otra, la provincia.
+fn lineas_direccion(destinatario: &Destinatario) -> Vec<String> {
+    let poblacion = [destinatario.codigo_postal.as_deref(), destinatario.localidad.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let provincia = destinatario
+        .provincia
+        .clone()
+        .filter(|provincia| destinatario.localidad.as_ref().is_none_or(|localidad| !localidad.eq_ignore_ascii_case(provincia)));
+
+    [Some(destinatario.direccion.clone()), Some(poblacion).filter(|p| !p.is_empty()), provincia]
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+fn generar_etiquetas(fichero: &Path, hoja: &Hoja, posicion_inicial: usize, destinatarios: &[Destinatario]) -> HermanarResult<()> {
+    let documento = Documento::new("Etiquetas de envío")?;
+    let por_hoja = hoja.columnas * hoja.filas;
+    let mut pagina = None;
+
+    for (indice, destinatario) in destinatarios.iter().enumerate() {
+        let posicion = (indice + posicion_inicial) % por_hoja;
+        if posicion == 0 {
+            pagina = None;
+        }
+        let pagina = pagina.get_or_insert_with(|| documento.pagina(ANCHO_A4, ALTO_A4));
+
+        let x = hoja.margen_izquierdo + (posicion % hoja.columnas) as f32 * hoja.ancho;
+        let y = hoja.margen_superior + (posicion / hoja.columnas) as f32 * hoja.alto;
+        let lineas = lineas_direccion(destinatario);
+        // El bloque de texto se centra en vertical dentro de la etiqueta
+        let alto_bloque = (lineas.len() + 1) as f32 * 4.5;
+        let inicio = y + (hoja.alto - alto_bloque) / 2.0 + 3.2;
+
+        escribir_direccion(pagina, x + MARGEN_ETIQUETA, inicio, hoja.ancho - 2.0 * MARGEN_ETIQUETA, 9.0, destinatario, &lineas);
+    }
+
+    documento.guardar(fichero)
+}
+
+fn generar_sobres(fichero: &Path, sobre: &Sobre, remitente: &DatosHermandad, destinatarios: &[Destinatario]) -> HermanarResult<()> {
+    let documento = Documento::new("Sobres de envío")?;
+    let remite: Vec<String> = [
+        Some(remitente.nombre.clone()).filter(|nombre| !nombre.is_empty()),
+        remitente.direccion.clone(),
+        remitente.poblacion(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    for destinatario in destinatarios {
+        let pagina = documento.pagina(sobre.ancho, sobre.alto);
+        for (indice, linea) in remite.iter().enumerate() {
+            pagina.texto_ajustado(12.0, 15.0 + indice as f32 * 4.0, 90.0, 8.0, indice == 0, linea);
+        }
+
+        let lineas = lineas_direccion(destinatario);
+        escribir_direccion(&pagina, sobre.x, sobre.y, sobre.ancho - sobre.x - 15.0, 11.0, destinatario, &lineas);
+    }
+
+    documento.guardar(fichero)
+}
+
+fn escribir_direccion(pagina: &Pagina, x: f32, y: f32, ancho: f32, tamano: f32, destinatario: &Destinatario, lineas: &[String]) {
+    let interlineado = tamano * 0.5;
+    pagina.texto_ajustado(x, y, ancho, tamano, true, &destinatario.nombre);
+    for (indice, linea) in lineas.iter().enumerate() {
+        pagina.texto_ajustado(x, y + (indice + 1) as f32 * interlineado, ancho, tamano, false, linea);
+    }
+}
+
+fn escribir_csv(fichero: &Path, destinatarios: &[Destinatario]) -> HermanarResult<()> {
+    exportacion::escribir_csv(
+        fichero,
+        &["Destinatario", "Dirección", "Código postal", "Localidad", "Provincia", "Hermanos"],
+        destinatarios.iter().map(|destinatario| {
+            [
+                destinatario.nombre.clone(),
+                destinatario.direccion.clone(),
+                destinatario.codigo_postal.clone().unwrap_or_default(),
+                destinatario.localidad.clone().unwrap_or_default(),
+                destinatario.provincia.clone().unwrap_or_default(),
+                destinatario.hermano_ids.len().to_string(),
+            ]
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_con_nombres_acentuados() {
+        let destinatarios = [Destinatario {
+            nombre: "Álvarez Ñúñez".to_string(),
+            direccion: "Óscar Romero, 3".to_string(),
+            codigo_postal: Some("41001".to_string()),
+            localidad: Some("Écija".to_string()),
+            provincia: Some("Sevilla".to_string()),
+            familia_id: Some(1),
+            hermano_ids: vec![1, 2],
+        }];
+        let fichero = std::env::temp_dir().join(format!("envio-{}.csv", std::process::id()));
+
+        escribir_csv(&fichero, &destinatarios).unwrap();
+        let contenido = std::fs::read_to_string(&fichero).unwrap();
+        let _ = std::fs::remove_file(&fichero);
+
+        let lineas: Vec<&str> = contenido.trim_start_matches('\u{feff}').split("\r\n").collect();
+        assert_eq!(lineas[0], "Destinatario;Dirección;Código postal;Localidad;Provincia;Hermanos");
+        assert_eq!(lineas[1], "Álvarez Ñúñez;Óscar Romero, 3;41001;Écija;Sevilla;2");
+    }
+}
@@ -0,0 +1,492 @@
+use chrono::NaiveDate;
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use crate::db::{
+    get_all_cuotas, get_all_familias, get_all_hermanos, get_hermanos_activos, Cuota, DbConnection, Familia, Hermano,
+    EstadoPago, Periodicidad,
+};
+use crate::db::numeracion::nombre_completo;
+use crate::error::{HermanarError, HermanarResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntidadExportacion {
+    Hermanos,
+    Familias,
+    Cuotas,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormatoExportacion {
+    /// UTF-8 con BOM y separado por punto y coma, como lo abre Excel en español.
+    Csv,
+    Xlsx,
+}
+
+/// Filtros de la exportación. Cada uno se aplica solo a la entidad que tiene
+/// ese dato; los demás se ignoran.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FiltroExportacion {
+    pub solo_activos: bool,
+    pub familia_id: Option<i32>,
+    pub hermano_id: Option<i32>,
+    pub anio: Option<i32>,
+    pub periodicidad: Option<Periodicidad>,
+    #[serde(alias = "trimestre")]
+    pub periodo: Option<i32>,
+    pub pagado: Option<bool>,
+    pub estado: Option<EstadoPago>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolicitudExportacion {
+    pub entidad: EntidadExportacion,
+    pub formato: FormatoExportacion,
+    pub fichero: String,
+    /// Columnas a exportar, en orden. Vacío exporta todas.
+    #[serde(default)]
+    pub columnas: Vec<String>,
+    #[serde(default)]
+    pub filtro: FiltroExportacion,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnaExportacion {
+    pub clave: String,
+    pub titulo: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Tipo {
+    Texto,
+    Entero,
+    Fecha,
+    Importe,
+    Booleano,
+}
+
+enum Valor {
+    Vacio,
+    Texto(String),
+    Entero(i64),
+    Fecha(NaiveDate),
+    Importe(f64),
+    Booleano(bool),
+}
+
+const COLUMNAS_HERMANOS: &[(&str, &str, Tipo)] = &[
+    ("numero_hermano", "Número", Tipo::Texto),
+    ("nombre", "Nombre", Tipo::Texto),
+    ("primer_apellido", "Primer apellido", Tipo::Texto),
+    ("segundo_apellido", "Segundo apellido", Tipo::Texto),
+    ("dni", "DNI", Tipo::Texto),
+    ("fecha_nacimiento", "Fecha de nacimiento", Tipo::Fecha),
+    ("localidad_nacimiento", "Localidad de nacimiento", Tipo::Texto),
+    ("provincia_nacimiento", "Provincia de nacimiento", Tipo::Texto),
+    ("fecha_alta", "Fecha de alta", Tipo::Fecha),
+    ("familia", "Familia", Tipo::Texto),
+    ("telefono", "Teléfono", Tipo::Texto),
+    ("email", "Email", Tipo::Texto),
+    ("direccion", "Dirección", Tipo::Texto),
+    ("localidad", "Localidad", Tipo::Texto),
+    ("provincia", "Provincia", Tipo::Texto),
+    ("codigo_postal", "Código postal", Tipo::Texto),
+    ("parroquia_bautismo", "Parroquia de bautismo", Tipo::Texto),
+    ("localidad_bautismo", "Localidad de bautismo", Tipo::Texto),
+    ("provincia_bautismo", "Provincia de bautismo", Tipo::Texto),
+    ("autorizacion_menores", "Autorización de menores", Tipo::Booleano),
+    ("nombre_representante_legal", "Representante legal", Tipo::Texto),
+    ("dni_representante_legal", "DNI del representante", Tipo::Texto),
+    ("hermano_aval_1", "Aval 1", Tipo::Texto),
+    ("hermano_aval_2", "Aval 2", Tipo::Texto),
+    ("activo", "Activo", Tipo::Booleano),
+    ("observaciones", "Observaciones", Tipo::Texto),
+];
+
+const COLUMNAS_FAMILIAS: &[(&str, &str, Tipo)] = &[
+    ("nombre_familia", "Familia", Tipo::Texto),
+    ("hermanos", "Hermanos", Tipo::Entero),
+    ("hermanos_activos", "Hermanos activos", Tipo::Entero),
+    ("titular_direccion", "Titular de la dirección", Tipo::Texto),
+    ("direccion", "Dirección", Tipo::Texto),
+    ("localidad", "Localidad", Tipo::Texto),
+    ("provincia", "Provincia", Tipo::Texto),
+    ("codigo_postal", "Código postal", Tipo::Texto),
+];
+
+const COLUMNAS_CUOTAS: &[(&str, &str, Tipo)] = &[
+    ("numero_hermano", "Número", Tipo::Texto),
+    ("hermano", "Hermano", Tipo::Texto),
+    ("anio", "Año", Tipo::Entero),
+    ("periodicidad", "Periodicidad", Tipo::Texto),
+    ("periodo", "Periodo", Tipo::Entero),
+    ("importe", "Importe", Tipo::Importe),
+    ("pagado", "Pagada", Tipo::Booleano),
+    ("estado", "Estado", Tipo::Texto),
+    ("importe_pagado", "Importe pagado", Tipo::Importe),
+    ("importe_pendiente", "Importe pendiente", Tipo::Importe),
+    ("fecha_pago", "Fecha de pago", Tipo::Fecha),
+    ("metodo_pago", "Método de pago", Tipo::Texto),
+    ("observaciones", "Observaciones", Tipo::Texto),
+];
+
+fn columnas_entidad(entidad: EntidadExportacion) -> &'static [(&'static str, &'static str, Tipo)] {
+    match entidad {
+        EntidadExportacion::Hermanos => COLUMNAS_HERMANOS,
+        EntidadExportacion::Familias => COLUMNAS_FAMILIAS,
+        EntidadExportacion::Cuotas => COLUMNAS_CUOTAS,
+    }
+}
+
+/// Columnas disponibles para una entidad, en el orden por defecto.
+pub fn get_columnas_exportacion(entidad: EntidadExportacion) -> Vec<ColumnaExportacion> {
+    columnas_entidad(entidad)
+        .iter()
+        .map(|(clave, titulo, _)| ColumnaExportacion { clave: clave.to_string(), titulo: titulo.to_string() })
+        .collect()
+}
+
+/// Escribe el listado en el fichero indicado y devuelve el número de filas
+/// exportadas, sin contar la cabecera.
+pub fn exportar(db: &DbConnection, solicitud: &SolicitudExportacion) -> HermanarResult<usize> {
+    let fichero = solicitud.fichero.trim();
+    if fichero.is_empty() {
+        return Err(HermanarError::validation("fichero", "Indique el fichero de destino"));
+    }
+
+    let disponibles = columnas_entidad(solicitud.entidad);
+    let columnas: Vec<(&str, &str, Tipo)> = if solicitud.columnas.is_empty() {
+        disponibles.to_vec()
+    } else {
+        solicitud
+            .columnas
+            .iter()
+            .map(|clave| {
+                disponibles
+                    .iter()
+                    .find(|(c, _, _)| c == clave)
+                    .copied()
+                    .ok_or_else(|| HermanarError::validation("columnas", format!("Columna desconocida: {}", clave)))
+            })
+            .collect::<HermanarResult<_>>()?
+    };
+
+    let filas = match solicitud.entidad {
+        EntidadExportacion::Hermanos => filas_hermanos(db, &solicitud.filtro, &columnas)?,
+        EntidadExportacion::Familias => filas_familias(db, &columnas)?,
+        EntidadExportacion::Cuotas => filas_cuotas(db, &solicitud.filtro, &columnas)?,
+    };
+
+    let titulos: Vec<&str> = columnas.iter().map(|(_, titulo, _)| *titulo).collect();
+    let ruta = Path::new(fichero);
+    match solicitud.formato {
+        FormatoExportacion::Csv => escribir_csv(ruta, &titulos, filas.iter().map(|fila| fila.iter().map(texto_csv))),
+        FormatoExportacion::Xlsx => escribir_xlsx(ruta, hoja(solicitud.entidad), &titulos, &filas)
+            .map_err(|e| HermanarError::database(format!("No se pudo escribir el fichero: {}", e))),
+    }?;
+
+    Ok(filas.len())
+}
+
+fn hoja(entidad: EntidadExportacion) -> &'static str {
+    match entidad {
+        EntidadExportacion::Hermanos => "Hermanos",
+        EntidadExportacion::Familias => "Familias",
+        EntidadExportacion::Cuotas => "Cuotas",
+    }
+}
+
+fn filas_hermanos(
+    db: &DbConnection,
+    filtro: &FiltroExportacion,
+    columnas: &[(&str, &str, Tipo)],
+) -> HermanarResult<Vec<Vec<Valor>>> {
+    let hermanos = if filtro.solo_activos { get_hermanos_activos(db)? } else { get_all_hermanos(db)? };
+    let familias: HashMap<i32, String> = get_all_familias(db)?
+        .into_iter()
+        .filter_map(|f| f.id.map(|id| (id, f.nombre_familia)))
+        .collect();
+
+    Ok(hermanos
+        .iter()
+        .filter(|h| filtro.familia_id.is_none() || h.familia_id == filtro.familia_id)
+        .map(|h| {
+            columnas
+                .iter()
+                .map(|&(clave, _, tipo)| valor_hermano(h, &familias, clave, tipo))
+                .collect()
+        })
+        .collect())
+}
+
+fn valor_hermano(h: &Hermano, familias: &HashMap<i32, String>, clave: &str, tipo: Tipo) -> Valor {
+    let texto = match clave {
+        "numero_hermano" => Some(h.numero_hermano.clone()),
+        "nombre" => Some(h.nombre.clone()),
+        "primer_apellido" => Some(h.primer_apellido.clone()),
+        "segundo_apellido" => h.segundo_apellido.clone(),
+        "dni" => h.dni.clone(),
+        "fecha_nacimiento" => h.fecha_nacimiento.clone(),
+        "localidad_nacimiento" => h.localidad_nacimiento.clone(),
+        "provincia_nacimiento" => h.provincia_nacimiento.clone(),
+        "fecha_alta" => Some(h.fecha_alta.clone()),
+        "familia" => h.familia_id.and_then(|id| familias.get(&id).cloned()),
+        "telefono" => h.telefono.clone(),
+        "email" => h.email.clone(),
+        "direccion" => h.direccion.clone(),
+        "localidad" => h.localidad.clone(),
+        "provincia" => h.provincia.clone(),
+        "codigo_postal" => h.codigo_postal.clone(),
+        "parroquia_bautismo" => h.parroquia_bautismo.clone(),
+        "localidad_bautismo" => h.localidad_bautismo.clone(),
+        "provincia_bautismo" => h.provincia_bautismo.clone(),
+        "autorizacion_menores" => return Valor::Booleano(h.autorizacion_menores),
+        "nombre_representante_legal" => h.nombre_representante_legal.clone(),
+        "dni_representante_legal" => h.dni_representante_legal.clone(),
+        "hermano_aval_1" => h.hermano_aval_1.clone(),
+        "hermano_aval_2" => h.hermano_aval_2.clone(),
+        "activo" => return Valor::Booleano(h.activo),
+        "observaciones" => h.observaciones.clone(),
+        _ => None,
+    };
+
+    valor_texto(texto, tipo)
+}
+
+fn filas_familias(db: &DbConnection, columnas: &[(&str, &str, Tipo)]) -> HermanarResult<Vec<Vec<Valor>>> {
+    let familias = get_all_familias(db)?;
+    let hermanos = get_all_hermanos(db)?;
+    let por_id: HashMap<i32, &Hermano> = hermanos.iter().filter_map(|h| h.id.map(|id| (id, h))).collect();
+
+    Ok(familias
+        .iter()
+        .map(|f| {
+            let miembros: Vec<&Hermano> = hermanos.iter().filter(|h| h.familia_id.is_some() && h.familia_id == f.id).collect();
+            let titular = f.hermano_direccion_id.and_then(|id| por_id.get(&id).copied());
+            columnas
+                .iter()
+                .map(|&(clave, _, tipo)| valor_familia(f, &miembros, titular, clave, tipo))
+                .collect()
+        })
+        .collect())
+}
+
+fn valor_familia(f: &Familia, miembros: &[&Hermano], titular: Option<&Hermano>, clave: &str, tipo: Tipo) -> Valor {
+    let texto = match clave {
+        "nombre_familia" => Some(f.nombre_familia.clone()),
+        "hermanos" => return Valor::Entero(miembros.len() as i64),
+        "hermanos_activos" => return Valor::Entero(miembros.iter().filter(|h| h.activo).count() as i64),
+        "titular_direccion" => titular.map(nombre_completo),
+        "direccion" => titular.and_then(|h| h.direccion.clone()),
+        "localidad" => titular.and_then(|h| h.localidad.clone()),
+        "provincia" => titular.and_then(|h| h.provincia.clone()),
+        "codigo_postal" => titular.and_then(|h| h.codigo_postal.clone()),
+        _ => None,
+    };
+
+    valor_texto(texto, tipo)
+}
+
+fn filas_cuotas(
+    db: &DbConnection,
+    filtro: &FiltroExportacion,
+    columnas: &[(&str, &str, Tipo)],
+) -> HermanarResult<Vec<Vec<Valor>>> {
+    let hermanos: HashMap<i32, Hermano> = get_all_hermanos(db)?
+        .into_iter()
+        .filter_map(|h| h.id.map(|id| (id, h)))
+        .collect();
+
+    Ok(get_all_cuotas(db)?
+        .iter()
+        .filter(|c| filtro.hermano_id.is_none_or(|id| c.hermano_id == id))
+        .filter(|c| filtro.anio.is_none_or(|anio| c.anio == anio))
+        .filter(|c| filtro.periodicidad.is_none_or(|periodicidad| c.periodicidad == periodicidad))
+        .filter(|c| filtro.periodo.is_none_or(|periodo| c.periodo == periodo))
+        .filter(|c| filtro.pagado.is_none_or(|pagado| c.pagado == pagado))
+        .filter(|c| filtro.estado.is_none_or(|estado| c.estado == estado))
+        .filter(|c| {
+            let hermano = hermanos.get(&c.hermano_id);
+            (!filtro.solo_activos || hermano.is_some_and(|h| h.activo))
+                && (filtro.familia_id.is_none() || hermano.is_some_and(|h| h.familia_id == filtro.familia_id))
+        })
+        .map(|c| {
+            let hermano = hermanos.get(&c.hermano_id);
+            columnas
+                .iter()
+                .map(|&(clave, _, tipo)| valor_cuota(c, hermano, clave, tipo))
+                .collect()
+        })
+        .collect())
+}
+
+fn valor_cuota(c: &Cuota, hermano: Option<&Hermano>, clave: &str, tipo: Tipo) -> Valor {
+    let texto = match clave {
+        "numero_hermano" => hermano.map(|h| h.numero_hermano.clone()),
+        "hermano" => hermano.map(nombre_completo),
+        "anio" => return Valor::Entero(c.anio as i64),
+        "periodicidad" => Some(c.periodicidad.clave().to_string()),
+        "periodo" => return Valor::Entero(c.periodo as i64),
+        "importe" => return Valor::Importe(c.importe),
+        "pagado" => return Valor::Booleano(c.pagado),
+        "estado" => Some(c.estado.clave().to_string()),
+        "importe_pagado" => return Valor::Importe(c.importe_pagado),
+        "importe_pendiente" => return Valor::Importe(c.importe_pendiente),
+        "fecha_pago" => c.fecha_pago.clone(),
+        "metodo_pago" => c.metodo_pago.clone(),
+        "observaciones" => c.observaciones.clone(),
+        _ => None,
+    };
+
+    valor_texto(texto, tipo)
+}
+
+// Las fechas se guardan como texto AAAA-MM-DD; si alguna no lo es se exporta
+// tal cual en lugar de perderla.
+fn valor_texto(texto: Option<String>, tipo: Tipo) -> Valor {
+    let Some(texto) = texto.filter(|t| !t.trim().is_empty()) else {
+        return Valor::Vacio;
+    };
+
+    match tipo {
+        Tipo::Fecha => texto
+            .get(..10)
+            .and_then(|f| NaiveDate::parse_from_str(f, "%Y-%m-%d").ok())
+            .map(Valor::Fecha)
+            .unwrap_or(Valor::Texto(texto)),
+        _ => Valor::Texto(texto),
+    }
+}
+
+/// Escribe un CSV como lo abre Excel con la configuración regional española:
+/// UTF-8 con BOM, punto y coma como separador y saltos de línea CRLF.
+pub(crate) fn escribir_csv<F>(ruta: &Path, titulos: &[&str], filas: impl IntoIterator<Item = F>) -> HermanarResult<()>
+where
+    F: IntoIterator,
+    F::Item: AsRef<str>,
+{
+    let error = |e: &dyn std::fmt::Display| HermanarError::database(format!("No se pudo escribir el fichero: {}", e));
+
+    let mut contenido = "\u{feff}".as_bytes().to_vec();
+    {
+        let mut escritor = csv::WriterBuilder::new()
+            .delimiter(b';')
+            .terminator(csv::Terminator::CRLF)
+            .from_writer(&mut contenido);
+        escritor.write_record(titulos).map_err(|e| error(&e))?;
+        for fila in filas {
+            escritor
+                .write_record(fila.into_iter().map(|celda| celda_csv(celda.as_ref())))
+                .map_err(|e| error(&e))?;
+        }
+        escritor.flush().map_err(|e| error(&e))?;
+    }
+
+    std::fs::write(ruta, contenido).map_err(|e| error(&e))
+}
+
+// Excel toma como fórmula cualquier celda que empiece por =, +, - o @, así
+// que un nombre o una observación podrían ejecutar algo al abrir el fichero.
+// Se les antepone un apóstrofo; los números negativos se dejan como están.
+fn celda_csv(texto: &str) -> String {
+    let Some(resto) = texto.strip_prefix(['=', '+', '-', '@']) else {
+        return texto.to_string();
+    };
+    let numero = !resto.is_empty() && resto.chars().all(|c| c.is_ascii_digit() || c == ',' || c == '.');
+    if numero && !texto.starts_with(['=', '@']) {
+        texto.to_string()
+    } else {
+        format!("'{}", texto)
+    }
+}
+
+// Formato español: fecha dd/mm/aaaa y coma decimal, que es lo que Excel
+// reconoce al abrir el CSV con la configuración regional española.
+fn texto_csv(valor: &Valor) -> String {
+    match valor {
+        Valor::Vacio => String::new(),
+        Valor::Texto(texto) => texto.clone(),
+        Valor::Entero(n) => n.to_string(),
+        Valor::Fecha(fecha) => fecha.format("%d/%m/%Y").to_string(),
+        Valor::Importe(importe) => format!("{:.2}", importe).replace('.', ","),
+        Valor::Booleano(b) => if *b { "Sí" } else { "No" }.to_string(),
+    }
+}
+
+fn escribir_xlsx(ruta: &Path, nombre_hoja: &str, titulos: &[&str], filas: &[Vec<Valor>]) -> Result<(), XlsxError> {
+    let cabecera = Format::new().set_bold();
+    let fecha = Format::new().set_num_format("dd/mm/yyyy");
+    let importe = Format::new().set_num_format("#,##0.00 \"€\"");
+
+    let mut libro = Workbook::new();
+    let hoja = libro.add_worksheet();
+    hoja.set_name(nombre_hoja)?;
+
+    for (columna, titulo) in titulos.iter().enumerate() {
+        hoja.write_string_with_format(0, columna as u16, *titulo, &cabecera)?;
+    }
+
+    for (indice, fila) in filas.iter().enumerate() {
+        let fila_hoja = indice as u32 + 1;
+        for (columna, valor) in fila.iter().enumerate() {
+            let columna = columna as u16;
+            match valor {
+                Valor::Vacio => {}
+                Valor::Texto(texto) => {
+                    hoja.write_string(fila_hoja, columna, texto)?;
+                }
+                Valor::Entero(n) => {
+                    hoja.write_number(fila_hoja, columna, *n as f64)?;
+                }
+                Valor::Fecha(f) => {
+                    hoja.write_date_with_format(fila_hoja, columna, f, &fecha)?;
+                }
+                Valor::Importe(n) => {
+                    hoja.write_number_with_format(fila_hoja, columna, *n, &importe)?;
+                }
+                Valor::Booleano(b) => {
+                    hoja.write_string(fila_hoja, columna, if *b { "Sí" } else { "No" })?;
+                }
+            }
+        }
+    }
+
+    if !titulos.is_empty() {
+        hoja.set_freeze_panes(1, 0)?;
+        hoja.autofilter(0, 0, filas.len() as u32, titulos.len() as u16 - 1)?;
+    }
+    hoja.autofit();
+
+    libro.save(ruta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn celdas_con_formula() {
+        assert_eq!(celda_csv("=HYPERLINK(\"http://x\")"), "'=HYPERLINK(\"http://x\")");
+        assert_eq!(celda_csv("+34 600 000 000"), "'+34 600 000 000");
+        assert_eq!(celda_csv("-1+1"), "'-1+1");
+        assert_eq!(celda_csv("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(celda_csv("-"), "'-");
+        assert_eq!(celda_csv("=1"), "'=1");
+        assert_eq!(celda_csv("-Ávila"), "'-Ávila");
+    }
+
+    #[test]
+    fn celdas_sin_formula() {
+        assert_eq!(celda_csv("Pérez"), "Pérez");
+        assert_eq!(celda_csv("Álvarez"), "Álvarez");
+        assert_eq!(celda_csv("Ñúñez"), "Ñúñez");
+        assert_eq!(celda_csv("-12,50"), "-12,50");
+        assert_eq!(celda_csv("12,50"), "12,50");
+        assert_eq!(celda_csv("a=b"), "a=b");
+        assert_eq!(celda_csv(""), "");
+    }
+}
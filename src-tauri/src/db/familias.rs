@@ -1,5 +1,8 @@
-use rusqlite::{params, Result, Row, OptionalExtension};
+use rusqlite::{params, Connection, Result, Row, OptionalExtension};
 use crate::db::{Familia, DbConnection};
+use crate::db::auditoria::{self, ENTIDAD_FAMILIA, ENTIDAD_HERMANO};
+use crate::db::hermanos::{buscar_hermano, buscar_hermanos_familia};
+use crate::error::{HermanarError, HermanarResult};
 
 impl Familia {
     fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
@@ -13,9 +16,12 @@ impl Familia {
     }
 }
 
-pub fn get_all_familias(db: &DbConnection) -> Result<Vec<Familia>, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    let mut stmt = conn.prepare(
+pub fn get_all_familias(db: &DbConnection) -> HermanarResult<Vec<Familia>> {
+    listar_familias(&db.reader())
+}
+
+pub(crate) fn listar_familias(conn: &Connection) -> HermanarResult<Vec<Familia>> {
+    let mut stmt = conn.prepare_cached(
         "SELECT id, nombre_familia, hermano_direccion_id, created_at, updated_at
          FROM familias
          ORDER BY nombre_familia"
@@ -29,27 +35,41 @@ pub fn get_all_familias(db: &DbConnection) -> Result<Vec<Familia>, anyhow::Error
     Ok(familias)
 }
 
-pub fn get_familia_by_id(db: &DbConnection, id: i32) -> Result<Option<Familia>, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    let mut stmt = conn.prepare(
+pub fn get_familia_by_id(db: &DbConnection, id: i32) -> HermanarResult<Option<Familia>> {
+    buscar_familia(&db.reader(), id)
+}
+
+pub(crate) fn buscar_familia(conn: &Connection, id: i32) -> HermanarResult<Option<Familia>> {
+    let mut stmt = conn.prepare_cached(
         "SELECT id, nombre_familia, hermano_direccion_id, created_at, updated_at
          FROM familias
-         WHERE id = ?"
+         WHERE id = ?1"
     )?;
 
-    let mut familias = stmt.query_map([id], |row| {
+    Ok(stmt.query_row([id], Familia::from_row).optional()?)
+}
+
+/// Familias que usan la dirección del hermano indicado.
+pub(crate) fn buscar_familias_con_direccion(conn: &Connection, hermano_id: i32) -> HermanarResult<Vec<Familia>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, nombre_familia, hermano_direccion_id, created_at, updated_at
+         FROM familias
+         WHERE hermano_direccion_id = ?1"
+    )?;
+
+    let familias = stmt.query_map([hermano_id], |row| {
         Familia::from_row(row)
     })?
     .collect::<Result<Vec<_>, _>>()?;
 
-    Ok(familias.pop())
+    Ok(familias)
 }
 
-pub fn search_familias(db: &DbConnection, query: &str) -> Result<Vec<Familia>, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
+pub fn search_familias(db: &DbConnection, query: &str) -> HermanarResult<Vec<Familia>> {
+    let conn = db.reader();
     let search_pattern = format!("%{}%", query);
 
-    let mut stmt = conn.prepare(
+    let mut stmt = conn.prepare_cached(
         "SELECT id, nombre_familia, hermano_direccion_id, created_at, updated_at
          FROM familias
          WHERE nombre_familia LIKE ?1
@@ -64,55 +84,86 @@ pub fn search_familias(db: &DbConnection, query: &str) -> Result<Vec<Familia>, a
     Ok(familias)
 }
 
-pub fn create_familia(db: &DbConnection, familia: &Familia) -> Result<i32, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    conn.execute(
+pub fn create_familia(db: &DbConnection, familia: &Familia) -> HermanarResult<i32> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+
+    tx.prepare_cached(
         "INSERT INTO familias
          (nombre_familia, hermano_direccion_id)
-         VALUES (?1, ?2)",
-        params![
-            familia.nombre_familia,
-            familia.hermano_direccion_id,
-        ],
-    )?;
+         VALUES (?1, ?2)"
+    )?
+    .execute(params![
+        familia.nombre_familia,
+        familia.hermano_direccion_id,
+    ])?;
 
-    Ok(conn.last_insert_rowid() as i32)
+    let id = tx.last_insert_rowid() as i32;
+    let nueva = buscar_familia(&tx, id)?;
+    auditoria::registrar(&tx, &db.operador(), ENTIDAD_FAMILIA, id, None, nueva.as_ref())?;
+
+    tx.commit()?;
+
+    Ok(id)
 }
 
-pub fn update_familia(db: &DbConnection, id: i32, familia: &Familia) -> Result<(), anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    conn.execute(
+pub fn update_familia(db: &DbConnection, id: i32, familia: &Familia) -> HermanarResult<()> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+
+    let antes = buscar_familia(&tx, id)?.ok_or_else(|| HermanarError::not_found("Familia"))?;
+
+    tx.prepare_cached(
         "UPDATE familias
          SET nombre_familia = ?1, hermano_direccion_id = ?2, updated_at = CURRENT_TIMESTAMP
-         WHERE id = ?3",
-        params![
-            familia.nombre_familia,
-            familia.hermano_direccion_id,
-            id,
-        ],
-    )?;
+         WHERE id = ?3"
+    )?
+    .execute(params![
+        familia.nombre_familia,
+        familia.hermano_direccion_id,
+        id,
+    ])?;
+
+    let despues = buscar_familia(&tx, id)?;
+    auditoria::registrar(&tx, &db.operador(), ENTIDAD_FAMILIA, id, Some(&antes), despues.as_ref())?;
+
+    tx.commit()?;
 
     Ok(())
 }
 
-pub fn delete_familia(db: &DbConnection, id: i32) -> Result<(), anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
+pub fn delete_familia(db: &DbConnection, id: i32) -> HermanarResult<()> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+    let operador = db.operador();
+
+    let familia = buscar_familia(&tx, id)?.ok_or_else(|| HermanarError::not_found("Familia"))?;
 
     // Verificar si la familia tiene hermanos activos
-    let mut stmt = conn.prepare("SELECT COUNT(*) FROM hermanos WHERE familia_id = ?1 AND activo = 1")?;
-    let count: i32 = stmt.query_row([id], |row| row.get(0))?;
+    let hermanos = buscar_hermanos_familia(&tx, id)?;
+
+    if hermanos.iter().any(|h| h.activo) {
+        return Err(HermanarError::conflict("No se puede eliminar la familia porque tiene hermanos activos asociados"));
+    }
 
-    if count > 0 {
-        return Err(anyhow::anyhow!("No se puede eliminar la familia porque tiene hermanos activos asociados"));
+    tx.prepare_cached("DELETE FROM familias WHERE id = ?1")?.execute([id])?;
+
+    // Los hermanos inactivos que quedaban en la familia pierden la referencia
+    auditoria::registrar(&tx, &operador, ENTIDAD_FAMILIA, id, Some(&familia), None)?;
+    for hermano in &hermanos {
+        let hermano_id = hermano.id.unwrap_or_default();
+        let despues = buscar_hermano(&tx, hermano_id)?;
+        auditoria::registrar(&tx, &operador, ENTIDAD_HERMANO, hermano_id, Some(hermano), despues.as_ref())?;
     }
 
-    conn.execute("DELETE FROM familias WHERE id = ?1", [id])?;
+    tx.commit()?;
+
     Ok(())
 }
 
-pub fn get_familia_stats(db: &DbConnection, id: i32) -> Result<(i32, i32), anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    let mut stmt = conn.prepare(
+pub fn get_familia_stats(db: &DbConnection, id: i32) -> HermanarResult<(i32, i32)> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(
         "SELECT
             COUNT(h.id) as total_hermanos,
             COUNT(CASE WHEN h.activo = 1 THEN 1 END) as hermanos_activos
@@ -131,13 +182,13 @@ pub fn get_familia_stats(db: &DbConnection, id: i32) -> Result<(i32, i32), anyho
     Ok(result)
 }
 
-pub fn get_familia_with_hermanos(db: &DbConnection, id: i32) -> Result<Option<Familia>, anyhow::Error> {
+pub fn get_familia_with_hermanos(db: &DbConnection, id: i32) -> HermanarResult<Option<Familia>> {
     get_familia_by_id(db, id)
 }
 
-pub fn get_familia_with_address(db: &DbConnection, id: i32) -> Result<Option<serde_json::Value>, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    let mut stmt = conn.prepare(
+pub fn get_familia_with_address(db: &DbConnection, id: i32) -> HermanarResult<Option<serde_json::Value>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(
         "SELECT
             f.id,
             f.nombre_familia,
@@ -146,7 +197,11 @@ pub fn get_familia_with_address(db: &DbConnection, id: i32) -> Result<Option<ser
             h.telefono,
             h.email,
             h.nombre,
-            h.apellidos
+            h.primer_apellido,
+            h.segundo_apellido,
+            h.codigo_postal,
+            h.localidad,
+            h.provincia
          FROM familias f
          LEFT JOIN hermanos h ON f.hermano_direccion_id = h.id
          WHERE f.id = ?1"
@@ -154,14 +209,24 @@ pub fn get_familia_with_address(db: &DbConnection, id: i32) -> Result<Option<ser
 
     let result = stmt.query_row([id], |row| {
         let direccion_principal = if row.get::<_, Option<String>>(3)?.is_some() {
+            let nombre = [
+                row.get::<_, Option<String>>(6)?,
+                row.get::<_, Option<String>>(7)?,
+                row.get::<_, Option<String>>(8)?,
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+
             Some(serde_json::json!({
                 "direccion": row.get::<_, Option<String>>(3)?,
+                "codigo_postal": row.get::<_, Option<String>>(9)?,
+                "localidad": row.get::<_, Option<String>>(10)?,
+                "provincia": row.get::<_, Option<String>>(11)?,
                 "telefono": row.get::<_, Option<String>>(4)?,
                 "email": row.get::<_, Option<String>>(5)?,
-                "nombre_hermano": format!("{} {}",
-                    row.get::<_, String>(6).unwrap_or_default(),
-                    row.get::<_, String>(7).unwrap_or_default()
-                )
+                "nombre_hermano": nombre
             }))
         } else {
             None
@@ -0,0 +1,192 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+use crate::db::{configuracion, DbConnection};
+use crate::error::{HermanarError, HermanarResult};
+
+const CLAVE_NOMBRE: &str = "hermandad_nombre";
+const CLAVE_CIF: &str = "hermandad_cif";
+const CLAVE_DIRECCION: &str = "hermandad_direccion";
+const CLAVE_CODIGO_POSTAL: &str = "hermandad_codigo_postal";
+const CLAVE_LOCALIDAD: &str = "hermandad_localidad";
+const CLAVE_PROVINCIA: &str = "hermandad_provincia";
+const CLAVE_TELEFONO: &str = "hermandad_telefono";
+const CLAVE_EMAIL: &str = "hermandad_email";
+const CLAVE_LOGO: &str = "hermandad_logo";
+
+/// Datos de la hermandad que aparecen en los documentos impresos.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatosHermandad {
+    pub nombre: String,
+    pub cif: Option<String>,
+    pub direccion: Option<String>,
+    pub codigo_postal: Option<String>,
+    pub localidad: Option<String>,
+    pub provincia: Option<String>,
+    pub telefono: Option<String>,
+    pub email: Option<String>,
+    /// Ruta de una imagen PNG o JPEG con el escudo de la hermandad.
+    pub logo: Option<String>,
+    /// Huella de la copia del escudo guardada con un documento emitido (ver
+    /// `congelar_logo`). Solo la tienen los datos guardados con un documento.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logo_huella: Option<String>,
+}
+
+impl DatosHermandad {
+    /// Código postal, localidad y provincia en una sola línea.
+    pub(crate) fn poblacion(&self) -> Option<String> {
+        let localidad = [self.codigo_postal.as_deref(), self.localidad.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let linea = match self.provincia.as_deref() {
+            Some(provincia) if localidad.is_empty() => provincia.to_string(),
+            Some(provincia) if self.localidad.as_deref() != Some(provincia) => format!("{} ({})", localidad, provincia),
+            _ => localidad,
+        };
+
+        Some(linea).filter(|linea| !linea.is_empty())
+    }
+}
+
+pub fn get_datos_hermandad(db: &DbConnection) -> HermanarResult<DatosHermandad> {
+    cargar_datos(&db.reader())
+}
+
+pub(crate) fn cargar_datos(conn: &Connection) -> HermanarResult<DatosHermandad> {
+    let leer = |clave: &str| -> HermanarResult<Option<String>> {
+        Ok(configuracion::leer(conn, clave)?.filter(|valor| !valor.is_empty()))
+    };
+
+    Ok(DatosHermandad {
+        nombre: leer(CLAVE_NOMBRE)?.unwrap_or_default(),
+        cif: leer(CLAVE_CIF)?,
+        direccion: leer(CLAVE_DIRECCION)?,
+        codigo_postal: leer(CLAVE_CODIGO_POSTAL)?,
+        localidad: leer(CLAVE_LOCALIDAD)?,
+        provincia: leer(CLAVE_PROVINCIA)?,
+        telefono: leer(CLAVE_TELEFONO)?,
+        email: leer(CLAVE_EMAIL)?,
+        logo: leer(CLAVE_LOGO)?,
+        logo_huella: None,
+    })
+}
+
+/// Como `cargar_datos`, pero exige que el nombre esté configurado, que es lo
+/// mínimo para emitir un documento.
+pub(crate) fn cargar_datos_documento(conn: &Connection) -> HermanarResult<DatosHermandad> {
+    let datos = cargar_datos(conn)?;
+    if datos.nombre.is_empty() {
+        return Err(HermanarError::validation(
+            "hermandad",
+            "Configure primero el nombre de la hermandad en los datos de la hermandad",
+        ));
+    }
+
+    Ok(datos)
+}
+
+/// Guarda en la base de datos una copia del escudo de `datos` y anota su
+/// huella, para que el documento con el que se guardan esos datos se vuelva a
+/// imprimir con la misma imagen aunque después cambie o desaparezca el
+/// fichero. Las copias se comparten entre documentos con el mismo escudo.
+pub(crate) fn congelar_logo(conn: &Connection, datos: &mut DatosHermandad) -> HermanarResult<()> {
+    let Some(logo) = datos.logo.as_deref().filter(|_| datos.logo_huella.is_none()) else {
+        return Ok(());
+    };
+    let contenido = fs::read(logo)
+        .map_err(|e| HermanarError::validation("logo", format!("No se pudo leer la imagen {}: {}", logo, e)))?;
+    let huella: String = Sha256::digest(&contenido).iter().map(|b| format!("{:02x}", b)).collect();
+    conn.prepare_cached("INSERT OR IGNORE INTO imagenes_documento (huella, contenido) VALUES (?1, ?2)")?
+        .execute(params![huella, contenido])?;
+    datos.logo_huella = Some(huella);
+
+    Ok(())
+}
+
+/// Contenido del escudo con el que se imprime un documento: la copia
+/// guardada si los datos la tienen o, si no, el fichero configurado.
+pub(crate) fn contenido_logo(conn: &Connection, datos: &DatosHermandad) -> HermanarResult<Option<Vec<u8>>> {
+    if let Some(huella) = &datos.logo_huella {
+        let contenido = conn
+            .prepare_cached("SELECT contenido FROM imagenes_documento WHERE huella = ?1")?
+            .query_row([huella], |row| row.get(0))
+            .optional()?;
+        return contenido
+            .map(Some)
+            .ok_or_else(|| HermanarError::not_found("Escudo"));
+    }
+
+    datos.logo.as_deref()
+        .map(|logo| {
+            fs::read(logo)
+                .map_err(|e| HermanarError::validation("logo", format!("No se pudo leer la imagen {}: {}", logo, e)))
+        })
+        .transpose()
+}
+
+pub fn set_datos_hermandad(db: &DbConnection, datos: &DatosHermandad) -> HermanarResult<()> {
+    let nombre = datos.nombre.trim();
+    if nombre.is_empty() {
+        return Err(HermanarError::validation("nombre", "Indique el nombre de la hermandad"));
+    }
+    let logo = datos.logo.as_deref().map(str::trim).unwrap_or_default();
+    if !logo.is_empty() && !Path::new(logo).is_file() {
+        return Err(HermanarError::validation("logo", format!("No se encuentra la imagen {}", logo)));
+    }
+    let texto = |valor: &Option<String>| valor.as_deref().map(str::trim).unwrap_or_default().to_string();
+
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+    configuracion::guardar(&tx, CLAVE_NOMBRE, nombre)?;
+    configuracion::guardar(&tx, CLAVE_CIF, &texto(&datos.cif).to_uppercase())?;
+    configuracion::guardar(&tx, CLAVE_DIRECCION, &texto(&datos.direccion))?;
+    configuracion::guardar(&tx, CLAVE_CODIGO_POSTAL, &texto(&datos.codigo_postal))?;
+    configuracion::guardar(&tx, CLAVE_LOCALIDAD, &texto(&datos.localidad))?;
+    configuracion::guardar(&tx, CLAVE_PROVINCIA, &texto(&datos.provincia))?;
+    configuracion::guardar(&tx, CLAVE_TELEFONO, &texto(&datos.telefono))?;
+    configuracion::guardar(&tx, CLAVE_EMAIL, &texto(&datos.email))?;
+    configuracion::guardar(&tx, CLAVE_LOGO, logo)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::base_de_datos_en_memoria;
+
+    #[test]
+    fn escudo_congelado() {
+        let conn = base_de_datos_en_memoria();
+        let fichero = std::env::temp_dir().join(format!("hermanar-escudo-{}.png", std::process::id()));
+        fs::write(&fichero, b"escudo antiguo").unwrap();
+
+        let mut datos = DatosHermandad {
+            nombre: "Hermandad".to_string(),
+            logo: Some(fichero.display().to_string()),
+            ..Default::default()
+        };
+        congelar_logo(&conn, &mut datos).unwrap();
+        let mut otros = datos.clone();
+        otros.logo_huella = None;
+        congelar_logo(&conn, &mut otros).unwrap();
+        assert_eq!(otros.logo_huella, datos.logo_huella);
+
+        // Ni cambiar ni borrar el fichero afecta a los datos ya congelados
+        fs::write(&fichero, b"escudo nuevo").unwrap();
+        assert_eq!(contenido_logo(&conn, &datos).unwrap().as_deref(), Some(&b"escudo antiguo"[..]));
+        fs::remove_file(&fichero).unwrap();
+        assert_eq!(contenido_logo(&conn, &datos).unwrap().as_deref(), Some(&b"escudo antiguo"[..]));
+
+        let copias: i64 = conn.query_row("SELECT COUNT(*) FROM imagenes_documento", [], |row| row.get(0)).unwrap();
+        assert_eq!(copias, 1);
+        assert!(contenido_logo(&conn, &DatosHermandad { logo_huella: None, ..datos }).is_err());
+    }
+}
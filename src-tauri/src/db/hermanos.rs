@@ -1,5 +1,19 @@
-use rusqlite::{params, Result, Row};
-use crate::db::{Hermano, DbConnection};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row, TransactionBehavior};
+use crate::db::{documentos, membresia, numeracion, papelera, Hermano, DbConnection, TipoDocumento};
+use crate::db::auditoria::{self, ENTIDAD_CUOTA, ENTIDAD_FAMILIA, ENTIDAD_HERMANO};
+use crate::db::cuotas::buscar_cuotas_hermano;
+use crate::db::familias::{buscar_familia, buscar_familias_con_direccion};
+use crate::error::{HermanarError, HermanarResult};
+
+pub(crate) const SELECT_HERMANOS: &str =
+    "SELECT id, numero_hermano, nombre, primer_apellido, segundo_apellido, dni,
+            fecha_nacimiento, localidad_nacimiento, provincia_nacimiento, fecha_alta,
+            familia_id, telefono, email, direccion, localidad, provincia, codigo_postal,
+            parroquia_bautismo, localidad_bautismo, provincia_bautismo,
+            autorizacion_menores, nombre_representante_legal, dni_representante_legal,
+            hermano_aval_1, hermano_aval_2, activo, observaciones, created_at, updated_at,
+            tipo_documento, tipo_documento_representante
+     FROM hermanos";
 
 impl Hermano {
     pub fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
@@ -33,22 +47,24 @@ impl Hermano {
             observaciones: row.get(26)?,
             created_at: row.get(27)?,
             updated_at: row.get(28)?,
+            tipo_documento: row.get::<_, Option<String>>(29)?.as_deref().and_then(TipoDocumento::desde_clave),
+            tipo_documento_representante: row.get::<_, Option<String>>(30)?
+                .as_deref()
+                .and_then(TipoDocumento::desde_clave),
         })
     }
 }
 
-pub fn get_all_hermanos(db: &DbConnection) -> Result<Vec<Hermano>, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    let mut stmt = conn.prepare(
-        "SELECT id, numero_hermano, nombre, primer_apellido, segundo_apellido, dni, 
-                fecha_nacimiento, localidad_nacimiento, provincia_nacimiento, fecha_alta, 
-                familia_id, telefono, email, direccion, localidad, provincia, codigo_postal,
-                parroquia_bautismo, localidad_bautismo, provincia_bautismo,
-                autorizacion_menores, nombre_representante_legal, dni_representante_legal,
-                hermano_aval_1, hermano_aval_2, activo, observaciones, created_at, updated_at
-         FROM hermanos
-         ORDER BY numero_hermano"
-    )?;
+pub fn get_all_hermanos(db: &DbConnection) -> HermanarResult<Vec<Hermano>> {
+    listar_hermanos(&db.reader())
+}
+
+pub(crate) fn listar_hermanos(conn: &Connection) -> HermanarResult<Vec<Hermano>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "{}
+         ORDER BY numero_hermano",
+        SELECT_HERMANOS
+    ))?;
 
     let hermanos = stmt.query_map([], |row| {
         Hermano::from_row(row)
@@ -58,19 +74,14 @@ pub fn get_all_hermanos(db: &DbConnection) -> Result<Vec<Hermano>, anyhow::Error
     Ok(hermanos)
 }
 
-pub fn get_hermanos_activos(db: &DbConnection) -> Result<Vec<Hermano>, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    let mut stmt = conn.prepare(
-        "SELECT id, numero_hermano, nombre, primer_apellido, segundo_apellido, dni, 
-                fecha_nacimiento, localidad_nacimiento, provincia_nacimiento, fecha_alta, 
-                familia_id, telefono, email, direccion, localidad, provincia, codigo_postal,
-                parroquia_bautismo, localidad_bautismo, provincia_bautismo,
-                autorizacion_menores, nombre_representante_legal, dni_representante_legal,
-                hermano_aval_1, hermano_aval_2, activo, observaciones, created_at, updated_at
-         FROM hermanos
+pub fn get_hermanos_activos(db: &DbConnection) -> HermanarResult<Vec<Hermano>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(&format!(
+        "{}
          WHERE activo = 1
-         ORDER BY numero_hermano"
-    )?;
+         ORDER BY numero_hermano",
+        SELECT_HERMANOS
+    ))?;
 
     let hermanos = stmt.query_map([], |row| {
         Hermano::from_row(row)
@@ -80,41 +91,30 @@ pub fn get_hermanos_activos(db: &DbConnection) -> Result<Vec<Hermano>, anyhow::E
     Ok(hermanos)
 }
 
-pub fn get_hermano_by_id(db: &DbConnection, id: i32) -> Result<Option<Hermano>, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    let mut stmt = conn.prepare(
-        "SELECT id, numero_hermano, nombre, primer_apellido, segundo_apellido, dni, 
-                fecha_nacimiento, localidad_nacimiento, provincia_nacimiento, fecha_alta, 
-                familia_id, telefono, email, direccion, localidad, provincia, codigo_postal,
-                parroquia_bautismo, localidad_bautismo, provincia_bautismo,
-                autorizacion_menores, nombre_representante_legal, dni_representante_legal,
-                hermano_aval_1, hermano_aval_2, activo, observaciones, created_at, updated_at
-         FROM hermanos
-         WHERE id = ?1"
-    )?;
+pub fn get_hermano_by_id(db: &DbConnection, id: i32) -> HermanarResult<Option<Hermano>> {
+    buscar_hermano(&db.reader(), id)
+}
 
-    match stmt.query_row([id], |row| Hermano::from_row(row)) {
-        Ok(hermano) => Ok(Some(hermano)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.into()),
-    }
+pub(crate) fn buscar_hermano(conn: &Connection, id: i32) -> HermanarResult<Option<Hermano>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "{}
+         WHERE id = ?1",
+        SELECT_HERMANOS
+    ))?;
+
+    Ok(stmt.query_row([id], Hermano::from_row).optional()?)
 }
 
-pub fn search_hermanos(db: &DbConnection, query: &str) -> Result<Vec<Hermano>, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
+pub fn search_hermanos(db: &DbConnection, query: &str) -> HermanarResult<Vec<Hermano>> {
+    let conn = db.reader();
     let search_pattern = format!("%{}%", query);
 
-    let mut stmt = conn.prepare(
-        "SELECT id, numero_hermano, nombre, primer_apellido, segundo_apellido, dni, 
-                fecha_nacimiento, localidad_nacimiento, provincia_nacimiento, fecha_alta, 
-                familia_id, telefono, email, direccion, localidad, provincia, codigo_postal,
-                parroquia_bautismo, localidad_bautismo, provincia_bautismo,
-                autorizacion_menores, nombre_representante_legal, dni_representante_legal,
-                hermano_aval_1, hermano_aval_2, activo, observaciones, created_at, updated_at
-         FROM hermanos
+    let mut stmt = conn.prepare_cached(&format!(
+        "{}
          WHERE (nombre LIKE ?1 OR primer_apellido LIKE ?1 OR segundo_apellido LIKE ?1 OR numero_hermano LIKE ?1 OR dni LIKE ?1)
-         ORDER BY numero_hermano"
-    )?;
+         ORDER BY numero_hermano",
+        SELECT_HERMANOS
+    ))?;
 
     let hermanos = stmt.query_map([&search_pattern], |row| {
         Hermano::from_row(row)
@@ -124,19 +124,17 @@ pub fn search_hermanos(db: &DbConnection, query: &str) -> Result<Vec<Hermano>, a
     Ok(hermanos)
 }
 
-pub fn get_hermanos_by_familia(db: &DbConnection, familia_id: i32) -> Result<Vec<Hermano>, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-    let mut stmt = conn.prepare(
-        "SELECT id, numero_hermano, nombre, primer_apellido, segundo_apellido, dni, 
-                fecha_nacimiento, localidad_nacimiento, provincia_nacimiento, fecha_alta, 
-                familia_id, telefono, email, direccion, localidad, provincia, codigo_postal,
-                parroquia_bautismo, localidad_bautismo, provincia_bautismo,
-                autorizacion_menores, nombre_representante_legal, dni_representante_legal,
-                hermano_aval_1, hermano_aval_2, activo, observaciones, created_at, updated_at
-         FROM hermanos
+pub fn get_hermanos_by_familia(db: &DbConnection, familia_id: i32) -> HermanarResult<Vec<Hermano>> {
+    buscar_hermanos_familia(&db.reader(), familia_id)
+}
+
+pub(crate) fn buscar_hermanos_familia(conn: &Connection, familia_id: i32) -> HermanarResult<Vec<Hermano>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "{}
          WHERE familia_id = ?1
-         ORDER BY numero_hermano"
-    )?;
+         ORDER BY numero_hermano",
+        SELECT_HERMANOS
+    ))?;
 
     let hermanos = stmt.query_map([familia_id], |row| {
         Hermano::from_row(row)
@@ -146,27 +144,38 @@ pub fn get_hermanos_by_familia(db: &DbConnection, familia_id: i32) -> Result<Vec
     Ok(hermanos)
 }
 
-pub fn create_hermano(db: &DbConnection, hermano: &Hermano) -> Result<i32, anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
-
-    // Generar número de hermano único si está vacío (5 dígitos numéricos)
-    let numero_hermano = if hermano.numero_hermano.trim().is_empty() {
-        let count: i32 = conn.query_row("SELECT COUNT(*) FROM hermanos", [], |row| {
-            Ok(row.get(0)?)
-        })?;
-        format!("{:05}", count + 1)
-    } else {
-        // Validar que sea numérico de 5 dígitos
-        let cleaned = hermano.numero_hermano.trim();
-        if cleaned.len() == 5 && cleaned.chars().all(|c| c.is_ascii_digit()) {
-            cleaned.to_string()
-        } else {
-            return Err(anyhow::anyhow!("El número de hermano debe tener exactamente 5 dígitos numéricos"));
-        }
-    };
+pub fn create_hermano(db: &DbConnection, hermano: &Hermano) -> HermanarResult<i32> {
+    let mut conn = db.writer();
+    // Inmediata para reservar la escritura antes de calcular el número, también
+    // frente a otra instancia que comparta el fichero
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let id = insertar_hermano(&tx, hermano)?;
+    let nuevo = buscar_hermano(&tx, id)?;
+    auditoria::registrar(&tx, &db.operador(), ENTIDAD_HERMANO, id, None, nuevo.as_ref())?;
+
+    tx.commit()?;
+
+    Ok(id)
+}
+
+/// Inserta un hermano en la conexión o transacción indicada, sin auditar.
+pub(crate) fn insertar_hermano(conn: &Connection, hermano: &Hermano) -> HermanarResult<i32> {
+    let numero_hermano = numeracion::numero_para_alta(conn, &hermano.numero_hermano)?;
+
+    let (dni, tipo_documento) =
+        documentos::preparar_documento("dni", hermano.dni.as_deref(), hermano.tipo_documento, None)?;
+    if let Some(dni) = &dni {
+        documentos::comprobar_documento_libre(conn, dni, None)?;
+    }
+    let (dni_representante_legal, tipo_documento_representante) = documentos::preparar_documento(
+        "dni_representante_legal",
+        hermano.dni_representante_legal.as_deref(),
+        hermano.tipo_documento_representante,
+        None,
+    )?;
 
     let segundo_apellido = hermano.segundo_apellido.as_ref().filter(|s| !s.trim().is_empty());
-    let dni = hermano.dni.as_ref().filter(|s| !s.trim().is_empty());
     let telefono = hermano.telefono.as_ref().filter(|s| !s.trim().is_empty());
     let email = hermano.email.as_ref().filter(|s| !s.trim().is_empty());
     let direccion = hermano.direccion.as_ref().filter(|s| !s.trim().is_empty());
@@ -180,55 +189,137 @@ pub fn create_hermano(db: &DbConnection, hermano: &Hermano) -> Result<i32, anyho
     let localidad_bautismo = hermano.localidad_bautismo.as_ref().filter(|s| !s.trim().is_empty());
     let provincia_bautismo = hermano.provincia_bautismo.as_ref().filter(|s| !s.trim().is_empty());
     let nombre_representante_legal = hermano.nombre_representante_legal.as_ref().filter(|s| !s.trim().is_empty());
-    let dni_representante_legal = hermano.dni_representante_legal.as_ref().filter(|s| !s.trim().is_empty());
     let observaciones = hermano.observaciones.as_ref().filter(|s| !s.trim().is_empty());
 
-    let _id = conn.execute(
+    conn.prepare_cached(
         "INSERT INTO hermanos
          (numero_hermano, nombre, primer_apellido, segundo_apellido, dni, fecha_nacimiento,
           localidad_nacimiento, provincia_nacimiento, fecha_alta, familia_id, telefono, email,
           direccion, localidad, provincia, codigo_postal, parroquia_bautismo, localidad_bautismo,
           provincia_bautismo, autorizacion_menores, nombre_representante_legal, dni_representante_legal,
-          hermano_aval_1, hermano_aval_2, activo, observaciones)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26)",
-        params![
-            numero_hermano,
-            hermano.nombre,
-            hermano.primer_apellido,
-            segundo_apellido,
-            dni,
-            fecha_nacimiento,
-            localidad_nacimiento,
-            provincia_nacimiento,
-            hermano.fecha_alta,
-            hermano.familia_id,
-            telefono,
-            email,
-            direccion,
-            localidad,
-            provincia,
-            codigo_postal,
-            parroquia_bautismo,
-            localidad_bautismo,
-            provincia_bautismo,
-            hermano.autorizacion_menores,
-            nombre_representante_legal,
-            dni_representante_legal,
-            hermano.hermano_aval_1,
-            hermano.hermano_aval_2,
-            hermano.activo,
-            observaciones,
-        ],
-    )?;
+          hermano_aval_1, hermano_aval_2, activo, observaciones, tipo_documento, tipo_documento_representante)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28)"
+    )?
+    .execute(params![
+        numero_hermano,
+        hermano.nombre,
+        hermano.primer_apellido,
+        segundo_apellido,
+        dni,
+        fecha_nacimiento,
+        localidad_nacimiento,
+        provincia_nacimiento,
+        hermano.fecha_alta,
+        hermano.familia_id,
+        telefono,
+        email,
+        direccion,
+        localidad,
+        provincia,
+        codigo_postal,
+        parroquia_bautismo,
+        localidad_bautismo,
+        provincia_bautismo,
+        hermano.autorizacion_menores,
+        nombre_representante_legal,
+        dni_representante_legal,
+        hermano.hermano_aval_1,
+        hermano.hermano_aval_2,
+        hermano.activo,
+        observaciones,
+        tipo_documento.map(|t| t.clave()),
+        tipo_documento_representante.map(|t| t.clave()),
+    ])?;
+
+    let id = conn.last_insert_rowid() as i32;
+    if hermano.activo {
+        membresia::abrir_periodo(conn, id, &hermano.fecha_alta, None)?;
+    }
 
-    Ok(conn.last_insert_rowid() as i32)
+    Ok(id)
 }
 
-pub fn update_hermano(db: &DbConnection, id: i32, hermano: &Hermano) -> Result<(), anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
+/// Vuelve a insertar un hermano tal y como estaba, conservando su `id` y sus
+/// fechas. Se usa al restaurarlo desde la papelera.
+pub(crate) fn reinsertar_hermano(conn: &Connection, hermano: &Hermano) -> HermanarResult<()> {
+    conn.prepare_cached(
+        "INSERT INTO hermanos
+         (id, numero_hermano, nombre, primer_apellido, segundo_apellido, dni, fecha_nacimiento,
+          localidad_nacimiento, provincia_nacimiento, fecha_alta, familia_id, telefono, email,
+          direccion, localidad, provincia, codigo_postal, parroquia_bautismo, localidad_bautismo,
+          provincia_bautismo, autorizacion_menores, nombre_representante_legal, dni_representante_legal,
+          hermano_aval_1, hermano_aval_2, activo, observaciones, created_at, updated_at,
+          tipo_documento, tipo_documento_representante)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20,
+                 ?21, ?22, ?23, ?24, ?25, ?26, ?27, COALESCE(?28, CURRENT_TIMESTAMP), COALESCE(?29, CURRENT_TIMESTAMP),
+                 ?30, ?31)"
+    )?
+    .execute(params![
+        hermano.id,
+        hermano.numero_hermano,
+        hermano.nombre,
+        hermano.primer_apellido,
+        hermano.segundo_apellido,
+        hermano.dni,
+        hermano.fecha_nacimiento,
+        hermano.localidad_nacimiento,
+        hermano.provincia_nacimiento,
+        hermano.fecha_alta,
+        hermano.familia_id,
+        hermano.telefono,
+        hermano.email,
+        hermano.direccion,
+        hermano.localidad,
+        hermano.provincia,
+        hermano.codigo_postal,
+        hermano.parroquia_bautismo,
+        hermano.localidad_bautismo,
+        hermano.provincia_bautismo,
+        hermano.autorizacion_menores,
+        hermano.nombre_representante_legal,
+        hermano.dni_representante_legal,
+        hermano.hermano_aval_1,
+        hermano.hermano_aval_2,
+        hermano.activo,
+        hermano.observaciones,
+        hermano.created_at,
+        hermano.updated_at,
+        hermano.tipo_documento.map(|t| t.clave()),
+        hermano.tipo_documento_representante.map(|t| t.clave()),
+    ])?;
+
+    Ok(())
+}
+
+pub fn update_hermano(db: &DbConnection, id: i32, hermano: &Hermano) -> HermanarResult<()> {
+    modificar_hermano(db, id, |conn| actualizar_hermano(conn, id, hermano))
+}
+
+pub(crate) fn actualizar_hermano(conn: &Connection, id: i32, hermano: &Hermano) -> HermanarResult<()> {
+    let actual = buscar_hermano(conn, id)?.ok_or_else(|| HermanarError::not_found("Hermano"))?;
+    let numero_hermano = numeracion::numero_para_actualizar(conn, id, &hermano.numero_hermano)?;
+
+    let (dni, tipo_documento) = documentos::preparar_documento(
+        "dni",
+        hermano.dni.as_deref(),
+        hermano.tipo_documento,
+        actual.dni.as_deref(),
+    )?;
+    if let Some(dni) = dni.as_ref().filter(|d| actual.dni.as_ref() != Some(*d)) {
+        documentos::comprobar_documento_libre(conn, dni, Some(id))?;
+    }
+    let (dni_representante_legal, tipo_documento_representante) = documentos::preparar_documento(
+        "dni_representante_legal",
+        hermano.dni_representante_legal.as_deref(),
+        hermano.tipo_documento_representante,
+        actual.dni_representante_legal.as_deref(),
+    )?;
+
+    if hermano.fecha_alta != actual.fecha_alta {
+        membresia::ajustar_primera_alta(conn, id, &hermano.fecha_alta)?;
+    }
 
     let segundo_apellido = hermano.segundo_apellido.as_ref().filter(|s| !s.trim().is_empty());
-    let dni = hermano.dni.as_ref().filter(|s| !s.trim().is_empty());
     let telefono = hermano.telefono.as_ref().filter(|s| !s.trim().is_empty());
     let email = hermano.email.as_ref().filter(|s| !s.trim().is_empty());
     let direccion = hermano.direccion.as_ref().filter(|s| !s.trim().is_empty());
@@ -242,10 +333,9 @@ pub fn update_hermano(db: &DbConnection, id: i32, hermano: &Hermano) -> Result<(
     let localidad_bautismo = hermano.localidad_bautismo.as_ref().filter(|s| !s.trim().is_empty());
     let provincia_bautismo = hermano.provincia_bautismo.as_ref().filter(|s| !s.trim().is_empty());
     let nombre_representante_legal = hermano.nombre_representante_legal.as_ref().filter(|s| !s.trim().is_empty());
-    let dni_representante_legal = hermano.dni_representante_legal.as_ref().filter(|s| !s.trim().is_empty());
     let observaciones = hermano.observaciones.as_ref().filter(|s| !s.trim().is_empty());
 
-    conn.execute(
+    let actualizados = conn.prepare_cached(
         "UPDATE hermanos
          SET numero_hermano = ?1, nombre = ?2, primer_apellido = ?3, segundo_apellido = ?4,
              dni = ?5, fecha_nacimiento = ?6, localidad_nacimiento = ?7, provincia_nacimiento = ?8,
@@ -254,68 +344,156 @@ pub fn update_hermano(db: &DbConnection, id: i32, hermano: &Hermano) -> Result<(
              localidad_bautismo = ?18, provincia_bautismo = ?19, autorizacion_menores = ?20,
              nombre_representante_legal = ?21, dni_representante_legal = ?22,
              hermano_aval_1 = ?23, hermano_aval_2 = ?24, activo = ?25, observaciones = ?26,
+             tipo_documento = ?27, tipo_documento_representante = ?28,
              updated_at = CURRENT_TIMESTAMP
-         WHERE id = ?27",
-        params![
-            hermano.numero_hermano,
-            hermano.nombre,
-            hermano.primer_apellido,
-            segundo_apellido,
-            dni,
-            fecha_nacimiento,
-            localidad_nacimiento,
-            provincia_nacimiento,
-            hermano.fecha_alta,
-            hermano.familia_id,
-            telefono,
-            email,
-            direccion,
-            localidad,
-            provincia,
-            codigo_postal,
-            parroquia_bautismo,
-            localidad_bautismo,
-            provincia_bautismo,
-            hermano.autorizacion_menores,
-            nombre_representante_legal,
-            dni_representante_legal,
-            hermano.hermano_aval_1,
-            hermano.hermano_aval_2,
-            hermano.activo,
-            observaciones,
-            id,
-        ],
-    )?;
+         WHERE id = ?29"
+    )?
+    .execute(params![
+        numero_hermano,
+        hermano.nombre,
+        hermano.primer_apellido,
+        segundo_apellido,
+        dni,
+        fecha_nacimiento,
+        localidad_nacimiento,
+        provincia_nacimiento,
+        hermano.fecha_alta,
+        hermano.familia_id,
+        telefono,
+        email,
+        direccion,
+        localidad,
+        provincia,
+        codigo_postal,
+        parroquia_bautismo,
+        localidad_bautismo,
+        provincia_bautismo,
+        hermano.autorizacion_menores,
+        nombre_representante_legal,
+        dni_representante_legal,
+        hermano.hermano_aval_1,
+        hermano.hermano_aval_2,
+        hermano.activo,
+        observaciones,
+        tipo_documento.map(|t| t.clave()),
+        tipo_documento_representante.map(|t| t.clave()),
+        id,
+    ])?;
+
+    if actualizados == 0 {
+        return Err(HermanarError::not_found("Hermano"));
+    }
 
-    Ok(())
+    membresia::sincronizar_periodo(conn, id, hermano.activo)
 }
 
-pub fn delete_hermano(db: &DbConnection, id: i32) -> Result<(), anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
+/// Elimina un hermano dejando una copia en la papelera, desde donde puede
+/// restaurarse con sus cuotas.
+pub fn delete_hermano(db: &DbConnection, id: i32) -> HermanarResult<()> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+    let operador = db.operador();
 
-    conn.execute("DELETE FROM hermanos WHERE id = ?1", [id])?;
+    let hermano = buscar_hermano(&tx, id)?.ok_or_else(|| HermanarError::not_found("Hermano"))?;
 
-    Ok(())
-}
+    // Sus cuotas se borran en cascada y las familias que usaban su dirección
+    // se quedan sin ella: se anotan también para que su historial esté completo
+    let cuotas = buscar_cuotas_hermano(&tx, id)?;
+    let familias = buscar_familias_con_direccion(&tx, id)?;
 
-pub fn set_hermano_inactive(db: &DbConnection, id: i32) -> Result<(), anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
+    let familias_direccion: Vec<i32> = familias.iter().filter_map(|f| f.id).collect();
+    papelera::guardar_hermano(&tx, &operador, &hermano, &cuotas, &familias_direccion)?;
 
-    conn.execute(
-        "UPDATE hermanos SET activo = 0, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
-        [id]
-    )?;
+    tx.prepare_cached("DELETE FROM hermanos WHERE id = ?1")?.execute([id])?;
+
+    auditoria::registrar(&tx, &operador, ENTIDAD_HERMANO, id, Some(&hermano), None)?;
+    for cuota in &cuotas {
+        auditoria::registrar(&tx, &operador, ENTIDAD_CUOTA, cuota.id.unwrap_or_default(), Some(cuota), None)?;
+    }
+    for familia in &familias {
+        let familia_id = familia.id.unwrap_or_default();
+        let despues = buscar_familia(&tx, familia_id)?;
+        auditoria::registrar(&tx, &operador, ENTIDAD_FAMILIA, familia_id, Some(familia), despues.as_ref())?;
+    }
+
+    tx.commit()?;
 
     Ok(())
 }
 
-pub fn update_hermano_familia(db: &DbConnection, hermano_id: i32, familia_id: Option<i32>) -> Result<(), anyhow::Error> {
-    let conn = db.lock().map_err(|_| anyhow::anyhow!("Error de base de datos"))?;
+pub fn set_hermano_inactive(db: &DbConnection, id: i32) -> HermanarResult<()> {
+    modificar_hermano(db, id, |conn| {
+        conn.prepare_cached("UPDATE hermanos SET activo = 0, updated_at = CURRENT_TIMESTAMP WHERE id = ?1")?
+            .execute([id])?;
+        membresia::sincronizar_periodo(conn, id, false)
+    })
+}
 
-    conn.execute(
-        "UPDATE hermanos SET familia_id = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
-        params![familia_id, hermano_id]
-    )?;
+pub fn update_hermano_familia(db: &DbConnection, hermano_id: i32, familia_id: Option<i32>) -> HermanarResult<()> {
+    modificar_hermano(db, hermano_id, |conn| {
+        conn.prepare_cached("UPDATE hermanos SET familia_id = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2")?
+            .execute(params![familia_id, hermano_id])?;
+        Ok(())
+    })
+}
+
+/// Aplica `cambio` a un hermano existente dentro de una transacción y anota en
+/// la auditoría su estado antes y después.
+pub(crate) fn modificar_hermano(
+    db: &DbConnection,
+    id: i32,
+    cambio: impl FnOnce(&Connection) -> HermanarResult<()>,
+) -> HermanarResult<()> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+
+    let antes = buscar_hermano(&tx, id)?.ok_or_else(|| HermanarError::not_found("Hermano"))?;
+    cambio(&tx)?;
+    let despues = buscar_hermano(&tx, id)?;
+    auditoria::registrar(&tx, &db.operador(), ENTIDAD_HERMANO, id, Some(&antes), despues.as_ref())?;
+
+    tx.commit()?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::base_de_datos_en_memoria;
+
+    #[test]
+    fn fecha_alta_y_primer_periodo() {
+        let conn = base_de_datos_en_memoria();
+        let hermano: Hermano = serde_json::from_value(serde_json::json!({
+            "numero_hermano": "1",
+            "nombre": "José",
+            "primer_apellido": "Núñez",
+            "fecha_alta": "2020-05-01",
+            "autorizacion_menores": false,
+            "activo": true
+        }))
+        .unwrap();
+        let id = insertar_hermano(&conn, &hermano).unwrap();
+        let primera_alta = |conn: &Connection| membresia::buscar_periodos(conn, id).unwrap()[0].fecha_alta.clone();
+        assert_eq!(primera_alta(&conn), "2020-05-01");
+
+        let mut editado = buscar_hermano(&conn, id).unwrap().unwrap();
+        editado.fecha_alta = "2019-03-15".to_string();
+        actualizar_hermano(&conn, id, &editado).unwrap();
+        assert_eq!(primera_alta(&conn), "2019-03-15");
+
+        // Con el primer periodo cerrado, el alta no puede pasar de su baja
+        conn.execute("UPDATE periodos_membresia SET fecha_baja = '2021-01-01' WHERE hermano_id = ?1", [id]).unwrap();
+        editado.activo = false;
+        editado.fecha_alta = "2021-06-01".to_string();
+        assert!(matches!(
+            actualizar_hermano(&conn, id, &editado),
+            Err(HermanarError::Validation { field, .. }) if field == "fecha_alta"
+        ));
+        assert_eq!(buscar_hermano(&conn, id).unwrap().unwrap().fecha_alta, "2019-03-15");
+        editado.fecha_alta = "2020-12-31".to_string();
+        actualizar_hermano(&conn, id, &editado).unwrap();
+        assert_eq!(primera_alta(&conn), "2020-12-31");
+    }
+}
@@ -0,0 +1,743 @@
+use calamine::{open_workbook_auto, Data, Reader};
+use chrono::{Datelike, NaiveDate};
+use rusqlite::{Connection, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use crate::db::{DbConnection, Hermano};
+use crate::db::auditoria::{self, ENTIDAD_FAMILIA, ENTIDAD_HERMANO};
+use crate::db::documentos::normalizar_documento;
+use crate::db::familias::buscar_familia;
+use crate::db::hermanos::{buscar_hermano, insertar_hermano};
+use crate::db::numeracion::PoliticaNumeracion;
+use crate::db::sepa::texto_sepa;
+use crate::error::{HermanarError, HermanarResult};
+
+const FILAS_EJEMPLO: usize = 5;
+
+/// Campos del hermano que se pueden importar, con los nombres de columna
+/// habituales en las hojas de cálculo que se reconocen automáticamente.
+/// `familia` es el nombre de la familia, que se busca o se crea.
+const CAMPOS: &[(&str, &[&str])] = &[
+    ("numero_hermano", &["numero", "n", "num", "no", "n_hermano", "numero_de_hermano"]),
+    ("nombre", &[]),
+    ("primer_apellido", &["apellido_1", "apellido1", "1_apellido", "primer_apellido"]),
+    ("segundo_apellido", &["apellido_2", "apellido2", "2_apellido"]),
+    ("dni", &["nif", "nie", "documento", "dni_nif"]),
+    ("fecha_nacimiento", &["nacimiento", "f_nacimiento", "fecha_de_nacimiento"]),
+    ("localidad_nacimiento", &["lugar_nacimiento", "localidad_de_nacimiento"]),
+    ("provincia_nacimiento", &["provincia_de_nacimiento"]),
+    ("fecha_alta", &["alta", "f_alta", "fecha_de_alta", "fecha_ingreso", "ingreso"]),
+    ("familia", &["nombre_familia"]),
+    ("telefono", &["tlf", "tfno", "movil", "telefono_movil"]),
+    ("email", &["correo", "correo_electronico", "e_mail", "mail"]),
+    ("direccion", &["domicilio", "calle"]),
+    ("localidad", &["poblacion", "municipio", "ciudad"]),
+    ("provincia", &[]),
+    ("codigo_postal", &["cp", "c_p", "cod_postal"]),
+    ("parroquia_bautismo", &["parroquia", "parroquia_de_bautismo"]),
+    ("localidad_bautismo", &["localidad_de_bautismo"]),
+    ("provincia_bautismo", &["provincia_de_bautismo"]),
+    ("autorizacion_menores", &["autorizacion"]),
+    ("nombre_representante_legal", &["representante", "representante_legal", "tutor"]),
+    ("dni_representante_legal", &["dni_representante", "dni_tutor"]),
+    ("hermano_aval_1", &["aval_1", "aval1"]),
+    ("hermano_aval_2", &["aval_2", "aval2"]),
+    ("activo", &["estado", "alta_activa"]),
+    ("observaciones", &["notas", "comentarios"]),
+];
+
+const CAMPOS_OBLIGATORIOS: &[&str] = &["nombre", "primer_apellido", "fecha_alta"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolicitudImportacion {
+    pub fichero: String,
+    /// Hoja a importar de un libro de Excel; sin ella se usa la primera.
+    pub hoja: Option<String>,
+    /// Columna del fichero de la que se toma cada campo del hermano.
+    pub mapeo: BTreeMap<String, String>,
+    /// Crea las familias que no existan en lugar de rechazar la fila.
+    #[serde(default)]
+    pub crear_familias: bool,
+}
+
+/// Contenido inicial de un fichero para preparar el mapeo de columnas.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VistaPreviaImportacion {
+    pub hojas: Vec<String>,
+    pub cabeceras: Vec<String>,
+    pub ejemplo: Vec<Vec<String>>,
+    pub total_filas: usize,
+    pub campos: Vec<String>,
+    /// Columna propuesta para cada campo según el nombre de su cabecera.
+    pub mapeo_sugerido: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TipoIncidencia {
+    Error,
+    /// El DNI o el número ya pertenecen a un hermano o a otra fila del fichero.
+    Duplicado,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidenciaImportacion {
+    /// Fila de la hoja, contando la cabecera como fila 1.
+    pub fila: usize,
+    pub campo: Option<String>,
+    pub tipo: TipoIncidencia,
+    pub mensaje: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InformeImportacion {
+    pub simulacion: bool,
+    pub filas: usize,
+    /// Filas importadas o, en una simulación, que se importarían.
+    pub validas: usize,
+    pub con_incidencias: usize,
+    pub familias_creadas: Vec<String>,
+    pub incidencias: Vec<IncidenciaImportacion>,
+    pub hermano_ids: Vec<i32>,
+}
+
+struct Tabla {
+    hojas: Vec<String>,
+    cabeceras: Vec<String>,
+    filas: Vec<Fila>,
+}
+
+// Fila con datos del fichero. `numero` es el que tiene en el fichero, contando
+// desde 1, para que las incidencias señalen la fila correcta aunque se hayan
+// saltado filas en blanco.
+struct Fila {
+    numero: usize,
+    celdas: Vec<String>,
+}
+
+/// Lee las cabeceras y las primeras filas de un fichero CSV o de Excel y
+/// propone a qué campo corresponde cada columna.
+pub fn previsualizar_importacion(fichero: &str, hoja: Option<&str>) -> HermanarResult<VistaPreviaImportacion> {
+    let tabla = leer_tabla(Path::new(fichero.trim()), hoja)?;
+
+    let mut mapeo_sugerido = BTreeMap::new();
+    for cabecera in &tabla.cabeceras {
+        let clave = clave_cabecera(cabecera);
+        let campo = CAMPOS
+            .iter()
+            .find(|(campo, sinonimos)| *campo == clave || sinonimos.contains(&clave.as_str()));
+        if let Some((campo, _)) = campo {
+            mapeo_sugerido.entry(campo.to_string()).or_insert_with(|| cabecera.clone());
+        }
+    }
+
+    Ok(VistaPreviaImportacion {
+        hojas: tabla.hojas,
+        cabeceras: tabla.cabeceras,
+        ejemplo: tabla.filas.iter().take(FILAS_EJEMPLO).map(|fila| fila.celdas.clone()).collect(),
+        total_filas: tabla.filas.len(),
+        campos: CAMPOS.iter().map(|(campo, _)| campo.to_string()).collect(),
+        mapeo_sugerido,
+    })
+}
+
+/// Comprueba todas las filas sin guardar nada. El informe es el mismo que
+/// devolvería `importar_hermanos` con el mismo fichero.
+pub fn simular_importacion(db: &DbConnection, solicitud: &SolicitudImportacion) -> HermanarResult<InformeImportacion> {
+    procesar(db, solicitud, true)
+}
+
+/// Da de alta todas las filas válidas en una sola transacción. Las filas con
+/// incidencias se omiten y se devuelven en el informe.
+pub fn importar_hermanos(db: &DbConnection, solicitud: &SolicitudImportacion) -> HermanarResult<InformeImportacion> {
+    procesar(db, solicitud, false)
+}
+
+// Cada fila se inserta dentro de un punto de guardado: si falla se deshace
+// solo esa fila, y las anteriores cuentan para detectar duplicados dentro del
+// propio fichero. En una simulación se deshace todo al terminar.
+fn procesar(db: &DbConnection, solicitud: &SolicitudImportacion, simulacion: bool) -> HermanarResult<InformeImportacion> {
+    let tabla = leer_tabla(Path::new(solicitud.fichero.trim()), solicitud.hoja.as_deref())?;
+    let columnas = resolver_mapeo(&tabla.cabeceras, &solicitud.mapeo)?;
+
+    let mut conn = db.writer();
+    let mut tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let operador = db.operador();
+
+    let mut familias = familias_por_nombre(&tx)?;
+    let mut documentos_fichero: HashMap<String, usize> = HashMap::new();
+    let mut numeros_fichero: HashMap<String, usize> = HashMap::new();
+    let politica = PoliticaNumeracion::cargar(&tx)?;
+
+    let mut informe = InformeImportacion {
+        simulacion,
+        filas: tabla.filas.len(),
+        validas: 0,
+        con_incidencias: 0,
+        familias_creadas: Vec::new(),
+        incidencias: Vec::new(),
+        hermano_ids: Vec::new(),
+    };
+
+    for fila in &tabla.filas {
+        let numero_fila = fila.numero;
+        let valor = |campo: &str| -> Option<&str> {
+            columnas
+                .get(campo)
+                .and_then(|&columna| fila.celdas.get(columna))
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+        };
+
+        let mut hermano = match leer_hermano(&valor) {
+            Ok(hermano) => hermano,
+            Err(errores) => {
+                informe.con_incidencias += 1;
+                informe.incidencias.extend(errores.into_iter().map(|(campo, mensaje)| IncidenciaImportacion {
+                    fila: numero_fila,
+                    campo: Some(campo.to_string()),
+                    tipo: TipoIncidencia::Error,
+                    mensaje,
+                }));
+                continue;
+            }
+        };
+
+        if let Some(incidencia) = buscar_duplicado(&tx, &hermano, &politica, &documentos_fichero, &numeros_fichero)? {
+            informe.con_incidencias += 1;
+            informe.incidencias.push(IncidenciaImportacion { fila: numero_fila, ..incidencia });
+            continue;
+        }
+
+        let punto = tx.savepoint()?;
+        let resultado = (|| -> HermanarResult<(i32, Option<(String, i32)>)> {
+            let mut familia_creada = None;
+            if let Some(nombre) = valor("familia") {
+                let clave = texto_sepa(nombre, usize::MAX).to_lowercase();
+                hermano.familia_id = match familias.get(&clave) {
+                    Some(&id) => Some(id),
+                    None if solicitud.crear_familias => {
+                        let id = crear_familia(&punto, &operador, nombre)?;
+                        familia_creada = Some((nombre.to_string(), id));
+                        Some(id)
+                    }
+                    None => {
+                        return Err(HermanarError::validation(
+                            "familia",
+                            format!("La familia '{}' no existe", nombre),
+                        ));
+                    }
+                };
+            }
+
+            let id = insertar_hermano(&punto, &hermano)?;
+            let nuevo = buscar_hermano(&punto, id)?;
+            auditoria::registrar(&punto, &operador, ENTIDAD_HERMANO, id, None, nuevo.as_ref())?;
+
+            Ok((id, familia_creada))
+        })();
+
+        match resultado {
+            Ok((id, familia_creada)) => {
+                punto.commit()?;
+                if let Some((nombre, id_familia)) = familia_creada {
+                    familias.insert(texto_sepa(&nombre, usize::MAX).to_lowercase(), id_familia);
+                    informe.familias_creadas.push(nombre);
+                }
+                if let Some(hermano) = buscar_hermano(&tx, id)? {
+                    if let Some(dni) = hermano.dni {
+                        documentos_fichero.insert(dni, numero_fila);
+                    }
+                    numeros_fichero.insert(hermano.numero_hermano, numero_fila);
+                }
+                informe.validas += 1;
+                informe.hermano_ids.push(id);
+            }
+            Err(error) => {
+                drop(punto);
+                informe.con_incidencias += 1;
+                informe.incidencias.push(IncidenciaImportacion {
+                    fila: numero_fila,
+                    campo: error.field().map(str::to_string),
+                    tipo: TipoIncidencia::Error,
+                    mensaje: error.to_string(),
+                });
+            }
+        }
+    }
+
+    if simulacion {
+        informe.hermano_ids.clear();
+        tx.rollback()?;
+    } else {
+        tx.commit()?;
+    }
+
+    Ok(informe)
+}
+
+// Índice de columna de cada campo mapeado, comprobando que los campos existen
+// y que los obligatorios tienen columna.
+fn resolver_mapeo(cabeceras: &[String], mapeo: &BTreeMap<String, String>) -> HermanarResult<HashMap<String, usize>> {
+    let mut columnas = HashMap::new();
+    for (campo, cabecera) in mapeo {
+        if cabecera.trim().is_empty() {
+            continue;
+        }
+        if !CAMPOS.iter().any(|(c, _)| c == campo) {
+            return Err(HermanarError::validation("mapeo", format!("Campo desconocido: {}", campo)));
+        }
+        let columna = cabeceras
+            .iter()
+            .position(|c| c == cabecera)
+            .ok_or_else(|| HermanarError::validation("mapeo", format!("El fichero no tiene la columna '{}'", cabecera)))?;
+        columnas.insert(campo.clone(), columna);
+    }
+
+    for obligatorio in CAMPOS_OBLIGATORIOS {
+        if !columnas.contains_key(*obligatorio) {
+            return Err(HermanarError::validation(
+                "mapeo",
+                format!("Indique la columna del campo obligatorio '{}'", obligatorio),
+            ));
+        }
+    }
+
+    Ok(columnas)
+}
+
+// Construye el hermano de una fila. Devuelve todos los errores de formato de
+// la fila a la vez.
+fn leer_hermano<'a>(valor: &impl Fn(&str) -> Option<&'a str>) -> Result<Hermano, Vec<(&'static str, String)>> {
+    let mut errores = Vec::new();
+    let texto = |campo: &str| valor(campo).map(str::to_string);
+
+    let mut fecha = |campo: &'static str| -> Option<String> {
+        let valor = valor(campo)?;
+        match parsear_fecha(valor) {
+            Some(fecha) => Some(fecha),
+            None => {
+                errores.push((campo, format!("Fecha no reconocida: '{}'", valor)));
+                None
+            }
+        }
+    };
+    let fecha_nacimiento = fecha("fecha_nacimiento");
+    let fecha_alta = fecha("fecha_alta");
+
+    let mut booleano = |campo: &'static str, por_defecto: bool| -> bool {
+        let Some(valor) = valor(campo) else {
+            return por_defecto;
+        };
+        match parsear_booleano(valor) {
+            Some(b) => b,
+            None => {
+                errores.push((campo, format!("Valor no reconocido: '{}' (use sí o no)", valor)));
+                por_defecto
+            }
+        }
+    };
+    let autorizacion_menores = booleano("autorizacion_menores", false);
+    let activo = booleano("activo", true);
+
+    for campo in CAMPOS_OBLIGATORIOS {
+        if valor(campo).is_none() {
+            errores.push((campo, "Campo obligatorio vacío".to_string()));
+        }
+    }
+
+    if !errores.is_empty() {
+        return Err(errores);
+    }
+
+    Ok(Hermano {
+        id: None,
+        numero_hermano: valor("numero_hermano").unwrap_or_default().to_string(),
+        nombre: valor("nombre").unwrap_or_default().to_string(),
+        primer_apellido: valor("primer_apellido").unwrap_or_default().to_string(),
+        segundo_apellido: texto("segundo_apellido"),
+        dni: texto("dni"),
+        fecha_nacimiento,
+        localidad_nacimiento: texto("localidad_nacimiento"),
+        provincia_nacimiento: texto("provincia_nacimiento"),
+        fecha_alta: fecha_alta.unwrap_or_default(),
+        familia_id: None,
+        telefono: texto("telefono"),
+        email: texto("email"),
+        direccion: texto("direccion"),
+        localidad: texto("localidad"),
+        provincia: texto("provincia"),
+        codigo_postal: texto("codigo_postal"),
+        parroquia_bautismo: texto("parroquia_bautismo"),
+        localidad_bautismo: texto("localidad_bautismo"),
+        provincia_bautismo: texto("provincia_bautismo"),
+        autorizacion_menores,
+        nombre_representante_legal: texto("nombre_representante_legal"),
+        dni_representante_legal: texto("dni_representante_legal"),
+        hermano_aval_1: texto("hermano_aval_1"),
+        hermano_aval_2: texto("hermano_aval_2"),
+        activo,
+        observaciones: texto("observaciones"),
+        created_at: None,
+        updated_at: None,
+        tipo_documento: None,
+        tipo_documento_representante: None,
+    })
+}
+
+// Detecta si el DNI o el número de la fila ya pertenecen a un hermano de la
+// base de datos o a una fila anterior del fichero.
+fn buscar_duplicado(
+    conn: &Connection,
+    hermano: &Hermano,
+    politica: &PoliticaNumeracion,
+    documentos_fichero: &HashMap<String, usize>,
+    numeros_fichero: &HashMap<String, usize>,
+) -> HermanarResult<Option<IncidenciaImportacion>> {
+    let duplicado = |campo: &str, mensaje: String| IncidenciaImportacion {
+        fila: 0,
+        campo: Some(campo.to_string()),
+        tipo: TipoIncidencia::Duplicado,
+        mensaje,
+    };
+
+    if let Some(Ok((dni, _))) = hermano.dni.as_deref().map(|d| normalizar_documento(d, None)) {
+        if let Some(fila) = documentos_fichero.get(&dni) {
+            return Ok(Some(duplicado("dni", format!("El documento {} ya aparece en la fila {}", dni, fila))));
+        }
+        if let Some(numero) = numero_con_documento(conn, &dni)? {
+            return Ok(Some(duplicado("dni", format!("El documento {} ya pertenece al hermano {}", dni, numero))));
+        }
+    }
+
+    // Un número vacío recibe el siguiente libre al insertar; uno mal escrito
+    // lo rechaza la propia alta.
+    if hermano.numero_hermano.trim().is_empty() {
+        return Ok(None);
+    }
+    if let Ok(numero) = politica.normalizar(&hermano.numero_hermano) {
+        if let Some(fila) = numeros_fichero.get(&numero) {
+            return Ok(Some(duplicado("numero_hermano", format!("El número {} ya aparece en la fila {}", numero, fila))));
+        }
+        let ocupado: bool = conn
+            .prepare_cached("SELECT EXISTS(SELECT 1 FROM hermanos WHERE numero_hermano = ?1)")?
+            .query_row([&numero], |row| row.get(0))?;
+        if ocupado {
+            return Ok(Some(duplicado("numero_hermano", format!("El número {} ya está asignado", numero))));
+        }
+    }
+
+    Ok(None)
+}
+
+fn numero_con_documento(conn: &Connection, dni: &str) -> HermanarResult<Option<String>> {
+    let mut stmt = conn.prepare_cached("SELECT numero_hermano FROM hermanos WHERE dni = ?1 LIMIT 1")?;
+    let mut filas = stmt.query([dni])?;
+
+    Ok(match filas.next()? {
+        Some(fila) => Some(fila.get(0)?),
+        None => None,
+    })
+}
+
+fn familias_por_nombre(conn: &Connection) -> HermanarResult<HashMap<String, i32>> {
+    let mut stmt = conn.prepare_cached("SELECT id, nombre_familia FROM familias ORDER BY id")?;
+    let familias = stmt
+        .query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut por_nombre = HashMap::new();
+    for (id, nombre) in familias {
+        por_nombre.entry(texto_sepa(&nombre, usize::MAX).to_lowercase()).or_insert(id);
+    }
+
+    Ok(por_nombre)
+}
+
+fn crear_familia(conn: &Connection, operador: &str, nombre: &str) -> HermanarResult<i32> {
+    conn.prepare_cached("INSERT INTO familias (nombre_familia) VALUES (?1)")?
+        .execute([nombre])?;
+
+    let id = conn.last_insert_rowid() as i32;
+    let nueva = buscar_familia(conn, id)?;
+    auditoria::registrar(conn, operador, ENTIDAD_FAMILIA, id, None, nueva.as_ref())?;
+
+    Ok(id)
+}
+
+// Nombre de cabecera comparable con los campos: minúsculas, sin acentos y con
+// guiones bajos en lugar de espacios y signos.
+fn clave_cabecera(cabecera: &str) -> String {
+    texto_sepa(cabecera, usize::MAX)
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+const MESES: &[&str] = &[
+    "enero", "febrero", "marzo", "abril", "mayo", "junio",
+    "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre",
+];
+
+/// Interpreta una fecha en los formatos habituales en España: 05/03/1980,
+/// 5-3-80, 05.03.1980, 1980-03-05, "5 de marzo de 1980" o un número de
+/// serie de Excel. Con el año en dos cifras se elige el siglo que deja la
+/// fecha en el pasado.
+pub(crate) fn parsear_fecha(valor: &str) -> Option<String> {
+    let valor = valor.trim();
+    // Las fechas ISO con hora (2024-01-31T10:00:00), como las que da Excel en
+    // las celdas de fecha y hora, se quedan con la parte de la fecha
+    let valor = valor
+        .split_once('T')
+        .filter(|(fecha, hora)| fecha.len() == 10 && hora.contains(':'))
+        .map_or(valor, |(fecha, _)| fecha);
+
+    if valor.len() == 5 && valor.chars().all(|c| c.is_ascii_digit()) {
+        let serie: i64 = valor.parse().ok()?;
+        let fecha = NaiveDate::from_ymd_opt(1899, 12, 30)? + chrono::Duration::days(serie);
+        return Some(fecha.format("%Y-%m-%d").to_string());
+    }
+
+    let minusculas = texto_sepa(valor, usize::MAX).to_lowercase();
+    let partes: Vec<&str> = minusculas
+        .split(|c: char| c == '/' || c == '-' || c == '.' || c.is_whitespace())
+        .filter(|p| !p.is_empty() && *p != "de" && *p != "del")
+        .collect();
+    // Una fecha con hora (2024-01-31 00:00:00) se queda con la parte de la fecha
+    let partes: Vec<&str> = partes.into_iter().take_while(|p| !p.contains(':')).collect();
+    if partes.len() != 3 {
+        return None;
+    }
+
+    let mes = |parte: &str| -> Option<u32> {
+        parte.parse().ok().or_else(|| {
+            MESES
+                .iter()
+                .position(|m| parte.len() >= 3 && m.starts_with(parte))
+                .map(|i| i as u32 + 1)
+        })
+    };
+
+    let (dia, mes, anio) = if partes[0].len() == 4 {
+        (partes[2].parse().ok()?, mes(partes[1])?, partes[0])
+    } else {
+        (partes[0].parse().ok()?, mes(partes[1])?, partes[2])
+    };
+
+    let anio: i32 = match anio.len() {
+        4 => anio.parse().ok()?,
+        2 => {
+            let corto: i32 = anio.parse().ok()?;
+            let actual = chrono::Local::now().year();
+            if 2000 + corto > actual { 1900 + corto } else { 2000 + corto }
+        }
+        _ => return None,
+    };
+
+    NaiveDate::from_ymd_opt(anio, mes, dia).map(|f| f.format("%Y-%m-%d").to_string())
+}
+
+fn parsear_booleano(valor: &str) -> Option<bool> {
+    match texto_sepa(valor, usize::MAX).to_lowercase().as_str() {
+        "si" | "s" | "x" | "1" | "true" | "verdadero" | "activo" | "alta" => Some(true),
+        "no" | "n" | "0" | "false" | "falso" | "inactivo" | "baja" => Some(false),
+        _ => None,
+    }
+}
+
+fn leer_tabla(ruta: &Path, hoja: Option<&str>) -> HermanarResult<Tabla> {
+    let extension = ruta
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let mut filas = match extension.as_str() {
+        "csv" | "txt" => leer_csv(ruta)?,
+        "xlsx" | "xlsm" | "xls" | "ods" => return leer_libro(ruta, hoja),
+        _ => {
+            return Err(HermanarError::validation(
+                "fichero",
+                "Formato no admitido; use un fichero CSV, XLSX, XLS u ODS",
+            ));
+        }
+    };
+
+    if filas.is_empty() {
+        return Err(HermanarError::validation("fichero", "El fichero está vacío"));
+    }
+    let cabeceras = filas.remove(0).celdas;
+
+    Ok(Tabla { hojas: Vec::new(), cabeceras, filas })
+}
+
+// Admite UTF-8 (con o sin BOM) y, si no lo es, ISO-8859-1, que es lo que
+// guarda Excel en español. El separador se deduce de la primera línea.
+fn leer_csv(ruta: &Path) -> HermanarResult<Vec<Fila>> {
+    let bytes = std::fs::read(ruta)
+        .map_err(|e| HermanarError::validation("fichero", format!("No se pudo leer el fichero: {}", e)))?;
+    let texto = match std::str::from_utf8(&bytes) {
+        Ok(texto) => texto.trim_start_matches('\u{feff}').to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    };
+
+    let primera = texto.lines().next().unwrap_or_default();
+    let separador = [b';', b',', b'\t']
+        .into_iter()
+        .max_by_key(|s| primera.matches(*s as char).count())
+        .unwrap_or(b';');
+
+    let mut lector = csv::ReaderBuilder::new()
+        .delimiter(separador)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(texto.as_bytes());
+
+    // El lector se salta las líneas vacías y da como posición del registro la
+    // de la primera de ellas, así que la línea se cuenta desde el primer byte
+    // que no es un salto.
+    let bytes = texto.as_bytes();
+    let mut linea = 1;
+    let mut leido = 0;
+    let mut filas = Vec::new();
+    for registro in lector.records() {
+        let registro = registro
+            .map_err(|e| HermanarError::validation("fichero", format!("CSV no válido: {}", e)))?;
+        let mut inicio = registro.position().map_or(leido, |p| p.byte() as usize);
+        while matches!(bytes.get(inicio), Some(b'\r' | b'\n')) {
+            inicio += 1;
+        }
+        linea += bytes[leido..inicio].iter().filter(|&&b| b == b'\n').count();
+        leido = inicio;
+
+        let celdas: Vec<String> = registro.iter().map(|c| c.trim().to_string()).collect();
+        if celdas.iter().any(|c| !c.is_empty()) {
+            filas.push(Fila { numero: linea, celdas });
+        }
+    }
+
+    Ok(filas)
+}
+
+fn leer_libro(ruta: &Path, hoja: Option<&str>) -> HermanarResult<Tabla> {
+    let mut libro = open_workbook_auto(ruta)
+        .map_err(|e| HermanarError::validation("fichero", format!("No se pudo abrir el libro: {}", e)))?;
+    let hojas = libro.sheet_names().to_vec();
+    let nombre = match hoja {
+        Some(hoja) => hoja.to_string(),
+        None => hojas
+            .first()
+            .cloned()
+            .ok_or_else(|| HermanarError::validation("fichero", "El libro no tiene hojas"))?,
+    };
+    let rango = libro
+        .worksheet_range(&nombre)
+        .map_err(|e| HermanarError::validation("hoja", format!("No se pudo leer la hoja '{}': {}", nombre, e)))?;
+
+    // El rango empieza en la primera celda con datos, no necesariamente en A1.
+    let primera = rango.start().map(|(fila, _)| fila as usize).unwrap_or(0);
+    let mut filas = rango
+        .rows()
+        .enumerate()
+        .map(|(indice, fila)| Fila {
+            numero: primera + indice + 1,
+            celdas: fila.iter().map(texto_celda).collect(),
+        })
+        .filter(|fila| fila.celdas.iter().any(|c| !c.is_empty()));
+    let cabeceras = filas
+        .next()
+        .ok_or_else(|| HermanarError::validation("fichero", "La hoja está vacía"))?
+        .celdas;
+
+    Ok(Tabla { hojas, cabeceras, filas: filas.collect() })
+}
+
+// Las fechas se pasan a AAAA-MM-DD y los números enteros se escriben sin
+// decimales, para que números de hermano, teléfonos y códigos postales
+// lleguen igual que se ven en la hoja.
+fn texto_celda(celda: &Data) -> String {
+    match celda {
+        Data::Empty | Data::Error(_) => String::new(),
+        Data::String(texto) => texto.trim().to_string(),
+        Data::Int(n) => n.to_string(),
+        Data::Float(n) if n.fract() == 0.0 => format!("{}", *n as i64),
+        Data::Float(n) => n.to_string(),
+        Data::Bool(b) => if *b { "sí" } else { "no" }.to_string(),
+        Data::DateTime(fecha) => fecha
+            .as_datetime()
+            .map(|f| f.format("%Y-%m-%d").to_string())
+            .unwrap_or_default(),
+        Data::DateTimeIso(texto) | Data::DurationIso(texto) => texto.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{ExcelDateTime, ExcelDateTimeType};
+
+    fn fichero_prueba(nombre: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("hermanar-{}-{}", std::process::id(), nombre))
+    }
+
+    #[test]
+    fn fechas() {
+        let esperada = Some("1980-03-05".to_string());
+        assert_eq!(parsear_fecha("05/03/1980"), esperada);
+        assert_eq!(parsear_fecha(" 5-3-80 "), esperada);
+        assert_eq!(parsear_fecha("05.03.1980"), esperada);
+        assert_eq!(parsear_fecha("1980-03-05"), esperada);
+        assert_eq!(parsear_fecha("1980-03-05 00:00:00"), esperada);
+        assert_eq!(parsear_fecha("1980-03-05T10:30:00"), esperada);
+        assert_eq!(parsear_fecha("1980-03-05T10:30:00.000Z"), esperada);
+        assert_eq!(parsear_fecha("5 de marzo de 1980"), esperada);
+        assert_eq!(parsear_fecha("5 DE OCTUBRE DE 1980"), Some("1980-10-05".to_string()));
+        assert_eq!(parsear_fecha("29285"), esperada);
+
+        assert_eq!(parsear_fecha("31/02/1980"), None);
+        assert_eq!(parsear_fecha("marzo de 1980"), None);
+        assert_eq!(parsear_fecha("1980-03-05T"), None);
+    }
+
+    #[test]
+    fn fechas_de_excel() {
+        let fecha = |serie: f64| Data::DateTime(ExcelDateTime::new(serie, ExcelDateTimeType::DateTime, false));
+        assert_eq!(texto_celda(&fecha(29285.0)), "1980-03-05");
+        assert_eq!(texto_celda(&fecha(29285.75)), "1980-03-05");
+
+        let iso = texto_celda(&Data::DateTimeIso("1980-03-05T18:00:00".to_string()));
+        assert_eq!(parsear_fecha(&iso), Some("1980-03-05".to_string()));
+    }
+
+    #[test]
+    fn filas_de_csv() {
+        let ruta = fichero_prueba("filas.csv");
+        std::fs::write(&ruta, "nombre;apellido\r\n\r\nJosé;Núñez\r\n;\r\n\r\nAna;\"Ruiz\r\nPérez\"\r\nLuis;Gil\r\n").unwrap();
+
+        let filas = leer_csv(&ruta).unwrap();
+        std::fs::remove_file(&ruta).unwrap();
+        let numeros: Vec<usize> = filas.iter().map(|fila| fila.numero).collect();
+        assert_eq!(numeros, [1, 3, 6, 8]);
+        assert_eq!(filas[1].celdas, ["José", "Núñez"]);
+    }
+
+    #[test]
+    fn filas_de_libro() {
+        let ruta = fichero_prueba("filas.xlsx");
+        let mut libro = rust_xlsxwriter::Workbook::new();
+        let hoja = libro.add_worksheet();
+        // La tabla empieza en la fila 3 y tiene una fila en blanco
+        hoja.write_string(2, 0, "nombre").unwrap();
+        hoja.write_string(3, 0, "José").unwrap();
+        hoja.write_string(5, 0, "Ana").unwrap();
+        libro.save(&ruta).unwrap();
+
+        let tabla = leer_libro(&ruta, None).unwrap();
+        std::fs::remove_file(&ruta).unwrap();
+        assert_eq!(tabla.cabeceras, ["nombre"]);
+        let numeros: Vec<usize> = tabla.filas.iter().map(|fila| fila.numero).collect();
+        assert_eq!(numeros, [4, 6]);
+    }
+}
@@ -0,0 +1,89 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use crate::db::DbConnection;
+use crate::error::HermanarResult;
+
+/// Resultado de una pasada de reparación de referencias rotas.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InformeIntegridad {
+    /// Cuotas eliminadas porque su hermano ya no existía.
+    pub cuotas_huerfanas: Vec<i32>,
+    /// Hermanos cuya familia ya no existía; se han quedado sin familia.
+    pub hermanos_sin_familia: Vec<i32>,
+    /// Familias cuyo hermano de dirección ya no existía; se ha vaciado el campo.
+    pub familias_sin_direccion: Vec<i32>,
+}
+
+impl InformeIntegridad {
+    pub fn is_empty(&self) -> bool {
+        self.cuotas_huerfanas.is_empty()
+            && self.hermanos_sin_familia.is_empty()
+            && self.familias_sin_direccion.is_empty()
+    }
+}
+
+/// Localiza y corrige las referencias rotas entre hermanos, familias y cuotas.
+///
+/// Aplica la misma regla que las claves foráneas: las cuotas de un hermano
+/// inexistente se eliminan (ON DELETE CASCADE) y las referencias a familias o
+/// hermanos de dirección inexistentes se ponen a NULL (ON DELETE SET NULL).
+pub fn reparar_huerfanos(conn: &Connection) -> HermanarResult<InformeIntegridad> {
+    let cuotas_huerfanas = ids(
+        conn,
+        "SELECT c.id FROM cuotas c
+         WHERE NOT EXISTS (SELECT 1 FROM hermanos h WHERE h.id = c.hermano_id)
+         ORDER BY c.id",
+    )?;
+    let hermanos_sin_familia = ids(
+        conn,
+        "SELECT h.id FROM hermanos h
+         WHERE h.familia_id IS NOT NULL
+           AND NOT EXISTS (SELECT 1 FROM familias f WHERE f.id = h.familia_id)
+         ORDER BY h.id",
+    )?;
+    let familias_sin_direccion = ids(
+        conn,
+        "SELECT f.id FROM familias f
+         WHERE f.hermano_direccion_id IS NOT NULL
+           AND NOT EXISTS (SELECT 1 FROM hermanos h WHERE h.id = f.hermano_direccion_id)
+         ORDER BY f.id",
+    )?;
+
+    for id in &cuotas_huerfanas {
+        conn.execute("DELETE FROM cuotas WHERE id = ?1", [id])?;
+    }
+    for id in &hermanos_sin_familia {
+        conn.execute(
+            "UPDATE hermanos SET familia_id = NULL, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+            [id],
+        )?;
+    }
+    for id in &familias_sin_direccion {
+        conn.execute(
+            "UPDATE familias SET hermano_direccion_id = NULL, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+            [id],
+        )?;
+    }
+
+    Ok(InformeIntegridad {
+        cuotas_huerfanas,
+        hermanos_sin_familia,
+        familias_sin_direccion,
+    })
+}
+
+pub fn reparar_integridad(db: &DbConnection) -> HermanarResult<InformeIntegridad> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+    let informe = reparar_huerfanos(&tx)?;
+    tx.commit()?;
+
+    Ok(informe)
+}
+
+fn ids(conn: &Connection, sql: &str) -> HermanarResult<Vec<i32>> {
+    let mut stmt = conn.prepare(sql)?;
+    let ids = stmt.query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<i32>, _>>()?;
+    Ok(ids)
+}
@@ -0,0 +1,361 @@
+use chrono::NaiveDate;
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::db::{DbConnection, Hermano};
+use crate::db::hermanos::{modificar_hermano, SELECT_HERMANOS};
+use crate::error::{HermanarError, HermanarResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MotivoBaja {
+    Voluntaria,
+    Fallecimiento,
+    Impago,
+    Traslado,
+    Otro,
+}
+
+impl MotivoBaja {
+    fn clave(&self) -> &'static str {
+        match self {
+            MotivoBaja::Voluntaria => "voluntaria",
+            MotivoBaja::Fallecimiento => "fallecimiento",
+            MotivoBaja::Impago => "impago",
+            MotivoBaja::Traslado => "traslado",
+            MotivoBaja::Otro => "otro",
+        }
+    }
+
+    fn desde_clave(clave: &str) -> Option<Self> {
+        match clave {
+            "voluntaria" => Some(MotivoBaja::Voluntaria),
+            "fallecimiento" => Some(MotivoBaja::Fallecimiento),
+            "impago" => Some(MotivoBaja::Impago),
+            "traslado" => Some(MotivoBaja::Traslado),
+            "otro" => Some(MotivoBaja::Otro),
+            _ => None,
+        }
+    }
+}
+
+/// Tiempo continuado de pertenencia a la hermandad. Un periodo sin
+/// `fecha_baja` es el actual; cada readmisión abre uno nuevo.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeriodoMembresia {
+    pub id: Option<i64>,
+    pub hermano_id: i32,
+    pub fecha_alta: String,
+    pub fecha_baja: Option<String>,
+    pub motivo_baja: Option<MotivoBaja>,
+    pub observaciones: Option<String>,
+    pub created_at: Option<String>,
+}
+
+impl PeriodoMembresia {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        Ok(PeriodoMembresia {
+            id: Some(row.get(0)?),
+            hermano_id: row.get(1)?,
+            fecha_alta: row.get(2)?,
+            fecha_baja: row.get(3)?,
+            motivo_baja: row.get::<_, Option<String>>(4)?.as_deref().and_then(MotivoBaja::desde_clave),
+            observaciones: row.get(5)?,
+            created_at: row.get(6)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolicitudBaja {
+    pub fecha: String,
+    pub motivo: MotivoBaja,
+    pub observaciones: Option<String>,
+}
+
+/// Movimiento de hermanos en un año, calculado a partir de los periodos.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumenMembresia {
+    pub anio: i32,
+    pub activos_inicio: i32,
+    pub altas: i32,
+    pub readmisiones: i32,
+    pub bajas: i32,
+    /// Bajas del año por motivo; las que no tienen motivo figuran como "sin_motivo".
+    pub bajas_por_motivo: BTreeMap<String, i32>,
+    pub activos_fin: i32,
+}
+
+pub fn get_periodos_hermano(db: &DbConnection, hermano_id: i32) -> HermanarResult<Vec<PeriodoMembresia>> {
+    buscar_periodos(&db.reader(), hermano_id)
+}
+
+pub(crate) fn buscar_periodos(conn: &Connection, hermano_id: i32) -> HermanarResult<Vec<PeriodoMembresia>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, hermano_id, fecha_alta, fecha_baja, motivo_baja, observaciones, created_at
+         FROM periodos_membresia
+         WHERE hermano_id = ?1
+         ORDER BY fecha_alta, id"
+    )?;
+
+    let periodos = stmt.query_map([hermano_id], |row| {
+        PeriodoMembresia::from_row(row)
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(periodos)
+}
+
+fn periodo_abierto(conn: &Connection, hermano_id: i32) -> HermanarResult<Option<PeriodoMembresia>> {
+    let periodo = conn
+        .prepare_cached(
+            "SELECT id, hermano_id, fecha_alta, fecha_baja, motivo_baja, observaciones, created_at
+             FROM periodos_membresia
+             WHERE hermano_id = ?1 AND fecha_baja IS NULL"
+        )?
+        .query_row([hermano_id], PeriodoMembresia::from_row)
+        .optional()?;
+
+    Ok(periodo)
+}
+
+pub(crate) fn abrir_periodo(
+    conn: &Connection,
+    hermano_id: i32,
+    fecha_alta: &str,
+    observaciones: Option<&str>,
+) -> HermanarResult<()> {
+    conn.prepare_cached(
+        "INSERT INTO periodos_membresia (hermano_id, fecha_alta, observaciones) VALUES (?1, ?2, ?3)"
+    )?
+    .execute(params![hermano_id, fecha_alta, observaciones])?;
+
+    Ok(())
+}
+
+fn cerrar_periodo(
+    conn: &Connection,
+    periodo: &PeriodoMembresia,
+    fecha_baja: &str,
+    motivo: Option<MotivoBaja>,
+    observaciones: Option<&str>,
+) -> HermanarResult<()> {
+    if fecha_baja < periodo.fecha_alta.as_str() {
+        return Err(HermanarError::validation(
+            "fecha",
+            format!("La fecha de baja no puede ser anterior al alta ({})", periodo.fecha_alta),
+        ));
+    }
+
+    conn.prepare_cached(
+        "UPDATE periodos_membresia
+         SET fecha_baja = ?1, motivo_baja = ?2, observaciones = COALESCE(?3, observaciones)
+         WHERE id = ?4"
+    )?
+    .execute(params![fecha_baja, motivo.map(|m| m.clave()), observaciones, periodo.id])?;
+
+    Ok(())
+}
+
+/// Lleva la fecha de alta del hermano, si cambia, al primero de sus periodos,
+/// que empieza con ella. No puede pasar de la fecha en que terminó ese periodo.
+pub(crate) fn ajustar_primera_alta(conn: &Connection, hermano_id: i32, fecha_alta: &str) -> HermanarResult<()> {
+    let Some(primero) = buscar_periodos(conn, hermano_id)?.into_iter().next() else {
+        return Ok(());
+    };
+    if primero.fecha_alta == fecha_alta {
+        return Ok(());
+    }
+    if let Some(fecha_baja) = primero.fecha_baja.as_deref().filter(|baja| fecha_alta > *baja) {
+        return Err(HermanarError::validation(
+            "fecha_alta",
+            format!("La fecha de alta no puede ser posterior a su primera baja ({})", fecha_baja),
+        ));
+    }
+
+    conn.prepare_cached("UPDATE periodos_membresia SET fecha_alta = ?1 WHERE id = ?2")?
+        .execute(params![fecha_alta, primero.id])?;
+
+    Ok(())
+}
+
+/// Vuelve a guardar los periodos de un hermano restaurado desde la papelera.
+pub(crate) fn reinsertar_periodos(conn: &Connection, periodos: &[PeriodoMembresia]) -> HermanarResult<()> {
+    for periodo in periodos {
+        conn.prepare_cached(
+            "INSERT INTO periodos_membresia
+             (hermano_id, fecha_alta, fecha_baja, motivo_baja, observaciones, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, COALESCE(?6, CURRENT_TIMESTAMP))"
+        )?
+        .execute(params![
+            periodo.hermano_id,
+            periodo.fecha_alta,
+            periodo.fecha_baja,
+            periodo.motivo_baja.map(|m| m.clave()),
+            periodo.observaciones,
+            periodo.created_at,
+        ])?;
+    }
+
+    Ok(())
+}
+
+/// Ajusta los periodos cuando el estado `activo` cambia sin pasar por
+/// `dar_de_baja` o `readmitir_hermano` (edición del formulario, desactivar):
+/// se abre o cierra el periodo con fecha de hoy y sin motivo.
+pub(crate) fn sincronizar_periodo(conn: &Connection, hermano_id: i32, activo: bool) -> HermanarResult<()> {
+    let hoy = hoy();
+
+    match (activo, periodo_abierto(conn, hermano_id)?) {
+        (true, None) => abrir_periodo(conn, hermano_id, &hoy, None),
+        (false, Some(periodo)) => {
+            let fecha = hoy.as_str().max(periodo.fecha_alta.as_str()).to_string();
+            cerrar_periodo(conn, &periodo, &fecha, None, None)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Da de baja a un hermano cerrando su periodo actual con la fecha y el motivo
+/// indicados.
+pub fn dar_de_baja(db: &DbConnection, hermano_id: i32, baja: &SolicitudBaja) -> HermanarResult<()> {
+    let fecha = validar_fecha("fecha", &baja.fecha)?;
+    let observaciones = baja.observaciones.as_deref().map(str::trim).filter(|o| !o.is_empty());
+
+    modificar_hermano(db, hermano_id, |conn| {
+        let periodo = periodo_abierto(conn, hermano_id)?
+            .ok_or_else(|| HermanarError::conflict("El hermano ya está dado de baja"))?;
+
+        cerrar_periodo(conn, &periodo, &fecha, Some(baja.motivo), observaciones)?;
+        conn.prepare_cached("UPDATE hermanos SET activo = 0, updated_at = CURRENT_TIMESTAMP WHERE id = ?1")?
+            .execute([hermano_id])?;
+
+        Ok(())
+    })
+}
+
+/// Readmite a un hermano dado de baja abriendo un periodo nuevo. Su fecha de
+/// alta original, que marca la antigüedad, no cambia.
+pub fn readmitir_hermano(
+    db: &DbConnection,
+    hermano_id: i32,
+    fecha: &str,
+    observaciones: Option<&str>,
+) -> HermanarResult<()> {
+    let fecha = validar_fecha("fecha", fecha)?;
+    let observaciones = observaciones.map(str::trim).filter(|o| !o.is_empty());
+
+    modificar_hermano(db, hermano_id, |conn| {
+        if periodo_abierto(conn, hermano_id)?.is_some() {
+            return Err(HermanarError::conflict("El hermano ya está de alta"));
+        }
+
+        let ultima_baja = buscar_periodos(conn, hermano_id)?
+            .into_iter()
+            .filter_map(|p| p.fecha_baja)
+            .max();
+        if let Some(ultima_baja) = ultima_baja.filter(|b| fecha < *b) {
+            return Err(HermanarError::validation(
+                "fecha",
+                format!("La readmisión no puede ser anterior a la última baja ({})", ultima_baja),
+            ));
+        }
+
+        abrir_periodo(conn, hermano_id, &fecha, observaciones)?;
+        conn.prepare_cached("UPDATE hermanos SET activo = 1, updated_at = CURRENT_TIMESTAMP WHERE id = ?1")?
+            .execute([hermano_id])?;
+
+        Ok(())
+    })
+}
+
+/// Hermanos que pertenecían a la hermandad en la fecha indicada. El día de la
+/// baja ya no cuenta como activo.
+pub fn get_hermanos_activos_en_fecha(db: &DbConnection, fecha: &str) -> HermanarResult<Vec<Hermano>> {
+    let fecha = validar_fecha("fecha", fecha)?;
+
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(&format!(
+        "{}
+         WHERE EXISTS (
+             SELECT 1 FROM periodos_membresia p
+             WHERE p.hermano_id = hermanos.id
+               AND p.fecha_alta <= ?1
+               AND (p.fecha_baja IS NULL OR p.fecha_baja > ?1)
+         )
+         ORDER BY numero_hermano",
+        SELECT_HERMANOS
+    ))?;
+
+    let hermanos = stmt.query_map([&fecha], |row| {
+        Hermano::from_row(row)
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(hermanos)
+}
+
+pub fn get_resumen_membresia(db: &DbConnection, anio: i32) -> HermanarResult<ResumenMembresia> {
+    let inicio = format!("{:04}-01-01", anio);
+    let fin = format!("{:04}-12-31", anio);
+
+    let conn = db.reader();
+
+    let activos_en = |fecha: &str| -> HermanarResult<i32> {
+        let total = conn
+            .prepare_cached(
+                "SELECT COUNT(DISTINCT hermano_id) FROM periodos_membresia
+                 WHERE fecha_alta <= ?1 AND (fecha_baja IS NULL OR fecha_baja > ?1)"
+            )?
+            .query_row([fecha], |row| row.get(0))?;
+        Ok(total)
+    };
+    let activos_inicio = activos_en(&inicio)?;
+    let activos_fin = activos_en(&fin)?;
+
+    // La primera alta de cada hermano es un alta nueva; las siguientes, readmisiones
+    let (altas, readmisiones) = conn
+        .prepare_cached(
+            "SELECT
+                COALESCE(SUM(CASE WHEN anteriores = 0 THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN anteriores > 0 THEN 1 ELSE 0 END), 0)
+             FROM (
+                 SELECT (SELECT COUNT(*) FROM periodos_membresia a
+                         WHERE a.hermano_id = p.hermano_id AND a.fecha_alta < p.fecha_alta) AS anteriores
+                 FROM periodos_membresia p
+                 WHERE p.fecha_alta BETWEEN ?1 AND ?2
+             )"
+        )?
+        .query_row([&inicio, &fin], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, i32>(1)?)))?;
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT COALESCE(motivo_baja, 'sin_motivo'), COUNT(*)
+         FROM periodos_membresia
+         WHERE fecha_baja BETWEEN ?1 AND ?2
+         GROUP BY 1"
+    )?;
+    let bajas_por_motivo = stmt.query_map([&inicio, &fin], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?))
+    })?
+    .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+    Ok(ResumenMembresia {
+        anio,
+        activos_inicio,
+        altas,
+        readmisiones,
+        bajas: bajas_por_motivo.values().sum(),
+        bajas_por_motivo,
+        activos_fin,
+    })
+}
+
+pub(crate) fn validar_fecha(campo: &str, fecha: &str) -> HermanarResult<String> {
+    NaiveDate::parse_from_str(fecha.trim(), "%Y-%m-%d")
+        .map(|f| f.format("%Y-%m-%d").to_string())
+        .map_err(|_| HermanarError::validation(campo, "La fecha debe tener el formato AAAA-MM-DD"))
+}
+
+pub(crate) fn hoy() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
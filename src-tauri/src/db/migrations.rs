@@ -0,0 +1,884 @@
+use rusqlite::{Connection, Transaction};
+use anyhow::Context;
+use std::path::Path;
+
+// Cada migración lleva el esquema de la versión `version - 1` a `version`.
+// Las migraciones se aplican en orden y nunca se modifican una vez publicadas:
+// cualquier cambio posterior del esquema debe añadirse como una nueva entrada.
+struct Migration {
+    version: i32,
+    descripcion: &'static str,
+    aplicar: fn(&Transaction) -> Result<(), anyhow::Error>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        descripcion: "Esquema inicial de hermanos, familias y cuotas",
+        aplicar: v1_esquema_inicial,
+    },
+    Migration {
+        version: 2,
+        descripcion: "Claves foráneas explícitas y reparación de referencias rotas",
+        aplicar: v2_claves_foraneas,
+    },
+    Migration {
+        version: 3,
+        descripcion: "Auditoría de cambios",
+        aplicar: v3_auditoria,
+    },
+    Migration {
+        version: 4,
+        descripcion: "Papelera de hermanos y cuotas eliminados",
+        aplicar: v4_papelera,
+    },
+    Migration {
+        version: 5,
+        descripcion: "Historial de números de hermano por año",
+        aplicar: v5_historial_numeros,
+    },
+    Migration {
+        version: 6,
+        descripcion: "Reservas de números de hermano",
+        aplicar: v6_reservas_numero,
+    },
+    Migration {
+        version: 7,
+        descripcion: "Periodos de pertenencia a la hermandad",
+        aplicar: v7_periodos_membresia,
+    },
+    Migration {
+        version: 8,
+        descripcion: "Tipo de documento de identidad",
+        aplicar: v8_tipo_documento,
+    },
+    Migration {
+        version: 9,
+        descripcion: "Mandatos SEPA y remesas de adeudos",
+        aplicar: v9_remesas_sepa,
+    },
+    Migration {
+        version: 10,
+        descripcion: "Devoluciones de adeudos SEPA",
+        aplicar: v10_devoluciones,
+    },
+    Migration {
+        version: 11,
+        descripcion: "Movimientos bancarios y conciliación de cuotas",
+        aplicar: v11_conciliacion,
+    },
+    Migration {
+        version: 12,
+        descripcion: "Recibos numerados de cuotas pagadas",
+        aplicar: v12_recibos,
+    },
+    Migration {
+        version: 13,
+        descripcion: "Certificados y registro de certificados emitidos",
+        aplicar: v13_certificados,
+    },
+    Migration {
+        version: 14,
+        descripcion: "Donantes, donativos y cuotas deducibles",
+        aplicar: v14_donativos,
+    },
+    Migration {
+        version: 15,
+        descripcion: "Categorías de hermano y tarifas de cuotas",
+        aplicar: v15_tarifas,
+    },
+    Migration {
+        version: 16,
+        descripcion: "Cuotas anuales, semestrales, trimestrales y mensuales",
+        aplicar: v16_periodicidad_cuotas,
+    },
+    Migration {
+        version: 17,
+        descripcion: "Pagos parciales de cuotas",
+        aplicar: v17_pagos,
+    },
+    Migration {
+        version: 18,
+        descripcion: "Copias del escudo impreso en recibos y certificados",
+        aplicar: v18_imagenes_documento,
+    },
+];
+
+/// Versión de esquema que espera esta versión de la aplicación.
+pub const SCHEMA_VERSION: i32 = MIGRATIONS.len() as i32;
+
+/// Versión de esquema almacenada en la base de datos (`PRAGMA user_version`).
+pub fn schema_version(conn: &Connection) -> Result<i32, anyhow::Error> {
+    let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    Ok(version)
+}
+
+/// Lleva la base de datos hasta `SCHEMA_VERSION`.
+///
+/// Rechaza bases de datos creadas por una versión más reciente de la aplicación
+/// y, si hay migraciones pendientes sobre una base de datos con datos, guarda
+/// antes una copia de seguridad junto al fichero original.
+pub fn run_migrations(conn: &mut Connection, db_path: &Path) -> Result<(), anyhow::Error> {
+    let version_actual = schema_version(conn)?;
+
+    if version_actual > SCHEMA_VERSION {
+        return Err(anyhow::anyhow!(
+            "La base de datos tiene la versión de esquema {} y esta versión de Hermanar solo admite hasta la {}. Actualice la aplicación.",
+            version_actual,
+            SCHEMA_VERSION
+        ));
+    }
+
+    if version_actual == SCHEMA_VERSION {
+        println!("Esquema de la base de datos al día (versión {}).", version_actual);
+        return Ok(());
+    }
+
+    if tiene_tablas(conn)? {
+        let copia = copia_de_seguridad(conn, db_path, version_actual)?;
+        println!("Copia de seguridad previa a la migración: {}", copia.display());
+    }
+
+    // Las migraciones que reconstruyen tablas necesitan las claves foráneas
+    // desactivadas; este PRAGMA no tiene efecto dentro de una transacción.
+    conn.pragma_update(None, "foreign_keys", false)?;
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version_actual) {
+        println!("Aplicando migración {}: {}", migration.version, migration.descripcion);
+
+        let tx = conn.transaction()?;
+        (migration.aplicar)(&tx)
+            .with_context(|| format!("Error al aplicar la migración {}", migration.version))?;
+        comprobar_claves_foraneas(&tx)
+            .with_context(|| format!("La migración {} ha dejado referencias rotas", migration.version))?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+fn comprobar_claves_foraneas(conn: &Connection) -> Result<(), anyhow::Error> {
+    let mut stmt = conn.prepare("PRAGMA foreign_key_check")?;
+    let violaciones = stmt.query_map([], |row| {
+        Ok(format!("{} (fila {})", row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    if !violaciones.is_empty() {
+        return Err(anyhow::anyhow!("Referencias rotas: {}", violaciones.join(", ")));
+    }
+
+    Ok(())
+}
+
+fn tiene_tablas(conn: &Connection) -> Result<bool, anyhow::Error> {
+    let count: i32 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+fn copia_de_seguridad(conn: &Connection, db_path: &Path, version: i32) -> Result<std::path::PathBuf, anyhow::Error> {
+    let marca = chrono::Local::now().format("%Y%m%d%H%M%S");
+    let nombre = format!(
+        "{}.v{}-{}.bak",
+        db_path.file_name().and_then(|n| n.to_str()).unwrap_or("hermanar.db"),
+        version,
+        marca
+    );
+    let destino = db_path.with_file_name(nombre);
+
+    // VACUUM INTO genera una copia consistente aunque la conexión siga abierta
+    conn.execute("VACUUM INTO ?1", [destino.to_string_lossy()])
+        .context("No se pudo crear la copia de seguridad previa a la migración")?;
+
+    Ok(destino)
+}
+
+// Versión 1: el esquema original. Se mantiene `IF NOT EXISTS` porque las
+// instalaciones anteriores al sistema de migraciones ya tienen estas tablas
+// con `user_version = 0`.
+fn v1_esquema_inicial(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS familias (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            nombre_familia TEXT NOT NULL UNIQUE,
+            hermano_direccion_id INTEGER,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE IF NOT EXISTS hermanos (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            numero_hermano TEXT NOT NULL UNIQUE,
+            nombre TEXT NOT NULL,
+            primer_apellido TEXT NOT NULL,
+            segundo_apellido TEXT,
+            dni TEXT,
+            fecha_nacimiento TEXT,
+            localidad_nacimiento TEXT,
+            provincia_nacimiento TEXT,
+            fecha_alta TEXT NOT NULL,
+            familia_id INTEGER,
+            telefono TEXT,
+            email TEXT,
+            direccion TEXT,
+            localidad TEXT,
+            provincia TEXT,
+            codigo_postal TEXT,
+            parroquia_bautismo TEXT,
+            localidad_bautismo TEXT,
+            provincia_bautismo TEXT,
+            autorizacion_menores BOOLEAN NOT NULL DEFAULT 0,
+            nombre_representante_legal TEXT,
+            dni_representante_legal TEXT,
+            hermano_aval_1 TEXT,
+            hermano_aval_2 TEXT,
+            activo BOOLEAN NOT NULL DEFAULT 1,
+            observaciones TEXT,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (familia_id) REFERENCES familias (id)
+        );
+
+        CREATE TABLE IF NOT EXISTS cuotas (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            hermano_id INTEGER NOT NULL,
+            anio INTEGER NOT NULL,
+            trimestre INTEGER NOT NULL CHECK(trimestre >= 1 AND trimestre <= 4),
+            importe REAL NOT NULL,
+            pagado BOOLEAN NOT NULL DEFAULT 0,
+            fecha_pago TEXT,
+            metodo_pago TEXT,
+            observaciones TEXT,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (hermano_id) REFERENCES hermanos (id) ON DELETE CASCADE,
+            UNIQUE(hermano_id, anio, trimestre)
+        );
+
+        -- Índices para mejorar el rendimiento
+        CREATE INDEX IF NOT EXISTS idx_hermanos_activo ON hermanos(activo);
+        CREATE INDEX IF NOT EXISTS idx_hermanos_familia ON hermanos(familia_id);
+        CREATE INDEX IF NOT EXISTS idx_cuotas_hermano ON cuotas(hermano_id);
+        CREATE INDEX IF NOT EXISTS idx_cuotas_anio ON cuotas(anio);
+        CREATE INDEX IF NOT EXISTS idx_cuotas_pagado ON cuotas(pagado);",
+    )?;
+
+    Ok(())
+}
+
+// Versión 2: hace explícito el comportamiento de las claves foráneas.
+//
+// - Al borrar una familia, sus hermanos (normalmente inactivos) se quedan sin familia.
+// - Al borrar el hermano que da la dirección a una familia, la familia se queda
+//   sin dirección principal en lugar de apuntar a un hermano inexistente.
+// - Las cuotas se siguen borrando en cascada con su hermano.
+//
+// SQLite no permite modificar claves foráneas, así que se reconstruyen las tablas.
+fn v2_claves_foraneas(tx: &Transaction) -> Result<(), anyhow::Error> {
+    let informe = super::integridad::reparar_huerfanos(tx)?;
+    if !informe.is_empty() {
+        println!(
+            "Referencias reparadas: {} cuotas huérfanas eliminadas {:?}, {} hermanos sin familia {:?}, {} familias sin dirección {:?}",
+            informe.cuotas_huerfanas.len(),
+            informe.cuotas_huerfanas,
+            informe.hermanos_sin_familia.len(),
+            informe.hermanos_sin_familia,
+            informe.familias_sin_direccion.len(),
+            informe.familias_sin_direccion,
+        );
+    }
+
+    tx.execute_batch(
+        "CREATE TABLE familias_v2 (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            nombre_familia TEXT NOT NULL UNIQUE,
+            hermano_direccion_id INTEGER,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (hermano_direccion_id) REFERENCES hermanos (id) ON DELETE SET NULL
+        );
+        INSERT INTO familias_v2 (id, nombre_familia, hermano_direccion_id, created_at, updated_at)
+            SELECT id, nombre_familia, hermano_direccion_id, created_at, updated_at FROM familias;
+        DROP TABLE familias;
+        ALTER TABLE familias_v2 RENAME TO familias;
+
+        CREATE TABLE hermanos_v2 (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            numero_hermano TEXT NOT NULL UNIQUE,
+            nombre TEXT NOT NULL,
+            primer_apellido TEXT NOT NULL,
+            segundo_apellido TEXT,
+            dni TEXT,
+            fecha_nacimiento TEXT,
+            localidad_nacimiento TEXT,
+            provincia_nacimiento TEXT,
+            fecha_alta TEXT NOT NULL,
+            familia_id INTEGER,
+            telefono TEXT,
+            email TEXT,
+            direccion TEXT,
+            localidad TEXT,
+            provincia TEXT,
+            codigo_postal TEXT,
+            parroquia_bautismo TEXT,
+            localidad_bautismo TEXT,
+            provincia_bautismo TEXT,
+            autorizacion_menores BOOLEAN NOT NULL DEFAULT 0,
+            nombre_representante_legal TEXT,
+            dni_representante_legal TEXT,
+            hermano_aval_1 TEXT,
+            hermano_aval_2 TEXT,
+            activo BOOLEAN NOT NULL DEFAULT 1,
+            observaciones TEXT,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (familia_id) REFERENCES familias (id) ON DELETE SET NULL
+        );
+        INSERT INTO hermanos_v2 SELECT * FROM hermanos;
+        DROP TABLE hermanos;
+        ALTER TABLE hermanos_v2 RENAME TO hermanos;
+
+        CREATE INDEX IF NOT EXISTS idx_hermanos_activo ON hermanos(activo);
+        CREATE INDEX IF NOT EXISTS idx_hermanos_familia ON hermanos(familia_id);
+        CREATE INDEX IF NOT EXISTS idx_familias_direccion ON familias(hermano_direccion_id);",
+    )?;
+
+    Ok(())
+}
+
+// Versión 3: registro de cambios sobre hermanos, familias y cuotas. Los valores
+// se guardan como JSON con la forma que tenía el registro en ese momento.
+fn v3_auditoria(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS auditoria (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            entidad TEXT NOT NULL,
+            entidad_id INTEGER NOT NULL,
+            operacion TEXT NOT NULL CHECK(operacion IN ('crear', 'actualizar', 'eliminar')),
+            valores_anteriores TEXT,
+            valores_nuevos TEXT,
+            operador TEXT NOT NULL,
+            fecha TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_auditoria_entidad ON auditoria(entidad, entidad_id);",
+    )?;
+
+    Ok(())
+}
+
+// Versión 4: papelera para deshacer eliminaciones y tabla de configuración
+// general. Cada elemento de la papelera guarda en `datos` una copia JSON de lo
+// eliminado, incluidas las cuotas y vínculos con familias de un hermano.
+fn v4_papelera(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS papelera (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            entidad TEXT NOT NULL CHECK(entidad IN ('hermano', 'cuota')),
+            entidad_id INTEGER NOT NULL,
+            descripcion TEXT NOT NULL,
+            datos TEXT NOT NULL,
+            operador TEXT NOT NULL,
+            fecha_eliminacion TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_papelera_fecha ON papelera(fecha_eliminacion);
+
+        CREATE TABLE IF NOT EXISTS configuracion (
+            clave TEXT PRIMARY KEY,
+            valor TEXT NOT NULL
+        );",
+    )?;
+
+    Ok(())
+}
+
+// Versión 5: número que tuvo cada hermano en cada renumeración anual, para
+// poder consultar la antigüedad histórica aunque el número cambie.
+fn v5_historial_numeros(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS historial_numeros (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            hermano_id INTEGER NOT NULL REFERENCES hermanos(id) ON DELETE CASCADE,
+            anio INTEGER NOT NULL,
+            numero_anterior TEXT,
+            numero_hermano TEXT NOT NULL,
+            fecha TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE(hermano_id, anio)
+        );
+        CREATE INDEX IF NOT EXISTS idx_historial_numeros_anio ON historial_numeros(anio);",
+    )?;
+
+    Ok(())
+}
+
+// Versión 6: números apartados para solicitudes de ingreso pendientes, que la
+// asignación automática no debe entregar a otro hermano.
+fn v6_reservas_numero(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS reservas_numero (
+            numero TEXT PRIMARY KEY,
+            solicitante TEXT NOT NULL,
+            observaciones TEXT,
+            fecha TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );",
+    )?;
+
+    Ok(())
+}
+
+// Versión 7: periodos de alta y baja de cada hermano. Se parte de un periodo
+// por hermano a partir de su fecha de alta; de los ya inactivos no se conoce
+// la fecha de baja, así que se toma la de su última modificación.
+fn v7_periodos_membresia(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS periodos_membresia (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            hermano_id INTEGER NOT NULL REFERENCES hermanos(id) ON DELETE CASCADE,
+            fecha_alta TEXT NOT NULL,
+            fecha_baja TEXT,
+            motivo_baja TEXT CHECK(motivo_baja IN ('voluntaria', 'fallecimiento', 'impago', 'traslado', 'otro')),
+            observaciones TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            CHECK(fecha_baja IS NULL OR fecha_baja >= fecha_alta)
+        );
+        CREATE INDEX IF NOT EXISTS idx_periodos_hermano ON periodos_membresia(hermano_id);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_periodos_abierto
+            ON periodos_membresia(hermano_id) WHERE fecha_baja IS NULL;
+
+        INSERT INTO periodos_membresia (hermano_id, fecha_alta, fecha_baja, observaciones)
+        SELECT id,
+               fecha_alta,
+               CASE WHEN activo = 0 THEN MAX(fecha_alta, date(updated_at)) END,
+               CASE WHEN activo = 0 THEN 'Baja anterior al registro de periodos' END
+        FROM hermanos
+        WHERE NOT EXISTS (SELECT 1 FROM periodos_membresia p WHERE p.hermano_id = hermanos.id);",
+    )?;
+
+    Ok(())
+}
+
+// Versión 8: tipo de los documentos del hermano y de su representante legal,
+// para distinguir DNI/NIE (que se validan) de documentos extranjeros. Los
+// documentos existentes no se tocan; se revisan con `revisar_documentos`.
+fn v8_tipo_documento(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "ALTER TABLE hermanos ADD COLUMN tipo_documento TEXT
+            CHECK(tipo_documento IN ('dni', 'nie', 'pasaporte', 'otro'));
+        ALTER TABLE hermanos ADD COLUMN tipo_documento_representante TEXT
+            CHECK(tipo_documento_representante IN ('dni', 'nie', 'pasaporte', 'otro'));
+        CREATE INDEX IF NOT EXISTS idx_hermanos_dni ON hermanos(dni);",
+    )?;
+
+    Ok(())
+}
+
+// Versión 9: mandatos de domiciliación por hermano o por familia y remesas de
+// adeudos SEPA. Cada adeudo guarda los datos del mandato con los que se envió,
+// para que la remesa siga siendo consultable aunque el mandato cambie o el
+// hermano se elimine.
+fn v9_remesas_sepa(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS mandatos_sepa (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            hermano_id INTEGER REFERENCES hermanos(id) ON DELETE CASCADE,
+            familia_id INTEGER REFERENCES familias(id) ON DELETE CASCADE,
+            referencia TEXT NOT NULL UNIQUE,
+            titular TEXT NOT NULL,
+            iban TEXT NOT NULL,
+            bic TEXT,
+            fecha_firma TEXT NOT NULL,
+            tipo TEXT NOT NULL DEFAULT 'recurrente' CHECK(tipo IN ('recurrente', 'puntual')),
+            activo INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            CHECK((hermano_id IS NULL) <> (familia_id IS NULL))
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_mandatos_hermano_activo
+            ON mandatos_sepa(hermano_id) WHERE activo = 1 AND hermano_id IS NOT NULL;
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_mandatos_familia_activo
+            ON mandatos_sepa(familia_id) WHERE activo = 1 AND familia_id IS NOT NULL;
+
+        CREATE TABLE IF NOT EXISTS remesas (
+            id INTEGER PRIMARY KEY,
+            referencia TEXT NOT NULL UNIQUE,
+            fecha_cobro TEXT NOT NULL,
+            num_operaciones INTEGER NOT NULL,
+            importe_total REAL NOT NULL,
+            fichero TEXT NOT NULL,
+            operador TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE IF NOT EXISTS remesa_cuotas (
+            remesa_id INTEGER NOT NULL REFERENCES remesas(id) ON DELETE CASCADE,
+            cuota_id INTEGER NOT NULL,
+            hermano_id INTEGER NOT NULL,
+            end_to_end_id TEXT NOT NULL UNIQUE,
+            mandato_referencia TEXT NOT NULL,
+            titular TEXT NOT NULL,
+            iban TEXT NOT NULL,
+            importe REAL NOT NULL,
+            secuencia TEXT NOT NULL CHECK(secuencia IN ('FRST', 'RCUR', 'OOFF')),
+            PRIMARY KEY (remesa_id, cuota_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_remesa_cuotas_cuota ON remesa_cuotas(cuota_id);
+        CREATE INDEX IF NOT EXISTS idx_remesa_cuotas_mandato ON remesa_cuotas(mandato_referencia);",
+    )?;
+
+    Ok(())
+}
+
+// Versión 10: recibos devueltos por el banco. La referencia de extremo a
+// extremo es única para que importar dos veces el mismo fichero no duplique
+// devoluciones.
+fn v10_devoluciones(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS devoluciones (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            remesa_id INTEGER NOT NULL REFERENCES remesas(id) ON DELETE CASCADE,
+            cuota_id INTEGER NOT NULL,
+            hermano_id INTEGER NOT NULL,
+            end_to_end_id TEXT NOT NULL UNIQUE,
+            codigo_motivo TEXT NOT NULL,
+            fecha_devolucion TEXT NOT NULL,
+            importe REAL NOT NULL,
+            comision REAL NOT NULL DEFAULT 0,
+            comision_repercutida INTEGER NOT NULL DEFAULT 0,
+            fichero TEXT NOT NULL,
+            operador TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_devoluciones_remesa ON devoluciones(remesa_id);
+        CREATE INDEX IF NOT EXISTS idx_devoluciones_cuota ON devoluciones(cuota_id);",
+    )?;
+
+    Ok(())
+}
+
+// Versión 11: apuntes importados de extractos Norma 43 y cuotas pagadas con
+// cada uno. La clave identifica el apunte por su contenido para no importarlo
+// dos veces desde extractos que se solapan.
+fn v11_conciliacion(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS movimientos_bancarios (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            clave TEXT NOT NULL UNIQUE,
+            cuenta TEXT NOT NULL,
+            fecha_operacion TEXT NOT NULL,
+            fecha_valor TEXT NOT NULL,
+            importe REAL NOT NULL,
+            concepto_comun TEXT NOT NULL DEFAULT '',
+            concepto_propio TEXT NOT NULL DEFAULT '',
+            documento TEXT NOT NULL DEFAULT '',
+            referencia_1 TEXT NOT NULL DEFAULT '',
+            referencia_2 TEXT NOT NULL DEFAULT '',
+            concepto TEXT NOT NULL DEFAULT '',
+            estado TEXT NOT NULL DEFAULT 'pendiente' CHECK(estado IN ('pendiente', 'conciliado', 'descartado')),
+            fichero TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_movimientos_estado ON movimientos_bancarios(estado, fecha_operacion);
+
+        CREATE TABLE IF NOT EXISTS conciliaciones (
+            movimiento_id INTEGER NOT NULL REFERENCES movimientos_bancarios(id) ON DELETE CASCADE,
+            cuota_id INTEGER NOT NULL,
+            importe REAL NOT NULL,
+            operador TEXT NOT NULL,
+            fecha TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (movimiento_id, cuota_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_conciliaciones_cuota ON conciliaciones(cuota_id);",
+    )?;
+
+    Ok(())
+}
+
+// Versión 12: recibos de cuotas pagadas, numerados de forma correlativa
+// dentro de cada año. Guardan una copia de los datos impresos para poder
+// volver a generarlos iguales aunque cambien la cuota, el hermano o la
+// hermandad. Un recibo anulado conserva su número.
+fn v12_recibos(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS recibos (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            anio INTEGER NOT NULL,
+            numero INTEGER NOT NULL,
+            cuota_id INTEGER NOT NULL,
+            hermano_id INTEGER NOT NULL,
+            numero_hermano TEXT NOT NULL,
+            nombre_hermano TEXT NOT NULL,
+            dni TEXT,
+            cuota_anio INTEGER NOT NULL,
+            cuota_trimestre INTEGER NOT NULL,
+            concepto TEXT NOT NULL,
+            importe REAL NOT NULL,
+            fecha_pago TEXT NOT NULL,
+            metodo_pago TEXT NOT NULL,
+            hermandad TEXT,
+            anulado INTEGER NOT NULL DEFAULT 0,
+            fecha_anulacion TEXT,
+            operador TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (anio, numero)
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_recibos_cuota_vigente ON recibos(cuota_id) WHERE anulado = 0;
+        CREATE INDEX IF NOT EXISTS idx_recibos_trimestre ON recibos(cuota_anio, cuota_trimestre);",
+    )?;
+
+    Ok(())
+}
+
+// Versión 13: plantillas de certificados modificadas por la hermandad (las
+// que no están en la tabla usan el texto predeterminado) y registro de los
+// certificados emitidos, numerados dentro de cada año, con el texto tal como
+// se imprimió.
+fn v13_certificados(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS plantillas_certificado (
+            tipo TEXT PRIMARY KEY CHECK (tipo IN ('pertenencia', 'antiguedad', 'al_corriente')),
+            titulo TEXT NOT NULL,
+            cuerpo TEXT NOT NULL,
+            firmante TEXT NOT NULL,
+            visto_bueno TEXT,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE IF NOT EXISTS certificados (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            anio INTEGER NOT NULL,
+            numero INTEGER NOT NULL,
+            tipo TEXT NOT NULL CHECK (tipo IN ('pertenencia', 'antiguedad', 'al_corriente')),
+            hermano_id INTEGER NOT NULL,
+            numero_hermano TEXT NOT NULL,
+            nombre_hermano TEXT NOT NULL,
+            finalidad TEXT,
+            fecha TEXT NOT NULL,
+            titulo TEXT NOT NULL,
+            cuerpo TEXT NOT NULL,
+            firmante TEXT NOT NULL,
+            visto_bueno TEXT,
+            hermandad TEXT NOT NULL,
+            operador TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (anio, numero)
+        );
+        CREATE INDEX IF NOT EXISTS idx_certificados_hermano ON certificados(hermano_id);",
+    )?;
+
+    Ok(())
+}
+
+// Versión 14: donantes (hermanos o no) y sus donativos, para el certificado
+// anual de donativos y el modelo 182. Las cuotas pueden marcarse como
+// deducibles para que cuenten también como donativo del hermano.
+fn v14_donativos(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS donantes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            nif TEXT NOT NULL UNIQUE,
+            naturaleza TEXT NOT NULL DEFAULT 'F' CHECK (naturaleza IN ('F', 'J')),
+            nombre TEXT NOT NULL,
+            apellidos TEXT,
+            hermano_id INTEGER REFERENCES hermanos(id) ON DELETE SET NULL,
+            direccion TEXT,
+            codigo_postal TEXT,
+            localidad TEXT,
+            provincia TEXT,
+            email TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_donantes_hermano ON donantes(hermano_id) WHERE hermano_id IS NOT NULL;
+
+        CREATE TABLE IF NOT EXISTS donativos (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            donante_id INTEGER NOT NULL REFERENCES donantes(id) ON DELETE RESTRICT,
+            fecha TEXT NOT NULL,
+            importe REAL NOT NULL CHECK (importe > 0),
+            tipo TEXT NOT NULL DEFAULT 'dinerario' CHECK (tipo IN ('dinerario', 'especie')),
+            periodicidad TEXT NOT NULL DEFAULT 'puntual'
+                CHECK (periodicidad IN ('puntual', 'mensual', 'trimestral', 'semestral', 'anual')),
+            concepto TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_donativos_donante ON donativos(donante_id);
+        CREATE INDEX IF NOT EXISTS idx_donativos_fecha ON donativos(fecha);
+
+        ALTER TABLE cuotas ADD COLUMN deducible INTEGER NOT NULL DEFAULT 0;",
+    )?;
+
+    Ok(())
+}
+
+// Versión 15: categorías de cuota con sus reglas de edad y antigüedad, las
+// tarifas de cada categoría con su periodo de vigencia y la categoría
+// asignada a mano a los hermanos que no se clasifican por edad. Se crean las
+// categorías habituales, sin tarifas.
+fn v15_tarifas(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS categorias_cuota (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            nombre TEXT NOT NULL UNIQUE,
+            edad_minima INTEGER CHECK (edad_minima >= 0),
+            edad_maxima INTEGER CHECK (edad_maxima >= 0),
+            antiguedad_minima INTEGER CHECK (antiguedad_minima >= 0),
+            automatica INTEGER NOT NULL DEFAULT 1,
+            exenta INTEGER NOT NULL DEFAULT 0,
+            orden INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            CHECK (edad_minima IS NULL OR edad_maxima IS NULL OR edad_minima <= edad_maxima)
+        );
+
+        CREATE TABLE IF NOT EXISTS tarifas (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            categoria_id INTEGER NOT NULL REFERENCES categorias_cuota(id) ON DELETE CASCADE,
+            importe REAL NOT NULL CHECK (importe >= 0),
+            vigente_desde TEXT NOT NULL,
+            vigente_hasta TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            CHECK (vigente_hasta IS NULL OR vigente_hasta >= vigente_desde)
+        );
+        CREATE INDEX IF NOT EXISTS idx_tarifas_categoria ON tarifas(categoria_id, vigente_desde);
+
+        CREATE TABLE IF NOT EXISTS categorias_hermano (
+            hermano_id INTEGER PRIMARY KEY REFERENCES hermanos(id) ON DELETE CASCADE,
+            categoria_id INTEGER NOT NULL REFERENCES categorias_cuota(id) ON DELETE RESTRICT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_categorias_hermano_categoria ON categorias_hermano(categoria_id);
+
+        INSERT INTO categorias_cuota (nombre, edad_minima, edad_maxima, automatica, exenta, orden) VALUES
+            ('Infantil', 0, 13, 1, 0, 1),
+            ('Juvenil', 14, 17, 1, 0, 2),
+            ('Adulto', 18, 64, 1, 0, 3),
+            ('Jubilado', 65, NULL, 1, 0, 4),
+            ('Hermano de honor', NULL, NULL, 0, 1, 5);",
+    )?;
+
+    Ok(())
+}
+
+// Versión 16: las cuotas dejan de ser siempre trimestrales. Cada cuota tiene
+// una periodicidad y el número de periodo dentro del año, y las existentes
+// pasan a ser trimestrales con su trimestre como periodo. Los recibos copian
+// también la periodicidad. Cada hermano puede pagar con una periodicidad
+// distinta de la general.
+//
+// SQLite no permite modificar restricciones, así que se reconstruye la tabla.
+fn v16_periodicidad_cuotas(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE cuotas_v16 (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            hermano_id INTEGER NOT NULL,
+            anio INTEGER NOT NULL,
+            periodicidad TEXT NOT NULL DEFAULT 'trimestral'
+                CHECK (periodicidad IN ('anual', 'semestral', 'trimestral', 'mensual')),
+            periodo INTEGER NOT NULL CHECK (periodo >= 1 AND periodo <= CASE periodicidad
+                WHEN 'anual' THEN 1 WHEN 'semestral' THEN 2 WHEN 'trimestral' THEN 4 ELSE 12 END),
+            importe REAL NOT NULL,
+            pagado BOOLEAN NOT NULL DEFAULT 0,
+            fecha_pago TEXT,
+            metodo_pago TEXT,
+            observaciones TEXT,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            deducible INTEGER NOT NULL DEFAULT 0,
+            FOREIGN KEY (hermano_id) REFERENCES hermanos (id) ON DELETE CASCADE,
+            UNIQUE(hermano_id, anio, periodicidad, periodo)
+        );
+        INSERT INTO cuotas_v16
+            (id, hermano_id, anio, periodicidad, periodo, importe, pagado, fecha_pago, metodo_pago,
+             observaciones, created_at, updated_at, deducible)
+            SELECT id, hermano_id, anio, 'trimestral', trimestre, importe, pagado, fecha_pago, metodo_pago,
+                   observaciones, created_at, updated_at, deducible
+            FROM cuotas;
+        DROP TABLE cuotas;
+        ALTER TABLE cuotas_v16 RENAME TO cuotas;
+
+        CREATE INDEX IF NOT EXISTS idx_cuotas_hermano ON cuotas(hermano_id);
+        CREATE INDEX IF NOT EXISTS idx_cuotas_anio ON cuotas(anio);
+        CREATE INDEX IF NOT EXISTS idx_cuotas_pagado ON cuotas(pagado);
+
+        DROP INDEX IF EXISTS idx_recibos_trimestre;
+        ALTER TABLE recibos RENAME COLUMN cuota_trimestre TO cuota_periodo;
+        ALTER TABLE recibos ADD COLUMN cuota_periodicidad TEXT NOT NULL DEFAULT 'trimestral';
+        CREATE INDEX IF NOT EXISTS idx_recibos_periodo ON recibos(cuota_anio, cuota_periodicidad, cuota_periodo);
+
+        CREATE TABLE IF NOT EXISTS periodicidades_hermano (
+            hermano_id INTEGER PRIMARY KEY REFERENCES hermanos(id) ON DELETE CASCADE,
+            periodicidad TEXT NOT NULL CHECK (periodicidad IN ('anual', 'semestral', 'trimestral', 'mensual')),
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );",
+    )?;
+
+    Ok(())
+}
+
+// Versión 17: registro de pagos de cuotas. Una cuota puede pagarse en varias
+// veces, cada pago con su recibo, y queda pagada cuando los pagos vigentes
+// cubren su importe; `pagado`, `fecha_pago` y `metodo_pago` pasan a
+// calcularse a partir de ellos. Las cuotas ya pagadas reciben un pago por
+// todo su importe con su recibo vigente y, si se cobraron por remesa, la
+// referencia del adeudo; las de 0 € quedan pagadas sin pagos. Una cuota ya
+// puede tener varios recibos vigentes.
+fn v17_pagos(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS pagos (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            cuota_id INTEGER NOT NULL REFERENCES cuotas(id) ON DELETE CASCADE,
+            importe REAL NOT NULL CHECK (importe > 0),
+            fecha TEXT NOT NULL,
+            metodo TEXT NOT NULL,
+            referencia TEXT,
+            observaciones TEXT,
+            recibo_id INTEGER REFERENCES recibos(id),
+            anulado INTEGER NOT NULL DEFAULT 0,
+            fecha_anulacion TEXT,
+            operador TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_pagos_cuota ON pagos(cuota_id);
+        CREATE INDEX IF NOT EXISTS idx_pagos_referencia ON pagos(referencia);
+
+        INSERT INTO pagos (cuota_id, importe, fecha, metodo, referencia, recibo_id, operador)
+        SELECT c.id, c.importe,
+               COALESCE(c.fecha_pago, date(c.updated_at), date('now')),
+               COALESCE(c.metodo_pago, 'efectivo'),
+               CASE WHEN c.metodo_pago = 'domiciliacion' THEN
+                   (SELECT rc.end_to_end_id FROM remesa_cuotas rc WHERE rc.cuota_id = c.id ORDER BY rc.remesa_id DESC LIMIT 1)
+               END,
+               (SELECT r.id FROM recibos r WHERE r.cuota_id = c.id AND r.anulado = 0),
+               COALESCE((SELECT r.operador FROM recibos r WHERE r.cuota_id = c.id AND r.anulado = 0), '')
+        FROM cuotas c
+        WHERE c.pagado = 1 AND c.importe > 0;
+        UPDATE cuotas SET pagado = 1 WHERE pagado = 0 AND ROUND(importe * 100) <= 0;
+
+        DROP INDEX IF EXISTS idx_recibos_cuota_vigente;
+        CREATE INDEX IF NOT EXISTS idx_recibos_cuota ON recibos(cuota_id);",
+    )?;
+
+    Ok(())
+}
+
+// Versión 18: copia de cada escudo impreso en un recibo o certificado,
+// identificada por la huella SHA-256 de su contenido. Los datos de la
+// hermandad guardados con el documento apuntan a esa copia, de modo que
+// cambiar o borrar el fichero del escudo no altera los documentos emitidos.
+fn v18_imagenes_documento(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS imagenes_documento (
+            huella TEXT PRIMARY KEY,
+            contenido BLOB NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );",
+    )?;
+
+    Ok(())
+}
@@ -1,11 +1,40 @@
 use rusqlite::{Connection, Result};
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use anyhow::Context;
 
 pub mod hermanos;
 pub mod familias;
 pub mod cuotas;
+pub mod migrations;
+pub mod integridad;
+pub mod backup;
+pub mod perfiles;
+pub mod pool;
+pub mod auditoria;
+pub mod configuracion;
+pub mod papelera;
+pub mod numeracion;
+pub mod membresia;
+pub mod documentos;
+pub mod sepa;
+pub mod remesas;
+pub mod devoluciones;
+pub mod norma43;
+pub mod conciliacion;
+pub mod importacion;
+pub mod exportacion;
+pub mod hermandad;
+pub(crate) mod pdf;
+pub mod carnets;
+pub mod recibos;
+pub mod certificados;
+pub mod envios;
+pub mod donativos;
+pub mod tarifas;
+pub mod pagos;
 
 // Re-export specific functions
 pub use hermanos::{
@@ -21,7 +50,8 @@ pub use familias::{
 pub use cuotas::{
     get_all_cuotas, get_cuotas_by_hermano, get_cuotas_by_year, get_cuotas_pendientes,
     create_cuota, update_cuota, delete_cuota, marcar_cuota_pagada,
-    generar_cuotas_trimestre, get_estadisticas_cuotas
+    generar_cuotas_trimestre, generar_cuotas_periodo, set_cuotas_deducibles, get_estadisticas_cuotas,
+    get_periodicidad_cuotas, set_periodicidad_cuotas, get_periodicidades_hermanos, set_periodicidad_hermano
 };
 
 // Tipos compartidos
@@ -56,6 +86,10 @@ pub struct Hermano {
     pub observaciones: Option<String>,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
+    #[serde(default)]
+    pub tipo_documento: Option<TipoDocumento>,
+    #[serde(default)]
+    pub tipo_documento_representante: Option<TipoDocumento>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -86,7 +120,10 @@ pub struct Cuota {
     pub id: Option<i32>,
     pub hermano_id: i32,
     pub anio: i32,
-    pub trimestre: i32,
+    /// Número del periodo dentro del año según la periodicidad: el trimestre
+    /// en las trimestrales, el mes en las mensuales, siempre 1 en las anuales.
+    #[serde(alias = "trimestre")]
+    pub periodo: i32,
     pub importe: f64,
     pub pagado: bool,
     pub fecha_pago: Option<String>,
@@ -94,137 +131,156 @@ pub struct Cuota {
     pub observaciones: Option<String>,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
+    /// La cuota cuenta como donativo en el certificado de donativos y el
+    /// modelo 182.
+    #[serde(default)]
+    pub deducible: bool,
+    #[serde(default)]
+    pub periodicidad: Periodicidad,
+    /// Suma de los pagos vigentes de la cuota. Se calcula a partir de ellos
+    /// y se ignora al guardar.
+    #[serde(default)]
+    pub importe_pagado: f64,
+    #[serde(default)]
+    pub importe_pendiente: f64,
+    #[serde(default)]
+    pub estado: EstadoPago,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EstadisticasCuotas {
+    /// Suma de los pagos vigentes, también los de cuotas a medio pagar.
     pub total_recaudado: f64,
+    /// Cuotas sin pagar del todo, incluidas las parciales.
     pub cuotas_pendientes: i32,
     pub cuotas_pagadas: i32,
+    #[serde(default)]
+    pub cuotas_parciales: i32,
+    #[serde(default)]
+    pub total_pendiente: f64,
     pub hermanos_al_dia: i32,
     pub hermanos_morosos: i32,
+    #[serde(default)]
+    pub por_periodicidad: Vec<EstadisticasPeriodicidad>,
 }
 
-pub type DbConnection = Arc<Mutex<Connection>>;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EstadisticasPeriodicidad {
+    pub periodicidad: Periodicidad,
+    pub total_recaudado: f64,
+    pub cuotas_pendientes: i32,
+    pub cuotas_pagadas: i32,
+    pub hermanos: i32,
+}
+
+pub use pool::Database;
+pub use documentos::TipoDocumento;
+pub use cuotas::Periodicidad;
+pub use pagos::EstadoPago;
 
-pub fn init_database() -> Result<DbConnection, anyhow::Error> {
+pub type DbConnection = Arc<Database>;
+
+/// Abre la base de datos del perfil activo. Si no se puede (el fichero está en
+/// una memoria USB desconectada o en una carpeta de red sin acceso), abre la
+/// del perfil por defecto, que queda activo, y devuelve también el motivo para
+/// mostrarlo. Solo falla si tampoco puede abrirse esta.
+pub fn init_database() -> Result<(DbConnection, Option<String>), anyhow::Error> {
     println!("Iniciando conexión a la base de datos...");
 
-    // Usar el directorio de datos de la aplicación
+    let data_dir = app_data_dir()?;
+    let mut registro = perfiles::cargar_registro(&data_dir)?;
+    let error = match registro.perfil_activo() {
+        Some(perfil) => {
+            println!("Perfil activo: {}", perfil.nombre);
+            match Database::open(Path::new(&perfil.ruta)) {
+                Ok(db) => {
+                    println!("Base de datos inicializada correctamente.");
+                    return Ok((Arc::new(db), None));
+                }
+                Err(e) => format!(
+                    "No se pudo abrir la base de datos del perfil «{}» ({}): {:#}",
+                    perfil.nombre, perfil.ruta, e
+                ),
+            }
+        }
+        None => format!("El perfil activo «{}» no existe en el registro de perfiles", registro.activo),
+    };
+    let activo = registro.activo.clone();
+
+    let perfil = perfiles::activar_perfil_por_defecto(&data_dir, &mut registro)?;
+    if perfil.nombre == activo {
+        return Err(anyhow::anyhow!(error));
+    }
+    eprintln!("Advertencia: {}. Se abre el perfil {}.", error, perfil.nombre);
+    let db = Database::open(Path::new(&perfil.ruta))
+        .with_context(|| format!("{}; tampoco se pudo abrir el perfil «{}»", error, perfil.nombre))?;
+
+    println!("Base de datos inicializada correctamente.");
+
+    let aviso = format!(
+        "{}. Se ha abierto el perfil «{}»; cuando la base de datos vuelva a estar disponible, cambie de perfil.",
+        error, perfil.nombre
+    );
+    Ok((Arc::new(db), Some(aviso)))
+}
+
+/// Directorio de datos de la aplicación, donde viven la base de datos por
+/// defecto y el registro de perfiles.
+pub fn app_data_dir() -> Result<PathBuf, anyhow::Error> {
     let app_data_dir = std::env::var("APPDATA")
         .or_else(|_| std::env::var("HOME").map(|home| format!("{}/.local/share", home)))
         .unwrap_or_else(|_| ".".to_string());
-    
-    let db_dir = format!("{}/hermanar", app_data_dir);
-    std::fs::create_dir_all(&db_dir)
+
+    let data_dir = Path::new(&app_data_dir).join("hermanar");
+    std::fs::create_dir_all(&data_dir)
         .context("No se pudo crear el directorio de datos")?;
-    
-    let db_path = format!("{}/hermanar.db", db_dir);
-    println!("Ruta de la base de datos: {}", db_path);
 
-    let conn = Connection::open(&db_path)
+    Ok(data_dir)
+}
+
+/// Abre (o crea) la base de datos de `db_path`, la migra al esquema actual y
+/// toma las copias de seguridad automáticas. Devuelve la conexión de escritura.
+pub fn open_database(db_path: &Path) -> Result<Connection, anyhow::Error> {
+    println!("Ruta de la base de datos: {}", db_path.display());
+
+    let mut conn = Connection::open(db_path)
         .context("No se pudo crear/abrir la base de datos")?;
 
-    println!("Conexión establecida, creando tablas...");
+    println!("Conexión establecida, aplicando migraciones...");
 
-    create_tables(&conn)?;
+    migrations::run_migrations(&mut conn, db_path)?;
+    configurar_conexion(&conn)?;
 
-    println!("Base de datos inicializada correctamente.");
+    // WAL permite leer mientras se escribe. Es persistente en el fichero, pero
+    // no está disponible en algunas carpetas de red; en ese caso se sigue con
+    // el diario clásico.
+    let modo: String = conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+    if !modo.eq_ignore_ascii_case("wal") {
+        eprintln!("Advertencia: No se pudo activar el modo WAL (modo actual: {})", modo);
+    }
 
-    Ok(Arc::new(Mutex::new(conn)))
+    if let Err(e) = backup::rotate_backups(&conn, db_path) {
+        eprintln!("Advertencia: No se pudieron crear las copias de seguridad automáticas: {}", e);
+    }
+
+    Ok(conn)
 }
 
-fn create_tables(conn: &Connection) -> Result<(), anyhow::Error> {
-    // Tabla de familias
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS familias (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            nombre_familia TEXT NOT NULL UNIQUE,
-            hermano_direccion_id INTEGER,
-            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
-            updated_at TEXT DEFAULT CURRENT_TIMESTAMP
-        )",
-        [],
-    )?;
-
-    // Tabla de hermanos
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS hermanos (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            numero_hermano TEXT NOT NULL UNIQUE,
-            nombre TEXT NOT NULL,
-            primer_apellido TEXT NOT NULL,
-            segundo_apellido TEXT,
-            dni TEXT,
-            fecha_nacimiento TEXT,
-            localidad_nacimiento TEXT,
-            provincia_nacimiento TEXT,
-            fecha_alta TEXT NOT NULL,
-            familia_id INTEGER,
-            telefono TEXT,
-            email TEXT,
-            direccion TEXT,
-            localidad TEXT,
-            provincia TEXT,
-            codigo_postal TEXT,
-            parroquia_bautismo TEXT,
-            localidad_bautismo TEXT,
-            provincia_bautismo TEXT,
-            autorizacion_menores BOOLEAN NOT NULL DEFAULT 0,
-            nombre_representante_legal TEXT,
-            dni_representante_legal TEXT,
-            hermano_aval_1 TEXT,
-            hermano_aval_2 TEXT,
-            activo BOOLEAN NOT NULL DEFAULT 1,
-            observaciones TEXT,
-            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
-            updated_at TEXT DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (familia_id) REFERENCES familias (id)
-        )",
-        [],
-    )?;
-
-    // Tabla de cuotas
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS cuotas (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            hermano_id INTEGER NOT NULL,
-            anio INTEGER NOT NULL,
-            trimestre INTEGER NOT NULL CHECK(trimestre >= 1 AND trimestre <= 4),
-            importe REAL NOT NULL,
-            pagado BOOLEAN NOT NULL DEFAULT 0,
-            fecha_pago TEXT,
-            metodo_pago TEXT,
-            observaciones TEXT,
-            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
-            updated_at TEXT DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (hermano_id) REFERENCES hermanos (id) ON DELETE CASCADE,
-            UNIQUE(hermano_id, anio, trimestre)
-        )",
-        [],
-    )?;
-
-    // Índices para mejorar el rendimiento
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_hermanos_activo ON hermanos(activo)",
-        [],
-    )?;
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_hermanos_familia ON hermanos(familia_id)",
-        [],
-    )?;
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_cuotas_hermano ON cuotas(hermano_id)",
-        [],
-    )?;
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_cuotas_anio ON cuotas(anio)",
-        [],
-    )?;
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_cuotas_pagado ON cuotas(pagado)",
-        [],
-    )?;
+/// Base de datos vacía en memoria con el esquema actual, para las pruebas.
+#[cfg(test)]
+pub(crate) fn base_de_datos_en_memoria() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    migrations::run_migrations(&mut conn, Path::new(":memory:")).unwrap();
+    configurar_conexion(&conn).unwrap();
+    conn
+}
 
+// Ajustes que SQLite guarda por conexión y no en el fichero, por lo que hay que
+// aplicarlos cada vez que se abre una.
+pub(crate) fn configurar_conexion(conn: &Connection) -> Result<(), anyhow::Error> {
+    conn.busy_timeout(Duration::from_secs(5))?;
+    conn.set_prepared_statement_cache_capacity(64);
+    conn.pragma_update(None, "foreign_keys", true)
+        .context("No se pudieron activar las claves foráneas")?;
     Ok(())
 }
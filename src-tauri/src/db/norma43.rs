@@ -0,0 +1,245 @@
+use crate::error::{HermanarError, HermanarResult};
+
+/// Apunte de un extracto bancario en formato Norma 43 (cuaderno 43 de la
+/// AEB/CSB). El importe va en céntimos, positivo para los abonos y negativo
+/// para los cargos.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ApunteNorma43 {
+    pub cuenta: String,
+    pub fecha_operacion: String,
+    pub fecha_valor: String,
+    pub centimos: i64,
+    pub concepto_comun: String,
+    pub concepto_propio: String,
+    pub documento: String,
+    pub referencia_1: String,
+    pub referencia_2: String,
+    /// Textos de los registros complementarios (23), separados por espacios.
+    pub concepto: String,
+}
+
+// Totales del registro final de cuenta (33), para comprobar que el fichero
+// está completo.
+#[derive(Default)]
+struct Totales {
+    apuntes_debe: i64,
+    importe_debe: i64,
+    apuntes_haber: i64,
+    importe_haber: i64,
+}
+
+/// Lee un fichero Norma 43. Los bancos suelen generarlo en ISO-8859-1: si el
+/// contenido no es UTF-8 válido, cada byte se toma como un carácter latino.
+pub(crate) fn leer(contenido: &[u8]) -> HermanarResult<Vec<ApunteNorma43>> {
+    let texto: String = match std::str::from_utf8(contenido) {
+        Ok(texto) => texto.to_string(),
+        Err(_) => contenido.iter().map(|&b| b as char).collect(),
+    };
+
+    let mut apuntes = Vec::new();
+    let mut cuenta: Option<String> = None;
+    let mut totales = Totales::default();
+    let mut fin_fichero = false;
+
+    for (indice, linea) in texto.lines().enumerate() {
+        let linea: Vec<char> = linea.trim_end_matches('\r').chars().collect();
+        if linea.iter().all(|c| c.is_whitespace()) {
+            continue;
+        }
+        let numero_linea = indice + 1;
+        let registro = campo(&linea, 1, 2);
+
+        match registro.as_str() {
+            "11" => {
+                cuenta = Some(format!("{}{}{}", campo(&linea, 3, 6), campo(&linea, 7, 10), campo(&linea, 11, 20)));
+                totales = Totales::default();
+            }
+            "22" => {
+                let Some(cuenta) = cuenta.clone() else {
+                    return Err(error_linea(numero_linea, "apunte sin cabecera de cuenta"));
+                };
+                // Los importes llevan los dos últimos dígitos como decimales
+                let centimos = entero(&linea, 29, 42, numero_linea)?;
+                let centimos = match campo(&linea, 28, 28).as_str() {
+                    "1" => {
+                        totales.apuntes_debe += 1;
+                        totales.importe_debe += centimos;
+                        -centimos
+                    }
+                    "2" => {
+                        totales.apuntes_haber += 1;
+                        totales.importe_haber += centimos;
+                        centimos
+                    }
+                    _ => return Err(error_linea(numero_linea, "clave de debe/haber desconocida")),
+                };
+
+                apuntes.push(ApunteNorma43 {
+                    cuenta,
+                    fecha_operacion: fecha(&linea, 11, numero_linea)?,
+                    fecha_valor: fecha(&linea, 17, numero_linea)?,
+                    centimos,
+                    concepto_comun: campo(&linea, 23, 24),
+                    concepto_propio: campo(&linea, 25, 27),
+                    documento: campo(&linea, 43, 52),
+                    referencia_1: campo(&linea, 53, 64),
+                    referencia_2: campo(&linea, 65, 80),
+                    concepto: String::new(),
+                });
+            }
+            "23" => {
+                let Some(apunte) = apuntes.last_mut() else {
+                    return Err(error_linea(numero_linea, "concepto complementario sin apunte"));
+                };
+                for texto in [campo(&linea, 5, 42), campo(&linea, 43, 80)] {
+                    if !texto.is_empty() {
+                        if !apunte.concepto.is_empty() {
+                            apunte.concepto.push(' ');
+                        }
+                        apunte.concepto.push_str(&texto);
+                    }
+                }
+            }
+            "24" => {}
+            "33" => {
+                let esperados = (
+                    entero(&linea, 21, 25, numero_linea)?,
+                    entero(&linea, 26, 39, numero_linea)?,
+                    entero(&linea, 40, 44, numero_linea)?,
+                    entero(&linea, 45, 58, numero_linea)?,
+                );
+                let leidos = (totales.apuntes_debe, totales.importe_debe, totales.apuntes_haber, totales.importe_haber);
+                if esperados != leidos {
+                    return Err(error_linea(
+                        numero_linea,
+                        "los totales de la cuenta no coinciden con sus apuntes; el fichero puede estar incompleto",
+                    ));
+                }
+                cuenta = None;
+            }
+            "88" => {
+                fin_fichero = true;
+            }
+            otro => {
+                return Err(error_linea(numero_linea, &format!("tipo de registro desconocido '{}'", otro)));
+            }
+        }
+    }
+
+    if !fin_fichero {
+        return Err(HermanarError::validation(
+            "fichero",
+            "El fichero no es un extracto Norma 43 completo (falta el registro de fin de fichero)",
+        ));
+    }
+
+    Ok(apuntes)
+}
+
+fn error_linea(linea: usize, mensaje: &str) -> HermanarError {
+    HermanarError::validation("fichero", format!("Extracto Norma 43 no válido, línea {}: {}", linea, mensaje))
+}
+
+// Campo entre las posiciones `desde` y `hasta` (contando desde 1, ambas
+// incluidas), sin los espacios de relleno.
+fn campo(linea: &[char], desde: usize, hasta: usize) -> String {
+    linea
+        .iter()
+        .skip(desde - 1)
+        .take(hasta + 1 - desde)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn entero(linea: &[char], desde: usize, hasta: usize, numero_linea: usize) -> HermanarResult<i64> {
+    let valor = campo(linea, desde, hasta);
+    if valor.is_empty() || !valor.chars().all(|c| c.is_ascii_digit()) {
+        return Err(error_linea(numero_linea, "se esperaba un número"));
+    }
+
+    valor.parse().map_err(|_| error_linea(numero_linea, "número fuera de rango"))
+}
+
+// Fechas en formato AAMMDD.
+fn fecha(linea: &[char], desde: usize, numero_linea: usize) -> HermanarResult<String> {
+    let valor = campo(linea, desde, desde + 5);
+    let fecha = chrono::NaiveDate::parse_from_str(&format!("20{}", valor), "%Y%m%d")
+        .map_err(|_| error_linea(numero_linea, "fecha no válida"))?;
+
+    Ok(fecha.format("%Y-%m-%d").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXTRACTO: &[&str] = &[
+        "112100041802000513322601012601312000000001000009783HERMANDAD DE LA PENA",
+        "22    041826010526010502015200000000002500000000000100001       CUOTA T1",
+        "2301TRANSFERENCIA DE JOSE NUNEZ           CUOTA HERMANO 00001",
+        "22    0418260110260111171001000000000003500000000002",
+        "22    041826012026012002015200000000001000000000000300002",
+        "3321000418020005133200001000000000003500000200000000003500200000000103150978",
+        "88999999999999999999000007",
+    ];
+
+    fn fichero(lineas: &[&str]) -> Vec<u8> {
+        lineas.join("\r\n").into_bytes()
+    }
+
+    #[test]
+    fn extracto_completo() {
+        let apuntes = leer(&fichero(EXTRACTO)).unwrap();
+        assert_eq!(apuntes.len(), 3);
+
+        assert_eq!(
+            apuntes[0],
+            ApunteNorma43 {
+                cuenta: "210004180200051332".to_string(),
+                fecha_operacion: "2026-01-05".to_string(),
+                fecha_valor: "2026-01-05".to_string(),
+                centimos: 2500,
+                concepto_comun: "02".to_string(),
+                concepto_propio: "015".to_string(),
+                documento: "0000000001".to_string(),
+                referencia_1: "00001".to_string(),
+                referencia_2: "CUOTA T1".to_string(),
+                concepto: "TRANSFERENCIA DE JOSE NUNEZ CUOTA HERMANO 00001".to_string(),
+            }
+        );
+        assert_eq!((apuntes[1].centimos, apuntes[1].fecha_valor.as_str()), (-350, "2026-01-11"));
+        assert_eq!((apuntes[1].referencia_1.as_str(), apuntes[1].concepto.as_str()), ("", ""));
+        assert_eq!((apuntes[2].centimos, apuntes[2].referencia_1.as_str()), (1000, "00002"));
+    }
+
+    #[test]
+    fn latin_1() {
+        let mut contenido = fichero(EXTRACTO);
+        let posicion = contenido.windows(5).position(|w| w == b"NUNEZ").unwrap();
+        contenido[posicion + 2] = 0xD1;
+
+        let apuntes = leer(&contenido).unwrap();
+        assert!(apuntes[0].concepto.starts_with("TRANSFERENCIA DE JOSE NUÑEZ"));
+    }
+
+    #[test]
+    fn extracto_no_valido() {
+        let error = |lineas: &[&str]| leer(&fichero(lineas)).unwrap_err().to_string();
+
+        assert!(error(&EXTRACTO[..6]).contains("fin de fichero"));
+        assert!(error(&EXTRACTO[1..]).contains("línea 1"));
+
+        let mut totales = EXTRACTO.to_vec();
+        totales[5] = "3321000418020005133200001000000000003500000100000000003500200000000103150978";
+        assert!(error(&totales).contains("línea 6"));
+
+        let mut importe = EXTRACTO.to_vec();
+        importe[1] = "22    04182601052601050201520000000000X500000000000100001       CUOTA T1";
+        assert!(error(&importe).contains("se esperaba un número"));
+
+        let mut desconocido = EXTRACTO.to_vec();
+        desconocido[2] = "99";
+        assert!(error(&desconocido).contains("'99'"));
+    }
+}
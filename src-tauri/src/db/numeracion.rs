@@ -0,0 +1,673 @@
+use chrono::Datelike;
+use rusqlite::{params, Connection, OptionalExtension, Result, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use crate::db::{configuracion, DbConnection, Hermano};
+use crate::db::auditoria::{self, ENTIDAD_HERMANO};
+use crate::db::hermanos::{buscar_hermano, listar_hermanos};
+use crate::error::{HermanarError, HermanarResult};
+
+/// Criterio para ordenar a los hermanos que tienen la misma fecha de alta.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CriterioDesempate {
+    /// El de mayor edad primero; sin fecha de nacimiento, al final.
+    FechaNacimiento,
+    /// Se mantiene el orden de la numeración actual.
+    NumeroActual,
+    /// Orden alfabético por apellidos y nombre.
+    Apellidos,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpcionesRenumeracion {
+    /// Año al que corresponde la numeración en el historial. Por defecto, el actual.
+    pub anio: Option<i32>,
+    /// Criterios de desempate, en orden de prioridad. Si todo coincide se
+    /// ordena por el orden de registro en la aplicación.
+    #[serde(default)]
+    pub desempates: Vec<CriterioDesempate>,
+    /// Renumera también a los hermanos dados de baja, a continuación de los
+    /// activos. Si no, las bajas conservan su número y quedan fuera.
+    #[serde(default)]
+    pub incluir_bajas: bool,
+}
+
+/// Número actual y propuesto de un hermano en una renumeración.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CambioNumero {
+    pub hermano_id: i32,
+    pub nombre_completo: String,
+    pub fecha_alta: String,
+    pub activo: bool,
+    pub numero_actual: String,
+    pub numero_nuevo: String,
+}
+
+impl CambioNumero {
+    pub fn cambia(&self) -> bool {
+        self.numero_actual != self.numero_nuevo
+    }
+}
+
+/// Número que tuvo un hermano tras la renumeración de un año.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NumeroHistorico {
+    pub anio: i32,
+    pub numero_anterior: Option<String>,
+    pub numero_hermano: String,
+    pub fecha: Option<String>,
+}
+
+impl NumeroHistorico {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        Ok(NumeroHistorico {
+            anio: row.get(0)?,
+            numero_anterior: row.get(1)?,
+            numero_hermano: row.get(2)?,
+            fecha: row.get(3)?,
+        })
+    }
+}
+
+/// Calcula la nueva numeración sin aplicarla.
+pub fn preview_renumeracion(db: &DbConnection, opciones: &OpcionesRenumeracion) -> HermanarResult<Vec<CambioNumero>> {
+    calcular_renumeracion(&db.reader(), opciones)
+}
+
+/// Renumera a los hermanos por antigüedad en una única transacción: o se
+/// aplican todos los cambios o ninguno. La numeración se vuelve a calcular
+/// dentro de la transacción, así que no importa si la vista previa se quedó
+/// desfasada.
+pub fn aplicar_renumeracion(db: &DbConnection, opciones: &OpcionesRenumeracion) -> HermanarResult<Vec<CambioNumero>> {
+    let anio = opciones.anio.unwrap_or_else(|| chrono::Local::now().year());
+
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+    let operador = db.operador();
+
+    let cambios = calcular_renumeracion(&tx, opciones)?;
+    let pendientes: Vec<&CambioNumero> = cambios.iter().filter(|c| c.cambia()).collect();
+    let anteriores = pendientes
+        .iter()
+        .map(|c| buscar_hermano(&tx, c.hermano_id))
+        .collect::<HermanarResult<Vec<_>>>()?;
+
+    // En dos pasos para no chocar con la restricción UNIQUE mientras unos
+    // hermanos ocupan el número que van a dejar libre otros
+    for cambio in &pendientes {
+        tx.prepare_cached("UPDATE hermanos SET numero_hermano = '~' || id WHERE id = ?1")?
+            .execute([cambio.hermano_id])?;
+    }
+    for cambio in &pendientes {
+        tx.prepare_cached(
+            "UPDATE hermanos SET numero_hermano = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2"
+        )?
+        .execute(params![cambio.numero_nuevo, cambio.hermano_id])?;
+    }
+
+    for (cambio, antes) in pendientes.iter().zip(&anteriores) {
+        let despues = buscar_hermano(&tx, cambio.hermano_id)?;
+        auditoria::registrar(&tx, &operador, ENTIDAD_HERMANO, cambio.hermano_id, antes.as_ref(), despues.as_ref())?;
+    }
+
+    for cambio in &cambios {
+        tx.prepare_cached(
+            "INSERT INTO historial_numeros (hermano_id, anio, numero_anterior, numero_hermano)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(hermano_id, anio) DO UPDATE SET
+                numero_hermano = excluded.numero_hermano,
+                fecha = CURRENT_TIMESTAMP"
+        )?
+        .execute(params![cambio.hermano_id, anio, cambio.numero_actual, cambio.numero_nuevo])?;
+    }
+
+    tx.commit()?;
+
+    Ok(cambios)
+}
+
+// Los hermanos activos se numeran por fecha de alta y desempates. Con
+// `incluir_bajas` los dados de baja conservan su orden relativo a
+// continuación, para que sus números no interfieran con los de los activos; si
+// no, no se tocan y los activos se numeran saltando los números que ocupan.
+fn calcular_renumeracion(conn: &Connection, opciones: &OpcionesRenumeracion) -> HermanarResult<Vec<CambioNumero>> {
+    let politica = PoliticaNumeracion::cargar(conn)?;
+    let (mut hermanos, bajas): (Vec<Hermano>, Vec<Hermano>) = listar_hermanos(conn)?
+        .into_iter()
+        .partition(|hermano| hermano.activo || opciones.incluir_bajas);
+
+    hermanos.sort_by(|a, b| {
+        b.activo.cmp(&a.activo).then_with(|| {
+            if a.activo {
+                comparar_antiguedad(a, b, &opciones.desempates)
+            } else {
+                comparar_numero(a, b)
+            }
+        })
+    });
+
+    let ocupados: HashSet<&str> = bajas.iter().map(|hermano| hermano.numero_hermano.as_str()).collect();
+    let valores_ocupados: HashSet<u64> =
+        bajas.iter().filter_map(|hermano| politica.valor(&hermano.numero_hermano)).collect();
+    let mut siguiente = 0;
+    let mut cambios = Vec::with_capacity(hermanos.len());
+    for hermano in hermanos {
+        let numero_nuevo = loop {
+            siguiente += 1;
+            let numero = politica.formatear(siguiente);
+            if !ocupados.contains(numero.as_str()) && !valores_ocupados.contains(&siguiente) {
+                break numero;
+            }
+        };
+        cambios.push(CambioNumero {
+            hermano_id: hermano.id.unwrap_or_default(),
+            nombre_completo: nombre_completo(&hermano),
+            fecha_alta: hermano.fecha_alta,
+            activo: hermano.activo,
+            numero_nuevo,
+            numero_actual: hermano.numero_hermano,
+        });
+    }
+
+    Ok(cambios)
+}
+
+fn comparar_antiguedad(a: &Hermano, b: &Hermano, desempates: &[CriterioDesempate]) -> Ordering {
+    let mut orden = a.fecha_alta.cmp(&b.fecha_alta);
+
+    for criterio in desempates {
+        orden = orden.then_with(|| match criterio {
+            CriterioDesempate::FechaNacimiento => {
+                match (a.fecha_nacimiento.as_deref(), b.fecha_nacimiento.as_deref()) {
+                    (Some(x), Some(y)) => x.cmp(y),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                }
+            }
+            CriterioDesempate::NumeroActual => comparar_numero(a, b),
+            CriterioDesempate::Apellidos => (&a.primer_apellido, &a.segundo_apellido, &a.nombre)
+                .cmp(&(&b.primer_apellido, &b.segundo_apellido, &b.nombre)),
+        });
+    }
+
+    orden.then_with(|| a.id.cmp(&b.id))
+}
+
+// Compara números de hermano por su valor numérico cuando lo tienen, para que
+// "00010" no quede antes que "9"
+fn comparar_numero(a: &Hermano, b: &Hermano) -> Ordering {
+    match (a.numero_hermano.parse::<u64>(), b.numero_hermano.parse::<u64>()) {
+        (Ok(x), Ok(y)) => x.cmp(&y),
+        _ => a.numero_hermano.cmp(&b.numero_hermano),
+    }
+    .then_with(|| a.id.cmp(&b.id))
+}
+
+pub(crate) fn nombre_completo(hermano: &Hermano) -> String {
+    format!(
+        "{} {} {}",
+        hermano.nombre,
+        hermano.primer_apellido,
+        hermano.segundo_apellido.as_deref().unwrap_or_default()
+    )
+    .trim()
+    .to_string()
+}
+
+pub fn get_historial_numeros(db: &DbConnection, hermano_id: i32) -> HermanarResult<Vec<NumeroHistorico>> {
+    buscar_historial_numeros(&db.reader(), hermano_id)
+}
+
+pub(crate) fn buscar_historial_numeros(conn: &Connection, hermano_id: i32) -> HermanarResult<Vec<NumeroHistorico>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT anio, numero_anterior, numero_hermano, fecha
+         FROM historial_numeros
+         WHERE hermano_id = ?1
+         ORDER BY anio DESC"
+    )?;
+
+    let historial = stmt.query_map([hermano_id], |row| {
+        NumeroHistorico::from_row(row)
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(historial)
+}
+
+/// Vuelve a guardar el historial de números de un hermano restaurado.
+pub(crate) fn reinsertar_historial_numeros(
+    conn: &Connection,
+    hermano_id: i32,
+    historial: &[NumeroHistorico],
+) -> HermanarResult<()> {
+    for numero in historial {
+        conn.prepare_cached(
+            "INSERT OR IGNORE INTO historial_numeros (hermano_id, anio, numero_anterior, numero_hermano, fecha)
+             VALUES (?1, ?2, ?3, ?4, COALESCE(?5, CURRENT_TIMESTAMP))"
+        )?
+        .execute(params![hermano_id, numero.anio, numero.numero_anterior, numero.numero_hermano, numero.fecha])?;
+    }
+
+    Ok(())
+}
+
+/// Números de todos los hermanos en la renumeración de un año.
+pub fn get_numeracion_anio(db: &DbConnection, anio: i32) -> HermanarResult<Vec<CambioNumero>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(
+        "SELECT h.id, h.nombre, h.primer_apellido, h.segundo_apellido, h.fecha_alta, h.activo,
+                n.numero_anterior, n.numero_hermano
+         FROM historial_numeros n
+         JOIN hermanos h ON h.id = n.hermano_id
+         WHERE n.anio = ?1
+         ORDER BY CAST(n.numero_hermano AS INTEGER), n.numero_hermano"
+    )?;
+
+    let numeracion = stmt.query_map([anio], |row| {
+        let nombre: String = row.get(1)?;
+        let primer_apellido: String = row.get(2)?;
+        let segundo_apellido: Option<String> = row.get(3)?;
+        Ok(CambioNumero {
+            hermano_id: row.get(0)?,
+            nombre_completo: format!("{} {} {}", nombre, primer_apellido, segundo_apellido.unwrap_or_default())
+                .trim()
+                .to_string(),
+            fecha_alta: row.get(4)?,
+            activo: row.get(5)?,
+            numero_actual: row.get::<_, Option<String>>(6)?.unwrap_or_default(),
+            numero_nuevo: row.get(7)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(numeracion)
+}
+
+const CLAVE_MODO: &str = "numeracion_modo";
+const CLAVE_ANCHO: &str = "numeracion_ancho";
+const CLAVE_PREFIJO: &str = "numeracion_prefijo";
+
+/// Cómo se elige el número de un hermano nuevo cuando no se indica ninguno.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModoNumeracion {
+    /// El menor número que no está en uso ni reservado, aprovechando huecos.
+    SiguienteLibre,
+    /// Uno más que el mayor número en uso o reservado.
+    MaximoMasUno,
+}
+
+impl ModoNumeracion {
+    fn clave(&self) -> &'static str {
+        match self {
+            ModoNumeracion::SiguienteLibre => "siguiente_libre",
+            ModoNumeracion::MaximoMasUno => "maximo_mas_uno",
+        }
+    }
+}
+
+/// Formato y forma de asignación de los números de hermano, guardados en la
+/// tabla de configuración.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoliticaNumeracion {
+    pub modo: ModoNumeracion,
+    /// Cifras mínimas; los números más cortos se completan con ceros.
+    pub ancho: usize,
+    pub prefijo: String,
+}
+
+impl Default for PoliticaNumeracion {
+    fn default() -> Self {
+        PoliticaNumeracion {
+            modo: ModoNumeracion::MaximoMasUno,
+            ancho: 5,
+            prefijo: String::new(),
+        }
+    }
+}
+
+impl PoliticaNumeracion {
+    pub(crate) fn cargar(conn: &Connection) -> HermanarResult<Self> {
+        let por_defecto = PoliticaNumeracion::default();
+
+        let modo = match configuracion::leer(conn, CLAVE_MODO)?.as_deref() {
+            Some("siguiente_libre") => ModoNumeracion::SiguienteLibre,
+            Some("maximo_mas_uno") => ModoNumeracion::MaximoMasUno,
+            _ => por_defecto.modo,
+        };
+        let ancho = configuracion::leer_entero(conn, CLAVE_ANCHO, por_defecto.ancho as i64)? as usize;
+        let prefijo = configuracion::leer(conn, CLAVE_PREFIJO)?.unwrap_or(por_defecto.prefijo);
+
+        Ok(PoliticaNumeracion { modo, ancho, prefijo })
+    }
+
+    pub(crate) fn formatear(&self, valor: u64) -> String {
+        format!("{}{:0ancho$}", self.prefijo, valor, ancho = self.ancho)
+    }
+
+    /// Valor numérico de un número con el formato de esta política.
+    fn valor(&self, numero: &str) -> Option<u64> {
+        numero.strip_prefix(self.prefijo.as_str()).and_then(valor_cifras)
+    }
+
+    /// Acepta el número con o sin prefijo y sin los ceros a la izquierda, y lo
+    /// devuelve con el formato completo: con prefijo "H" y ancho 5, "12" y
+    /// "H12" pasan a ser "H00012".
+    pub(crate) fn normalizar(&self, numero: &str) -> HermanarResult<String> {
+        let numero = numero.trim();
+        let cifras = numero.strip_prefix(self.prefijo.as_str()).unwrap_or(numero);
+
+        match valor_cifras(cifras) {
+            Some(valor) if valor > 0 => Ok(self.formatear(valor)),
+            _ if self.prefijo.is_empty() => Err(HermanarError::validation(
+                "numero_hermano",
+                "El número de hermano debe ser un número positivo",
+            )),
+            _ => Err(HermanarError::validation(
+                "numero_hermano",
+                format!("El número de hermano debe ser un número positivo, opcionalmente precedido de '{}'", self.prefijo),
+            )),
+        }
+    }
+
+    fn validar(&self) -> HermanarResult<()> {
+        if !(1..=10).contains(&self.ancho) {
+            return Err(HermanarError::validation("ancho", "El ancho debe estar entre 1 y 10 cifras"));
+        }
+        if self.prefijo.len() > 10
+            || self.prefijo.chars().any(|c| c.is_ascii_digit() || c.is_whitespace())
+        {
+            return Err(HermanarError::validation(
+                "prefijo",
+                "El prefijo no puede contener cifras ni espacios y tiene un máximo de 10 caracteres",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn valor_cifras(cifras: &str) -> Option<u64> {
+    if cifras.is_empty() || !cifras.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    cifras.parse().ok()
+}
+
+pub fn get_politica_numeracion(db: &DbConnection) -> HermanarResult<PoliticaNumeracion> {
+    PoliticaNumeracion::cargar(&db.reader())
+}
+
+/// Cambia la política de numeración. Los números ya asignados no se tocan;
+/// para darles el nuevo formato hay que renumerar.
+pub fn set_politica_numeracion(db: &DbConnection, politica: &PoliticaNumeracion) -> HermanarResult<()> {
+    politica.validar()?;
+
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+
+    configuracion::guardar(&tx, CLAVE_MODO, politica.modo.clave())?;
+    configuracion::guardar(&tx, CLAVE_ANCHO, &politica.ancho.to_string())?;
+    configuracion::guardar(&tx, CLAVE_PREFIJO, &politica.prefijo)?;
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Número que recibiría ahora mismo un hermano nuevo sin número indicado.
+pub fn get_siguiente_numero(db: &DbConnection) -> HermanarResult<String> {
+    let conn = db.reader();
+    let politica = PoliticaNumeracion::cargar(&conn)?;
+    siguiente_numero(&conn, &politica)
+}
+
+fn siguiente_numero(conn: &Connection, politica: &PoliticaNumeracion) -> HermanarResult<String> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT numero_hermano FROM hermanos
+         UNION ALL
+         SELECT numero FROM reservas_numero"
+    )?;
+    let mut usados = stmt.query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .filter_map(|numero| politica.valor(numero))
+        .collect::<Vec<_>>();
+    usados.sort_unstable();
+    usados.dedup();
+
+    let siguiente = match politica.modo {
+        ModoNumeracion::MaximoMasUno => usados.last().copied().unwrap_or(0) + 1,
+        ModoNumeracion::SiguienteLibre => {
+            let mut candidato = 1;
+            for valor in usados {
+                if valor > candidato {
+                    break;
+                }
+                if valor == candidato {
+                    candidato += 1;
+                }
+            }
+            candidato
+        }
+    };
+
+    Ok(politica.formatear(siguiente))
+}
+
+/// Número definitivo para un hermano nuevo. Sin número solicitado se asigna
+/// uno según la política; con él, se normaliza y se comprueba que está libre.
+/// Si el número estaba reservado, la reserva se consume.
+///
+/// Debe llamarse dentro de la transacción (inmediata) que inserta al hermano,
+/// de modo que dos altas simultáneas no puedan obtener el mismo número.
+pub(crate) fn numero_para_alta(conn: &Connection, solicitado: &str) -> HermanarResult<String> {
+    let politica = PoliticaNumeracion::cargar(conn)?;
+
+    let numero = if solicitado.trim().is_empty() {
+        siguiente_numero(conn, &politica)?
+    } else {
+        let numero = politica.normalizar(solicitado)?;
+        comprobar_numero_libre(conn, &numero, None)?;
+        numero
+    };
+
+    conn.prepare_cached("DELETE FROM reservas_numero WHERE numero = ?1")?
+        .execute([&numero])?;
+
+    Ok(numero)
+}
+
+/// Número normalizado para un hermano existente cuyo número se edita a mano.
+/// Un número que no cambia se respeta aunque tenga un formato anterior a la
+/// política actual.
+pub(crate) fn numero_para_actualizar(conn: &Connection, hermano_id: i32, solicitado: &str) -> HermanarResult<String> {
+    let actual: Option<String> = conn
+        .prepare_cached("SELECT numero_hermano FROM hermanos WHERE id = ?1")?
+        .query_row([hermano_id], |row| row.get(0))
+        .optional()?;
+    if actual.as_deref() == Some(solicitado.trim()) {
+        return Ok(solicitado.trim().to_string());
+    }
+
+    let numero = PoliticaNumeracion::cargar(conn)?.normalizar(solicitado)?;
+    if actual.as_deref() == Some(numero.as_str()) {
+        return Ok(numero);
+    }
+
+    comprobar_numero_libre(conn, &numero, Some(hermano_id))?;
+    if reserva_de(conn, &numero)?.is_some() {
+        return Err(HermanarError::conflict(format!(
+            "El número {} está reservado para una solicitud pendiente",
+            numero
+        )));
+    }
+
+    Ok(numero)
+}
+
+fn comprobar_numero_libre(conn: &Connection, numero: &str, excepto: Option<i32>) -> HermanarResult<()> {
+    let ocupado: Option<(i32, String)> = conn
+        .prepare_cached(
+            "SELECT id, nombre || ' ' || primer_apellido FROM hermanos
+             WHERE numero_hermano = ?1 AND (?2 IS NULL OR id != ?2)"
+        )?
+        .query_row(params![numero, excepto], |row| Ok((row.get(0)?, row.get(1)?)))
+        .optional()?;
+
+    match ocupado {
+        Some((_, nombre)) => Err(HermanarError::validation(
+            "numero_hermano",
+            format!("El número {} ya está asignado a {}", numero, nombre),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Número apartado para una solicitud de ingreso pendiente.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReservaNumero {
+    pub numero: String,
+    pub solicitante: String,
+    pub observaciones: Option<String>,
+    pub fecha: Option<String>,
+}
+
+impl ReservaNumero {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        Ok(ReservaNumero {
+            numero: row.get(0)?,
+            solicitante: row.get(1)?,
+            observaciones: row.get(2)?,
+            fecha: row.get(3)?,
+        })
+    }
+}
+
+fn reserva_de(conn: &Connection, numero: &str) -> HermanarResult<Option<ReservaNumero>> {
+    let reserva = conn
+        .prepare_cached("SELECT numero, solicitante, observaciones, fecha FROM reservas_numero WHERE numero = ?1")?
+        .query_row([numero], ReservaNumero::from_row)
+        .optional()?;
+
+    Ok(reserva)
+}
+
+pub fn list_reservas(db: &DbConnection) -> HermanarResult<Vec<ReservaNumero>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(
+        "SELECT numero, solicitante, observaciones, fecha
+         FROM reservas_numero
+         ORDER BY fecha, numero"
+    )?;
+
+    let reservas = stmt.query_map([], |row| {
+        ReservaNumero::from_row(row)
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(reservas)
+}
+
+/// Reserva un número para una solicitud pendiente. Sin número se reserva el
+/// siguiente según la política. La reserva se consume al dar de alta al
+/// hermano con ese número. Una renumeración no tiene en cuenta las reservas,
+/// así que conviene revisarlas después de renumerar.
+pub fn reservar_numero(
+    db: &DbConnection,
+    solicitante: &str,
+    numero: Option<&str>,
+    observaciones: Option<&str>,
+) -> HermanarResult<ReservaNumero> {
+    let solicitante = solicitante.trim();
+    if solicitante.is_empty() {
+        return Err(HermanarError::validation("solicitante", "El nombre del solicitante es obligatorio"));
+    }
+
+    let mut conn = db.writer();
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let politica = PoliticaNumeracion::cargar(&tx)?;
+
+    let numero = match numero.map(str::trim).filter(|n| !n.is_empty()) {
+        Some(numero) => {
+            let numero = politica.normalizar(numero)?;
+            comprobar_numero_libre(&tx, &numero, None)?;
+            if let Some(reserva) = reserva_de(&tx, &numero)? {
+                return Err(HermanarError::validation(
+                    "numero",
+                    format!("El número {} ya está reservado para {}", numero, reserva.solicitante),
+                ));
+            }
+            numero
+        }
+        None => siguiente_numero(&tx, &politica)?,
+    };
+
+    tx.prepare_cached(
+        "INSERT INTO reservas_numero (numero, solicitante, observaciones) VALUES (?1, ?2, ?3)"
+    )?
+    .execute(params![numero, solicitante, observaciones.map(str::trim).filter(|o| !o.is_empty())])?;
+
+    let reserva = reserva_de(&tx, &numero)?.ok_or_else(|| HermanarError::not_found("Reserva"))?;
+    tx.commit()?;
+
+    Ok(reserva)
+}
+
+pub fn liberar_reserva(db: &DbConnection, numero: &str) -> HermanarResult<()> {
+    let conn = db.writer();
+
+    let eliminadas = conn
+        .prepare_cached("DELETE FROM reservas_numero WHERE numero = ?1")?
+        .execute([numero.trim()])?;
+
+    if eliminadas == 0 {
+        return Err(HermanarError::not_found("Reserva"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::base_de_datos_en_memoria;
+
+    #[test]
+    fn renumeracion_sin_bajas() {
+        let conn = base_de_datos_en_memoria();
+        conn.execute_batch(
+            "INSERT INTO hermanos (id, numero_hermano, nombre, primer_apellido, fecha_alta, activo) VALUES
+                 (1, '00001', 'Ana', 'Ruiz', '2010-01-01', 1),
+                 (2, '00002', 'Luis', 'Gil', '2000-01-01', 0),
+                 (3, '00003', 'José', 'Núñez', '2005-01-01', 1),
+                 (4, '00004', 'Eva', 'Díaz', '1995-01-01', 1);",
+        )
+        .unwrap();
+        let numeracion = |incluir_bajas: bool| -> Vec<(i32, String)> {
+            let opciones = OpcionesRenumeracion { anio: Some(2026), desempates: Vec::new(), incluir_bajas };
+            calcular_renumeracion(&conn, &opciones)
+                .unwrap()
+                .into_iter()
+                .map(|cambio| (cambio.hermano_id, cambio.numero_nuevo))
+                .collect()
+        };
+
+        // La baja conserva su número, que los activos se saltan
+        assert_eq!(
+            numeracion(false),
+            [(4, "00001".to_string()), (3, "00003".to_string()), (1, "00004".to_string())]
+        );
+        assert_eq!(
+            numeracion(true),
+            [
+                (4, "00001".to_string()),
+                (3, "00002".to_string()),
+                (1, "00003".to_string()),
+                (2, "00004".to_string()),
+            ]
+        );
+    }
+}
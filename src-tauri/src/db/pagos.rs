@@ -0,0 +1,519 @@
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+use crate::db::{recibos, Cuota, DbConnection};
+use crate::db::auditoria::{self, ENTIDAD_CUOTA};
+use crate::db::cuotas::buscar_cuota;
+use crate::db::membresia::{hoy, validar_fecha};
+use crate::error::{HermanarError, HermanarResult};
+
+/// Situación de cobro de una cuota según los pagos vigentes que tiene.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EstadoPago {
+    #[default]
+    Pendiente,
+    Parcial,
+    Pagada,
+}
+
+impl EstadoPago {
+    pub(crate) fn clave(&self) -> &'static str {
+        match self {
+            EstadoPago::Pendiente => "pendiente",
+            EstadoPago::Parcial => "parcial",
+            EstadoPago::Pagada => "pagada",
+        }
+    }
+}
+
+/// Pago anotado a una cuota. Una cuota puede pagarse en varias veces y cada
+/// pago lleva su propio recibo. Los pagos anulados, por una devolución o una
+/// corrección, se conservan pero no cuentan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pago {
+    pub id: Option<i64>,
+    pub cuota_id: i32,
+    pub importe: f64,
+    pub fecha: String,
+    pub metodo: String,
+    /// Referencia del cobro en el banco: la de extremo a extremo en las
+    /// domiciliaciones, la del movimiento en las transferencias conciliadas.
+    pub referencia: Option<String>,
+    pub observaciones: Option<String>,
+    pub recibo_id: Option<i64>,
+    #[serde(default)]
+    pub numero_recibo: Option<String>,
+    #[serde(default)]
+    pub anulado: bool,
+    #[serde(default)]
+    pub fecha_anulacion: Option<String>,
+    #[serde(default)]
+    pub operador: String,
+    pub created_at: Option<String>,
+}
+
+const SELECT_PAGOS: &str = "SELECT p.id, p.cuota_id, p.importe, p.fecha, p.metodo, p.referencia, p.observaciones,
+            p.recibo_id, r.anio, r.numero, p.anulado, p.fecha_anulacion, p.operador, p.created_at
+     FROM pagos p
+     LEFT JOIN recibos r ON r.id = p.recibo_id";
+
+impl Pago {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        let anio_recibo: Option<i32> = row.get(8)?;
+        let numero_recibo: Option<i32> = row.get(9)?;
+
+        Ok(Pago {
+            id: Some(row.get(0)?),
+            cuota_id: row.get(1)?,
+            importe: row.get(2)?,
+            fecha: row.get(3)?,
+            metodo: row.get(4)?,
+            referencia: row.get(5)?,
+            observaciones: row.get(6)?,
+            recibo_id: row.get(7)?,
+            numero_recibo: anio_recibo.zip(numero_recibo).map(|(anio, numero)| format!("{}/{:05}", anio, numero)),
+            anulado: row.get(10)?,
+            fecha_anulacion: row.get(11)?,
+            operador: row.get(12)?,
+            created_at: row.get(13)?,
+        })
+    }
+}
+
+/// Datos de un pago que se va a anotar a una cuota.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolicitudPago {
+    pub cuota_id: i32,
+    /// Sin importe se paga todo lo que le queda pendiente a la cuota.
+    pub importe: Option<f64>,
+    pub fecha: String,
+    pub metodo: String,
+    pub referencia: Option<String>,
+    pub observaciones: Option<String>,
+}
+
+/// Pagos de una cuota, anulados incluidos, por orden de fecha.
+pub fn get_pagos_cuota(db: &DbConnection, cuota_id: i32) -> HermanarResult<Vec<Pago>> {
+    buscar_pagos(&db.reader(), "p.cuota_id = ?1", cuota_id)
+}
+
+/// Pagos de todas las cuotas de un hermano, anulados incluidos.
+pub fn get_pagos_hermano(db: &DbConnection, hermano_id: i32) -> HermanarResult<Vec<Pago>> {
+    buscar_pagos_hermano(&db.reader(), hermano_id)
+}
+
+pub(crate) fn buscar_pagos_hermano(conn: &Connection, hermano_id: i32) -> HermanarResult<Vec<Pago>> {
+    buscar_pagos(conn, "p.cuota_id IN (SELECT id FROM cuotas WHERE hermano_id = ?1)", hermano_id)
+}
+
+pub(crate) fn buscar_pagos_cuota(conn: &Connection, cuota_id: i32) -> HermanarResult<Vec<Pago>> {
+    buscar_pagos(conn, "p.cuota_id = ?1", cuota_id)
+}
+
+fn buscar_pagos(conn: &Connection, condicion: &str, id: i32) -> HermanarResult<Vec<Pago>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "{} WHERE {} ORDER BY p.fecha, p.id",
+        SELECT_PAGOS, condicion
+    ))?;
+
+    let pagos = stmt.query_map([id], Pago::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(pagos)
+}
+
+fn buscar_pago(conn: &Connection, id: i64) -> HermanarResult<Option<Pago>> {
+    let mut stmt = conn.prepare_cached(&format!("{} WHERE p.id = ?1", SELECT_PAGOS))?;
+
+    Ok(stmt.query_row([id], Pago::from_row).optional()?)
+}
+
+/// Anota un pago a una cuota y emite su recibo. Devuelve el pago tal como
+/// ha quedado, con el número de recibo.
+pub fn create_pago(db: &DbConnection, solicitud: &SolicitudPago) -> HermanarResult<Pago> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+
+    let id = registrar_pago(&tx, &db.operador(), solicitud)?;
+    let pago = buscar_pago(&tx, id)?.ok_or_else(|| HermanarError::not_found("Pago"))?;
+
+    tx.commit()?;
+
+    Ok(pago)
+}
+
+/// Anula un pago que no llegó a cobrarse o se anotó por error. Su recibo
+/// queda anulado y la cuota vuelve a deber ese importe.
+pub fn anular_pago(db: &DbConnection, id: i64, motivo: Option<String>) -> HermanarResult<()> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+
+    registrar_anulacion(&tx, &db.operador(), id, motivo.as_deref())?;
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Anula un pago como `anular_pago`, anotando `motivo` en sus observaciones,
+/// y registra el cambio de la cuota en la auditoría. Debe llamarse dentro de
+/// una transacción.
+fn registrar_anulacion(conn: &Connection, operador: &str, id: i64, motivo: Option<&str>) -> HermanarResult<()> {
+    let pago = buscar_pago(conn, id)?.ok_or_else(|| HermanarError::not_found("Pago"))?;
+    if pago.anulado {
+        return Err(HermanarError::conflict("El pago ya está anulado"));
+    }
+    let antes = buscar_cuota(conn, pago.cuota_id)?.ok_or_else(|| HermanarError::not_found("Cuota"))?;
+
+    anular(conn, &pago)?;
+    if let Some(motivo) = motivo.map(str::trim).filter(|m| !m.is_empty()) {
+        conn.prepare_cached(
+            "UPDATE pagos
+             SET observaciones = CASE WHEN COALESCE(observaciones, '') = '' THEN ?1 ELSE observaciones || char(10) || ?1 END
+             WHERE id = ?2"
+        )?
+        .execute(params![motivo, id])?;
+    }
+    actualizar_estado(conn, pago.cuota_id)?;
+
+    let despues = buscar_cuota(conn, pago.cuota_id)?;
+    auditoria::registrar(conn, operador, ENTIDAD_CUOTA, pago.cuota_id, Some(&antes), despues.as_ref())?;
+
+    Ok(())
+}
+
+/// Anota un pago a una cuota como `anotar_pago` y registra el cambio de la
+/// cuota en la auditoría. Debe llamarse dentro de una transacción.
+pub(crate) fn registrar_pago(conn: &Connection, operador: &str, solicitud: &SolicitudPago) -> HermanarResult<i64> {
+    let antes = buscar_cuota(conn, solicitud.cuota_id)?.ok_or_else(|| HermanarError::not_found("Cuota"))?;
+    let id = anotar_pago(conn, operador, solicitud)?;
+    let despues = buscar_cuota(conn, solicitud.cuota_id)?;
+    auditoria::registrar(conn, operador, ENTIDAD_CUOTA, solicitud.cuota_id, Some(&antes), despues.as_ref())?;
+
+    Ok(id)
+}
+
+/// Anota un pago a una cuota, emite su recibo y recalcula el estado de la
+/// cuota. Devuelve el id del pago. Debe llamarse dentro de una transacción
+/// que ya anote la cuota en la auditoría.
+pub(crate) fn anotar_pago(conn: &Connection, operador: &str, solicitud: &SolicitudPago) -> HermanarResult<i64> {
+    let fecha = validar_fecha("fecha", &solicitud.fecha)?;
+    let metodo = solicitud.metodo.trim();
+    if metodo.is_empty() {
+        return Err(HermanarError::validation("metodo", "Indique la forma de pago"));
+    }
+
+    let cuota = buscar_cuota(conn, solicitud.cuota_id)?.ok_or_else(|| HermanarError::not_found("Cuota"))?;
+    let pendiente = centimos(cuota.importe_pendiente);
+    if pendiente <= 0 {
+        return Err(HermanarError::conflict(format!("La cuota {} ya está pagada", cuota.etiqueta_periodo())));
+    }
+    let importe = match solicitud.importe {
+        Some(importe) if !importe.is_finite() || centimos(importe) <= 0 => {
+            return Err(HermanarError::validation("importe", "El importe debe ser mayor que cero"));
+        }
+        Some(importe) if centimos(importe) > pendiente => {
+            return Err(HermanarError::validation(
+                "importe",
+                format!("A la cuota solo le quedan {:.2} € por pagar", cuota.importe_pendiente),
+            ));
+        }
+        Some(importe) => centimos(importe),
+        None => pendiente,
+    };
+
+    conn.prepare_cached(
+        "INSERT INTO pagos (cuota_id, importe, fecha, metodo, referencia, observaciones, operador)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"
+    )?
+    .execute(params![
+        solicitud.cuota_id,
+        importe as f64 / 100.0,
+        fecha,
+        metodo,
+        solicitud.referencia.as_deref().map(str::trim).filter(|r| !r.is_empty()),
+        solicitud.observaciones,
+        operador,
+    ])?;
+    let id = conn.last_insert_rowid();
+
+    let recibo_id = recibos::emitir_recibo(conn, operador, id)?;
+    conn.prepare_cached("UPDATE pagos SET recibo_id = ?1 WHERE id = ?2")?
+        .execute(params![recibo_id, id])?;
+    actualizar_estado(conn, solicitud.cuota_id)?;
+
+    Ok(id)
+}
+
+/// Anula los pagos de una cuota cobrados con la referencia `referencia`, que
+/// el banco ha devuelto, sumando `recargo` al importe de la cuota y añadiendo
+/// `nota` a sus observaciones. Debe llamarse dentro de una transacción.
+pub(crate) fn revertir_pago(
+    conn: &Connection,
+    operador: &str,
+    cuota_id: i32,
+    referencia: &str,
+    recargo: f64,
+    nota: &str,
+) -> HermanarResult<()> {
+    let antes = buscar_cuota(conn, cuota_id)?.ok_or_else(|| HermanarError::not_found("Cuota"))?;
+
+    for pago in buscar_pagos_cuota(conn, cuota_id)? {
+        if !pago.anulado && pago.referencia.as_deref() == Some(referencia) {
+            anular(conn, &pago)?;
+        }
+    }
+    conn.prepare_cached(
+        "UPDATE cuotas
+         SET importe = ROUND(importe + ?1, 2),
+             observaciones = CASE WHEN COALESCE(observaciones, '') = '' THEN ?2 ELSE observaciones || char(10) || ?2 END,
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = ?3"
+    )?
+    .execute(params![recargo, nota, cuota_id])?;
+    actualizar_estado(conn, cuota_id)?;
+
+    let despues = buscar_cuota(conn, cuota_id)?;
+    auditoria::registrar(conn, operador, ENTIDAD_CUOTA, cuota_id, Some(&antes), despues.as_ref())?;
+
+    Ok(())
+}
+
+/// Anula todos los pagos vigentes de una cuota, que deja de estar pagada.
+/// Debe llamarse dentro de una transacción.
+pub(crate) fn anular_pagos_cuota(conn: &Connection, cuota_id: i32) -> HermanarResult<()> {
+    for pago in buscar_pagos_cuota(conn, cuota_id)? {
+        if !pago.anulado {
+            anular(conn, &pago)?;
+        }
+    }
+
+    actualizar_estado(conn, cuota_id)
+}
+
+fn anular(conn: &Connection, pago: &Pago) -> HermanarResult<()> {
+    conn.prepare_cached(
+        "UPDATE pagos SET anulado = 1, fecha_anulacion = CURRENT_TIMESTAMP WHERE id = ?1"
+    )?
+    .execute([pago.id])?;
+    if let Some(recibo_id) = pago.recibo_id {
+        recibos::anular_recibo(conn, recibo_id)?;
+    }
+
+    Ok(())
+}
+
+/// Recalcula `pagado`, `fecha_pago` y `metodo_pago` de una cuota a partir de
+/// sus pagos vigentes: está pagada cuando cubren todo su importe, con la
+/// fecha y la forma del último. Una cuota de 0 € no debe nada y está pagada
+/// sin pagos. Debe llamarse dentro de una transacción.
+pub(crate) fn actualizar_estado(conn: &Connection, cuota_id: i32) -> HermanarResult<()> {
+    let importe: f64 = conn
+        .prepare_cached("SELECT importe FROM cuotas WHERE id = ?1")?
+        .query_row([cuota_id], |row| row.get(0))?;
+    let pagos = buscar_pagos_cuota(conn, cuota_id)?;
+    let vigentes = pagos.iter().filter(|p| !p.anulado);
+    let pagado: i64 = vigentes.clone().map(|p| centimos(p.importe)).sum();
+    let ultimo = vigentes.max_by(|a, b| (&a.fecha, a.id).cmp(&(&b.fecha, b.id)));
+
+    let pagada = pagado >= centimos(importe);
+    let (fecha, metodo) = match ultimo {
+        Some(ultimo) if pagada => (Some(ultimo.fecha.clone()), Some(ultimo.metodo.clone())),
+        _ => (None, None),
+    };
+
+    conn.prepare_cached(
+        "UPDATE cuotas
+         SET pagado = ?1, fecha_pago = ?2, metodo_pago = ?3, updated_at = CURRENT_TIMESTAMP
+         WHERE id = ?4 AND (pagado IS NOT ?1 OR fecha_pago IS NOT ?2 OR metodo_pago IS NOT ?3)"
+    )?
+    .execute(params![pagada, fecha, metodo, cuota_id])?;
+
+    Ok(())
+}
+
+/// Vuelve a insertar los pagos de una cuota restaurada desde la papelera con
+/// sus ids. Las copias anteriores a los pagos solo traen la cuota marcada
+/// como pagada, y se le anota un pago por todo su importe. Debe llamarse
+/// después de reinsertar la cuota.
+pub(crate) fn reinsertar_pagos(conn: &Connection, cuota: &Cuota, pagos: &[Pago]) -> HermanarResult<()> {
+    let cuota_id = cuota.id.unwrap_or_default();
+
+    if pagos.is_empty() && cuota.pagado && cuota.importe > 0.0 {
+        conn.prepare_cached(
+            "INSERT INTO pagos (cuota_id, importe, fecha, metodo, recibo_id, operador)
+             VALUES (?1, ?2, ?3, ?4,
+                     (SELECT id FROM recibos WHERE cuota_id = ?1 AND anulado = 0 ORDER BY id DESC LIMIT 1),
+                     COALESCE((SELECT operador FROM recibos WHERE cuota_id = ?1 ORDER BY id DESC LIMIT 1), ''))"
+        )?
+        .execute(params![
+            cuota_id,
+            cuota.importe,
+            cuota.fecha_pago.clone().unwrap_or_else(hoy),
+            cuota.metodo_pago.as_deref().unwrap_or("efectivo"),
+        ])?;
+    }
+
+    for pago in pagos {
+        conn.prepare_cached(
+            "INSERT INTO pagos
+             (id, cuota_id, importe, fecha, metodo, referencia, observaciones, recibo_id, anulado, fecha_anulacion,
+              operador, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, COALESCE(?12, CURRENT_TIMESTAMP))"
+        )?
+        .execute(params![
+            pago.id,
+            cuota_id,
+            pago.importe,
+            pago.fecha,
+            pago.metodo,
+            pago.referencia,
+            pago.observaciones,
+            pago.recibo_id,
+            pago.anulado,
+            pago.fecha_anulacion,
+            pago.operador,
+            pago.created_at,
+        ])?;
+    }
+
+    actualizar_estado(conn, cuota_id)
+}
+
+fn centimos(importe: f64) -> i64 {
+    (importe * 100.0).round() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::base_de_datos_en_memoria;
+
+    // Hermano con una cuota del primer trimestre de 2026 por `importe`
+    fn con_cuota(importe: f64) -> Connection {
+        let conn = base_de_datos_en_memoria();
+        conn.execute_batch(
+            "INSERT INTO hermanos (id, numero_hermano, nombre, primer_apellido, fecha_alta)
+             VALUES (1, '1', 'José', 'Núñez', '2020-01-01');",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO cuotas (id, hermano_id, anio, periodicidad, periodo, importe) VALUES (1, 1, 2026, 'trimestral', 1, ?1)",
+            [importe],
+        )
+        .unwrap();
+        actualizar_estado(&conn, 1).unwrap();
+        conn
+    }
+
+    fn pagar(conn: &Connection, importe: Option<f64>, fecha: &str) -> HermanarResult<i64> {
+        let solicitud = SolicitudPago {
+            cuota_id: 1,
+            importe,
+            fecha: fecha.to_string(),
+            metodo: "efectivo".to_string(),
+            referencia: None,
+            observaciones: None,
+        };
+        registrar_pago(conn, "prueba", &solicitud)
+    }
+
+    fn cuota(conn: &Connection) -> Cuota {
+        buscar_cuota(conn, 1).unwrap().unwrap()
+    }
+
+    fn recibos_vigentes(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM recibos WHERE anulado = 0", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn pagos_parciales() {
+        let conn = con_cuota(30.0);
+
+        pagar(&conn, Some(10.0), "2026-01-10").unwrap();
+        let parcial = cuota(&conn);
+        assert_eq!(parcial.estado, EstadoPago::Parcial);
+        assert!(!parcial.pagado);
+        assert_eq!(parcial.fecha_pago, None);
+        assert_eq!(centimos(parcial.importe_pendiente), 2000);
+
+        pagar(&conn, Some(15.5), "2026-02-10").unwrap();
+        assert_eq!(cuota(&conn).estado, EstadoPago::Parcial);
+
+        // Sin importe se paga lo que queda
+        pagar(&conn, None, "2026-03-10").unwrap();
+        let pagada = cuota(&conn);
+        assert_eq!(pagada.estado, EstadoPago::Pagada);
+        assert!(pagada.pagado);
+        assert_eq!(pagada.fecha_pago.as_deref(), Some("2026-03-10"));
+        assert_eq!(centimos(pagada.importe_pagado), 3000);
+        assert_eq!(recibos_vigentes(&conn), 3);
+    }
+
+    #[test]
+    fn pago_de_mas() {
+        let conn = con_cuota(30.0);
+        pagar(&conn, Some(20.0), "2026-01-10").unwrap();
+
+        assert!(matches!(
+            pagar(&conn, Some(10.01), "2026-01-11"),
+            Err(HermanarError::Validation { field, .. }) if field == "importe"
+        ));
+        assert!(pagar(&conn, Some(0.0), "2026-01-11").is_err());
+        pagar(&conn, Some(10.0), "2026-01-11").unwrap();
+        assert!(matches!(pagar(&conn, Some(1.0), "2026-01-12"), Err(HermanarError::Conflict { .. })));
+        assert_eq!(buscar_pagos_cuota(&conn, 1).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn anulacion_de_pago() {
+        let conn = con_cuota(30.0);
+        let primero = pagar(&conn, Some(10.0), "2026-01-10").unwrap();
+        let segundo = pagar(&conn, None, "2026-02-10").unwrap();
+        assert_eq!(cuota(&conn).fecha_pago.as_deref(), Some("2026-02-10"));
+
+        registrar_anulacion(&conn, "prueba", segundo, Some("Cheque sin fondos")).unwrap();
+        let parcial = cuota(&conn);
+        assert_eq!(parcial.estado, EstadoPago::Parcial);
+        assert!(!parcial.pagado);
+        assert_eq!(parcial.fecha_pago, None);
+        assert_eq!(parcial.metodo_pago, None);
+        assert_eq!(recibos_vigentes(&conn), 1);
+        let anulado = buscar_pago(&conn, segundo).unwrap().unwrap();
+        assert!(anulado.anulado);
+        assert_eq!(anulado.observaciones.as_deref(), Some("Cheque sin fondos"));
+        assert!(registrar_anulacion(&conn, "prueba", segundo, None).is_err());
+
+        registrar_anulacion(&conn, "prueba", primero, None).unwrap();
+        assert_eq!(cuota(&conn).estado, EstadoPago::Pendiente);
+        assert_eq!(recibos_vigentes(&conn), 0);
+    }
+
+    #[test]
+    fn anulacion_de_todos_los_pagos() {
+        let conn = con_cuota(30.0);
+        pagar(&conn, Some(10.0), "2026-01-10").unwrap();
+        pagar(&conn, None, "2026-02-10").unwrap();
+
+        anular_pagos_cuota(&conn, 1).unwrap();
+        let pendiente = cuota(&conn);
+        assert_eq!(pendiente.estado, EstadoPago::Pendiente);
+        assert_eq!(pendiente.fecha_pago, None);
+        assert_eq!(recibos_vigentes(&conn), 0);
+        assert!(buscar_pagos_cuota(&conn, 1).unwrap().iter().all(|pago| pago.anulado));
+        let recibos: i64 = conn.query_row("SELECT COUNT(*) FROM recibos", [], |row| row.get(0)).unwrap();
+        assert_eq!(recibos, 2);
+    }
+
+    #[test]
+    fn cuota_sin_importe() {
+        let conn = con_cuota(0.0);
+
+        let gratuita = cuota(&conn);
+        assert_eq!(gratuita.estado, EstadoPago::Pagada);
+        assert!(gratuita.pagado);
+        assert_eq!(gratuita.fecha_pago, None);
+        assert!(matches!(pagar(&conn, None, "2026-01-10"), Err(HermanarError::Conflict { .. })));
+        assert_eq!(recibos_vigentes(&conn), 0);
+    }
+}
@@ -0,0 +1,325 @@
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+use crate::db::{configuracion, Cuota, DbConnection, Hermano, Periodicidad};
+use crate::db::auditoria::{self, ENTIDAD_CUOTA, ENTIDAD_FAMILIA, ENTIDAD_HERMANO};
+use crate::db::cuotas::{
+    buscar_cuota, buscar_periodicidad_hermano, comprobar_periodo_libre, reinsertar_cuota,
+    reinsertar_periodicidad_hermano,
+};
+use crate::db::familias::buscar_familia;
+use crate::db::hermanos::{buscar_hermano, reinsertar_hermano};
+use crate::db::membresia::{buscar_periodos, reinsertar_periodos, PeriodoMembresia};
+use crate::db::numeracion::{buscar_historial_numeros, reinsertar_historial_numeros, NumeroHistorico};
+use crate::db::pagos::{buscar_pagos_cuota, buscar_pagos_hermano, reinsertar_pagos, Pago};
+use crate::db::sepa::{buscar_mandatos_hermano, reinsertar_mandatos, MandatoSepa};
+use crate::db::tarifas::{buscar_categoria_manual, reinsertar_categoria_manual};
+use crate::error::{HermanarError, HermanarResult};
+
+const CLAVE_DIAS_PAPELERA: &str = "papelera_dias";
+const DIAS_PAPELERA_POR_DEFECTO: i64 = 30;
+
+/// Un hermano o una cuota eliminados que todavía pueden restaurarse.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElementoPapelera {
+    pub id: i64,
+    pub entidad: String,
+    pub entidad_id: i32,
+    pub descripcion: String,
+    pub datos: serde_json::Value,
+    pub operador: String,
+    pub fecha_eliminacion: String,
+}
+
+impl ElementoPapelera {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        let datos: String = row.get(4)?;
+        Ok(ElementoPapelera {
+            id: row.get(0)?,
+            entidad: row.get(1)?,
+            entidad_id: row.get(2)?,
+            descripcion: row.get(3)?,
+            datos: serde_json::from_str(&datos).unwrap_or(serde_json::Value::Null),
+            operador: row.get(5)?,
+            fecha_eliminacion: row.get(6)?,
+        })
+    }
+}
+
+/// Copia de un hermano eliminado junto con lo que se perdió con él: sus
+/// cuotas y los pagos de estas, historial de números, periodos de
+/// pertenencia, mandatos SEPA, categoría de cuota asignada a mano y
+/// periodicidad propia (borrados en cascada) y las familias que usaban su
+/// dirección.
+#[derive(Debug, Serialize, Deserialize)]
+struct HermanoEliminado {
+    hermano: Hermano,
+    cuotas: Vec<Cuota>,
+    familias_direccion: Vec<i32>,
+    #[serde(default)]
+    historial_numeros: Vec<NumeroHistorico>,
+    #[serde(default)]
+    periodos: Vec<PeriodoMembresia>,
+    #[serde(default)]
+    mandatos: Vec<MandatoSepa>,
+    #[serde(default)]
+    categoria_manual: Option<i32>,
+    #[serde(default)]
+    periodicidad: Option<Periodicidad>,
+    #[serde(default)]
+    pagos: Vec<Pago>,
+}
+
+/// Copia de una cuota eliminada con sus pagos (borrados en cascada). Las
+/// copias anteriores a los pagos son solo la cuota.
+#[derive(Debug, Serialize, Deserialize)]
+struct CuotaEliminada {
+    #[serde(flatten)]
+    cuota: Cuota,
+    #[serde(default)]
+    pagos: Vec<Pago>,
+}
+
+/// Guarda en la papelera un hermano que se va a eliminar. Debe llamarse dentro
+/// de la misma transacción que el borrado.
+pub(crate) fn guardar_hermano(
+    conn: &Connection,
+    operador: &str,
+    hermano: &Hermano,
+    cuotas: &[Cuota],
+    familias_direccion: &[i32],
+) -> HermanarResult<()> {
+    let descripcion = format!(
+        "{} - {} {} {}",
+        hermano.numero_hermano,
+        hermano.nombre,
+        hermano.primer_apellido,
+        hermano.segundo_apellido.as_deref().unwrap_or_default()
+    );
+    let datos = HermanoEliminado {
+        hermano: hermano.clone(),
+        cuotas: cuotas.to_vec(),
+        familias_direccion: familias_direccion.to_vec(),
+        historial_numeros: buscar_historial_numeros(conn, hermano.id.unwrap_or_default())?,
+        periodos: buscar_periodos(conn, hermano.id.unwrap_or_default())?,
+        mandatos: buscar_mandatos_hermano(conn, hermano.id.unwrap_or_default())?,
+        categoria_manual: buscar_categoria_manual(conn, hermano.id.unwrap_or_default())?,
+        periodicidad: buscar_periodicidad_hermano(conn, hermano.id.unwrap_or_default())?,
+        pagos: buscar_pagos_hermano(conn, hermano.id.unwrap_or_default())?,
+    };
+
+    guardar(conn, operador, ENTIDAD_HERMANO, hermano.id.unwrap_or_default(), descripcion.trim(), &datos)
+}
+
+/// Guarda en la papelera una cuota que se va a eliminar. Debe llamarse dentro
+/// de la misma transacción que el borrado.
+pub(crate) fn guardar_cuota(conn: &Connection, operador: &str, cuota: &Cuota) -> HermanarResult<()> {
+    let numero_hermano: Option<String> = conn
+        .prepare_cached("SELECT numero_hermano FROM hermanos WHERE id = ?1")?
+        .query_row([cuota.hermano_id], |row| row.get(0))
+        .optional()?;
+
+    let descripcion = format!(
+        "Cuota {} del hermano {}",
+        cuota.etiqueta_periodo(),
+        numero_hermano.unwrap_or_else(|| cuota.hermano_id.to_string())
+    );
+
+    let datos = CuotaEliminada {
+        cuota: cuota.clone(),
+        pagos: buscar_pagos_cuota(conn, cuota.id.unwrap_or_default())?,
+    };
+
+    guardar(conn, operador, ENTIDAD_CUOTA, cuota.id.unwrap_or_default(), &descripcion, &datos)
+}
+
+fn guardar<T: Serialize>(
+    conn: &Connection,
+    operador: &str,
+    entidad: &str,
+    entidad_id: i32,
+    descripcion: &str,
+    datos: &T,
+) -> HermanarResult<()> {
+    let datos = serde_json::to_string(datos).map_err(|e| HermanarError::database(e.to_string()))?;
+
+    conn.prepare_cached(
+        "INSERT INTO papelera (entidad, entidad_id, descripcion, datos, operador)
+         VALUES (?1, ?2, ?3, ?4, ?5)"
+    )?
+    .execute(params![entidad, entidad_id, descripcion, datos, operador])?;
+
+    Ok(())
+}
+
+pub fn list_papelera(db: &DbConnection) -> HermanarResult<Vec<ElementoPapelera>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, entidad, entidad_id, descripcion, datos, operador, fecha_eliminacion
+         FROM papelera
+         ORDER BY fecha_eliminacion DESC, id DESC"
+    )?;
+
+    let elementos = stmt.query_map([], |row| {
+        ElementoPapelera::from_row(row)
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(elementos)
+}
+
+/// Restaura un elemento de la papelera con su `id` original. Un hermano vuelve
+/// con sus cuotas, su familia (si todavía existe) y como dirección de las
+/// familias que la usaban y no han elegido otra desde entonces.
+pub fn restaurar_papelera(db: &DbConnection, id: i64) -> HermanarResult<ElementoPapelera> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+    let operador = db.operador();
+
+    let elemento = tx
+        .prepare_cached(
+            "SELECT id, entidad, entidad_id, descripcion, datos, operador, fecha_eliminacion
+             FROM papelera
+             WHERE id = ?1"
+        )?
+        .query_row([id], ElementoPapelera::from_row)
+        .optional()?
+        .ok_or_else(|| HermanarError::not_found("Elemento de la papelera"))?;
+
+    match elemento.entidad.as_str() {
+        ENTIDAD_HERMANO => {
+            let datos: HermanoEliminado = serde_json::from_value(elemento.datos.clone())
+                .map_err(|e| HermanarError::database(format!("Copia de la papelera dañada: {}", e)))?;
+            restaurar_hermano(&tx, &operador, datos)?;
+        }
+        ENTIDAD_CUOTA => {
+            let datos: CuotaEliminada = serde_json::from_value(elemento.datos.clone())
+                .map_err(|e| HermanarError::database(format!("Copia de la papelera dañada: {}", e)))?;
+            restaurar_cuota(&tx, &operador, datos)?;
+        }
+        otra => {
+            return Err(HermanarError::database(format!("Entidad desconocida en la papelera: {}", otra)));
+        }
+    }
+
+    tx.prepare_cached("DELETE FROM papelera WHERE id = ?1")?.execute([id])?;
+    tx.commit()?;
+
+    Ok(elemento)
+}
+
+fn restaurar_hermano(conn: &Connection, operador: &str, datos: HermanoEliminado) -> HermanarResult<()> {
+    let mut hermano = datos.hermano;
+    let id = hermano.id.ok_or_else(|| HermanarError::database("Copia de la papelera sin id"))?;
+
+    let ocupado: Option<i32> = conn
+        .prepare_cached("SELECT id FROM hermanos WHERE numero_hermano = ?1")?
+        .query_row([&hermano.numero_hermano], |row| row.get(0))
+        .optional()?;
+    if ocupado.is_some() {
+        return Err(HermanarError::conflict(format!(
+            "El número de hermano {} ya está asignado a otro hermano",
+            hermano.numero_hermano
+        )));
+    }
+
+    if let Some(familia_id) = hermano.familia_id {
+        if buscar_familia(conn, familia_id)?.is_none() {
+            hermano.familia_id = None;
+        }
+    }
+
+    reinsertar_hermano(conn, &hermano)?;
+    let restaurado = buscar_hermano(conn, id)?;
+    auditoria::registrar(conn, operador, ENTIDAD_HERMANO, id, None, restaurado.as_ref())?;
+
+    for cuota in &datos.cuotas {
+        reinsertar_cuota(conn, cuota)?;
+        let cuota_id = cuota.id.unwrap_or_default();
+        let pagos: Vec<Pago> = datos.pagos.iter().filter(|p| p.cuota_id == cuota_id).cloned().collect();
+        reinsertar_pagos(conn, cuota, &pagos)?;
+        let restaurada = buscar_cuota(conn, cuota_id)?;
+        auditoria::registrar(conn, operador, ENTIDAD_CUOTA, cuota_id, None, restaurada.as_ref())?;
+    }
+
+    reinsertar_historial_numeros(conn, id, &datos.historial_numeros)?;
+    reinsertar_periodos(conn, &datos.periodos)?;
+    reinsertar_mandatos(conn, &datos.mandatos)?;
+    if let Some(categoria_id) = datos.categoria_manual {
+        reinsertar_categoria_manual(conn, id, categoria_id)?;
+    }
+    if let Some(periodicidad) = datos.periodicidad {
+        reinsertar_periodicidad_hermano(conn, id, periodicidad)?;
+    }
+
+    for familia_id in datos.familias_direccion {
+        let Some(antes) = buscar_familia(conn, familia_id)? else {
+            continue;
+        };
+        if antes.hermano_direccion_id.is_some() {
+            continue;
+        }
+
+        conn.prepare_cached(
+            "UPDATE familias SET hermano_direccion_id = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2"
+        )?
+        .execute(params![id, familia_id])?;
+
+        let despues = buscar_familia(conn, familia_id)?;
+        auditoria::registrar(conn, operador, ENTIDAD_FAMILIA, familia_id, Some(&antes), despues.as_ref())?;
+    }
+
+    Ok(())
+}
+
+fn restaurar_cuota(conn: &Connection, operador: &str, datos: CuotaEliminada) -> HermanarResult<()> {
+    let cuota = &datos.cuota;
+    let id = cuota.id.ok_or_else(|| HermanarError::database("Copia de la papelera sin id"))?;
+
+    if buscar_hermano(conn, cuota.hermano_id)?.is_none() {
+        return Err(HermanarError::conflict(
+            "El hermano de esta cuota ya no existe; restáurelo primero desde la papelera",
+        ));
+    }
+
+    comprobar_periodo_libre(conn, cuota, None)?;
+    reinsertar_cuota(conn, cuota)?;
+    reinsertar_pagos(conn, cuota, &datos.pagos)?;
+    let restaurada = buscar_cuota(conn, id)?;
+    auditoria::registrar(conn, operador, ENTIDAD_CUOTA, id, None, restaurada.as_ref())?;
+
+    Ok(())
+}
+
+/// Elimina definitivamente los elementos que llevan en la papelera más de
+/// `dias` días. Sin `dias` se usa el plazo configurado. Devuelve cuántos se
+/// han eliminado.
+pub fn purgar_papelera(db: &DbConnection, dias: Option<i64>) -> HermanarResult<usize> {
+    let conn = db.writer();
+
+    let dias = match dias {
+        Some(dias) => dias,
+        None => configuracion::leer_entero(&conn, CLAVE_DIAS_PAPELERA, DIAS_PAPELERA_POR_DEFECTO)?,
+    };
+    if dias < 0 {
+        return Err(HermanarError::validation("dias", "El número de días no puede ser negativo"));
+    }
+
+    let eliminados = conn
+        .prepare_cached("DELETE FROM papelera WHERE fecha_eliminacion <= datetime('now', ?1)")?
+        .execute([format!("-{} days", dias)])?;
+
+    Ok(eliminados)
+}
+
+/// Días que se conservan los elementos de la papelera antes de purgarlos.
+pub fn get_dias_papelera(db: &DbConnection) -> HermanarResult<i64> {
+    configuracion::leer_entero(&db.reader(), CLAVE_DIAS_PAPELERA, DIAS_PAPELERA_POR_DEFECTO)
+}
+
+pub fn set_dias_papelera(db: &DbConnection, dias: i64) -> HermanarResult<()> {
+    if dias < 1 {
+        return Err(HermanarError::validation("dias", "El plazo de la papelera debe ser de al menos un día"));
+    }
+
+    configuracion::guardar(&db.writer(), CLAVE_DIAS_PAPELERA, &dias.to_string())
+}
@@ -0,0 +1,446 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use printpdf::image_crate::{self, DynamicImage, GenericImageView, RgbImage};
+use printpdf::path::PaintMode;
+use printpdf::{
+    BuiltinFont, Color, CustomPdfConformance, Image, ImageTransform, IndirectFontRef, Mm, OffsetDateTime,
+    PdfConformance, PdfDocument, PdfDocumentReference, PdfLayerReference, Rect, Rgb,
+};
+use qrcode::QrCode;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use crate::error::{HermanarError, HermanarResult};
+
+const PUNTO_EN_MM: f32 = 25.4 / 72.0;
+
+/// Documento PDF con las fuentes estándar Helvetica, que no hace falta
+/// incrustar y admiten los acentos y la eñe. Las coordenadas de las páginas
+/// se dan en milímetros desde la esquina superior izquierda.
+pub(crate) struct Documento {
+    pdf: PdfDocumentReference,
+    regular: IndirectFontRef,
+    negrita: IndirectFontRef,
+    identificador: Option<String>,
+}
+
+pub(crate) struct Pagina {
+    capa: PdfLayerReference,
+    alto: f32,
+    regular: IndirectFontRef,
+    negrita: IndirectFontRef,
+}
+
+impl Documento {
+    pub fn new(titulo: &str) -> HermanarResult<Self> {
+        Self::crear(PdfDocument::empty(titulo), None)
+    }
+
+    /// Documento que, con el mismo contenido, produce siempre el mismo
+    /// fichero: toma de `identificador` el identificador del PDF y de `fecha`
+    /// las fechas de creación y modificación.
+    pub fn reproducible(titulo: &str, identificador: &str, fecha: NaiveDateTime) -> HermanarResult<Self> {
+        let identificador: String = Sha256::digest(identificador.as_bytes())[..16]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let fecha = OffsetDateTime::from_unix_timestamp(fecha.and_utc().timestamp())
+            .map_err(|e| HermanarError::database(format!("Fecha de documento no válida: {}", e)))?;
+        let pdf = PdfDocument::empty(titulo).with_creation_date(fecha).with_mod_date(fecha);
+
+        Self::crear(pdf, Some(identificador))
+    }
+
+    // Sin metadatos XMP ni perfil ICC: los documentos son para imprimir en
+    // casa y el perfil por defecto añade más de medio mega a cada fichero.
+    fn crear(pdf: PdfDocumentReference, identificador: Option<String>) -> HermanarResult<Self> {
+        let pdf = pdf.with_conformance(PdfConformance::Custom(CustomPdfConformance {
+            identifier: "Hermanar".into(),
+            allows_default_fonts: true,
+            ..Default::default()
+        }));
+        let regular = pdf.add_builtin_font(BuiltinFont::Helvetica).map_err(error_pdf)?;
+        let negrita = pdf.add_builtin_font(BuiltinFont::HelveticaBold).map_err(error_pdf)?;
+
+        Ok(Documento { pdf, regular, negrita, identificador })
+    }
+
+    pub fn pagina(&self, ancho: f32, alto: f32) -> Pagina {
+        let (pagina, capa) = self.pdf.add_page(Mm(ancho), Mm(alto), "Contenido");
+
+        Pagina {
+            capa: self.pdf.get_page(pagina).get_layer(capa),
+            alto,
+            regular: self.regular.clone(),
+            negrita: self.negrita.clone(),
+        }
+    }
+
+    pub fn guardar(self, ruta: &Path) -> HermanarResult<()> {
+        let mut contenido = self.pdf.save_to_bytes().map_err(error_pdf)?;
+
+        // printpdf escribe en el trailer dos identificadores aleatorios de 32
+        // caracteres; en los reproducibles se sustituyen ambos por el del
+        // documento, que tiene la misma longitud.
+        if let Some(identificador) = &self.identificador {
+            if let Some(posicion) = buscar(&contenido, b"/ID[(") {
+                let primero = posicion + 5;
+                let segundo = primero + identificador.len() + 2;
+                if contenido.get(segundo - 2..segundo) == Some(b")(".as_slice())
+                    && contenido.get(segundo + identificador.len()) == Some(&b')')
+                {
+                    contenido[primero..primero + identificador.len()].copy_from_slice(identificador.as_bytes());
+                    contenido[segundo..segundo + identificador.len()].copy_from_slice(identificador.as_bytes());
+                }
+            }
+        }
+
+        std::fs::write(ruta, contenido)
+            .map_err(|e| HermanarError::database(format!("No se pudo crear el fichero {}: {}", ruta.display(), e)))
+    }
+}
+
+impl Pagina {
+    /// Escribe una línea de texto con la línea base a `y` milímetros del
+    /// borde superior.
+    pub fn texto(&self, x: f32, y: f32, tamano: f32, negrita: bool, texto: &str) {
+        let fuente = if negrita { &self.negrita } else { &self.regular };
+        self.capa.use_text(texto, tamano, Mm(x), Mm(self.alto - y), fuente);
+    }
+
+    /// Como `texto`, recortando con puntos suspensivos lo que no quepa en
+    /// `ancho` milímetros.
+    pub fn texto_ajustado(&self, x: f32, y: f32, ancho: f32, tamano: f32, negrita: bool, texto: &str) {
+        if ancho_texto(texto, tamano, negrita) <= ancho {
+            self.texto(x, y, tamano, negrita, texto);
+            return;
+        }
+
+        let mut recortado: String = texto.to_string();
+        while !recortado.is_empty() && ancho_texto(&format!("{}...", recortado), tamano, negrita) > ancho {
+            recortado.pop();
+        }
+        self.texto(x, y, tamano, negrita, &format!("{}...", recortado.trim_end()));
+    }
+
+    /// Escribe un párrafo partiendo las líneas por palabras para que no pasen
+    /// de `ancho` milímetros, con `interlineado` milímetros entre líneas base.
+    /// Devuelve la posición de la línea base siguiente a la última.
+    pub fn parrafo(&self, x: f32, y: f32, ancho: f32, tamano: f32, interlineado: f32, texto: &str) -> f32 {
+        let mut y = y;
+        let mut linea = String::new();
+        for palabra in texto.split_whitespace() {
+            let ampliada = if linea.is_empty() { palabra.to_string() } else { format!("{} {}", linea, palabra) };
+            if !linea.is_empty() && ancho_texto(&ampliada, tamano, false) > ancho {
+                self.texto(x, y, tamano, false, &linea);
+                y += interlineado;
+                linea = palabra.to_string();
+            } else {
+                linea = ampliada;
+            }
+        }
+        if !linea.is_empty() {
+            self.texto(x, y, tamano, false, &linea);
+            y += interlineado;
+        }
+
+        y
+    }
+
+    pub fn rectangulo(&self, x: f32, y: f32, ancho: f32, alto: f32, relleno: Option<(f32, f32, f32)>) {
+        let rectangulo = Rect::new(Mm(x), Mm(self.alto - y - alto), Mm(x + ancho), Mm(self.alto - y));
+        match relleno {
+            Some((r, g, b)) => {
+                self.capa.set_fill_color(Color::Rgb(Rgb::new(r, g, b, None)));
+                self.capa.add_rect(rectangulo.with_mode(PaintMode::Fill));
+                self.capa.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+            }
+            None => {
+                self.capa.set_outline_thickness(0.5);
+                self.capa.add_rect(rectangulo.with_mode(PaintMode::Stroke));
+            }
+        }
+    }
+
+    /// Dibuja el código QR de `contenido` como un cuadrado de `lado`
+    /// milímetros, sin margen alrededor.
+    pub fn qr(&self, x: f32, y: f32, lado: f32, contenido: &str) -> HermanarResult<()> {
+        let codigo = QrCode::new(contenido.as_bytes())
+            .map_err(|e| HermanarError::database(format!("No se pudo generar el código QR: {}", e)))?;
+        let modulos = codigo.width();
+        let colores = codigo.to_colors();
+        let modulo = lado / modulos as f32;
+
+        self.capa.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+        for fila in 0..modulos {
+            // Los módulos oscuros seguidos de una fila se pintan como un solo
+            // rectángulo
+            let mut columna = 0;
+            while columna < modulos {
+                if colores[fila * modulos + columna] != qrcode::Color::Dark {
+                    columna += 1;
+                    continue;
+                }
+                let inicio = columna;
+                while columna < modulos && colores[fila * modulos + columna] == qrcode::Color::Dark {
+                    columna += 1;
+                }
+                let x0 = x + inicio as f32 * modulo;
+                let y0 = self.alto - y - (fila + 1) as f32 * modulo;
+                let rectangulo = Rect::new(Mm(x0), Mm(y0), Mm(x0 + (columna - inicio) as f32 * modulo), Mm(y0 + modulo));
+                self.capa.add_rect(rectangulo.with_mode(PaintMode::Fill));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Coloca la imagen dentro del recuadro indicado conservando su
+    /// proporción. Las transparencias se componen sobre fondo blanco.
+    pub fn imagen(&self, x: f32, y: f32, ancho: f32, alto: f32, ruta: &Path) -> HermanarResult<()> {
+        let imagen = image_crate::open(ruta)
+            .map_err(|e| HermanarError::validation("logo", format!("No se pudo leer la imagen {}: {}", ruta.display(), e)))?;
+        self.dibujar_imagen(x, y, ancho, alto, imagen);
+
+        Ok(())
+    }
+
+    /// Como `imagen`, con el contenido de un fichero PNG o JPEG ya leído.
+    pub fn imagen_datos(&self, x: f32, y: f32, ancho: f32, alto: f32, contenido: &[u8]) -> HermanarResult<()> {
+        let imagen = image_crate::load_from_memory(contenido)
+            .map_err(|e| HermanarError::validation("logo", format!("No se pudo leer la imagen: {}", e)))?;
+        self.dibujar_imagen(x, y, ancho, alto, imagen);
+
+        Ok(())
+    }
+
+    fn dibujar_imagen(&self, x: f32, y: f32, ancho: f32, alto: f32, imagen: DynamicImage) {
+        let (ancho_px, alto_px) = imagen.dimensions();
+        if ancho_px == 0 || alto_px == 0 {
+            return;
+        }
+
+        let rgba = imagen.to_rgba8();
+        let rgb = RgbImage::from_fn(ancho_px, alto_px, |px, py| {
+            let [r, g, b, a] = rgba.get_pixel(px, py).0;
+            let mezcla = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+            image_crate::Rgb([mezcla(r), mezcla(g), mezcla(b)])
+        });
+
+        let escala = (ancho / ancho_px as f32).min(alto / alto_px as f32);
+        let (ancho_final, alto_final) = (ancho_px as f32 * escala, alto_px as f32 * escala);
+        // A 25,4 ppp cada píxel mide un milímetro
+        Image::from_dynamic_image(&DynamicImage::ImageRgb8(rgb)).add_to_layer(
+            self.capa.clone(),
+            ImageTransform {
+                translate_x: Some(Mm(x + (ancho - ancho_final) / 2.0)),
+                translate_y: Some(Mm(self.alto - y - alto + (alto - alto_final) / 2.0)),
+                scale_x: Some(escala),
+                scale_y: Some(escala),
+                dpi: Some(25.4),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+fn buscar(contenido: &[u8], patron: &[u8]) -> Option<usize> {
+    contenido.windows(patron.len()).rposition(|ventana| ventana == patron)
+}
+
+fn error_pdf(error: printpdf::Error) -> HermanarError {
+    HermanarError::database(format!("No se pudo generar el PDF: {}", error))
+}
+
+/// Ruta del PDF que se va a generar, comprobando que se ha indicado.
+pub(crate) fn fichero_destino(fichero: &str) -> HermanarResult<&Path> {
+    let fichero = fichero.trim();
+    if fichero.is_empty() {
+        return Err(HermanarError::validation("fichero", "Indique el fichero de destino"));
+    }
+
+    Ok(Path::new(fichero))
+}
+
+/// Ancho en milímetros de `texto` en Helvetica, según las métricas estándar
+/// de la fuente. Las letras acentuadas miden lo mismo que sin acento.
+pub(crate) fn ancho_texto(texto: &str, tamano: f32, negrita: bool) -> f32 {
+    let milesimas: u32 = texto.chars().map(|c| ancho_caracter(c, negrita)).sum();
+
+    milesimas as f32 / 1000.0 * tamano * PUNTO_EN_MM
+}
+
+#[rustfmt::skip]
+const ANCHOS_HELVETICA: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+#[rustfmt::skip]
+const ANCHOS_HELVETICA_NEGRITA: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+fn ancho_caracter(c: char, negrita: bool) -> u32 {
+    let base = match c {
+        'á' | 'à' | 'ä' | 'â' => 'a',
+        'é' | 'è' | 'ë' | 'ê' => 'e',
+        'í' | 'ì' | 'ï' | 'î' => 'i',
+        'ó' | 'ò' | 'ö' | 'ô' => 'o',
+        'ú' | 'ù' | 'ü' | 'û' => 'u',
+        'Á' | 'À' | 'Ä' | 'Â' => 'A',
+        'É' | 'È' | 'Ë' | 'Ê' => 'E',
+        'Í' | 'Ì' | 'Ï' | 'Î' => 'I',
+        'Ó' | 'Ò' | 'Ö' | 'Ô' => 'O',
+        'Ú' | 'Ù' | 'Ü' | 'Û' => 'U',
+        'ñ' => 'n',
+        'Ñ' => 'N',
+        'ç' => 'c',
+        'Ç' => 'C',
+        'º' | 'ª' => 'o',
+        '€' => '0',
+        c => c,
+    };
+    let tabla = if negrita { &ANCHOS_HELVETICA_NEGRITA } else { &ANCHOS_HELVETICA };
+
+    match (base as u32).checked_sub(32) {
+        Some(indice) if (indice as usize) < tabla.len() => tabla[indice as usize] as u32,
+        _ => 556,
+    }
+}
+
+/// Fecha AAAA-MM-DD en formato dd/mm/aaaa. Si no es una fecha se devuelve
+/// tal cual.
+pub(crate) fn fecha_espanola(fecha: &str) -> String {
+    match NaiveDate::parse_from_str(fecha.get(..10).unwrap_or(fecha), "%Y-%m-%d") {
+        Ok(fecha) => fecha.format("%d/%m/%Y").to_string(),
+        Err(_) => fecha.to_string(),
+    }
+}
+
+/// Fecha AAAA-MM-DD escrita como en los documentos: 18 de octubre de 2026.
+/// Si no es una fecha se devuelve tal cual.
+pub(crate) fn fecha_en_letras(fecha: &str) -> String {
+    const MESES: [&str; 12] = [
+        "enero", "febrero", "marzo", "abril", "mayo", "junio",
+        "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre",
+    ];
+
+    match NaiveDate::parse_from_str(fecha.get(..10).unwrap_or(fecha), "%Y-%m-%d") {
+        Ok(fecha) => format!("{} de {} de {}", fecha.day(), MESES[fecha.month0() as usize], fecha.year()),
+        Err(_) => fecha.to_string(),
+    }
+}
+
+/// Importe con separador de miles y coma decimal: 1.234,50 €.
+pub(crate) fn formatear_importe(importe: f64) -> String {
+    let centimos = (importe.abs() * 100.0).round() as u64;
+    let cifras = (centimos / 100).to_string();
+    let mut euros = String::new();
+    for (indice, cifra) in cifras.chars().enumerate() {
+        if indice > 0 && (cifras.len() - indice).is_multiple_of(3) {
+            euros.push('.');
+        }
+        euros.push(cifra);
+    }
+    let signo = if importe < 0.0 && centimos > 0 { "-" } else { "" };
+
+    format!("{}{},{:02} €", signo, euros, centimos % 100)
+}
+
+/// Importe escrito en letra, como se pone en los recibos: "doce euros con
+/// cincuenta céntimos".
+pub(crate) fn importe_en_letras(importe: f64) -> String {
+    let centimos = (importe.abs() * 100.0).round() as u64;
+    let (euros, centimos) = (centimos / 100, centimos % 100);
+
+    let mut texto = match euros {
+        1 => "un euro".to_string(),
+        _ => format!("{} euros", apocope(&numero_en_letras(euros))),
+    };
+    match centimos {
+        0 => {}
+        1 => texto.push_str(" con un céntimo"),
+        _ => texto.push_str(&format!(" con {} céntimos", apocope(&numero_en_letras(centimos)))),
+    }
+
+    texto
+}
+
+const UNIDADES: [&str; 30] = [
+    "cero", "uno", "dos", "tres", "cuatro", "cinco", "seis", "siete", "ocho", "nueve",
+    "diez", "once", "doce", "trece", "catorce", "quince", "dieciséis", "diecisiete", "dieciocho", "diecinueve",
+    "veinte", "veintiuno", "veintidós", "veintitrés", "veinticuatro", "veinticinco", "veintiséis", "veintisiete",
+    "veintiocho", "veintinueve",
+];
+const DECENAS: [&str; 10] = [
+    "", "", "", "treinta", "cuarenta", "cincuenta", "sesenta", "setenta", "ochenta", "noventa",
+];
+const CENTENAS: [&str; 10] = [
+    "", "ciento", "doscientos", "trescientos", "cuatrocientos", "quinientos", "seiscientos", "setecientos",
+    "ochocientos", "novecientos",
+];
+
+fn numero_en_letras(numero: u64) -> String {
+    if numero == 0 {
+        return UNIDADES[0].to_string();
+    }
+
+    let (millones, miles, resto) = (numero / 1_000_000, numero / 1000 % 1000, numero % 1000);
+    let mut partes = Vec::new();
+    match millones {
+        0 => {}
+        1 => partes.push("un millón".to_string()),
+        _ => partes.push(format!("{} millones", apocope(&numero_en_letras(millones)))),
+    }
+    match miles {
+        0 => {}
+        1 => partes.push("mil".to_string()),
+        _ => partes.push(format!("{} mil", apocope(&centenas_en_letras(miles)))),
+    }
+    if resto > 0 {
+        partes.push(centenas_en_letras(resto));
+    }
+
+    partes.join(" ")
+}
+
+fn centenas_en_letras(numero: u64) -> String {
+    let (centena, resto) = ((numero / 100) as usize, (numero % 100) as usize);
+    if centena == 1 && resto == 0 {
+        return "cien".to_string();
+    }
+
+    let decenas = match resto {
+        0 => String::new(),
+        1..=29 => UNIDADES[resto].to_string(),
+        _ if resto % 10 == 0 => DECENAS[resto / 10].to_string(),
+        _ => format!("{} y {}", DECENAS[resto / 10], UNIDADES[resto % 10]),
+    };
+
+    [CENTENAS[centena], decenas.as_str()]
+        .iter()
+        .filter(|parte| !parte.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// "uno" delante de un sustantivo se escribe "un": veintiún euros, treinta y
+// un mil.
+fn apocope(numero: &str) -> String {
+    if let Some(inicio) = numero.strip_suffix("veintiuno") {
+        format!("{}veintiún", inicio)
+    } else if let Some(inicio) = numero.strip_suffix("uno") {
+        format!("{}un", inicio)
+    } else {
+        numero.to_string()
+    }
+}
@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::db::{app_data_dir, DbConnection};
+use crate::error::{HermanarError, HermanarResult};
+
+const FICHERO_REGISTRO: &str = "perfiles.json";
+const PERFIL_POR_DEFECTO: &str = "Principal";
+
+/// Una hermandad (o asociación) con su propia base de datos.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Perfil {
+    pub nombre: String,
+    pub ruta: String,
+}
+
+/// Registro de perfiles guardado en `perfiles.json` dentro del directorio de
+/// datos de la aplicación.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistroPerfiles {
+    pub activo: String,
+    pub perfiles: Vec<Perfil>,
+}
+
+impl RegistroPerfiles {
+    pub fn perfil_activo(&self) -> Option<&Perfil> {
+        self.buscar(&self.activo)
+    }
+
+    fn buscar(&self, nombre: &str) -> Option<&Perfil> {
+        self.perfiles.iter().find(|p| p.nombre.eq_ignore_ascii_case(nombre))
+    }
+}
+
+/// Motivo por el que, al arrancar, no se abrió el perfil activo sino el
+/// perfil por defecto. La interfaz lo recoge una vez para mostrarlo.
+pub struct AvisoArranque(Mutex<Option<String>>);
+
+impl AvisoArranque {
+    pub fn new(aviso: Option<String>) -> Self {
+        AvisoArranque(Mutex::new(aviso))
+    }
+
+    pub fn tomar(&self) -> Option<String> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
+
+/// Lee el registro de perfiles. Si todavía no existe, crea uno con un único
+/// perfil que apunta a la base de datos de siempre (`hermanar.db`).
+pub fn cargar_registro(data_dir: &Path) -> Result<RegistroPerfiles, anyhow::Error> {
+    let fichero = data_dir.join(FICHERO_REGISTRO);
+
+    if !fichero.exists() {
+        let registro = RegistroPerfiles {
+            activo: PERFIL_POR_DEFECTO.to_string(),
+            perfiles: vec![Perfil {
+                nombre: PERFIL_POR_DEFECTO.to_string(),
+                ruta: data_dir.join("hermanar.db").to_string_lossy().to_string(),
+            }],
+        };
+        guardar_registro(data_dir, &registro)?;
+        return Ok(registro);
+    }
+
+    let contenido = std::fs::read_to_string(&fichero)?;
+    let registro = serde_json::from_str(&contenido)?;
+
+    Ok(registro)
+}
+
+/// Deja activo el perfil por defecto, volviéndolo a dar de alta con la base
+/// de datos de siempre si se había quitado del registro, y lo devuelve. Se usa
+/// al arrancar cuando no se puede abrir la base de datos del perfil activo.
+pub(crate) fn activar_perfil_por_defecto(
+    data_dir: &Path,
+    registro: &mut RegistroPerfiles,
+) -> Result<Perfil, anyhow::Error> {
+    let perfil = match registro.buscar(PERFIL_POR_DEFECTO) {
+        Some(perfil) => perfil.clone(),
+        None => {
+            let perfil = Perfil {
+                nombre: PERFIL_POR_DEFECTO.to_string(),
+                ruta: data_dir.join("hermanar.db").to_string_lossy().to_string(),
+            };
+            registro.perfiles.push(perfil.clone());
+            perfil
+        }
+    };
+    registro.activo = perfil.nombre.clone();
+    guardar_registro(data_dir, registro)?;
+
+    Ok(perfil)
+}
+
+fn guardar_registro(data_dir: &Path, registro: &RegistroPerfiles) -> Result<(), anyhow::Error> {
+    let contenido = serde_json::to_string_pretty(registro)?;
+
+    // Escritura atómica: un corte a mitad no debe dejar el registro vacío
+    let temporal = data_dir.join(format!("{}.tmp", FICHERO_REGISTRO));
+    std::fs::write(&temporal, contenido)?;
+    std::fs::rename(&temporal, data_dir.join(FICHERO_REGISTRO))?;
+
+    Ok(())
+}
+
+pub fn list_profiles() -> HermanarResult<RegistroPerfiles> {
+    let data_dir = app_data_dir().map_err(error_registro)?;
+    cargar_registro(&data_dir).map_err(error_registro)
+}
+
+/// Da de alta un perfil. Sin `ruta`, la base de datos se crea en el directorio
+/// de datos de la aplicación; con ella puede apuntar a cualquier fichero, por
+/// ejemplo en una memoria USB o en una carpeta compartida.
+pub fn create_profile(nombre: &str, ruta: Option<&str>) -> HermanarResult<Perfil> {
+    let nombre = nombre.trim();
+    if nombre.is_empty() {
+        return Err(HermanarError::validation("nombre", "El nombre del perfil es obligatorio"));
+    }
+
+    let data_dir = app_data_dir().map_err(error_registro)?;
+    let mut registro = cargar_registro(&data_dir).map_err(error_registro)?;
+
+    if registro.buscar(nombre).is_some() {
+        return Err(HermanarError::Duplicate { field: "nombre".to_string() });
+    }
+
+    let ruta = match ruta.map(str::trim).filter(|r| !r.is_empty()) {
+        Some(ruta) => PathBuf::from(ruta),
+        None => data_dir.join("perfiles").join(format!("{}.db", nombre_fichero(nombre))),
+    };
+
+    if ruta.is_dir() {
+        return Err(HermanarError::validation("ruta", "La ruta debe ser un fichero, no una carpeta"));
+    }
+    if let Some(directorio) = ruta.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(directorio).map_err(|e| {
+            HermanarError::validation("ruta", format!("No se puede usar la carpeta indicada: {}", e))
+        })?;
+    }
+    if registro.perfiles.iter().any(|p| Path::new(&p.ruta) == ruta) {
+        return Err(HermanarError::Duplicate { field: "ruta".to_string() });
+    }
+
+    let perfil = Perfil {
+        nombre: nombre.to_string(),
+        ruta: ruta.to_string_lossy().to_string(),
+    };
+    registro.perfiles.push(perfil.clone());
+    guardar_registro(&data_dir, &registro).map_err(error_registro)?;
+
+    Ok(perfil)
+}
+
+/// Cambia al perfil indicado reabriendo las conexiones compartidas. Las
+/// conexiones anteriores solo se sustituyen si la nueva base de datos se abre y
+/// migra sin errores.
+pub fn switch_profile(db: &DbConnection, nombre: &str) -> HermanarResult<Perfil> {
+    let data_dir = app_data_dir().map_err(error_registro)?;
+    let mut registro = cargar_registro(&data_dir).map_err(error_registro)?;
+
+    let perfil = registro
+        .buscar(nombre)
+        .cloned()
+        .ok_or_else(|| HermanarError::not_found("Perfil"))?;
+
+    db.reopen(Path::new(&perfil.ruta))
+        .map_err(|e| HermanarError::database(format!("No se pudo abrir la base de datos del perfil: {:#}", e)))?;
+
+    registro.activo = perfil.nombre.clone();
+    guardar_registro(&data_dir, &registro).map_err(error_registro)?;
+
+    println!("Perfil activo: {}", perfil.nombre);
+
+    Ok(perfil)
+}
+
+/// Quita un perfil del registro sin borrar su base de datos.
+pub fn delete_profile(nombre: &str) -> HermanarResult<()> {
+    let data_dir = app_data_dir().map_err(error_registro)?;
+    let mut registro = cargar_registro(&data_dir).map_err(error_registro)?;
+
+    let perfil = registro
+        .buscar(nombre)
+        .cloned()
+        .ok_or_else(|| HermanarError::not_found("Perfil"))?;
+
+    if perfil.nombre == registro.activo {
+        return Err(HermanarError::conflict("No se puede eliminar el perfil activo"));
+    }
+
+    registro.perfiles.retain(|p| p.nombre != perfil.nombre);
+    guardar_registro(&data_dir, &registro).map_err(error_registro)?;
+
+    Ok(())
+}
+
+fn nombre_fichero(nombre: &str) -> String {
+    nombre
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+fn error_registro(e: anyhow::Error) -> HermanarError {
+    HermanarError::database(format!("Error en el registro de perfiles: {}", e))
+}
@@ -0,0 +1,143 @@
+use rusqlite::{Connection, OpenFlags};
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, RwLock};
+use crate::db::{configurar_conexion, open_database};
+
+// Conexiones de solo lectura que se mantienen abiertas además de la de escritura
+const LECTORES: usize = 4;
+
+/// Capa de conexiones a la base de datos.
+///
+/// Hay una única conexión de escritura, que serializa todas las modificaciones,
+/// y un pequeño grupo de conexiones de lectura. Con el diario en modo WAL las
+/// lecturas no bloquean a la escritura ni entre sí, de modo que un informe
+/// largo no detiene al resto de comandos.
+///
+/// Si un comando entra en pánico con una conexión tomada, la conexión se
+/// recupera en lugar de dejar la base de datos inutilizable el resto de la sesión.
+pub struct Database {
+    ruta: RwLock<PathBuf>,
+    operador: RwLock<String>,
+    escritura: Mutex<Connection>,
+    lectores: Vec<Mutex<Connection>>,
+    siguiente: AtomicUsize,
+}
+
+impl Database {
+    pub fn open(db_path: &Path) -> Result<Self, anyhow::Error> {
+        let (escritura, lectores) = abrir_conexiones(db_path)?;
+
+        Ok(Database {
+            ruta: RwLock::new(db_path.to_path_buf()),
+            operador: RwLock::new(operador_por_defecto()),
+            escritura: Mutex::new(escritura),
+            lectores: lectores.into_iter().map(Mutex::new).collect(),
+            siguiente: AtomicUsize::new(0),
+        })
+    }
+
+    /// Conexión para consultas. Las conexiones de lectura tienen activado
+    /// `query_only`, así que cualquier intento de modificar datos falla.
+    pub fn reader(&self) -> MutexGuard<'_, Connection> {
+        let inicio = self.siguiente.fetch_add(1, Ordering::Relaxed);
+
+        for i in 0..self.lectores.len() {
+            let lector = &self.lectores[(inicio + i) % self.lectores.len()];
+            if let Ok(conn) = lector.try_lock() {
+                return conn;
+            }
+        }
+
+        // Todos ocupados: esperar al que tocaba por turno
+        recuperar(&self.lectores[inicio % self.lectores.len()])
+    }
+
+    /// Conexión única para modificaciones.
+    pub fn writer(&self) -> MutexGuard<'_, Connection> {
+        recuperar(&self.escritura)
+    }
+
+    /// Ruta del fichero de la base de datos en uso.
+    pub fn path(&self) -> PathBuf {
+        self.ruta.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Persona que figura como autora de los cambios en la auditoría.
+    pub fn operador(&self) -> String {
+        self.operador.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn set_operador(&self, operador: &str) {
+        *self.operador.write().unwrap_or_else(|e| e.into_inner()) = operador.trim().to_string();
+    }
+
+    /// Ejecuta `f` con la conexión de escritura mientras se mantienen tomadas
+    /// todas las de lectura, para operaciones que sustituyen la base de datos
+    /// entera (restaurar una copia).
+    pub fn exclusive<T>(&self, f: impl FnOnce(&mut Connection) -> T) -> T {
+        let mut escritura = self.writer();
+        let _lectores: Vec<_> = self.lectores.iter().map(recuperar).collect();
+        f(&mut escritura)
+    }
+
+    /// Sustituye todas las conexiones por otras a `db_path`. Las conexiones
+    /// actuales solo se cierran si las nuevas se han abierto correctamente.
+    pub fn reopen(&self, db_path: &Path) -> Result<(), anyhow::Error> {
+        let (nueva_escritura, nuevos_lectores) = abrir_conexiones(db_path)?;
+
+        let mut escritura = self.writer();
+        let mut lectores: Vec<_> = self.lectores.iter().map(recuperar).collect();
+
+        *escritura = nueva_escritura;
+        for (lector, nuevo) in lectores.iter_mut().zip(nuevos_lectores) {
+            **lector = nuevo;
+        }
+        *self.ruta.write().unwrap_or_else(|e| e.into_inner()) = db_path.to_path_buf();
+
+        Ok(())
+    }
+}
+
+fn abrir_conexiones(db_path: &Path) -> Result<(Connection, Vec<Connection>), anyhow::Error> {
+    let escritura = open_database(db_path)?;
+
+    let mut lectores = Vec::with_capacity(LECTORES);
+    for _ in 0..LECTORES {
+        let conn = Connection::open_with_flags(
+            db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .context("No se pudo abrir una conexión de lectura")?;
+        configurar_conexion(&conn)?;
+        conn.pragma_update(None, "query_only", true)?;
+        lectores.push(conn);
+    }
+
+    Ok((escritura, lectores))
+}
+
+// Hasta que se indique otro, se usa el usuario del sistema operativo
+fn operador_por_defecto() -> String {
+    std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_else(|_| "desconocido".to_string())
+}
+
+// Toma la conexión aunque otro hilo entrara en pánico con ella. Si el pánico
+// dejó una transacción a medias se deshace antes de devolverla.
+fn recuperar(mutex: &Mutex<Connection>) -> MutexGuard<'_, Connection> {
+    match mutex.lock() {
+        Ok(conn) => conn,
+        Err(envenenado) => {
+            eprintln!("Advertencia: Recuperando una conexión tras un error interno");
+            mutex.clear_poison();
+            let conn = envenenado.into_inner();
+            if !conn.is_autocommit() {
+                let _ = conn.execute_batch("ROLLBACK");
+            }
+            conn
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use chrono::{Datelike, NaiveDateTime};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use crate::db::{DbConnection, Periodicidad};
+use crate::db::cuotas::buscar_cuota;
+use crate::db::hermandad::{cargar_datos_documento, congelar_logo, contenido_logo, DatosHermandad};
+use crate::db::hermanos::buscar_hermano;
+use crate::db::numeracion::nombre_completo;
+use crate::db::pdf::{ancho_texto, fecha_espanola, fichero_destino, formatear_importe, importe_en_letras, Documento};
+use crate::db::remesas::METODO_DOMICILIACION;
+use crate::error::{HermanarError, HermanarResult};
+
+// Recibo en media hoja A4 apaisada.
+const ANCHO_RECIBO: f32 = 210.0;
+const ALTO_RECIBO: f32 = 148.0;
+
+/// Recibo emitido por un pago de una cuota, con los datos tal como se
+/// imprimen.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recibo {
+    pub id: i64,
+    /// Número completo del recibo: año y número correlativo, p. ej. 2026/00012.
+    pub numero_recibo: String,
+    pub anio: i32,
+    pub numero: i32,
+    pub cuota_id: i32,
+    pub hermano_id: i32,
+    pub numero_hermano: String,
+    pub nombre_hermano: String,
+    pub dni: Option<String>,
+    pub cuota_anio: i32,
+    pub cuota_periodicidad: Periodicidad,
+    pub cuota_periodo: i32,
+    pub concepto: String,
+    pub importe: f64,
+    pub fecha_pago: String,
+    pub metodo_pago: String,
+    pub anulado: bool,
+    pub fecha_anulacion: Option<String>,
+    pub operador: String,
+    pub created_at: String,
+}
+
+const COLUMNAS_RECIBO: &str = "id, anio, numero, cuota_id, hermano_id, numero_hermano, nombre_hermano, dni,
+     cuota_anio, cuota_periodo, concepto, importe, fecha_pago, metodo_pago, anulado, fecha_anulacion,
+     operador, created_at, cuota_periodicidad";
+
+impl Recibo {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        let anio: i32 = row.get(1)?;
+        let numero: i32 = row.get(2)?;
+
+        Ok(Recibo {
+            id: row.get(0)?,
+            numero_recibo: format!("{}/{:05}", anio, numero),
+            anio,
+            numero,
+            cuota_id: row.get(3)?,
+            hermano_id: row.get(4)?,
+            numero_hermano: row.get(5)?,
+            nombre_hermano: row.get(6)?,
+            dni: row.get(7)?,
+            cuota_anio: row.get(8)?,
+            cuota_periodicidad: Periodicidad::desde_clave(&row.get::<_, String>(18)?).unwrap_or_default(),
+            cuota_periodo: row.get(9)?,
+            concepto: row.get(10)?,
+            importe: row.get(11)?,
+            fecha_pago: row.get(12)?,
+            metodo_pago: row.get(13)?,
+            anulado: row.get(14)?,
+            fecha_anulacion: row.get(15)?,
+            operador: row.get(16)?,
+            created_at: row.get(17)?,
+        })
+    }
+}
+
+/// Emite el recibo de un pago recién anotado, con el siguiente número del
+/// año del pago. Un pago que no cubre toda la cuota se recibe como pago a
+/// cuenta, o como resto de la cuota si es el que la completa. Debe llamarse
+/// dentro de una transacción.
+pub(crate) fn emitir_recibo(conn: &Connection, operador: &str, pago_id: i64) -> HermanarResult<i64> {
+    let (cuota_id, importe, fecha_pago, metodo_pago): (i32, f64, String, String) = conn
+        .prepare_cached("SELECT cuota_id, importe, fecha, metodo FROM pagos WHERE id = ?1")?
+        .query_row([pago_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+        .optional()?
+        .ok_or_else(|| HermanarError::not_found("Pago"))?;
+    let cuota = buscar_cuota(conn, cuota_id)?.ok_or_else(|| HermanarError::not_found("Cuota"))?;
+    let hermano = buscar_hermano(conn, cuota.hermano_id)?.ok_or_else(|| HermanarError::not_found("Hermano"))?;
+    let anio = fecha_pago
+        .get(..4)
+        .and_then(|anio| anio.parse().ok())
+        .unwrap_or_else(|| chrono::Local::now().year());
+    // El pago ya está anotado, así que lo pendiente de la cuota no lo incluye.
+    let concepto = if (importe * 100.0).round() >= (cuota.importe * 100.0).round() {
+        format!("Cuota {}", cuota.descripcion_periodo())
+    } else if cuota.importe_pendiente > 0.0 {
+        format!("Pago a cuenta de la cuota {}", cuota.descripcion_periodo())
+    } else {
+        format!("Resto de la cuota {}", cuota.descripcion_periodo())
+    };
+
+    let numero: i32 = conn
+        .prepare_cached("SELECT COALESCE(MAX(numero), 0) + 1 FROM recibos WHERE anio = ?1")?
+        .query_row([anio], |row| row.get(0))?;
+
+    conn.prepare_cached(
+        "INSERT INTO recibos
+         (anio, numero, cuota_id, hermano_id, numero_hermano, nombre_hermano, dni, cuota_anio, cuota_periodicidad,
+          cuota_periodo, concepto, importe, fecha_pago, metodo_pago, operador)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)"
+    )?
+    .execute(params![
+        anio,
+        numero,
+        cuota_id,
+        cuota.hermano_id,
+        hermano.numero_hermano,
+        nombre_completo(&hermano),
+        hermano.dni,
+        cuota.anio,
+        cuota.periodicidad.clave(),
+        cuota.periodo,
+        concepto,
+        importe,
+        fecha_pago,
+        metodo_pago,
+        operador,
+    ])?;
+
+    Ok(conn.last_insert_rowid())
+}
+
+/// Anula el recibo de un pago anulado. El número no se reutiliza. Debe
+/// llamarse dentro de una transacción.
+pub(crate) fn anular_recibo(conn: &Connection, id: i64) -> HermanarResult<()> {
+    conn.prepare_cached(
+        "UPDATE recibos SET anulado = 1, fecha_anulacion = CURRENT_TIMESTAMP
+         WHERE id = ?1 AND anulado = 0"
+    )?
+    .execute([id])?;
+
+    Ok(())
+}
+
+pub fn get_recibos_cuota(db: &DbConnection, cuota_id: i32) -> HermanarResult<Vec<Recibo>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM recibos WHERE cuota_id = ?1 ORDER BY anio, numero",
+        COLUMNAS_RECIBO
+    ))?;
+
+    let recibos = stmt.query_map([cuota_id], Recibo::from_row)?.collect::<Result<Vec<_>, _>>()?;
+
+    Ok(recibos)
+}
+
+pub fn list_recibos(db: &DbConnection, anio: i32) -> HermanarResult<Vec<Recibo>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM recibos WHERE anio = ?1 ORDER BY numero",
+        COLUMNAS_RECIBO
+    ))?;
+
+    let recibos = stmt.query_map([anio], Recibo::from_row)?.collect::<Result<Vec<_>, _>>()?;
+
+    Ok(recibos)
+}
+
+/// Genera el PDF de un recibo. Los datos de la hermandad se fijan la primera
+/// vez que se imprime, de modo que volver a generarlo produce el mismo
+/// fichero.
+pub fn generar_pdf_recibo(db: &DbConnection, id: i64, fichero: &str) -> HermanarResult<()> {
+    let fichero = fichero_destino(fichero)?;
+
+    let (recibo, hermandad, logo) = {
+        let mut conn = db.writer();
+        let tx = conn.transaction()?;
+        let recibo = buscar_recibo(&tx, id)?.ok_or_else(|| HermanarError::not_found("Recibo"))?;
+        let hermandad = fijar_hermandad(&tx, id)?;
+        let logo = contenido_logo(&tx, &hermandad)?;
+        tx.commit()?;
+        (recibo, hermandad, logo)
+    };
+
+    let emitido = NaiveDateTime::parse_from_str(&recibo.created_at, "%Y-%m-%d %H:%M:%S").unwrap_or_default();
+    let documento = Documento::reproducible(
+        &format!("Recibo {}", recibo.numero_recibo),
+        &format!("recibo {} {}", recibo.numero_recibo, recibo.anulado),
+        emitido,
+    )?;
+    dibujar_recibo(&documento, &recibo, &hermandad, logo.as_deref())?;
+
+    documento.guardar(fichero)
+}
+
+/// Genera en un solo PDF los recibos de los pagos de las cuotas de un
+/// periodo, uno por página. Los pagos que aún no tienen recibo, como los
+/// cobrados antes de existir los recibos, lo reciben ahora. Devuelve el
+/// número de recibos impresos.
+pub fn imprimir_recibos_periodo(
+    db: &DbConnection,
+    anio: i32,
+    periodicidad: Periodicidad,
+    periodo: i32,
+    fichero: &str,
+) -> HermanarResult<usize> {
+    periodicidad.validar_periodo(periodo)?;
+    let fichero = fichero_destino(fichero)?;
+
+    let recibos = {
+        let mut conn = db.writer();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let operador = db.operador();
+
+        let sin_recibo = {
+            let mut stmt = tx.prepare_cached(
+                "SELECT p.id FROM pagos p
+                 JOIN cuotas c ON c.id = p.cuota_id
+                 WHERE c.anio = ?1 AND c.periodicidad = ?2 AND c.periodo = ?3
+                   AND p.anulado = 0 AND p.recibo_id IS NULL
+                 ORDER BY p.fecha, p.id"
+            )?;
+            let ids = stmt.query_map(params![anio, periodicidad.clave(), periodo], |row| row.get::<_, i64>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            ids
+        };
+        for pago_id in sin_recibo {
+            let recibo_id = emitir_recibo(&tx, &operador, pago_id)?;
+            tx.prepare_cached("UPDATE pagos SET recibo_id = ?1 WHERE id = ?2")?
+                .execute(params![recibo_id, pago_id])?;
+        }
+
+        let recibos = {
+            let mut stmt = tx.prepare_cached(&format!(
+                "SELECT {} FROM recibos
+                 WHERE cuota_anio = ?1 AND cuota_periodicidad = ?2 AND cuota_periodo = ?3 AND anulado = 0
+                 ORDER BY anio, numero",
+                COLUMNAS_RECIBO
+            ))?;
+            let recibos = stmt.query_map(params![anio, periodicidad.clave(), periodo], Recibo::from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            recibos
+        };
+        let mut con_hermandad = Vec::with_capacity(recibos.len());
+        for recibo in recibos {
+            let hermandad = fijar_hermandad(&tx, recibo.id)?;
+            let logo = contenido_logo(&tx, &hermandad)?;
+            con_hermandad.push((recibo, hermandad, logo));
+        }
+
+        tx.commit()?;
+        con_hermandad
+    };
+    if recibos.is_empty() {
+        return Err(HermanarError::validation("periodo", "No hay pagos de cuotas en ese periodo"));
+    }
+
+    let documento = Documento::new(&format!("Recibos {}", periodicidad.descripcion(anio, periodo)))?;
+    for (recibo, hermandad, logo) in &recibos {
+        dibujar_recibo(&documento, recibo, hermandad, logo.as_deref())?;
+    }
+    documento.guardar(fichero)?;
+
+    Ok(recibos.len())
+}
+
+fn buscar_recibo(conn: &Connection, id: i64) -> HermanarResult<Option<Recibo>> {
+    let recibo = conn
+        .prepare_cached(&format!("SELECT {} FROM recibos WHERE id = ?1", COLUMNAS_RECIBO))?
+        .query_row([id], Recibo::from_row)
+        .optional()?;
+
+    Ok(recibo)
+}
+
+// Datos de la hermandad con los que se imprime el recibo: los guardados en su
+// primera impresión o, si es la primera, los actuales, que quedan guardados
+// junto con una copia del escudo. Los recibos guardados antes de existir esas
+// copias reciben la del escudo actual, que ya no cambia.
+fn fijar_hermandad(conn: &Connection, id: i64) -> HermanarResult<DatosHermandad> {
+    let guardados: Option<String> = conn
+        .prepare_cached("SELECT hermandad FROM recibos WHERE id = ?1")?
+        .query_row([id], |row| row.get(0))?;
+    let guardados: Option<DatosHermandad> = guardados.and_then(|json| serde_json::from_str(&json).ok());
+    if let Some(datos) = &guardados {
+        if datos.logo.is_none() || datos.logo_huella.is_some() {
+            return Ok(datos.clone());
+        }
+    }
+
+    let mut datos = match guardados {
+        Some(datos) => datos,
+        None => cargar_datos_documento(conn)?,
+    };
+    congelar_logo(conn, &mut datos)?;
+    let json = serde_json::to_string(&datos)
+        .map_err(|e| HermanarError::database(format!("No se pudieron guardar los datos del recibo: {}", e)))?;
+    conn.prepare_cached("UPDATE recibos SET hermandad = ?1 WHERE id = ?2")?
+        .execute(params![json, id])?;
+
+    Ok(datos)
+}
+
+fn dibujar_recibo(
+    documento: &Documento,
+    recibo: &Recibo,
+    hermandad: &DatosHermandad,
+    logo: Option<&[u8]>,
+) -> HermanarResult<()> {
+    let pagina = documento.pagina(ANCHO_RECIBO, ALTO_RECIBO);
+    pagina.rectangulo(10.0, 10.0, ANCHO_RECIBO - 20.0, ALTO_RECIBO - 20.0, None);
+
+    // Cabecera: hermandad a la izquierda, número y fecha a la derecha
+    let texto_x = match logo {
+        Some(logo) => {
+            pagina.imagen_datos(15.0, 15.0, 22.0, 22.0, logo)?;
+            42.0
+        }
+        None => 16.0,
+    };
+    pagina.texto_ajustado(texto_x, 22.0, 140.0 - texto_x, 12.0, true, &hermandad.nombre);
+    let lineas_hermandad = [
+        hermandad.cif.as_ref().map(|cif| format!("CIF: {}", cif)),
+        hermandad.direccion.clone(),
+        hermandad.poblacion(),
+    ];
+    for (indice, linea) in lineas_hermandad.iter().flatten().enumerate() {
+        pagina.texto_ajustado(texto_x, 28.0 + indice as f32 * 4.5, 140.0 - texto_x, 8.5, false, linea);
+    }
+
+    let derecha = ANCHO_RECIBO - 16.0;
+    let numero = format!("Nº {}", recibo.numero_recibo);
+    pagina.texto(derecha - ancho_texto("RECIBO", 16.0, true), 22.0, 16.0, true, "RECIBO");
+    pagina.texto(derecha - ancho_texto(&numero, 11.0, true), 29.0, 11.0, true, &numero);
+    let fecha = format!("Fecha: {}", fecha_espanola(&recibo.fecha_pago));
+    pagina.texto(derecha - ancho_texto(&fecha, 9.0, false), 35.0, 9.0, false, &fecha);
+
+    pagina.rectangulo(16.0, 44.0, ANCHO_RECIBO - 32.0, 0.4, Some((0.45, 0.1, 0.15)));
+
+    let etiqueta = |x: f32, y: f32, etiqueta: &str, valor: &str, ancho: f32| {
+        pagina.texto(x, y, 9.5, true, etiqueta);
+        let desplazamiento = ancho_texto(etiqueta, 9.5, true) + 2.0;
+        pagina.texto_ajustado(x + desplazamiento, y, ancho - desplazamiento, 9.5, false, valor);
+    };
+    etiqueta(16.0, 56.0, "Recibí de:", &recibo.nombre_hermano, 178.0);
+    etiqueta(16.0, 64.0, "Nº de hermano:", &recibo.numero_hermano, 90.0);
+    if let Some(dni) = &recibo.dni {
+        etiqueta(110.0, 64.0, "DNI:", dni, 84.0);
+    }
+    etiqueta(16.0, 72.0, "En concepto de:", &recibo.concepto, 178.0);
+    etiqueta(16.0, 80.0, "Forma de pago:", &descripcion_metodo(&recibo.metodo_pago), 90.0);
+    etiqueta(110.0, 80.0, "Fecha de pago:", &fecha_espanola(&recibo.fecha_pago), 84.0);
+
+    pagina.texto(16.0, 94.0, 13.0, true, &format!("Importe: {}", formatear_importe(recibo.importe)));
+    pagina.texto_ajustado(16.0, 100.0, 178.0, 9.0, false, &format!("Son {}.", importe_en_letras(recibo.importe)));
+
+    if recibo.anulado {
+        pagina.texto(16.0, 118.0, 20.0, true, "ANULADO");
+    }
+
+    pagina.rectangulo(125.0, 120.0, 65.0, 0.3, Some((0.0, 0.0, 0.0)));
+    pagina.texto(125.0, 125.0, 8.0, false, "Por la hermandad, el Tesorero");
+
+    Ok(())
+}
+
+fn descripcion_metodo(metodo: &str) -> String {
+    match metodo {
+        "efectivo" => "Efectivo".to_string(),
+        "transferencia" => "Transferencia bancaria".to_string(),
+        METODO_DOMICILIACION => "Domiciliación bancaria".to_string(),
+        "tarjeta" => "Tarjeta".to_string(),
+        "bizum" => "Bizum".to_string(),
+        otro => {
+            let mut letras = otro.chars();
+            match letras.next() {
+                Some(primera) => primera.to_uppercase().chain(letras).collect(),
+                None => String::new(),
+            }
+        }
+    }
+}
@@ -0,0 +1,602 @@
+use rusqlite::{params, Connection, OptionalExtension, Result, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::db::{Cuota, DbConnection, Hermano};
+use crate::db::cuotas::buscar_cuota;
+use crate::db::pagos::{registrar_pago, SolicitudPago};
+use crate::db::hermanos::buscar_hermano;
+use crate::db::membresia::{hoy, validar_fecha};
+use crate::db::sepa::{cargar_acreedor, mandato_usado, mandato_vigente, texto_sepa, AcreedorSepa, MandatoSepa, TipoMandato};
+use crate::error::{HermanarError, HermanarResult};
+
+pub(crate) const METODO_DOMICILIACION: &str = "domiciliacion";
+
+// Tipos de adeudo de SEPA Core: primero y siguientes de un mandato
+// recurrente, y único de un mandato puntual.
+const SECUENCIA_PRIMERO: &str = "FRST";
+const SECUENCIA_RECURRENTE: &str = "RCUR";
+const SECUENCIA_UNICO: &str = "OOFF";
+
+/// Fichero de adeudos generado y enviado al banco.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Remesa {
+    pub id: i64,
+    pub referencia: String,
+    pub fecha_cobro: String,
+    pub num_operaciones: i32,
+    pub importe_total: f64,
+    pub fichero: String,
+    pub operador: String,
+    pub created_at: String,
+}
+
+impl Remesa {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        Ok(Remesa {
+            id: row.get(0)?,
+            referencia: row.get(1)?,
+            fecha_cobro: row.get(2)?,
+            num_operaciones: row.get(3)?,
+            importe_total: row.get(4)?,
+            fichero: row.get(5)?,
+            operador: row.get(6)?,
+            created_at: row.get(7)?,
+        })
+    }
+}
+
+/// Adeudo de una cuota dentro de una remesa, con los datos del mandato tal y
+/// como se enviaron al banco.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CuotaRemesa {
+    pub remesa_id: i64,
+    pub cuota_id: i32,
+    pub hermano_id: i32,
+    pub end_to_end_id: String,
+    pub mandato_referencia: String,
+    pub titular: String,
+    pub iban: String,
+    pub importe: f64,
+    pub secuencia: String,
+}
+
+impl CuotaRemesa {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        Ok(CuotaRemesa {
+            remesa_id: row.get(0)?,
+            cuota_id: row.get(1)?,
+            hermano_id: row.get(2)?,
+            end_to_end_id: row.get(3)?,
+            mandato_referencia: row.get(4)?,
+            titular: row.get(5)?,
+            iban: row.get(6)?,
+            importe: row.get(7)?,
+            secuencia: row.get(8)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolicitudRemesa {
+    pub cuota_ids: Vec<i32>,
+    pub fecha_cobro: String,
+    /// Ruta en la que se escribe el fichero XML.
+    pub fichero: String,
+    /// Texto que precede al detalle de cada cuota en el concepto del adeudo.
+    pub concepto: Option<String>,
+}
+
+struct Adeudo {
+    cuota: Cuota,
+    hermano: Hermano,
+    mandato: MandatoSepa,
+    centimos: i64,
+    secuencia: &'static str,
+    end_to_end_id: String,
+}
+
+pub fn list_remesas(db: &DbConnection) -> HermanarResult<Vec<Remesa>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, referencia, fecha_cobro, num_operaciones, importe_total, fichero, operador, created_at
+         FROM remesas
+         ORDER BY created_at DESC, id DESC"
+    )?;
+
+    let remesas = stmt.query_map([], |row| {
+        Remesa::from_row(row)
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(remesas)
+}
+
+pub(crate) fn buscar_remesa(conn: &Connection, id: i64) -> HermanarResult<Option<Remesa>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, referencia, fecha_cobro, num_operaciones, importe_total, fichero, operador, created_at
+         FROM remesas
+         WHERE id = ?1"
+    )?;
+
+    Ok(stmt.query_row([id], Remesa::from_row).optional()?)
+}
+
+pub fn get_cuotas_remesa(db: &DbConnection, remesa_id: i64) -> HermanarResult<Vec<CuotaRemesa>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(
+        "SELECT remesa_id, cuota_id, hermano_id, end_to_end_id, mandato_referencia, titular, iban, importe, secuencia
+         FROM remesa_cuotas
+         WHERE remesa_id = ?1
+         ORDER BY end_to_end_id"
+    )?;
+
+    let cuotas = stmt.query_map([remesa_id], |row| {
+        CuotaRemesa::from_row(row)
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(cuotas)
+}
+
+/// Genera un fichero de adeudos SEPA Core (pain.008.001.02) con las cuotas
+/// indicadas, que quedan pagadas por domiciliación en la fecha de cobro, y
+/// registra la remesa. Cada cuota se carga en el mandato de su hermano o, si
+/// no lo tiene, en el de su familia.
+pub fn generar_remesa(db: &DbConnection, solicitud: &SolicitudRemesa) -> HermanarResult<Remesa> {
+    let fecha_cobro = validar_fecha("fecha_cobro", &solicitud.fecha_cobro)?;
+    if fecha_cobro <= hoy() {
+        return Err(HermanarError::validation("fecha_cobro", "La fecha de cobro debe ser posterior a hoy"));
+    }
+    let fichero = solicitud.fichero.trim();
+    if fichero.is_empty() {
+        return Err(HermanarError::validation("fichero", "Indique dónde guardar el fichero de la remesa"));
+    }
+    let mut cuota_ids = solicitud.cuota_ids.clone();
+    cuota_ids.sort_unstable();
+    cuota_ids.dedup();
+    if cuota_ids.is_empty() {
+        return Err(HermanarError::validation("cuota_ids", "Seleccione al menos una cuota"));
+    }
+
+    let mut conn = db.writer();
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let operador = db.operador();
+
+    let acreedor = cargar_acreedor(&tx)?.ok_or_else(|| {
+        HermanarError::validation("acreedor", "Configure antes los datos de la hermandad como acreedora SEPA")
+    })?;
+
+    let id: i64 = tx.query_row("SELECT COALESCE(MAX(id), 0) + 1 FROM remesas", [], |row| row.get(0))?;
+    let ahora = chrono::Local::now();
+    let referencia = format!("REMESA-{:06}-{}", id, ahora.format("%Y%m%d%H%M%S"));
+
+    let mut adeudos = Vec::with_capacity(cuota_ids.len());
+    // Si cada mandato de la remesa se había usado ya en otra anterior. Todos
+    // los adeudos de un mandato recurrente nuevo van como primeros: el banco
+    // rechaza los siguientes mientras no se haya liquidado el primero.
+    let mut mandatos_en_remesa: HashMap<String, bool> = HashMap::new();
+    for cuota_id in cuota_ids {
+        let cuota = buscar_cuota(&tx, cuota_id)?.ok_or_else(|| HermanarError::not_found("Cuota"))?;
+        let hermano = buscar_hermano(&tx, cuota.hermano_id)?.ok_or_else(|| HermanarError::not_found("Hermano"))?;
+        if cuota.pagado {
+            return Err(HermanarError::conflict(format!(
+                "La cuota {} del hermano {} ya está pagada",
+                cuota.etiqueta_periodo(), hermano.numero_hermano
+            )));
+        }
+
+        let mandato = mandato_vigente(&tx, &hermano)?.ok_or_else(|| {
+            HermanarError::validation(
+                "cuota_ids",
+                format!(
+                    "El hermano {} no tiene un mandato SEPA activo, ni propio ni de su familia",
+                    hermano.numero_hermano
+                ),
+            )
+        })?;
+        if mandato.fecha_firma > fecha_cobro {
+            return Err(HermanarError::validation(
+                "fecha_cobro",
+                format!("El mandato {} se firmó después de la fecha de cobro", mandato.referencia),
+            ));
+        }
+
+        let centimos = (cuota.importe_pendiente * 100.0).round() as i64;
+        if centimos <= 0 {
+            return Err(HermanarError::validation(
+                "cuota_ids",
+                format!("La cuota {} del hermano {} no tiene importe", cuota.etiqueta_periodo(), hermano.numero_hermano),
+            ));
+        }
+
+        let en_remesa = mandatos_en_remesa.contains_key(&mandato.referencia);
+        let usado_antes = match mandatos_en_remesa.get(&mandato.referencia) {
+            Some(&usado) => usado,
+            None => mandato_usado(&tx, &mandato.referencia)?,
+        };
+        mandatos_en_remesa.insert(mandato.referencia.clone(), usado_antes);
+        let secuencia = match mandato.tipo {
+            TipoMandato::Puntual if usado_antes => {
+                return Err(HermanarError::conflict(format!(
+                    "El mandato puntual {} ya se ha usado para otro adeudo",
+                    mandato.referencia
+                )));
+            }
+            TipoMandato::Puntual if en_remesa => {
+                return Err(HermanarError::validation(
+                    "cuota_ids",
+                    format!(
+                        "El mandato puntual {} solo admite un adeudo y la remesa incluye varias cuotas que se cargan en él",
+                        mandato.referencia
+                    ),
+                ));
+            }
+            TipoMandato::Puntual => SECUENCIA_UNICO,
+            TipoMandato::Recurrente if usado_antes => SECUENCIA_RECURRENTE,
+            TipoMandato::Recurrente => SECUENCIA_PRIMERO,
+        };
+
+        adeudos.push(Adeudo {
+            end_to_end_id: format!("R{:06}C{:08}", id, cuota_id),
+            cuota,
+            hermano,
+            mandato,
+            centimos,
+            secuencia,
+        });
+    }
+
+    let total_centimos: i64 = adeudos.iter().map(|a| a.centimos).sum();
+    tx.prepare_cached(
+        "INSERT INTO remesas (id, referencia, fecha_cobro, num_operaciones, importe_total, fichero, operador)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"
+    )?
+    .execute(params![
+        id,
+        referencia,
+        fecha_cobro,
+        adeudos.len() as i32,
+        total_centimos as f64 / 100.0,
+        fichero,
+        operador,
+    ])?;
+
+    let mut insertar = tx.prepare_cached(
+        "INSERT INTO remesa_cuotas
+         (remesa_id, cuota_id, hermano_id, end_to_end_id, mandato_referencia, titular, iban, importe, secuencia)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+    )?;
+    for adeudo in &adeudos {
+        let cuota_id = adeudo.cuota.id.unwrap_or_default();
+        insertar.execute(params![
+            id,
+            cuota_id,
+            adeudo.cuota.hermano_id,
+            adeudo.end_to_end_id,
+            adeudo.mandato.referencia,
+            adeudo.mandato.titular,
+            adeudo.mandato.iban,
+            adeudo.centimos as f64 / 100.0,
+            adeudo.secuencia,
+        ])?;
+        registrar_pago(&tx, &operador, &SolicitudPago {
+            cuota_id,
+            importe: Some(adeudo.centimos as f64 / 100.0),
+            fecha: fecha_cobro.clone(),
+            metodo: METODO_DOMICILIACION.to_string(),
+            referencia: Some(adeudo.end_to_end_id.clone()),
+            observaciones: None,
+        })?;
+    }
+    drop(insertar);
+
+    let concepto = solicitud.concepto.as_deref().map(str::trim).filter(|c| !c.is_empty());
+    let xml = pain_008(&acreedor, &referencia, &ahora.format("%Y-%m-%dT%H:%M:%S").to_string(), &fecha_cobro, concepto, &adeudos);
+    std::fs::write(fichero, xml)
+        .map_err(|e| HermanarError::database(format!("No se pudo escribir el fichero de la remesa: {}", e)))?;
+
+    let remesa = buscar_remesa(&tx, id)?.ok_or_else(|| HermanarError::not_found("Remesa"))?;
+    if let Err(e) = tx.commit() {
+        let _ = std::fs::remove_file(fichero);
+        return Err(e.into());
+    }
+
+    Ok(remesa)
+}
+
+fn importe_sepa(centimos: i64) -> String {
+    format!("{}.{:02}", centimos / 100, centimos % 100)
+}
+
+/// Escritor mínimo de XML con sangría para el fichero de la remesa. Los textos
+/// ya vienen limitados al juego de caracteres SEPA, pero se escapan igualmente.
+struct Xml {
+    texto: String,
+    nivel: usize,
+}
+
+impl Xml {
+    fn abrir(&mut self, etiqueta: &str) {
+        self.texto.push_str(&format!("{}<{}>\n", "  ".repeat(self.nivel), etiqueta));
+        self.nivel += 1;
+    }
+
+    fn cerrar(&mut self, etiqueta: &str) {
+        self.nivel -= 1;
+        let nombre = etiqueta.split_whitespace().next().unwrap_or(etiqueta);
+        self.texto.push_str(&format!("{}</{}>\n", "  ".repeat(self.nivel), nombre));
+    }
+
+    fn valor(&mut self, etiqueta: &str, valor: &str) {
+        let nombre = etiqueta.split_whitespace().next().unwrap_or(etiqueta);
+        let valor = valor
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+            .replace('\'', "&apos;");
+        self.texto.push_str(&format!("{}<{}>{}</{}>\n", "  ".repeat(self.nivel), etiqueta, valor, nombre));
+    }
+
+    fn entidad(&mut self, etiqueta: &str, bic: Option<&str>) {
+        self.abrir(etiqueta);
+        self.abrir("FinInstnId");
+        match bic {
+            Some(bic) => self.valor("BIC", bic),
+            None => {
+                self.abrir("Othr");
+                self.valor("Id", "NOTPROVIDED");
+                self.cerrar("Othr");
+            }
+        }
+        self.cerrar("FinInstnId");
+        self.cerrar(etiqueta);
+    }
+
+    fn cuenta(&mut self, etiqueta: &str, iban: &str) {
+        self.abrir(etiqueta);
+        self.abrir("Id");
+        self.valor("IBAN", iban);
+        self.cerrar("Id");
+        self.cerrar(etiqueta);
+    }
+}
+
+// Un bloque de pago (PmtInf) por cada tipo de adeudo presente en la remesa.
+fn pain_008(
+    acreedor: &AcreedorSepa,
+    referencia: &str,
+    creacion: &str,
+    fecha_cobro: &str,
+    concepto: Option<&str>,
+    adeudos: &[Adeudo],
+) -> String {
+    let mut xml = Xml {
+        texto: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"),
+        nivel: 0,
+    };
+    let nombre_acreedor = texto_sepa(&acreedor.nombre, 70);
+    let total: i64 = adeudos.iter().map(|a| a.centimos).sum();
+
+    xml.abrir(
+        "Document xmlns=\"urn:iso:std:iso:20022:tech:xsd:pain.008.001.02\" \
+         xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\"",
+    );
+    xml.abrir("CstmrDrctDbtInitn");
+
+    xml.abrir("GrpHdr");
+    xml.valor("MsgId", referencia);
+    xml.valor("CreDtTm", creacion);
+    xml.valor("NbOfTxs", &adeudos.len().to_string());
+    xml.valor("CtrlSum", &importe_sepa(total));
+    xml.abrir("InitgPty");
+    xml.valor("Nm", &nombre_acreedor);
+    xml.abrir("Id");
+    xml.abrir("OrgId");
+    xml.abrir("Othr");
+    xml.valor("Id", &acreedor.identificador);
+    xml.cerrar("Othr");
+    xml.cerrar("OrgId");
+    xml.cerrar("Id");
+    xml.cerrar("InitgPty");
+    xml.cerrar("GrpHdr");
+
+    for secuencia in [SECUENCIA_PRIMERO, SECUENCIA_RECURRENTE, SECUENCIA_UNICO] {
+        let bloque: Vec<&Adeudo> = adeudos.iter().filter(|a| a.secuencia == secuencia).collect();
+        if bloque.is_empty() {
+            continue;
+        }
+
+        xml.abrir("PmtInf");
+        xml.valor("PmtInfId", &format!("{}-{}", referencia, secuencia));
+        xml.valor("PmtMtd", "DD");
+        xml.valor("BtchBookg", "true");
+        xml.valor("NbOfTxs", &bloque.len().to_string());
+        xml.valor("CtrlSum", &importe_sepa(bloque.iter().map(|a| a.centimos).sum()));
+        xml.abrir("PmtTpInf");
+        xml.abrir("SvcLvl");
+        xml.valor("Cd", "SEPA");
+        xml.cerrar("SvcLvl");
+        xml.abrir("LclInstrm");
+        xml.valor("Cd", "CORE");
+        xml.cerrar("LclInstrm");
+        xml.valor("SeqTp", secuencia);
+        xml.cerrar("PmtTpInf");
+        xml.valor("ReqdColltnDt", fecha_cobro);
+        xml.abrir("Cdtr");
+        xml.valor("Nm", &nombre_acreedor);
+        xml.cerrar("Cdtr");
+        xml.cuenta("CdtrAcct", &acreedor.iban);
+        xml.entidad("CdtrAgt", acreedor.bic.as_deref());
+        xml.valor("ChrgBr", "SLEV");
+        xml.abrir("CdtrSchmeId");
+        xml.abrir("Id");
+        xml.abrir("PrvtId");
+        xml.abrir("Othr");
+        xml.valor("Id", &acreedor.identificador);
+        xml.abrir("SchmeNm");
+        xml.valor("Prtry", "SEPA");
+        xml.cerrar("SchmeNm");
+        xml.cerrar("Othr");
+        xml.cerrar("PrvtId");
+        xml.cerrar("Id");
+        xml.cerrar("CdtrSchmeId");
+
+        for adeudo in bloque {
+            let detalle = format!(
+                "Cuota {} - Hermano {} {} {}",
+                adeudo.cuota.etiqueta_periodo(),
+                adeudo.hermano.numero_hermano,
+                adeudo.hermano.nombre,
+                adeudo.hermano.primer_apellido
+            );
+            let concepto = match concepto {
+                Some(concepto) => format!("{} - {}", concepto, detalle),
+                None => detalle,
+            };
+
+            xml.abrir("DrctDbtTxInf");
+            xml.abrir("PmtId");
+            xml.valor("EndToEndId", &adeudo.end_to_end_id);
+            xml.cerrar("PmtId");
+            xml.valor("InstdAmt Ccy=\"EUR\"", &importe_sepa(adeudo.centimos));
+            xml.abrir("DrctDbtTx");
+            xml.abrir("MndtRltdInf");
+            xml.valor("MndtId", &adeudo.mandato.referencia);
+            xml.valor("DtOfSgntr", &adeudo.mandato.fecha_firma);
+            xml.cerrar("MndtRltdInf");
+            xml.cerrar("DrctDbtTx");
+            xml.entidad("DbtrAgt", adeudo.mandato.bic.as_deref());
+            xml.abrir("Dbtr");
+            xml.valor("Nm", &texto_sepa(&adeudo.mandato.titular, 70));
+            xml.cerrar("Dbtr");
+            xml.cuenta("DbtrAcct", &adeudo.mandato.iban);
+            xml.abrir("RmtInf");
+            xml.valor("Ustrd", &texto_sepa(&concepto, 140));
+            xml.cerrar("RmtInf");
+            xml.cerrar("DrctDbtTxInf");
+        }
+
+        xml.cerrar("PmtInf");
+    }
+
+    xml.cerrar("CstmrDrctDbtInitn");
+    xml.cerrar("Document");
+
+    xml.texto
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adeudo(numero: &str, centimos: i64, secuencia: &'static str) -> Adeudo {
+        let hermano: Hermano = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "numero_hermano": numero,
+            "nombre": "José",
+            "primer_apellido": "Núñez",
+            "fecha_alta": "2020-01-01",
+            "autorizacion_menores": false,
+            "activo": true
+        }))
+        .unwrap();
+        let cuota: Cuota = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "hermano_id": 1,
+            "anio": 2026,
+            "periodo": 1,
+            "importe": centimos as f64 / 100.0,
+            "pagado": false
+        }))
+        .unwrap();
+
+        Adeudo {
+            cuota,
+            hermano,
+            mandato: MandatoSepa {
+                id: Some(1),
+                hermano_id: Some(1),
+                familia_id: None,
+                referencia: format!("MND-{}", numero),
+                titular: "José Núñez & Hijos".to_string(),
+                iban: "ES9121000418450200051332".to_string(),
+                bic: None,
+                fecha_firma: "2020-01-01".to_string(),
+                tipo: TipoMandato::Recurrente,
+                activo: true,
+                created_at: None,
+                updated_at: None,
+            },
+            centimos,
+            secuencia,
+            end_to_end_id: format!("E2E-{}", numero),
+        }
+    }
+
+    #[test]
+    fn escapado_xml() {
+        let mut xml = Xml { texto: String::new(), nivel: 0 };
+        xml.valor("Ustrd", r#"<"A" & 'B'>"#);
+        assert_eq!(xml.texto, "<Ustrd>&lt;&quot;A&quot; &amp; &apos;B&apos;&gt;</Ustrd>\n");
+    }
+
+    #[test]
+    fn importes() {
+        assert_eq!(importe_sepa(1250), "12.50");
+        assert_eq!(importe_sepa(5), "0.05");
+        assert_eq!(importe_sepa(100000), "1000.00");
+    }
+
+    #[test]
+    fn fichero_pain_008() {
+        let acreedor = AcreedorSepa {
+            nombre: "Hermandad de la Peña".to_string(),
+            identificador: "ES26000G12345678".to_string(),
+            iban: "ES9121000418450200051332".to_string(),
+            bic: Some("CAIXESBBXXX".to_string()),
+        };
+        let adeudos = [
+            adeudo("00001", 1250, SECUENCIA_RECURRENTE),
+            adeudo("00002", 1000, SECUENCIA_PRIMERO),
+            adeudo("00003", 775, SECUENCIA_RECURRENTE),
+        ];
+        let xml = pain_008(&acreedor, "REMESA-1", "2026-01-02T10:00:00", "2026-01-10", Some("Cuotas"), &adeudos);
+
+        let documento = roxmltree::Document::parse(&xml).unwrap();
+        let elementos = |nombre: &str| -> Vec<roxmltree::Node> {
+            documento.descendants().filter(|n| n.tag_name().name() == nombre).collect()
+        };
+        let textos = |nombre: &str| -> Vec<String> {
+            elementos(nombre).iter().map(|n| n.text().unwrap_or_default().to_string()).collect()
+        };
+
+        let cabecera = elementos("GrpHdr")[0];
+        let dato = |nodo: roxmltree::Node, nombre: &str| {
+            nodo.children().find(|n| n.tag_name().name() == nombre).and_then(|n| n.text()).map(str::to_string)
+        };
+        assert_eq!(dato(cabecera, "MsgId").as_deref(), Some("REMESA-1"));
+        assert_eq!(dato(cabecera, "NbOfTxs").as_deref(), Some("3"));
+        assert_eq!(dato(cabecera, "CtrlSum").as_deref(), Some("30.25"));
+
+        // Un bloque por tipo de adeudo, primero los FRST.
+        let bloques = elementos("PmtInf");
+        assert_eq!(bloques.len(), 2);
+        assert_eq!(dato(bloques[0], "PmtInfId").as_deref(), Some("REMESA-1-FRST"));
+        assert_eq!(dato(bloques[0], "CtrlSum").as_deref(), Some("10.00"));
+        assert_eq!(dato(bloques[1], "PmtInfId").as_deref(), Some("REMESA-1-RCUR"));
+        assert_eq!(dato(bloques[1], "NbOfTxs").as_deref(), Some("2"));
+        assert_eq!(dato(bloques[1], "CtrlSum").as_deref(), Some("20.25"));
+        assert_eq!(textos("SeqTp"), ["FRST", "RCUR"]);
+        assert_eq!(textos("EndToEndId"), ["E2E-00002", "E2E-00001", "E2E-00003"]);
+        assert_eq!(textos("InstdAmt"), ["10.00", "12.50", "7.75"]);
+        assert!(elementos("InstdAmt").iter().all(|n| n.attribute("Ccy") == Some("EUR")));
+        assert_eq!(textos("ReqdColltnDt"), ["2026-01-10", "2026-01-10"]);
+
+        // Textos en el juego de caracteres SEPA y entidad del deudor sin BIC.
+        assert!(textos("Nm").contains(&"Jose Nunez Hijos".to_string()));
+        assert!(textos("Nm").contains(&"Hermandad de la Pena".to_string()));
+        assert_eq!(textos("Ustrd")[0], "Cuotas - Cuota 2026 T1 - Hermano 00002 Jose Nunez");
+        assert_eq!(textos("Id").iter().filter(|id| *id == "NOTPROVIDED").count(), 3);
+    }
+}
@@ -0,0 +1,568 @@
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+use crate::db::{configuracion, DbConnection, Hermano};
+use crate::db::familias::buscar_familia;
+use crate::db::hermanos::buscar_hermano;
+use crate::db::membresia::{hoy, validar_fecha};
+use crate::error::{HermanarError, HermanarResult};
+
+const CLAVE_ACREEDOR_NOMBRE: &str = "sepa_acreedor_nombre";
+const CLAVE_ACREEDOR_IDENTIFICADOR: &str = "sepa_acreedor_identificador";
+const CLAVE_ACREEDOR_IBAN: &str = "sepa_acreedor_iban";
+const CLAVE_ACREEDOR_BIC: &str = "sepa_acreedor_bic";
+
+const SELECT_MANDATOS: &str =
+    "SELECT id, hermano_id, familia_id, referencia, titular, iban, bic, fecha_firma, tipo, activo,
+            created_at, updated_at
+     FROM mandatos_sepa";
+
+/// Un mandato recurrente sirve para todas las cuotas; uno puntual solo
+/// autoriza un único adeudo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TipoMandato {
+    Recurrente,
+    Puntual,
+}
+
+impl TipoMandato {
+    fn clave(&self) -> &'static str {
+        match self {
+            TipoMandato::Recurrente => "recurrente",
+            TipoMandato::Puntual => "puntual",
+        }
+    }
+
+    fn desde_clave(clave: &str) -> Option<Self> {
+        match clave {
+            "recurrente" => Some(TipoMandato::Recurrente),
+            "puntual" => Some(TipoMandato::Puntual),
+            _ => None,
+        }
+    }
+}
+
+/// Orden de domiciliación firmada por el titular de la cuenta. Pertenece a un
+/// hermano o a una familia; las cuotas de un hermano sin mandato propio se
+/// cargan en el de su familia.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MandatoSepa {
+    pub id: Option<i64>,
+    pub hermano_id: Option<i32>,
+    pub familia_id: Option<i32>,
+    pub referencia: String,
+    pub titular: String,
+    pub iban: String,
+    pub bic: Option<String>,
+    pub fecha_firma: String,
+    pub tipo: TipoMandato,
+    pub activo: bool,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+impl MandatoSepa {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        Ok(MandatoSepa {
+            id: Some(row.get(0)?),
+            hermano_id: row.get(1)?,
+            familia_id: row.get(2)?,
+            referencia: row.get(3)?,
+            titular: row.get(4)?,
+            iban: row.get(5)?,
+            bic: row.get(6)?,
+            fecha_firma: row.get(7)?,
+            tipo: TipoMandato::desde_clave(&row.get::<_, String>(8)?).unwrap_or(TipoMandato::Recurrente),
+            activo: row.get(9)?,
+            created_at: row.get(10)?,
+            updated_at: row.get(11)?,
+        })
+    }
+}
+
+/// Datos de la hermandad como acreedora de los adeudos.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcreedorSepa {
+    pub nombre: String,
+    /// Identificador de acreedor SEPA (p. ej. ES12000G12345678).
+    pub identificador: String,
+    pub iban: String,
+    pub bic: Option<String>,
+}
+
+/// Quita espacios, pasa a mayúsculas y comprueba longitud y dígitos de
+/// control (módulo 97) de un IBAN.
+pub fn normalizar_iban(iban: &str) -> Result<String, String> {
+    let iban: String = iban.chars().filter(|c| !c.is_whitespace() && *c != '-').collect::<String>().to_uppercase();
+
+    if iban.len() < 15 || iban.len() > 34 || !iban.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("El IBAN debe tener entre 15 y 34 letras y cifras".to_string());
+    }
+    if !iban[..2].chars().all(|c| c.is_ascii_alphabetic()) || !iban[2..4].chars().all(|c| c.is_ascii_digit()) {
+        return Err("El IBAN debe empezar por el código del país y dos dígitos de control".to_string());
+    }
+    if iban.starts_with("ES") && iban.len() != 24 {
+        return Err("Un IBAN español tiene 24 caracteres".to_string());
+    }
+
+    let reordenado = format!("{}{}", &iban[4..], &iban[..4]);
+    if modulo_97(&reordenado) != 1 {
+        return Err("Los dígitos de control del IBAN no son correctos".to_string());
+    }
+
+    Ok(iban)
+}
+
+/// Comprueba el formato de un BIC de 8 u 11 caracteres.
+pub fn normalizar_bic(bic: &str) -> Result<String, String> {
+    let bic: String = bic.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_uppercase();
+
+    let valido = (bic.len() == 8 || bic.len() == 11)
+        && bic.chars().all(|c| c.is_ascii_alphanumeric())
+        && bic[..6].chars().all(|c| c.is_ascii_alphabetic());
+    if !valido {
+        return Err("El BIC debe tener 8 u 11 caracteres (p. ej. CAIXESBBXXX)".to_string());
+    }
+
+    Ok(bic)
+}
+
+/// Comprueba el identificador de acreedor: país, dos dígitos de control, tres
+/// caracteres de sufijo y el NIF. Los dígitos de control se calculan sobre el
+/// NIF y el país, sin el sufijo.
+pub fn normalizar_identificador_acreedor(identificador: &str) -> Result<String, String> {
+    let identificador: String = identificador
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_uppercase();
+
+    if identificador.len() < 8 || identificador.len() > 35 || !identificador.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("El identificador de acreedor no tiene un formato válido".to_string());
+    }
+
+    let control = format!("{}{}00", &identificador[7..], &identificador[..2]);
+    let esperado = 98 - modulo_97(&control);
+    if identificador[2..4] != format!("{:02}", esperado) {
+        return Err("Los dígitos de control del identificador de acreedor no son correctos".to_string());
+    }
+
+    Ok(identificador)
+}
+
+// Resto módulo 97 de una cadena alfanumérica en la que cada letra vale su
+// posición más 9 (A = 10, B = 11, ...), como en el cálculo del IBAN.
+fn modulo_97(valor: &str) -> u32 {
+    valor.chars().fold(0, |resto, c| {
+        let digitos = c.to_digit(36).unwrap_or(0);
+        if digitos >= 10 {
+            (resto * 100 + digitos) % 97
+        } else {
+            (resto * 10 + digitos) % 97
+        }
+    })
+}
+
+/// Adapta un texto al juego de caracteres latino básico que admiten los
+/// ficheros SEPA: quita acentos, cambia ñ por n y sustituye cualquier otro
+/// carácter no admitido por un espacio.
+pub(crate) fn texto_sepa(texto: &str, maximo: usize) -> String {
+    let convertido: String = texto
+        .chars()
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ä' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ó' | 'ò' | 'ô' | 'ö' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' => 'u',
+            'Á' | 'À' | 'Â' | 'Ä' => 'A',
+            'É' | 'È' | 'Ê' | 'Ë' => 'E',
+            'Í' | 'Ì' | 'Î' | 'Ï' => 'I',
+            'Ó' | 'Ò' | 'Ô' | 'Ö' => 'O',
+            'Ú' | 'Ù' | 'Û' | 'Ü' => 'U',
+            'ñ' => 'n',
+            'Ñ' => 'N',
+            'ç' => 'c',
+            'Ç' => 'C',
+            c if c.is_ascii_alphanumeric() || "/-?:().,'+ ".contains(c) => c,
+            _ => ' ',
+        })
+        .collect();
+
+    convertido.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(maximo).collect()
+}
+
+fn validar_mandato(conn: &Connection, mandato: &MandatoSepa, excepto: Option<i64>) -> HermanarResult<MandatoSepa> {
+    let mut mandato = mandato.clone();
+
+    match (mandato.hermano_id, mandato.familia_id) {
+        (Some(hermano_id), None) => {
+            buscar_hermano(conn, hermano_id)?.ok_or_else(|| HermanarError::not_found("Hermano"))?;
+        }
+        (None, Some(familia_id)) => {
+            buscar_familia(conn, familia_id)?.ok_or_else(|| HermanarError::not_found("Familia"))?;
+        }
+        _ => {
+            return Err(HermanarError::validation(
+                "hermano_id",
+                "El mandato debe pertenecer a un hermano o a una familia, pero no a ambos",
+            ));
+        }
+    }
+
+    mandato.referencia = mandato.referencia.trim().to_string();
+    let referencia_valida = !mandato.referencia.is_empty()
+        && mandato.referencia.len() <= 35
+        && mandato.referencia.chars().all(|c| c.is_ascii_alphanumeric() || "/-?:().,'+".contains(c));
+    if !referencia_valida {
+        return Err(HermanarError::validation(
+            "referencia",
+            "La referencia del mandato debe tener hasta 35 letras, cifras o signos / - ? : ( ) . , ' +, sin espacios",
+        ));
+    }
+
+    mandato.titular = mandato.titular.trim().to_string();
+    if mandato.titular.is_empty() {
+        return Err(HermanarError::validation("titular", "Indique el titular de la cuenta"));
+    }
+
+    mandato.iban = normalizar_iban(&mandato.iban).map_err(|e| HermanarError::validation("iban", e))?;
+    mandato.bic = match mandato.bic.as_deref().map(str::trim) {
+        Some(bic) if !bic.is_empty() => Some(normalizar_bic(bic).map_err(|e| HermanarError::validation("bic", e))?),
+        _ => None,
+    };
+
+    mandato.fecha_firma = validar_fecha("fecha_firma", &mandato.fecha_firma)?;
+    if mandato.fecha_firma > hoy() {
+        return Err(HermanarError::validation("fecha_firma", "La fecha de firma no puede ser futura"));
+    }
+
+    if mandato.activo {
+        let otro: Option<String> = conn
+            .prepare_cached(
+                "SELECT referencia FROM mandatos_sepa
+                 WHERE activo = 1 AND (hermano_id = ?1 OR familia_id = ?2) AND id IS NOT ?3"
+            )?
+            .query_row(params![mandato.hermano_id, mandato.familia_id, excepto], |row| row.get(0))
+            .optional()?;
+        if let Some(otro) = otro {
+            return Err(HermanarError::conflict(format!(
+                "Ya hay un mandato activo ({}); revóquelo antes de dar de alta otro",
+                otro
+            )));
+        }
+    }
+
+    Ok(mandato)
+}
+
+pub fn list_mandatos_sepa(db: &DbConnection) -> HermanarResult<Vec<MandatoSepa>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(&format!("{} ORDER BY activo DESC, referencia", SELECT_MANDATOS))?;
+
+    let mandatos = stmt.query_map([], |row| {
+        MandatoSepa::from_row(row)
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(mandatos)
+}
+
+pub(crate) fn buscar_mandato(conn: &Connection, id: i64) -> HermanarResult<Option<MandatoSepa>> {
+    let mut stmt = conn.prepare_cached(&format!("{} WHERE id = ?1", SELECT_MANDATOS))?;
+
+    Ok(stmt.query_row([id], MandatoSepa::from_row).optional()?)
+}
+
+/// Mandatos propios de un hermano, activos o revocados.
+pub(crate) fn buscar_mandatos_hermano(conn: &Connection, hermano_id: i32) -> HermanarResult<Vec<MandatoSepa>> {
+    let mut stmt = conn.prepare_cached(&format!("{} WHERE hermano_id = ?1 ORDER BY id", SELECT_MANDATOS))?;
+
+    let mandatos = stmt.query_map([hermano_id], |row| {
+        MandatoSepa::from_row(row)
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(mandatos)
+}
+
+/// Vuelve a insertar con su `id` original los mandatos de un hermano
+/// restaurado de la papelera.
+pub(crate) fn reinsertar_mandatos(conn: &Connection, mandatos: &[MandatoSepa]) -> HermanarResult<()> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO mandatos_sepa
+         (id, hermano_id, familia_id, referencia, titular, iban, bic, fecha_firma, tipo, activo, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)"
+    )?;
+
+    for mandato in mandatos {
+        stmt.execute(params![
+            mandato.id,
+            mandato.hermano_id,
+            mandato.familia_id,
+            mandato.referencia,
+            mandato.titular,
+            mandato.iban,
+            mandato.bic,
+            mandato.fecha_firma,
+            mandato.tipo.clave(),
+            mandato.activo,
+            mandato.created_at,
+            mandato.updated_at,
+        ])?;
+    }
+
+    Ok(())
+}
+
+/// Mandato con el que se cobran las cuotas de un hermano: el suyo propio si
+/// lo tiene y, si no, el de su familia.
+pub fn get_mandato_vigente(db: &DbConnection, hermano_id: i32) -> HermanarResult<Option<MandatoSepa>> {
+    let conn = db.reader();
+    let hermano = buscar_hermano(&conn, hermano_id)?.ok_or_else(|| HermanarError::not_found("Hermano"))?;
+
+    mandato_vigente(&conn, &hermano)
+}
+
+pub(crate) fn mandato_vigente(conn: &Connection, hermano: &Hermano) -> HermanarResult<Option<MandatoSepa>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "{} WHERE activo = 1 AND (hermano_id = ?1 OR familia_id = ?2)
+         ORDER BY hermano_id IS NULL
+         LIMIT 1",
+        SELECT_MANDATOS
+    ))?;
+
+    Ok(stmt.query_row(params![hermano.id, hermano.familia_id], MandatoSepa::from_row).optional()?)
+}
+
+/// Indica si el mandato ya se ha usado en alguna remesa.
+pub(crate) fn mandato_usado(conn: &Connection, referencia: &str) -> HermanarResult<bool> {
+    let usado = conn
+        .prepare_cached("SELECT EXISTS(SELECT 1 FROM remesa_cuotas WHERE mandato_referencia = ?1)")?
+        .query_row([referencia], |row| row.get(0))?;
+
+    Ok(usado)
+}
+
+pub fn create_mandato_sepa(db: &DbConnection, mandato: &MandatoSepa) -> HermanarResult<i64> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+
+    let mandato = validar_mandato(&tx, mandato, None)?;
+    tx.prepare_cached(
+        "INSERT INTO mandatos_sepa
+         (hermano_id, familia_id, referencia, titular, iban, bic, fecha_firma, tipo, activo)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+    )?
+    .execute(params![
+        mandato.hermano_id,
+        mandato.familia_id,
+        mandato.referencia,
+        mandato.titular,
+        mandato.iban,
+        mandato.bic,
+        mandato.fecha_firma,
+        mandato.tipo.clave(),
+        mandato.activo,
+    ])?;
+    let id = tx.last_insert_rowid();
+
+    tx.commit()?;
+
+    Ok(id)
+}
+
+/// Actualiza un mandato. La referencia y la cuenta de un mandato que ya se ha
+/// usado en una remesa no pueden cambiarse: el banco identifica el mandato por
+/// su referencia, y los adeudos a otra cuenta necesitan un mandato nuevo.
+pub fn update_mandato_sepa(db: &DbConnection, id: i64, mandato: &MandatoSepa) -> HermanarResult<()> {
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+
+    let actual = buscar_mandato(&tx, id)?.ok_or_else(|| HermanarError::not_found("Mandato"))?;
+    let mandato = validar_mandato(&tx, mandato, Some(id))?;
+    comprobar_cambios_mandato(&tx, &actual, &mandato)?;
+
+    tx.prepare_cached(
+        "UPDATE mandatos_sepa
+         SET hermano_id = ?1, familia_id = ?2, referencia = ?3, titular = ?4, iban = ?5, bic = ?6,
+             fecha_firma = ?7, tipo = ?8, activo = ?9, updated_at = CURRENT_TIMESTAMP
+         WHERE id = ?10"
+    )?
+    .execute(params![
+        mandato.hermano_id,
+        mandato.familia_id,
+        mandato.referencia,
+        mandato.titular,
+        mandato.iban,
+        mandato.bic,
+        mandato.fecha_firma,
+        mandato.tipo.clave(),
+        mandato.activo,
+        id,
+    ])?;
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+fn comprobar_cambios_mandato(conn: &Connection, actual: &MandatoSepa, mandato: &MandatoSepa) -> HermanarResult<()> {
+    if mandato.referencia == actual.referencia && mandato.iban == actual.iban {
+        return Ok(());
+    }
+    if !mandato_usado(conn, &actual.referencia)? {
+        return Ok(());
+    }
+
+    if mandato.referencia != actual.referencia {
+        return Err(HermanarError::validation(
+            "referencia",
+            "El mandato ya se ha usado en una remesa y su referencia no puede cambiarse",
+        ));
+    }
+    Err(HermanarError::validation(
+        "iban",
+        "El mandato ya se ha usado en una remesa y su cuenta no puede cambiarse. \
+         Revoque este mandato y registre uno nuevo con la nueva cuenta",
+    ))
+}
+
+/// Deja un mandato sin efecto. Se conserva para poder consultar las remesas en
+/// las que se usó.
+pub fn revocar_mandato_sepa(db: &DbConnection, id: i64) -> HermanarResult<()> {
+    let actualizados = db
+        .writer()
+        .prepare_cached("UPDATE mandatos_sepa SET activo = 0, updated_at = CURRENT_TIMESTAMP WHERE id = ?1")?
+        .execute([id])?;
+
+    if actualizados == 0 {
+        return Err(HermanarError::not_found("Mandato"));
+    }
+
+    Ok(())
+}
+
+/// Datos de la hermandad como acreedora, o `None` si todavía no se han
+/// configurado.
+pub fn get_acreedor_sepa(db: &DbConnection) -> HermanarResult<Option<AcreedorSepa>> {
+    cargar_acreedor(&db.reader())
+}
+
+pub(crate) fn cargar_acreedor(conn: &Connection) -> HermanarResult<Option<AcreedorSepa>> {
+    let (Some(nombre), Some(identificador), Some(iban)) = (
+        configuracion::leer(conn, CLAVE_ACREEDOR_NOMBRE)?,
+        configuracion::leer(conn, CLAVE_ACREEDOR_IDENTIFICADOR)?,
+        configuracion::leer(conn, CLAVE_ACREEDOR_IBAN)?,
+    ) else {
+        return Ok(None);
+    };
+
+    Ok(Some(AcreedorSepa {
+        nombre,
+        identificador,
+        iban,
+        bic: configuracion::leer(conn, CLAVE_ACREEDOR_BIC)?.filter(|bic| !bic.is_empty()),
+    }))
+}
+
+pub fn set_acreedor_sepa(db: &DbConnection, acreedor: &AcreedorSepa) -> HermanarResult<()> {
+    let nombre = acreedor.nombre.trim();
+    if nombre.is_empty() {
+        return Err(HermanarError::validation("nombre", "Indique el nombre de la hermandad"));
+    }
+    let identificador = normalizar_identificador_acreedor(&acreedor.identificador)
+        .map_err(|e| HermanarError::validation("identificador", e))?;
+    let iban = normalizar_iban(&acreedor.iban).map_err(|e| HermanarError::validation("iban", e))?;
+    let bic = match acreedor.bic.as_deref().map(str::trim) {
+        Some(bic) if !bic.is_empty() => normalizar_bic(bic).map_err(|e| HermanarError::validation("bic", e))?,
+        _ => String::new(),
+    };
+
+    let mut conn = db.writer();
+    let tx = conn.transaction()?;
+    configuracion::guardar(&tx, CLAVE_ACREEDOR_NOMBRE, nombre)?;
+    configuracion::guardar(&tx, CLAVE_ACREEDOR_IDENTIFICADOR, &identificador)?;
+    configuracion::guardar(&tx, CLAVE_ACREEDOR_IBAN, &iban)?;
+    configuracion::guardar(&tx, CLAVE_ACREEDOR_BIC, &bic)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iban() {
+        assert_eq!(normalizar_iban("es91 2100 0418 4502 0005 1332"), Ok("ES9121000418450200051332".to_string()));
+        assert_eq!(normalizar_iban("DE89-3704-0044-0532-0130-00"), Ok("DE89370400440532013000".to_string()));
+        assert!(normalizar_iban("GB82WEST12345698765432").is_ok());
+
+        assert!(normalizar_iban("ES9121000418450200051333").is_err());
+        assert!(normalizar_iban("ES912100041845020005133").is_err());
+        assert!(normalizar_iban("9121000418450200051332ES").is_err());
+        assert!(normalizar_iban("ES91 2100 0418 4502 0005 133Ñ").is_err());
+        assert!(normalizar_iban("ES91").is_err());
+    }
+
+    #[test]
+    fn bic() {
+        assert_eq!(normalizar_bic("caixesbbxxx"), Ok("CAIXESBBXXX".to_string()));
+        assert_eq!(normalizar_bic("BSCH ES MM"), Ok("BSCHESMM".to_string()));
+
+        assert!(normalizar_bic("CAIXESB").is_err());
+        assert!(normalizar_bic("CAIXESBBXX").is_err());
+        assert!(normalizar_bic("CA1XESBBXXX").is_err());
+    }
+
+    #[test]
+    fn identificador_acreedor() {
+        assert_eq!(normalizar_identificador_acreedor("es26 000 g12345678"), Ok("ES26000G12345678".to_string()));
+        // El sufijo no interviene en los dígitos de control.
+        assert!(normalizar_identificador_acreedor("ES26ZZZG12345678").is_ok());
+
+        assert!(normalizar_identificador_acreedor("ES27000G12345678").is_err());
+        assert!(normalizar_identificador_acreedor("ES26000G12345679").is_err());
+        assert!(normalizar_identificador_acreedor("ES26000").is_err());
+    }
+
+    #[test]
+    fn texto_latino_basico() {
+        assert_eq!(texto_sepa("  Hermandad de Nª Sª   de la Peña & Cía ", 70), "Hermandad de N S de la Pena Cia");
+        assert_eq!(texto_sepa("Muñoz", 3), "Mun");
+    }
+
+    #[test]
+    fn cuenta_de_mandato_usado() {
+        let conn = crate::db::base_de_datos_en_memoria();
+        conn.execute_batch(
+            "INSERT INTO hermanos (id, numero_hermano, nombre, primer_apellido, fecha_alta)
+             VALUES (1, '1', 'José', 'Núñez', '2020-01-01');
+             INSERT INTO mandatos_sepa (id, hermano_id, referencia, titular, iban, fecha_firma)
+             VALUES (1, 1, 'MND-1', 'José Núñez', 'ES9121000418450200051332', '2020-01-01');",
+        )
+        .unwrap();
+        let actual = buscar_mandato(&conn, 1).unwrap().unwrap();
+        let otra_cuenta = MandatoSepa { iban: "DE89370400440532013000".to_string(), ..actual.clone() };
+        let otro_titular = MandatoSepa { titular: "Ana Núñez".to_string(), ..actual.clone() };
+
+        // Sin usar, la cuenta puede corregirse
+        assert!(comprobar_cambios_mandato(&conn, &actual, &otra_cuenta).is_ok());
+
+        conn.execute_batch(
+            "INSERT INTO remesas (id, referencia, fecha_cobro, num_operaciones, importe_total, fichero, operador)
+             VALUES (1, 'REM-1', '2026-01-10', 1, 15, 'remesa.xml', 'prueba');
+             INSERT INTO remesa_cuotas
+             (remesa_id, cuota_id, hermano_id, end_to_end_id, mandato_referencia, titular, iban, importe, secuencia)
+             VALUES (1, 1, 1, 'E2E-1', 'MND-1', 'José Núñez', 'ES9121000418450200051332', 15, 'FRST');",
+        )
+        .unwrap();
+        assert!(matches!(
+            comprobar_cambios_mandato(&conn, &actual, &otra_cuenta),
+            Err(HermanarError::Validation { field, .. }) if field == "iban"
+        ));
+        assert!(comprobar_cambios_mandato(&conn, &actual, &otro_titular).is_ok());
+    }
+}
@@ -0,0 +1,481 @@
+use chrono::NaiveDate;
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::db::{DbConnection, Hermano};
+use crate::db::hermanos::{buscar_hermano, listar_hermanos};
+use crate::db::membresia::{hoy, validar_fecha};
+use crate::db::numeracion::nombre_completo;
+use crate::error::{HermanarError, HermanarResult};
+
+/// Categoría de hermano a efectos de cuotas. Las automáticas se asignan por
+/// edad y antigüedad; las demás, como los hermanos de honor, se asignan a
+/// mano a cada hermano.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoriaCuota {
+    pub id: Option<i32>,
+    pub nombre: String,
+    /// Edad mínima en años cumplidos, incluida.
+    pub edad_minima: Option<i32>,
+    /// Edad máxima en años cumplidos, incluida.
+    pub edad_maxima: Option<i32>,
+    /// Años completos desde el alta.
+    pub antiguedad_minima: Option<i32>,
+    pub automatica: bool,
+    /// Los hermanos de una categoría exenta no pagan cuota.
+    pub exenta: bool,
+    /// Las categorías automáticas se prueban en este orden y se asigna la
+    /// primera cuyas reglas cumple el hermano.
+    pub orden: i32,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+/// Importe anual de una categoría durante un periodo. Sin `vigente_hasta`
+/// sigue vigente.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tarifa {
+    pub id: Option<i32>,
+    pub categoria_id: i32,
+    pub importe: f64,
+    pub vigente_desde: String,
+    pub vigente_hasta: Option<String>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+/// Categoría y tarifa que corresponden a un hermano activo en una fecha.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoriaHermano {
+    pub hermano_id: i32,
+    pub numero_hermano: String,
+    pub nombre: String,
+    pub edad: Option<i32>,
+    pub categoria_id: Option<i32>,
+    pub categoria: Option<String>,
+    /// La categoría está asignada a mano y no por sus reglas.
+    pub manual: bool,
+    pub exenta: bool,
+    /// Importe anual de la tarifa vigente, si la hay.
+    pub importe: Option<f64>,
+}
+
+const COLUMNAS_CATEGORIA: &str = "id, nombre, edad_minima, edad_maxima, antiguedad_minima, automatica, exenta, orden,
+     created_at, updated_at";
+
+const COLUMNAS_TARIFA: &str = "id, categoria_id, importe, vigente_desde, vigente_hasta, created_at, updated_at";
+
+impl CategoriaCuota {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        Ok(CategoriaCuota {
+            id: Some(row.get(0)?),
+            nombre: row.get(1)?,
+            edad_minima: row.get(2)?,
+            edad_maxima: row.get(3)?,
+            antiguedad_minima: row.get(4)?,
+            automatica: row.get(5)?,
+            exenta: row.get(6)?,
+            orden: row.get(7)?,
+            created_at: row.get(8)?,
+            updated_at: row.get(9)?,
+        })
+    }
+
+    // Las reglas sin valor no se comprueban; una regla de edad no se cumple
+    // si no se conoce la fecha de nacimiento.
+    fn admite(&self, edad: Option<i32>, antiguedad: Option<i32>) -> bool {
+        let cumple = |dato: Option<i32>, regla: Option<i32>, comparar: fn(i32, i32) -> bool| match (regla, dato) {
+            (None, _) => true,
+            (Some(regla), Some(dato)) => comparar(dato, regla),
+            (Some(_), None) => false,
+        };
+
+        cumple(edad, self.edad_minima, |edad, minima| edad >= minima)
+            && cumple(edad, self.edad_maxima, |edad, maxima| edad <= maxima)
+            && cumple(antiguedad, self.antiguedad_minima, |antiguedad, minima| antiguedad >= minima)
+    }
+}
+
+impl Tarifa {
+    fn from_row(row: &Row) -> Result<Self, rusqlite::Error> {
+        Ok(Tarifa {
+            id: Some(row.get(0)?),
+            categoria_id: row.get(1)?,
+            importe: row.get(2)?,
+            vigente_desde: row.get(3)?,
+            vigente_hasta: row.get(4)?,
+            created_at: row.get(5)?,
+            updated_at: row.get(6)?,
+        })
+    }
+
+    fn vigente(&self, fecha: &str) -> bool {
+        self.vigente_desde.as_str() <= fecha && self.vigente_hasta.as_deref().is_none_or(|hasta| fecha <= hasta)
+    }
+}
+
+pub fn get_categorias_cuota(db: &DbConnection) -> HermanarResult<Vec<CategoriaCuota>> {
+    listar_categorias(&db.reader())
+}
+
+fn listar_categorias(conn: &Connection) -> HermanarResult<Vec<CategoriaCuota>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM categorias_cuota ORDER BY orden, id",
+        COLUMNAS_CATEGORIA
+    ))?;
+
+    let categorias = stmt.query_map([], CategoriaCuota::from_row)?.collect::<Result<Vec<_>, _>>()?;
+
+    Ok(categorias)
+}
+
+fn buscar_categoria(conn: &Connection, id: i32) -> HermanarResult<Option<CategoriaCuota>> {
+    let categoria = conn
+        .prepare_cached(&format!("SELECT {} FROM categorias_cuota WHERE id = ?1", COLUMNAS_CATEGORIA))?
+        .query_row([id], CategoriaCuota::from_row)
+        .optional()?;
+
+    Ok(categoria)
+}
+
+pub fn create_categoria_cuota(db: &DbConnection, categoria: &CategoriaCuota) -> HermanarResult<i32> {
+    let categoria = validar_categoria(categoria)?;
+
+    let conn = db.writer();
+    conn.prepare_cached(
+        "INSERT INTO categorias_cuota (nombre, edad_minima, edad_maxima, antiguedad_minima, automatica, exenta, orden)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"
+    )?
+    .execute(params![
+        categoria.nombre,
+        categoria.edad_minima,
+        categoria.edad_maxima,
+        categoria.antiguedad_minima,
+        categoria.automatica,
+        categoria.exenta,
+        categoria.orden,
+    ])?;
+
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+pub fn update_categoria_cuota(db: &DbConnection, id: i32, categoria: &CategoriaCuota) -> HermanarResult<()> {
+    let categoria = validar_categoria(categoria)?;
+
+    let conn = db.writer();
+    let actualizadas = conn
+        .prepare_cached(
+            "UPDATE categorias_cuota
+             SET nombre = ?1, edad_minima = ?2, edad_maxima = ?3, antiguedad_minima = ?4, automatica = ?5,
+                 exenta = ?6, orden = ?7, updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?8"
+        )?
+        .execute(params![
+            categoria.nombre,
+            categoria.edad_minima,
+            categoria.edad_maxima,
+            categoria.antiguedad_minima,
+            categoria.automatica,
+            categoria.exenta,
+            categoria.orden,
+            id,
+        ])?;
+    if actualizadas == 0 {
+        return Err(HermanarError::not_found("Categoría"));
+    }
+
+    Ok(())
+}
+
+/// Elimina una categoría y sus tarifas. No se puede eliminar mientras tenga
+/// hermanos asignados a mano.
+pub fn delete_categoria_cuota(db: &DbConnection, id: i32) -> HermanarResult<()> {
+    let conn = db.writer();
+    let asignados: i32 = conn
+        .prepare_cached("SELECT COUNT(*) FROM categorias_hermano WHERE categoria_id = ?1")?
+        .query_row([id], |row| row.get(0))?;
+    if asignados > 0 {
+        return Err(HermanarError::conflict(format!(
+            "No se puede eliminar la categoría porque tiene {} hermanos asignados",
+            asignados
+        )));
+    }
+    if conn.prepare_cached("DELETE FROM categorias_cuota WHERE id = ?1")?.execute([id])? == 0 {
+        return Err(HermanarError::not_found("Categoría"));
+    }
+
+    Ok(())
+}
+
+fn validar_categoria(categoria: &CategoriaCuota) -> HermanarResult<CategoriaCuota> {
+    let nombre = categoria.nombre.trim();
+    if nombre.is_empty() {
+        return Err(HermanarError::validation("nombre", "Indique el nombre de la categoría"));
+    }
+    for (campo, valor) in [
+        ("edad_minima", categoria.edad_minima),
+        ("edad_maxima", categoria.edad_maxima),
+        ("antiguedad_minima", categoria.antiguedad_minima),
+    ] {
+        if valor.is_some_and(|valor| !(0..=150).contains(&valor)) {
+            return Err(HermanarError::validation(campo, "Los años deben estar entre 0 y 150"));
+        }
+    }
+    if let (Some(minima), Some(maxima)) = (categoria.edad_minima, categoria.edad_maxima) {
+        if minima > maxima {
+            return Err(HermanarError::validation("edad_maxima", "La edad máxima no puede ser menor que la mínima"));
+        }
+    }
+
+    Ok(CategoriaCuota { nombre: nombre.to_string(), ..categoria.clone() })
+}
+
+/// Tarifas de una categoría o, sin ella, de todas.
+pub fn get_tarifas(db: &DbConnection, categoria_id: Option<i32>) -> HermanarResult<Vec<Tarifa>> {
+    let conn = db.reader();
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM tarifas
+         WHERE ?1 IS NULL OR categoria_id = ?1
+         ORDER BY categoria_id, vigente_desde DESC",
+        COLUMNAS_TARIFA
+    ))?;
+
+    let tarifas = stmt.query_map([categoria_id], Tarifa::from_row)?.collect::<Result<Vec<_>, _>>()?;
+
+    Ok(tarifas)
+}
+
+pub fn create_tarifa(db: &DbConnection, tarifa: &Tarifa) -> HermanarResult<i32> {
+    let conn = db.writer();
+    let tarifa = validar_tarifa(&conn, None, tarifa)?;
+
+    conn.prepare_cached(
+        "INSERT INTO tarifas (categoria_id, importe, vigente_desde, vigente_hasta) VALUES (?1, ?2, ?3, ?4)"
+    )?
+    .execute(params![tarifa.categoria_id, tarifa.importe, tarifa.vigente_desde, tarifa.vigente_hasta])?;
+
+    Ok(conn.last_insert_rowid() as i32)
+}
+
+pub fn update_tarifa(db: &DbConnection, id: i32, tarifa: &Tarifa) -> HermanarResult<()> {
+    let conn = db.writer();
+    let tarifa = validar_tarifa(&conn, Some(id), tarifa)?;
+
+    let actualizadas = conn
+        .prepare_cached(
+            "UPDATE tarifas
+             SET categoria_id = ?1, importe = ?2, vigente_desde = ?3, vigente_hasta = ?4,
+                 updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?5"
+        )?
+        .execute(params![tarifa.categoria_id, tarifa.importe, tarifa.vigente_desde, tarifa.vigente_hasta, id])?;
+    if actualizadas == 0 {
+        return Err(HermanarError::not_found("Tarifa"));
+    }
+
+    Ok(())
+}
+
+pub fn delete_tarifa(db: &DbConnection, id: i32) -> HermanarResult<()> {
+    if db.writer().prepare_cached("DELETE FROM tarifas WHERE id = ?1")?.execute([id])? == 0 {
+        return Err(HermanarError::not_found("Tarifa"));
+    }
+
+    Ok(())
+}
+
+// Comprueba los datos de la tarifa y que su vigencia no se solape con la de
+// otra tarifa de la misma categoría.
+fn validar_tarifa(conn: &Connection, id: Option<i32>, tarifa: &Tarifa) -> HermanarResult<Tarifa> {
+    if buscar_categoria(conn, tarifa.categoria_id)?.is_none() {
+        return Err(HermanarError::not_found("Categoría"));
+    }
+    let importe = (tarifa.importe * 100.0).round() / 100.0;
+    if !importe.is_finite() || importe < 0.0 {
+        return Err(HermanarError::validation("importe", "El importe no puede ser negativo"));
+    }
+    let vigente_desde = validar_fecha("vigente_desde", &tarifa.vigente_desde)?;
+    let vigente_hasta = tarifa
+        .vigente_hasta
+        .as_deref()
+        .filter(|hasta| !hasta.trim().is_empty())
+        .map(|hasta| validar_fecha("vigente_hasta", hasta))
+        .transpose()?;
+    if vigente_hasta.as_ref().is_some_and(|hasta| *hasta < vigente_desde) {
+        return Err(HermanarError::validation("vigente_hasta", "La tarifa no puede terminar antes de empezar"));
+    }
+
+    let solapada: Option<String> = conn
+        .prepare_cached(
+            "SELECT vigente_desde FROM tarifas
+             WHERE categoria_id = ?1 AND id IS NOT ?2
+               AND (?4 IS NULL OR vigente_desde <= ?4)
+               AND (vigente_hasta IS NULL OR vigente_hasta >= ?3)
+             LIMIT 1"
+        )?
+        .query_row(params![tarifa.categoria_id, id, vigente_desde, vigente_hasta], |row| row.get(0))
+        .optional()?;
+    if let Some(desde) = solapada {
+        return Err(HermanarError::conflict(format!(
+            "La vigencia se solapa con la tarifa de la categoría que empieza el {}",
+            desde
+        )));
+    }
+
+    Ok(Tarifa { importe, vigente_desde, vigente_hasta, ..tarifa.clone() })
+}
+
+/// Asigna a mano la categoría de un hermano. Sin categoría, el hermano vuelve
+/// a clasificarse por las reglas de las categorías automáticas.
+pub fn set_categoria_hermano(db: &DbConnection, hermano_id: i32, categoria_id: Option<i32>) -> HermanarResult<()> {
+    let conn = db.writer();
+    if buscar_hermano(&conn, hermano_id)?.is_none() {
+        return Err(HermanarError::not_found("Hermano"));
+    }
+
+    match categoria_id {
+        Some(categoria_id) => {
+            if buscar_categoria(&conn, categoria_id)?.is_none() {
+                return Err(HermanarError::not_found("Categoría"));
+            }
+            conn.prepare_cached(
+                "INSERT INTO categorias_hermano (hermano_id, categoria_id) VALUES (?1, ?2)
+                 ON CONFLICT(hermano_id) DO UPDATE SET categoria_id = excluded.categoria_id"
+            )?
+            .execute([hermano_id, categoria_id])?;
+        }
+        None => {
+            conn.prepare_cached("DELETE FROM categorias_hermano WHERE hermano_id = ?1")?.execute([hermano_id])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Categoría asignada a mano a un hermano, si la tiene.
+pub(crate) fn buscar_categoria_manual(conn: &Connection, hermano_id: i32) -> HermanarResult<Option<i32>> {
+    let categoria_id = conn
+        .prepare_cached("SELECT categoria_id FROM categorias_hermano WHERE hermano_id = ?1")?
+        .query_row([hermano_id], |row| row.get(0))
+        .optional()?;
+
+    Ok(categoria_id)
+}
+
+/// Vuelve a asignar la categoría manual de un hermano restaurado de la
+/// papelera, si la categoría sigue existiendo.
+pub(crate) fn reinsertar_categoria_manual(conn: &Connection, hermano_id: i32, categoria_id: i32) -> HermanarResult<()> {
+    conn.prepare_cached(
+        "INSERT INTO categorias_hermano (hermano_id, categoria_id)
+         SELECT ?1, id FROM categorias_cuota WHERE id = ?2"
+    )?
+    .execute([hermano_id, categoria_id])?;
+
+    Ok(())
+}
+
+/// Categoría y tarifa de cada hermano activo en una fecha, por defecto hoy.
+pub fn get_categorias_hermanos(db: &DbConnection, fecha: Option<String>) -> HermanarResult<Vec<CategoriaHermano>> {
+    let fecha = match fecha.as_deref() {
+        Some(fecha) => validar_fecha("fecha", fecha)?,
+        None => hoy(),
+    };
+
+    let conn = db.reader();
+    let tarifas = CuadroTarifas::cargar(&conn)?;
+    let hermanos = listar_hermanos(&conn)?;
+
+    Ok(hermanos
+        .iter()
+        .filter(|hermano| hermano.activo)
+        .map(|hermano| {
+            let categoria = tarifas.categoria(hermano, &fecha);
+            CategoriaHermano {
+                hermano_id: hermano.id.unwrap_or_default(),
+                numero_hermano: hermano.numero_hermano.clone(),
+                nombre: nombre_completo(hermano),
+                edad: anios_cumplidos(hermano.fecha_nacimiento.as_deref(), &fecha),
+                categoria_id: categoria.and_then(|c| c.id),
+                categoria: categoria.map(|c| c.nombre.clone()),
+                manual: hermano.id.is_some_and(|id| tarifas.manuales.contains_key(&id)),
+                exenta: categoria.is_some_and(|c| c.exenta),
+                importe: categoria.and_then(|c| tarifas.importe_anual(c, &fecha)),
+            }
+        })
+        .collect())
+}
+
+/// Importe que corresponde a un hermano en una fecha según su categoría.
+pub(crate) enum ImporteTarifa {
+    Importe(f64),
+    Exento,
+    SinCategoria,
+    SinTarifa(String),
+}
+
+/// Categorías, tarifas y asignaciones manuales cargadas de una vez para
+/// calcular el importe de muchos hermanos.
+pub(crate) struct CuadroTarifas {
+    categorias: Vec<CategoriaCuota>,
+    tarifas: Vec<Tarifa>,
+    manuales: HashMap<i32, i32>,
+}
+
+impl CuadroTarifas {
+    pub(crate) fn cargar(conn: &Connection) -> HermanarResult<Self> {
+        let categorias = listar_categorias(conn)?;
+        let tarifas = conn
+            .prepare_cached(&format!("SELECT {} FROM tarifas", COLUMNAS_TARIFA))?
+            .query_map([], Tarifa::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        let manuales = conn
+            .prepare_cached("SELECT hermano_id, categoria_id FROM categorias_hermano")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+
+        Ok(CuadroTarifas { categorias, tarifas, manuales })
+    }
+
+    /// Categoría de un hermano en una fecha: la asignada a mano o, si no
+    /// tiene, la primera categoría automática cuyas reglas cumple.
+    fn categoria(&self, hermano: &Hermano, fecha: &str) -> Option<&CategoriaCuota> {
+        if let Some(categoria_id) = hermano.id.and_then(|id| self.manuales.get(&id)) {
+            return self.categorias.iter().find(|c| c.id == Some(*categoria_id));
+        }
+
+        let edad = anios_cumplidos(hermano.fecha_nacimiento.as_deref(), fecha);
+        let antiguedad = anios_cumplidos(Some(&hermano.fecha_alta), fecha);
+        self.categorias.iter().filter(|c| c.automatica).find(|c| c.admite(edad, antiguedad))
+    }
+
+    fn importe_anual(&self, categoria: &CategoriaCuota, fecha: &str) -> Option<f64> {
+        self.tarifas
+            .iter()
+            .find(|tarifa| Some(tarifa.categoria_id) == categoria.id && tarifa.vigente(fecha))
+            .map(|tarifa| tarifa.importe)
+    }
+
+    /// Importe anual que corresponde a un hermano según la tarifa vigente en
+    /// `fecha` para su categoría.
+    pub(crate) fn importe(&self, hermano: &Hermano, fecha: &str) -> ImporteTarifa {
+        let Some(categoria) = self.categoria(hermano, fecha) else {
+            return ImporteTarifa::SinCategoria;
+        };
+        if categoria.exenta {
+            return ImporteTarifa::Exento;
+        }
+
+        match self.importe_anual(categoria, fecha) {
+            Some(importe) => ImporteTarifa::Importe(importe),
+            None => ImporteTarifa::SinTarifa(categoria.nombre.clone()),
+        }
+    }
+}
+
+// Años cumplidos en `fecha` desde `desde`, que puede llevar hora detrás.
+fn anios_cumplidos(desde: Option<&str>, fecha: &str) -> Option<i32> {
+    let desde = NaiveDate::parse_from_str(desde?.get(..10)?, "%Y-%m-%d").ok()?;
+    let fecha = NaiveDate::parse_from_str(fecha, "%Y-%m-%d").ok()?;
+
+    fecha.years_since(desde).map(|anios| anios as i32)
+}
@@ -0,0 +1,128 @@
+use rusqlite::ffi;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// Errores de dominio que devuelven las funciones de `db` y los comandos.
+///
+/// Se serializan como `{ kind, field, message }` para que el frontend pueda
+/// distinguir el tipo de error y resaltar el campo afectado.
+#[derive(Debug, thiserror::Error)]
+pub enum HermanarError {
+    #[error("{entidad} no encontrado")]
+    NotFound { entidad: String },
+
+    #[error("Ya existe un registro con el mismo valor en '{field}'")]
+    Duplicate { field: String },
+
+    #[error("{message}")]
+    Validation { field: String, message: String },
+
+    #[error("{message}")]
+    Conflict { message: String },
+
+    #[error("La base de datos está ocupada por otra operación, inténtelo de nuevo")]
+    Busy,
+
+    #[error("Error de base de datos: {message}")]
+    Database { message: String },
+}
+
+pub type HermanarResult<T> = Result<T, HermanarError>;
+
+impl HermanarError {
+    pub fn not_found(entidad: &str) -> Self {
+        HermanarError::NotFound { entidad: entidad.to_string() }
+    }
+
+    pub fn validation(field: &str, message: impl Into<String>) -> Self {
+        HermanarError::Validation { field: field.to_string(), message: message.into() }
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        HermanarError::Conflict { message: message.into() }
+    }
+
+    pub fn database(message: impl Into<String>) -> Self {
+        HermanarError::Database { message: message.into() }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            HermanarError::NotFound { .. } => "not_found",
+            HermanarError::Duplicate { .. } => "duplicate",
+            HermanarError::Validation { .. } => "validation",
+            HermanarError::Conflict { .. } => "conflict",
+            HermanarError::Busy => "busy",
+            HermanarError::Database { .. } => "database",
+        }
+    }
+
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            HermanarError::Duplicate { field } | HermanarError::Validation { field, .. } => Some(field),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for HermanarError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("HermanarError", 3)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("field", &self.field())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+impl From<rusqlite::Error> for HermanarError {
+    fn from(error: rusqlite::Error) -> Self {
+        match &error {
+            rusqlite::Error::QueryReturnedNoRows => HermanarError::not_found("Registro"),
+            rusqlite::Error::SqliteFailure(e, msg) => match e.extended_code {
+                ffi::SQLITE_CONSTRAINT_UNIQUE | ffi::SQLITE_CONSTRAINT_PRIMARYKEY => HermanarError::Duplicate {
+                    field: columnas_restriccion(msg.as_deref()),
+                },
+                ffi::SQLITE_CONSTRAINT_NOTNULL => {
+                    let field = columnas_restriccion(msg.as_deref());
+                    HermanarError::Validation {
+                        message: format!("El campo '{}' es obligatorio", field),
+                        field,
+                    }
+                }
+                ffi::SQLITE_CONSTRAINT_CHECK => HermanarError::Validation {
+                    field: columnas_restriccion(msg.as_deref())
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    message: "El valor no cumple las reglas de la base de datos".to_string(),
+                },
+                ffi::SQLITE_CONSTRAINT_FOREIGNKEY => HermanarError::conflict(
+                    "La operación hace referencia a un registro que no existe o que todavía está en uso",
+                ),
+                _ if e.code == ffi::ErrorCode::DatabaseBusy || e.code == ffi::ErrorCode::DatabaseLocked => {
+                    HermanarError::Busy
+                }
+                _ => HermanarError::database(error.to_string()),
+            },
+            _ => HermanarError::database(error.to_string()),
+        }
+    }
+}
+
+// Extrae los nombres de columna de mensajes como
+// "UNIQUE constraint failed: hermanos.numero_hermano" o
+// "UNIQUE constraint failed: cuotas.hermano_id, cuotas.anio, cuotas.periodicidad, cuotas.periodo".
+fn columnas_restriccion(msg: Option<&str>) -> String {
+    let Some(msg) = msg else {
+        return String::new();
+    };
+    let detalle = msg.split_once(": ").map(|(_, d)| d).unwrap_or(msg);
+
+    detalle
+        .split(", ")
+        .map(|columna| columna.rsplit_once('.').map(|(_, c)| c).unwrap_or(columna))
+        .collect::<Vec<_>>()
+        .join(",")
+}
@@ -1,17 +1,26 @@
 mod db;
 mod commands;
+mod error;
 
 use commands::*;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    // Inicializar base de datos
-    let db = db::init_database().expect("Error al inicializar la base de datos");
+    // Inicializar base de datos. Si no se puede abrir la del perfil activo se
+    // abre la del perfil por defecto y se avisa en la interfaz.
+    let (db, aviso) = match db::init_database() {
+        Ok(inicio) => inicio,
+        Err(e) => {
+            eprintln!("Error al inicializar la base de datos: {:#}", e);
+            std::process::exit(1);
+        }
+    };
 
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_process::init())
         .manage(db)
+        .manage(db::perfiles::AvisoArranque::new(aviso))
         .invoke_handler(tauri::generate_handler![
             // Comandos de hermanos
             get_all_hermanos_cmd,
@@ -47,6 +56,131 @@ pub fn run() {
             delete_cuota_cmd,
             generar_cuotas_trimestre_cmd,
             get_estadisticas_cuotas_cmd,
+            set_cuotas_deducibles_cmd,
+            generar_cuotas_periodo_cmd,
+            get_periodicidad_cuotas_cmd,
+            set_periodicidad_cuotas_cmd,
+            get_periodicidades_hermanos_cmd,
+            set_periodicidad_hermano_cmd,
+            // Comandos de pagos
+            get_pagos_cuota_cmd,
+            get_pagos_hermano_cmd,
+            create_pago_cmd,
+            anular_pago_cmd,
+            // Comandos de mantenimiento
+            reparar_integridad_cmd,
+            backup_database_cmd,
+            restore_database_cmd,
+            list_backups_cmd,
+            // Comandos de perfiles
+            list_profiles_cmd,
+            create_profile_cmd,
+            switch_profile_cmd,
+            delete_profile_cmd,
+            take_aviso_arranque_cmd,
+            // Comandos de auditoría
+            get_historial_hermano_cmd,
+            get_historial_cuota_cmd,
+            get_historial_familia_cmd,
+            get_operador_cmd,
+            set_operador_cmd,
+            // Comandos de papelera
+            list_papelera_cmd,
+            restaurar_papelera_cmd,
+            purgar_papelera_cmd,
+            get_dias_papelera_cmd,
+            set_dias_papelera_cmd,
+            // Comandos de numeración
+            preview_renumeracion_cmd,
+            aplicar_renumeracion_cmd,
+            get_historial_numeros_cmd,
+            get_numeracion_anio_cmd,
+            get_politica_numeracion_cmd,
+            set_politica_numeracion_cmd,
+            get_siguiente_numero_cmd,
+            list_reservas_cmd,
+            reservar_numero_cmd,
+            liberar_reserva_cmd,
+            // Comandos de altas y bajas
+            get_periodos_hermano_cmd,
+            dar_de_baja_cmd,
+            readmitir_hermano_cmd,
+            get_hermanos_activos_en_fecha_cmd,
+            get_resumen_membresia_cmd,
+            // Comandos de documentos de identidad
+            comprobar_documento_cmd,
+            revisar_documentos_cmd,
+            // Comandos de domiciliaciones SEPA
+            list_mandatos_sepa_cmd,
+            get_mandato_vigente_cmd,
+            create_mandato_sepa_cmd,
+            update_mandato_sepa_cmd,
+            revocar_mandato_sepa_cmd,
+            validar_iban_cmd,
+            get_acreedor_sepa_cmd,
+            set_acreedor_sepa_cmd,
+            generar_remesa_cmd,
+            list_remesas_cmd,
+            get_cuotas_remesa_cmd,
+            importar_devoluciones_cmd,
+            get_devoluciones_cmd,
+            // Comandos de conciliación bancaria
+            importar_extracto_cmd,
+            list_movimientos_cmd,
+            get_cuotas_movimiento_cmd,
+            proponer_conciliaciones_cmd,
+            confirmar_conciliaciones_cmd,
+            descartar_movimiento_cmd,
+            // Comandos de importación
+            previsualizar_importacion_cmd,
+            simular_importacion_cmd,
+            importar_hermanos_cmd,
+            // Comandos de exportación
+            get_columnas_exportacion_cmd,
+            exportar_cmd,
+            // Comandos de documentos impresos
+            get_datos_hermandad_cmd,
+            set_datos_hermandad_cmd,
+            generar_carnets_cmd,
+            verificar_carnet_cmd,
+            get_recibos_cuota_cmd,
+            list_recibos_cmd,
+            generar_pdf_recibo_cmd,
+            imprimir_recibos_periodo_cmd,
+            // Comandos de certificados
+            get_variables_certificado_cmd,
+            get_plantillas_certificado_cmd,
+            set_plantilla_certificado_cmd,
+            restablecer_plantilla_certificado_cmd,
+            emitir_certificado_cmd,
+            list_certificados_cmd,
+            generar_pdf_certificado_cmd,
+            // Comandos de envíos
+            get_lista_envio_cmd,
+            generar_envio_cmd,
+            // Comandos de donativos y modelo 182
+            get_donantes_cmd,
+            create_donante_cmd,
+            update_donante_cmd,
+            delete_donante_cmd,
+            get_donativos_cmd,
+            create_donativo_cmd,
+            update_donativo_cmd,
+            delete_donativo_cmd,
+            get_resumen_modelo182_cmd,
+            generar_modelo182_cmd,
+            generar_certificados_donativos_cmd,
+            // Comandos de categorías y tarifas
+            get_categorias_cuota_cmd,
+            create_categoria_cuota_cmd,
+            update_categoria_cuota_cmd,
+            delete_categoria_cuota_cmd,
+            get_tarifas_cmd,
+            create_tarifa_cmd,
+            update_tarifa_cmd,
+            delete_tarifa_cmd,
+            set_categoria_hermano_cmd,
+            get_categorias_hermanos_cmd,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
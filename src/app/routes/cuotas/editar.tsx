@@ -6,6 +6,8 @@ import { useNavigate, useParams } from 'react-router'
 import { useState, useEffect } from 'react'
 import { invoke } from '@tauri-apps/api/core'
 import { useToastContext } from '@/contexts/toast-context'
+import { getErrorMessage, getFieldErrors } from '@/lib/utils'
+import type { Periodicidad } from '@/types'
 
 interface Hermano {
     id: number
@@ -19,7 +21,8 @@ interface Cuota {
     id: number
     hermano_id: number
     anio: number
-    trimestre: number
+    periodicidad: Periodicidad
+    periodo: number
     importe: number
     pagado: boolean
     fecha_pago?: string
@@ -32,12 +35,14 @@ export function Component() {
     const { id } = useParams()
     const toast = useToastContext()
     const [loading, setLoading] = useState(false)
+    const [fieldErrors, setFieldErrors] = useState<Record<string, string>>({})
     const [loadingData, setLoadingData] = useState(true)
     const [hermanos, setHermanos] = useState<Hermano[]>([])
     const [formData, setFormData] = useState({
         hermano_id: 0,
         anio: new Date().getFullYear(),
-        trimestre: 1,
+        periodicidad: 'trimestral' as Periodicidad,
+        periodo: 1,
         importe: 50,
         pagado: false,
         fecha_pago: '',
@@ -56,7 +61,8 @@ export function Component() {
                     setFormData({
                         hermano_id: cuota.hermano_id,
                         anio: cuota.anio,
-                        trimestre: cuota.trimestre,
+                        periodicidad: cuota.periodicidad,
+                        periodo: cuota.periodo,
                         importe: cuota.importe,
                         pagado: cuota.pagado,
                         fecha_pago: cuota.fecha_pago || '',
@@ -84,6 +90,7 @@ export function Component() {
     const handleSubmit = async (e: React.FormEvent) => {
         e.preventDefault()
         setLoading(true)
+        setFieldErrors({})
 
         try {
             // Preparar datos para enviar, convirtiendo cadenas vacías a undefined
@@ -102,7 +109,8 @@ export function Component() {
             navigate('/cuotas')
         } catch (error) {
             console.error('Error updating cuota:', error)
-            toast.error('Error al actualizar la cuota')
+            setFieldErrors(getFieldErrors(error))
+            toast.error(`Error al actualizar la cuota: ${getErrorMessage(error)}`)
         } finally {
             setLoading(false)
         }
@@ -127,6 +135,7 @@ export function Component() {
                 <Select
                     label="Hermano"
                     value={formData.hermano_id.toString()}
+                    error={fieldErrors.hermano_id}
                     onChange={(e) =>
                         setFormData({
                             ...formData,
@@ -144,6 +153,7 @@ export function Component() {
                     <Select
                         label="Año"
                         value={formData.anio.toString()}
+                        error={fieldErrors.anio}
                         onChange={(e) =>
                             setFormData({
                                 ...formData,
@@ -162,6 +172,7 @@ export function Component() {
                         step="0.01"
                         min="0"
                         value={formData.importe}
+                        error={fieldErrors.importe}
                         onChange={(e) =>
                             setFormData({
                                 ...formData,
@@ -199,6 +210,7 @@ export function Component() {
                             label="Fecha de Pago"
                             type="date"
                             value={formData.fecha_pago}
+                            error={fieldErrors.fecha_pago}
                             onChange={(e) =>
                                 setFormData({
                                     ...formData,
@@ -210,6 +222,7 @@ export function Component() {
                         <Select
                             label="Método de Pago"
                             value={formData.metodo_pago}
+                            error={fieldErrors.metodo_pago}
                             onChange={(e) =>
                                 setFormData({
                                     ...formData,
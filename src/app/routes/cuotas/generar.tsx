@@ -1,10 +1,27 @@
 import { Card } from '@/components/ui/card'
 import { Button } from '@/components/ui/button'
 import { Input } from '@/components/ui/input'
+import { Select } from '@/components/ui/select'
 import { Modal } from '@/components/ui/modal'
 import { Users, CheckCircle, AlertCircle, AlertTriangle } from 'lucide-react'
-import { useState } from 'react'
+import { useEffect, useState } from 'react'
 import { invoke } from '@tauri-apps/api/core'
+import { etiquetaPeriodo, getErrorMessage, getFieldErrors } from '@/lib/utils'
+import type { Periodicidad } from '@/types'
+
+const PERIODOS: Record<Periodicidad, number> = {
+    anual: 1,
+    semestral: 2,
+    trimestral: 4,
+    mensual: 12
+}
+
+const NOMBRES_PERIODICIDAD: Record<Periodicidad, string> = {
+    anual: 'Anual',
+    semestral: 'Semestral',
+    trimestral: 'Trimestral',
+    mensual: 'Mensual'
+}
 
 interface GenerarCuotasProps {
     onCuotasGenerated: () => void
@@ -12,8 +29,11 @@ interface GenerarCuotasProps {
 
 export function GenerarCuotas({ onCuotasGenerated }: GenerarCuotasProps) {
     const [anio, setAnio] = useState(new Date().getFullYear())
+    const [periodicidad, setPeriodicidad] = useState<Periodicidad>('trimestral')
+    const [periodo, setPeriodo] = useState(1)
     const [importe, setImporte] = useState(50)
     const [isGenerating, setIsGenerating] = useState(false)
+    const [fieldErrors, setFieldErrors] = useState<Record<string, string>>({})
     const [showConfirmModal, setShowConfirmModal] = useState(false)
     const [resultado, setResultado] = useState<{
         tipo: 'success' | 'error'
@@ -21,6 +41,19 @@ export function GenerarCuotas({ onCuotasGenerated }: GenerarCuotasProps) {
         cuotasCreadas?: number
     } | null>(null)
 
+    useEffect(() => {
+        invoke<Periodicidad>('get_periodicidad_cuotas_cmd')
+            .then(setPeriodicidad)
+            .catch((error) =>
+                console.error('Error loading periodicidad:', error)
+            )
+    }, [])
+
+    const cambiarPeriodicidad = (nueva: Periodicidad) => {
+        setPeriodicidad(nueva)
+        setPeriodo(1)
+    }
+
     const handleGenerar = async () => {
         if (!anio || !importe) {
             setResultado({
@@ -45,14 +78,15 @@ export function GenerarCuotas({ onCuotasGenerated }: GenerarCuotasProps) {
         setShowConfirmModal(false)
         setIsGenerating(true)
         setResultado(null)
+        setFieldErrors({})
 
         try {
-            // Para cuotas anuales usamos trimestre 1
             const cuotasCreadas = await invoke<number>(
-                'generar_cuotas_trimestre_cmd',
+                'generar_cuotas_periodo_cmd',
                 {
                     anio,
-                    trimestre: 1,
+                    periodicidad,
+                    periodo,
                     importe
                 }
             )
@@ -72,9 +106,10 @@ export function GenerarCuotas({ onCuotasGenerated }: GenerarCuotasProps) {
                 })
             }
         } catch (error) {
+            setFieldErrors(getFieldErrors(error))
             setResultado({
                 tipo: 'error',
-                mensaje: `Error al generar las cuotas: ${error}`
+                mensaje: `Error al generar las cuotas: ${getErrorMessage(error)}`
             })
         } finally {
             setIsGenerating(false)
@@ -83,7 +118,9 @@ export function GenerarCuotas({ onCuotasGenerated }: GenerarCuotasProps) {
 
     const resetForm = () => {
         setResultado(null)
+        setFieldErrors({})
         setAnio(new Date().getFullYear())
+        setPeriodo(1)
         setImporte(50)
     }
 
@@ -103,8 +140,9 @@ export function GenerarCuotas({ onCuotasGenerated }: GenerarCuotasProps) {
                                     Esta acción no se puede deshacer
                                 </h4>
                                 <p className="text-sm text-amber-700">
-                                    Se generarán cuotas anuales para todos los
-                                    hermanos activos.
+                                    Se generarán cuotas para todos los hermanos
+                                    activos que pagan con periodicidad{' '}
+                                    {NOMBRES_PERIODICIDAD[periodicidad].toLowerCase()}.
                                 </p>
                             </div>
                         </div>
@@ -121,7 +159,15 @@ export function GenerarCuotas({ onCuotasGenerated }: GenerarCuotasProps) {
                         </div>
                         <div className="flex justify-between items-center">
                             <span className="text-sm font-medium text-gray-600">
-                                Importe anual:
+                                Periodo:
+                            </span>
+                            <span className="text-lg font-semibold text-gray-900">
+                                {etiquetaPeriodo(periodicidad, periodo)}
+                            </span>
+                        </div>
+                        <div className="flex justify-between items-center">
+                            <span className="text-sm font-medium text-gray-600">
+                                Importe por cuota:
                             </span>
                             <span className="text-lg font-semibold text-gray-900">
                                 €{importe.toFixed(2)}
@@ -149,7 +195,7 @@ export function GenerarCuotas({ onCuotasGenerated }: GenerarCuotasProps) {
 
             <Card
                 title="Generar Cuotas Automáticamente"
-                subtitle="Crear cuotas anuales para todos los hermanos activos"
+                subtitle="Crear las cuotas de un periodo para todos los hermanos activos"
             >
                 <div className="space-y-6">
                     <div className="bg-blue-50 border border-blue-200 rounded-lg p-4">
@@ -160,10 +206,11 @@ export function GenerarCuotas({ onCuotasGenerated }: GenerarCuotasProps) {
                                     Información importante
                                 </h4>
                                 <p className="text-sm text-blue-700">
-                                    Esta función genera automáticamente cuotas
-                                    anuales para todos los hermanos activos del
-                                    año seleccionado. Si ya existen cuotas para
-                                    algunos hermanos en ese año, no se crearán
+                                    Esta función genera automáticamente la
+                                    cuota del periodo seleccionado para todos
+                                    los hermanos activos que pagan con esa
+                                    periodicidad. Si algún hermano ya tiene una
+                                    cuota que cubre el periodo, no se crearán
                                     duplicados.
                                 </p>
                             </div>
@@ -180,10 +227,39 @@ export function GenerarCuotas({ onCuotasGenerated }: GenerarCuotasProps) {
                             max={2050}
                         />
 
+                        <Select
+                            label="Periodicidad"
+                            value={periodicidad}
+                            onChange={(e) =>
+                                cambiarPeriodicidad(
+                                    e.target.value as Periodicidad
+                                )
+                            }
+                            options={Object.entries(NOMBRES_PERIODICIDAD).map(
+                                ([value, label]) => ({ value, label })
+                            )}
+                        />
+
+                        <Select
+                            label="Periodo"
+                            value={periodo.toString()}
+                            error={fieldErrors.periodo}
+                            onChange={(e) => setPeriodo(Number(e.target.value))}
+                            disabled={periodicidad === 'anual'}
+                            options={Array.from(
+                                { length: PERIODOS[periodicidad] },
+                                (_, i) => ({
+                                    value: (i + 1).toString(),
+                                    label: etiquetaPeriodo(periodicidad, i + 1)
+                                })
+                            )}
+                        />
+
                         <Input
-                            label="Importe Anual (€)"
+                            label="Importe por Cuota (€)"
                             type="number"
                             value={importe}
+                            error={fieldErrors.importe}
                             onChange={(e) =>
                                 setImporte(parseFloat(e.target.value))
                             }
@@ -21,6 +21,7 @@ import { invoke } from '@tauri-apps/api/core'
 import { GenerarCuotas } from './generar'
 import { useNavigate } from 'react-router'
 import type { Cuota } from '@/types'
+import { etiquetaPeriodo } from '@/lib/utils'
 import { useToastContext } from '@/contexts/toast-context'
 
 interface Hermano {
@@ -221,10 +222,12 @@ export function Component() {
             )
         },
         {
-            key: 'trimestre',
-            label: 'Trimestre',
-            render: (value) => (
-                <div className="text-sm text-gray-900">{value}º Trim.</div>
+            key: 'periodo',
+            label: 'Periodo',
+            render: (value, cuota) => (
+                <div className="text-sm text-gray-900">
+                    {etiquetaPeriodo(cuota.periodicidad, Number(value))}
+                </div>
             )
         },
         {
@@ -8,12 +8,15 @@ import { useEffect, useState } from 'react'
 import { useNavigate } from 'react-router'
 import { invoke } from '@tauri-apps/api/core'
 import { useToastContext } from '@/contexts/toast-context'
+import { etiquetaPeriodo } from '@/lib/utils'
+import type { Periodicidad } from '@/types'
 
 interface Cuota extends Record<string, unknown> {
     id: number
     hermano_id: number
     anio: number
-    trimestre: number
+    periodicidad: Periodicidad
+    periodo: number
     importe: number
     pagado: boolean
     fecha_pago?: string
@@ -171,10 +174,12 @@ export function Component() {
             )
         },
         {
-            key: 'trimestre',
-            label: 'Trimestre',
-            render: (value) => (
-                <div className="text-sm text-gray-900">{value}º Trim.</div>
+            key: 'periodo',
+            label: 'Periodo',
+            render: (value, cuota) => (
+                <div className="text-sm text-gray-900">
+                    {etiquetaPeriodo(cuota.periodicidad, Number(value))}
+                </div>
             )
         },
         {
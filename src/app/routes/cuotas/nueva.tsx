@@ -6,6 +6,7 @@ import { useNavigate } from 'react-router'
 import { useState, useEffect } from 'react'
 import { invoke } from '@tauri-apps/api/core'
 import { useToastContext } from '@/contexts/toast-context'
+import { getErrorMessage, getFieldErrors } from '@/lib/utils'
 
 interface Hermano {
     id: number
@@ -19,11 +20,13 @@ export function Component() {
     const navigate = useNavigate()
     const toast = useToastContext()
     const [loading, setLoading] = useState(false)
+    const [fieldErrors, setFieldErrors] = useState<Record<string, string>>({})
     const [hermanos, setHermanos] = useState<Hermano[]>([])
     const [formData, setFormData] = useState({
         hermano_id: 0,
         anio: new Date().getFullYear(),
-        trimestre: 1,
+        periodicidad: 'trimestral',
+        periodo: 1,
         importe: 50,
         pagado: false,
         fecha_pago: '',
@@ -54,6 +57,7 @@ export function Component() {
         }
 
         setLoading(true)
+        setFieldErrors({})
 
         try {
             // Preparar datos para enviar, convirtiendo cadenas vacías a undefined
@@ -69,7 +73,8 @@ export function Component() {
             navigate('/cuotas')
         } catch (error) {
             console.error('Error creating cuota:', error)
-            toast.error('Error al crear la cuota')
+            setFieldErrors(getFieldErrors(error))
+            toast.error(`Error al crear la cuota: ${getErrorMessage(error)}`)
         } finally {
             setLoading(false)
         }
@@ -87,6 +92,7 @@ export function Component() {
                 <Select
                     label="Hermano"
                     value={formData.hermano_id.toString()}
+                    error={fieldErrors.hermano_id}
                     onChange={(e) =>
                         setFormData({
                             ...formData,
@@ -107,6 +113,7 @@ export function Component() {
                     <Select
                         label="Año"
                         value={formData.anio.toString()}
+                        error={fieldErrors.anio}
                         onChange={(e) =>
                             setFormData({
                                 ...formData,
@@ -125,6 +132,7 @@ export function Component() {
                         step="0.01"
                         min="0"
                         value={formData.importe}
+                        error={fieldErrors.importe}
                         onChange={(e) =>
                             setFormData({
                                 ...formData,
@@ -162,6 +170,7 @@ export function Component() {
                             label="Fecha de Pago"
                             type="date"
                             value={formData.fecha_pago}
+                            error={fieldErrors.fecha_pago}
                             onChange={(e) =>
                                 setFormData({
                                     ...formData,
@@ -173,6 +182,7 @@ export function Component() {
                         <Select
                             label="Método de Pago"
                             value={formData.metodo_pago}
+                            error={fieldErrors.metodo_pago}
                             onChange={(e) =>
                                 setFormData({
                                     ...formData,
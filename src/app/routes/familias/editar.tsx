@@ -7,6 +7,7 @@ import { useNavigate, useParams } from 'react-router'
 import { useState, useEffect } from 'react'
 import { invoke } from '@tauri-apps/api/core'
 import { useToastContext } from '@/contexts/toast-context'
+import { getErrorMessage, getFieldErrors } from '@/lib/utils'
 
 interface Hermano extends Record<string, unknown> {
     id?: number
@@ -31,6 +32,7 @@ export function Component() {
     const { id } = useParams()
     const toast = useToastContext()
     const [loading, setLoading] = useState(false)
+    const [fieldErrors, setFieldErrors] = useState<Record<string, string>>({})
     const [loadingData, setLoadingData] = useState(true)
     const [hermanos, setHermanos] = useState<Hermano[]>([])
     const [hermanosDeFamilia, setHermanosDeFamilia] = useState<Hermano[]>([])
@@ -82,6 +84,7 @@ export function Component() {
     const handleSubmit = async (e: React.FormEvent) => {
         e.preventDefault()
         setLoading(true)
+        setFieldErrors({})
 
         try {
             const dataToSend = {
@@ -97,7 +100,8 @@ export function Component() {
             navigate('/familias')
         } catch (error) {
             console.error('Error updating familia:', error)
-            toast.error('Error al actualizar la familia')
+            setFieldErrors(getFieldErrors(error))
+            toast.error(`Error al actualizar la familia: ${getErrorMessage(error)}`)
         } finally {
             setLoading(false)
         }
@@ -137,7 +141,9 @@ export function Component() {
             toast.success('Hermano removido de la familia correctamente')
         } catch (error) {
             console.error('Error removing hermano:', error)
-            toast.error('Error al remover hermano de la familia')
+            toast.error(
+                `Error al remover hermano de la familia: ${getErrorMessage(error)}`
+            )
         }
     }
 
@@ -188,6 +194,7 @@ export function Component() {
                     <Input
                         label="Nombre de Familia"
                         value={formData.nombre_familia}
+                        error={fieldErrors.nombre_familia}
                         onChange={(e) =>
                             setFormData({
                                 ...formData,
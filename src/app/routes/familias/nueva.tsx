@@ -6,6 +6,7 @@ import { useNavigate } from 'react-router'
 import { useState, useEffect } from 'react'
 import { invoke } from '@tauri-apps/api/core'
 import { useToastContext } from '@/contexts/toast-context'
+import { getErrorMessage, getFieldErrors } from '@/lib/utils'
 
 interface Hermano {
     id?: number
@@ -19,6 +20,7 @@ export function Component() {
     const navigate = useNavigate()
     const toast = useToastContext()
     const [loading, setLoading] = useState(false)
+    const [fieldErrors, setFieldErrors] = useState<Record<string, string>>({})
     const [hermanos, setHermanos] = useState<Hermano[]>([])
     const [formData, setFormData] = useState({
         nombre_familia: '',
@@ -43,6 +45,7 @@ export function Component() {
     const handleSubmit = async (e: React.FormEvent) => {
         e.preventDefault()
         setLoading(true)
+        setFieldErrors({})
 
         try {
             const dataToSend = {
@@ -55,7 +58,8 @@ export function Component() {
             navigate('/familias')
         } catch (error) {
             console.error('Error creating familia:', error)
-            toast.error('Error al crear la familia')
+            setFieldErrors(getFieldErrors(error))
+            toast.error(`Error al crear la familia: ${getErrorMessage(error)}`)
         } finally {
             setLoading(false)
         }
@@ -70,6 +74,7 @@ export function Component() {
                 <Input
                     label="Nombre de Familia"
                     value={formData.nombre_familia}
+                    error={fieldErrors.nombre_familia}
                     onChange={(e) =>
                         setFormData({
                             ...formData,
@@ -17,13 +17,15 @@ import {
 import { useEffect, useState } from 'react'
 import { invoke } from '@tauri-apps/api/core'
 import { useParams, useNavigate } from 'react-router'
-import type { Hermano } from '@/types'
+import type { Hermano, Periodicidad } from '@/types'
+import { etiquetaPeriodo } from '@/lib/utils'
 
 interface Cuota extends Record<string, unknown> {
     id: number
     hermano_id: number
     anio: number
-    trimestre: number
+    periodicidad: Periodicidad
+    periodo: number
     importe: number
     pagado: boolean
     fecha_pago?: string
@@ -108,9 +110,9 @@ export function Component() {
             render: (value) => String(value)
         },
         {
-            key: 'trimestre',
-            label: 'Trimestre',
-            render: (value) => `${value}º Trimestre`
+            key: 'periodo',
+            label: 'Periodo',
+            render: (value, cuota) => etiquetaPeriodo(cuota.periodicidad, Number(value))
         },
         {
             key: 'importe',
@@ -7,6 +7,7 @@ import { useState, useEffect } from 'react'
 import { invoke } from '@tauri-apps/api/core'
 import type { Hermano } from '@/types'
 import { useToastContext } from '@/contexts/toast-context'
+import { getErrorMessage, getFieldErrors } from '@/lib/utils'
 import { NuevaFamiliaModal } from '@/components/nueva-familia-modal'
 
 interface Familia {
@@ -19,6 +20,7 @@ export function Component() {
     const { id } = useParams()
     const toast = useToastContext()
     const [loading, setLoading] = useState(false)
+    const [fieldErrors, setFieldErrors] = useState<Record<string, string>>({})
     const [loadingData, setLoadingData] = useState(true)
     const [familias, setFamilias] = useState<Familia[]>([])
     const [showNuevaFamiliaModal, setShowNuevaFamiliaModal] = useState(false)
@@ -117,6 +119,7 @@ export function Component() {
     const handleSubmit = async (e: React.FormEvent) => {
         e.preventDefault()
         setLoading(true)
+        setFieldErrors({})
 
         try {
             // Preparar datos para enviar, convirtiendo cadenas vacías a undefined
@@ -156,7 +159,8 @@ export function Component() {
             navigate('/hermanos')
         } catch (error) {
             console.error('Error updating hermano:', error)
-            toast.error('Error al actualizar el hermano')
+            setFieldErrors(getFieldErrors(error))
+            toast.error(`Error al actualizar el hermano: ${getErrorMessage(error)}`)
         } finally {
             setLoading(false)
         }
@@ -186,17 +190,16 @@ export function Component() {
                             <Input
                                 label="Número de Hermano"
                                 value={formData.numero_hermano}
+                                error={fieldErrors.numero_hermano}
                                 onChange={(e) => {
-                                    const value = e.target.value
-                                        .replace(/\D/g, '')
-                                        .slice(0, 5)
+                                    const value = e.target.value.trim()
                                     setFormData({
                                         ...formData,
                                         numero_hermano: value
                                     })
                                 }}
-                                helperText="5 dígitos numéricos"
-                                maxLength={5}
+                                helperText="Se completa con ceros a la izquierda según la numeración configurada"
+                                maxLength={20}
                                 placeholder="00001"
                                 required
                             />
@@ -248,6 +251,7 @@ export function Component() {
                         <Input
                             label="Nombre"
                             value={formData.nombre}
+                            error={fieldErrors.nombre}
                             onChange={(e) =>
                                 setFormData({
                                     ...formData,
@@ -261,6 +265,7 @@ export function Component() {
                             <Input
                                 label="Primer Apellido"
                                 value={formData.primer_apellido}
+                                error={fieldErrors.primer_apellido}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -272,6 +277,7 @@ export function Component() {
                             <Input
                                 label="Segundo Apellido"
                                 value={formData.segundo_apellido}
+                                error={fieldErrors.segundo_apellido}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -285,6 +291,7 @@ export function Component() {
                             <Input
                                 label="DNI"
                                 value={formData.dni}
+                                error={fieldErrors.dni}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -296,6 +303,7 @@ export function Component() {
                                 label="Fecha de Alta"
                                 type="date"
                                 value={formData.fecha_alta}
+                                error={fieldErrors.fecha_alta}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -318,6 +326,7 @@ export function Component() {
                                 label="Fecha de Nacimiento"
                                 type="date"
                                 value={formData.fecha_nacimiento}
+                                error={fieldErrors.fecha_nacimiento}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -328,6 +337,7 @@ export function Component() {
                             <Input
                                 label="Localidad de Nacimiento"
                                 value={formData.localidad_nacimiento}
+                                error={fieldErrors.localidad_nacimiento}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -338,6 +348,7 @@ export function Component() {
                             <Input
                                 label="Provincia de Nacimiento"
                                 value={formData.provincia_nacimiento}
+                                error={fieldErrors.provincia_nacimiento}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -358,6 +369,7 @@ export function Component() {
                             <Input
                                 label="Parroquia de Bautismo"
                                 value={formData.parroquia_bautismo}
+                                error={fieldErrors.parroquia_bautismo}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -368,6 +380,7 @@ export function Component() {
                             <Input
                                 label="Localidad de Bautismo"
                                 value={formData.localidad_bautismo}
+                                error={fieldErrors.localidad_bautismo}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -378,6 +391,7 @@ export function Component() {
                             <Input
                                 label="Provincia de Bautismo"
                                 value={formData.provincia_bautismo}
+                                error={fieldErrors.provincia_bautismo}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -398,6 +412,7 @@ export function Component() {
                             <Input
                                 label="Teléfono"
                                 value={formData.telefono}
+                                error={fieldErrors.telefono}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -409,6 +424,7 @@ export function Component() {
                                 label="Email"
                                 type="email"
                                 value={formData.email}
+                                error={fieldErrors.email}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -421,6 +437,7 @@ export function Component() {
                         <Input
                             label="Dirección"
                             value={formData.direccion}
+                            error={fieldErrors.direccion}
                             onChange={(e) =>
                                 setFormData({
                                     ...formData,
@@ -433,6 +450,7 @@ export function Component() {
                             <Input
                                 label="Localidad"
                                 value={formData.localidad}
+                                error={fieldErrors.localidad}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -443,6 +461,7 @@ export function Component() {
                             <Input
                                 label="Provincia"
                                 value={formData.provincia}
+                                error={fieldErrors.provincia}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -453,6 +472,7 @@ export function Component() {
                             <Input
                                 label="Código Postal"
                                 value={formData.codigo_postal}
+                                error={fieldErrors.codigo_postal}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -496,6 +516,7 @@ export function Component() {
                                 <Input
                                     label="Nombre y Apellidos del padre/madre/tutor"
                                     value={formData.nombre_representante_legal}
+                                    error={fieldErrors.nombre_representante_legal}
                                     onChange={(e) =>
                                         setFormData({
                                             ...formData,
@@ -507,6 +528,7 @@ export function Component() {
                                 <Input
                                     label="DNI del representante legal"
                                     value={formData.dni_representante_legal}
+                                    error={fieldErrors.dni_representante_legal}
                                     onChange={(e) =>
                                         setFormData({
                                             ...formData,
@@ -529,6 +551,7 @@ export function Component() {
                             <Input
                                 label="Hermano 1"
                                 value={formData.hermano_aval_1}
+                                error={fieldErrors.hermano_aval_1}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -540,6 +563,7 @@ export function Component() {
                             <Input
                                 label="Hermano 2"
                                 value={formData.hermano_aval_2}
+                                error={fieldErrors.hermano_aval_2}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
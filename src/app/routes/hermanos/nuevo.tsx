@@ -7,6 +7,7 @@ import { useState, useEffect } from 'react'
 import { invoke } from '@tauri-apps/api/core'
 import { useToastContext } from '@/contexts/toast-context'
 import { NuevaFamiliaModal } from '@/components/nueva-familia-modal'
+import { getErrorMessage, getFieldErrors } from '@/lib/utils'
 
 interface Familia {
     id: number
@@ -17,6 +18,7 @@ export function Component() {
     const navigate = useNavigate()
     const toast = useToastContext()
     const [loading, setLoading] = useState(false)
+    const [fieldErrors, setFieldErrors] = useState<Record<string, string>>({})
     const [familias, setFamilias] = useState<Familia[]>([])
     const [showNuevaFamiliaModal, setShowNuevaFamiliaModal] = useState(false)
     const [formData, setFormData] = useState({
@@ -89,6 +91,7 @@ export function Component() {
     const handleSubmit = async (e: React.FormEvent) => {
         e.preventDefault()
         setLoading(true)
+        setFieldErrors({})
 
         try {
             const dataToSend = prepareHermanoData()
@@ -97,7 +100,8 @@ export function Component() {
             navigate('/hermanos')
         } catch (error) {
             console.error('Error creating hermano:', error)
-            toast.error('Error al crear el hermano')
+            setFieldErrors(getFieldErrors(error))
+            toast.error(`Error al crear el hermano: ${getErrorMessage(error)}`)
         } finally {
             setLoading(false)
         }
@@ -120,17 +124,16 @@ export function Component() {
                             <Input
                                 label="Número de Hermano"
                                 value={formData.numero_hermano}
+                                error={fieldErrors.numero_hermano}
                                 onChange={(e) => {
-                                    const value = e.target.value
-                                        .replace(/\D/g, '')
-                                        .slice(0, 5)
+                                    const value = e.target.value.trim()
                                     setFormData({
                                         ...formData,
                                         numero_hermano: value
                                     })
                                 }}
-                                helperText="5 dígitos numéricos. Dejar vacío para generar automáticamente"
-                                maxLength={5}
+                                helperText="Dejar vacío para asignar automáticamente el siguiente número"
+                                maxLength={20}
                                 placeholder="00001"
                             />
                             <Select
@@ -181,6 +184,7 @@ export function Component() {
                         <Input
                             label="Nombre"
                             value={formData.nombre}
+                            error={fieldErrors.nombre}
                             onChange={(e) =>
                                 setFormData({
                                     ...formData,
@@ -194,6 +198,7 @@ export function Component() {
                             <Input
                                 label="Primer Apellido"
                                 value={formData.primer_apellido}
+                                error={fieldErrors.primer_apellido}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -205,6 +210,7 @@ export function Component() {
                             <Input
                                 label="Segundo Apellido"
                                 value={formData.segundo_apellido}
+                                error={fieldErrors.segundo_apellido}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -218,6 +224,7 @@ export function Component() {
                             <Input
                                 label="DNI"
                                 value={formData.dni}
+                                error={fieldErrors.dni}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -229,6 +236,7 @@ export function Component() {
                                 label="Fecha de Alta"
                                 type="date"
                                 value={formData.fecha_alta}
+                                error={fieldErrors.fecha_alta}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -251,6 +259,7 @@ export function Component() {
                                 label="Fecha de Nacimiento"
                                 type="date"
                                 value={formData.fecha_nacimiento}
+                                error={fieldErrors.fecha_nacimiento}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -261,6 +270,7 @@ export function Component() {
                             <Input
                                 label="Localidad de Nacimiento"
                                 value={formData.localidad_nacimiento}
+                                error={fieldErrors.localidad_nacimiento}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -271,6 +281,7 @@ export function Component() {
                             <Input
                                 label="Provincia de Nacimiento"
                                 value={formData.provincia_nacimiento}
+                                error={fieldErrors.provincia_nacimiento}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -291,6 +302,7 @@ export function Component() {
                             <Input
                                 label="Parroquia de Bautismo"
                                 value={formData.parroquia_bautismo}
+                                error={fieldErrors.parroquia_bautismo}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -301,6 +313,7 @@ export function Component() {
                             <Input
                                 label="Localidad de Bautismo"
                                 value={formData.localidad_bautismo}
+                                error={fieldErrors.localidad_bautismo}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -311,6 +324,7 @@ export function Component() {
                             <Input
                                 label="Provincia de Bautismo"
                                 value={formData.provincia_bautismo}
+                                error={fieldErrors.provincia_bautismo}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -331,6 +345,7 @@ export function Component() {
                             <Input
                                 label="Teléfono"
                                 value={formData.telefono}
+                                error={fieldErrors.telefono}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -342,6 +357,7 @@ export function Component() {
                                 label="Email"
                                 type="email"
                                 value={formData.email}
+                                error={fieldErrors.email}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -354,6 +370,7 @@ export function Component() {
                         <Input
                             label="Dirección"
                             value={formData.direccion}
+                            error={fieldErrors.direccion}
                             onChange={(e) =>
                                 setFormData({
                                     ...formData,
@@ -366,6 +383,7 @@ export function Component() {
                             <Input
                                 label="Localidad"
                                 value={formData.localidad}
+                                error={fieldErrors.localidad}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -376,6 +394,7 @@ export function Component() {
                             <Input
                                 label="Provincia"
                                 value={formData.provincia}
+                                error={fieldErrors.provincia}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -386,6 +405,7 @@ export function Component() {
                             <Input
                                 label="Código Postal"
                                 value={formData.codigo_postal}
+                                error={fieldErrors.codigo_postal}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -429,6 +449,7 @@ export function Component() {
                                 <Input
                                     label="Nombre y Apellidos del padre/madre/tutor"
                                     value={formData.nombre_representante_legal}
+                                    error={fieldErrors.nombre_representante_legal}
                                     onChange={(e) =>
                                         setFormData({
                                             ...formData,
@@ -440,6 +461,7 @@ export function Component() {
                                 <Input
                                     label="DNI del representante legal"
                                     value={formData.dni_representante_legal}
+                                    error={fieldErrors.dni_representante_legal}
                                     onChange={(e) =>
                                         setFormData({
                                             ...formData,
@@ -462,6 +484,7 @@ export function Component() {
                             <Input
                                 label="Hermano 1"
                                 value={formData.hermano_aval_1}
+                                error={fieldErrors.hermano_aval_1}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -473,6 +496,7 @@ export function Component() {
                             <Input
                                 label="Hermano 2"
                                 value={formData.hermano_aval_2}
+                                error={fieldErrors.hermano_aval_2}
                                 onChange={(e) =>
                                     setFormData({
                                         ...formData,
@@ -512,7 +536,10 @@ export function Component() {
                         )
                         return hermanoId
                     } catch (error) {
-                        toast.error('Error al crear el hermano')
+                        setFieldErrors(getFieldErrors(error))
+                        toast.error(
+                            `Error al crear el hermano: ${getErrorMessage(error)}`
+                        )
                         throw error
                     }
                 }}
@@ -537,7 +564,7 @@ export function Component() {
                         toast.success('Familia creada correctamente')
                     } catch (error) {
                         toast.error(
-                            `Error al vincular hermano con familia: ${error}`
+                            `Error al vincular hermano con familia: ${getErrorMessage(error)}`
                         )
                     }
                 }}
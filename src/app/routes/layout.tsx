@@ -1,11 +1,24 @@
 import { Outlet } from 'react-router'
 import { Users, Building2, Euro, Home, Menu, X } from 'lucide-react'
-import { useState } from 'react'
+import { useEffect, useState } from 'react'
 import { Link, useLocation } from 'react-router'
+import { invoke } from '@tauri-apps/api/core'
+import { useToastContext } from '@/contexts/toast-context'
 
 export function Component() {
     const [sidebarOpen, setSidebarOpen] = useState(false)
     const location = useLocation()
+    const toast = useToastContext()
+
+    // Si al arrancar no se pudo abrir el perfil activo, se explica por qué
+    // se está trabajando con el perfil por defecto
+    useEffect(() => {
+        invoke<string | null>('take_aviso_arranque_cmd')
+            .then((aviso) => {
+                if (aviso) toast.warning(aviso, 20000)
+            })
+            .catch((err) => console.error('Error al leer el aviso de arranque:', err))
+    }, [])
 
     const navigation = [
         {
@@ -1,6 +1,43 @@
 import { clsx, type ClassValue } from 'clsx'
 import { twMerge } from 'tailwind-merge'
+import type { HermanarError, Periodicidad } from '@/types'
 
 export function cn(...inputs: ClassValue[]) {
     return twMerge(clsx(inputs))
 }
+
+export function isHermanarError(error: unknown): error is HermanarError {
+    return (
+        typeof error === 'object' &&
+        error !== null &&
+        'kind' in error &&
+        'message' in error
+    )
+}
+
+export function etiquetaPeriodo(periodicidad: Periodicidad, periodo: number): string {
+    switch (periodicidad) {
+        case 'anual':
+            return 'Anual'
+        case 'semestral':
+            return `${periodo}º Sem.`
+        case 'mensual':
+            return new Date(2000, periodo - 1).toLocaleString('es-ES', { month: 'long' })
+        default:
+            return `${periodo}º Trim.`
+    }
+}
+
+export function getErrorMessage(error: unknown): string {
+    if (isHermanarError(error)) return error.message
+    return String(error)
+}
+
+// Mensaje de error por campo para marcarlo en el formulario. Las restricciones
+// de varias columnas llegan con los campos separados por comas.
+export function getFieldErrors(error: unknown): Record<string, string> {
+    if (!isHermanarError(error) || !error.field) return {}
+    return Object.fromEntries(
+        error.field.split(',').map((field) => [field, error.message])
+    )
+}
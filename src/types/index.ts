@@ -79,11 +79,14 @@ export interface FamiliaWithAddressResponse {
     }
 }
 
+export type Periodicidad = 'anual' | 'semestral' | 'trimestral' | 'mensual'
+
 export interface Cuota extends Record<string, unknown> {
     id?: number
     hermano_id: number
     anio: number
-    trimestre: number // 1, 2, 3, 4
+    periodicidad: Periodicidad
+    periodo: number // 1 (anual), 1-2, 1-4 o 1-12 según la periodicidad
     importe: number
     pagado: boolean
     fecha_pago?: string // ISO format YYYY-MM-DD
@@ -91,12 +94,36 @@ export interface Cuota extends Record<string, unknown> {
     observaciones?: string
     created_at?: string
     updated_at?: string
+    // Calculados a partir de los pagos de la cuota
+    importe_pagado?: number
+    importe_pendiente?: number
+    estado?: EstadoPago
+}
+
+export type EstadoPago = 'pendiente' | 'parcial' | 'pagada'
+
+export interface Pago {
+    id?: number
+    cuota_id: number
+    importe: number
+    fecha: string // ISO format YYYY-MM-DD
+    metodo: string
+    referencia?: string
+    observaciones?: string
+    recibo_id?: number
+    numero_recibo?: string
+    anulado: boolean
+    fecha_anulacion?: string
+    operador: string
+    created_at?: string
 }
 
 export interface EstadisticasCuotas {
     total_recaudado: number
     cuotas_pendientes: number
     cuotas_pagadas: number
+    cuotas_parciales: number
+    total_pendiente: number
     hermanos_al_dia: number
     hermanos_morosos: number
 }
@@ -212,6 +239,21 @@ export interface UseApiListState<T> {
     error: string | null
 }
 
+// Error devuelto por los comandos del backend
+export type HermanarErrorKind =
+    | 'not_found'
+    | 'duplicate'
+    | 'validation'
+    | 'conflict'
+    | 'busy'
+    | 'database'
+
+export interface HermanarError {
+    kind: HermanarErrorKind
+    field: string | null // Campo afectado en errores de duplicado o validación
+    message: string
+}
+
 // Tipos de utilidades
 export type DeepPartial<T> = {
     [P in keyof T]?: DeepPartial<T[P]>